@@ -0,0 +1,41 @@
+//! Builds the sample book in memory, records a salary and prints the balance.
+//!
+//! Run with `cargo run --example demo`.
+
+use chrono::Utc;
+use mm_schema::{
+    init_sample_data, Database, Denomination, EntryStatus, Error, Money, MoneyFormat, Result,
+};
+use rust_decimal_macros::dec;
+
+fn main() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+
+    db.init_schema()?;
+    init_sample_data(&mut db)?;
+
+    db.insert_transaction(
+        Utc::now(),
+        "Salary",
+        "SALARY-01",
+        EntryStatus::Posted,
+        "1101",
+        "4100",
+        "EUR",
+        "EUR",
+        Money::new(dec!(3000.0)),
+    )?;
+
+    for row in db.get_general_balance()? {
+        let asset = db.get_asset(&row.asset)?.ok_or(Error::NotFound)?;
+        let format = MoneyFormat::en_us().for_asset(&Denomination::from(&asset));
+        println!(
+            "{:<6} {:<30} {:>16}",
+            row.account_number,
+            row.account_name,
+            format.format(row.balance)
+        );
+    }
+
+    Ok(())
+}
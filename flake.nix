@@ -62,6 +62,8 @@
         buildInputs = with pkgs;
           [
             pkg-config
+            # SQLCipher of the `encryption` feature
+            openssl
             # Add additional build inputs here
           ]
           ++ lib.optionals pkgs.stdenv.isDarwin [
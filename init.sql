@@ -73,6 +73,10 @@ INSERT INTO accounts (account_number, name, account_type_id, parent_account_id,
         ('5500', 'Financial Expenses', 5, 25, true, '2023-01-01'),
             ('5501', 'Bank Fees', 5, 41, true, '2023-01-01'),
             ('5502', 'Credit Card Interest', 5, 41, true, '2023-01-01'),
-            ('5503', 'Investment Fees', 5, 41, true, '2023-01-01');
+            ('5503', 'Investment Fees', 5, 41, true, '2023-01-01'),
+            ('5504', 'Withholding Tax', 5, 41, true, '2023-01-01'),
+
+    -- Suspense account for imported transactions awaiting review
+        ('3900', 'Uncategorized', 3, 16, true, '2023-01-01');
 
 COMMIT;
@@ -0,0 +1,605 @@
+use std::{
+    io,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    thread,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    backup::SnapshotPolicy,
+    error::{Error, Result},
+    interface::{Database, GeneralBalanceReport},
+    journal::RegisterLine,
+    json::{BookDocument, JsonImport},
+    models::{
+        Account, AccountType, Asset, AssetPrice, AssetType, AuditRecord, AuditTable,
+        CorporateAction, Dividend, EntryStatus, Lot, NewAccount, NewJournalEntry, NormalBalance,
+    },
+    money::Money,
+    performance::PortfolioPerformance,
+    plain_text::PlainTextFormat,
+    valuation::ValuationReport,
+    IncomeYieldReport,
+};
+
+#[cfg(feature = "importers")]
+use crate::{
+    bank_statement::StatementImport,
+    import::{ImportSummary, StatementLine},
+    models::CsvImportProfile,
+    plain_text_import::PlainTextImport,
+    qif::QifOptions,
+};
+
+/// Jobs waiting for the database thread before callers are made to wait
+pub const DEFAULT_QUEUE_CAPACITY: usize = 64;
+
+type Job = Box<dyn FnOnce(&mut Database) + Send>;
+
+/// A handle to a [`Database`] owned by a dedicated thread, for use from async
+/// code.
+///
+/// Every call is sent to the thread as a job over a bounded queue, so blocking
+/// SQLite I/O never runs on the async runtime and a busy database applies
+/// backpressure instead of piling up work. Handles are cheap to clone and share
+/// the same thread, which exits once the last handle is dropped.
+///
+/// Calls are cancellation-safe: dropping a pending future either cancels the
+/// job before it starts or lets it run to completion, and a transaction is
+/// never left open between jobs. Use [`transaction`](Self::transaction) to group
+/// several operations atomically.
+#[derive(Clone)]
+pub struct AsyncDatabase {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl AsyncDatabase {
+    /// Opens the book at `path` on a new database thread
+    pub async fn open<P: Into<String>>(path: P) -> Result<Self> {
+        let path = path.into();
+        Self::spawn(move || Ok(Database::new(&path)?), DEFAULT_QUEUE_CAPACITY).await
+    }
+
+    /// Opens an empty in-memory book on a new database thread
+    pub async fn open_in_memory() -> Result<Self> {
+        Self::spawn(|| Ok(Database::new_in_memory()?), DEFAULT_QUEUE_CAPACITY).await
+    }
+
+    /// Moves an open database to a new thread
+    pub async fn new(db: Database) -> Result<Self> {
+        Self::with_capacity(db, DEFAULT_QUEUE_CAPACITY).await
+    }
+
+    /// Moves an open database to a new thread, queueing at most `capacity`
+    /// jobs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub async fn with_capacity(db: Database, capacity: usize) -> Result<Self> {
+        Self::spawn(move || Ok(db), capacity).await
+    }
+
+    async fn spawn<F>(open: F, capacity: usize) -> Result<Self>
+    where
+        F: FnOnce() -> Result<Database> + Send + 'static,
+    {
+        let (jobs, mut queue) = mpsc::channel::<Job>(capacity);
+        let (ready, opened) = oneshot::channel();
+
+        thread::Builder::new()
+            .name("mm-schema-db".to_string())
+            .spawn(move || {
+                let mut db = match open() {
+                    Ok(db) => db,
+                    Err(e) => {
+                        let _ = ready.send(Err(e));
+                        return;
+                    }
+                };
+                let _ = ready.send(Ok(()));
+
+                while let Some(job) = queue.blocking_recv() {
+                    // A panicking job drops its reply, which its caller sees as
+                    // an error; the thread carries on with a clean connection
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| job(&mut db)));
+                    if result.is_err() && !db.conn().is_autocommit() {
+                        let _ = db.conn().execute_batch("ROLLBACK");
+                    }
+                }
+            })?;
+
+        opened.await.map_err(|_| stopped())??;
+
+        Ok(Self { jobs })
+    }
+
+    /// Runs `f` on the database thread and returns its result.
+    ///
+    /// Waits for room in the queue first. If the returned future is dropped
+    /// before the job starts, the job is skipped.
+    pub async fn call<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Database) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (reply, response) = oneshot::channel();
+        let job: Job = Box::new(move |db| {
+            if reply.is_closed() {
+                return;
+            }
+            let _ = reply.send(f(db));
+        });
+
+        self.jobs.send(job).await.map_err(|_| stopped())?;
+        response.await.map_err(|_| panicked())?
+    }
+
+    /// Runs `f` on the database thread inside a transaction, which is
+    /// committed if `f` succeeds and rolled back if it fails or panics.
+    ///
+    /// Once started, the transaction is finished on the database thread even
+    /// if the returned future is dropped.
+    pub async fn transaction<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Database) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.call(move |db| {
+            db.conn().execute_batch("SAVEPOINT async_transaction")?;
+            match f(db) {
+                Ok(value) => {
+                    db.conn().execute_batch("RELEASE async_transaction")?;
+                    Ok(value)
+                }
+                Err(e) => {
+                    db.conn().execute_batch(
+                        "ROLLBACK TO async_transaction; RELEASE async_transaction",
+                    )?;
+                    Err(e)
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// The database thread is gone, having panicked while opening the book
+fn stopped() -> Error {
+    Error::Io(io::Error::new(
+        io::ErrorKind::BrokenPipe,
+        "the database thread has stopped",
+    ))
+}
+
+/// A job dropped its reply without answering, by panicking
+fn panicked() -> Error {
+    Error::Io(io::Error::other("the database job panicked"))
+}
+
+/// Declares async versions of [`Database`] methods. Each runs the method of
+/// the same name with the same arguments, or the given body, as a job.
+macro_rules! async_methods {
+    ($(
+        $(#[$attr:meta])*
+        fn $name:ident($($arg:ident: $ty:ty),* $(,)?) -> $ret:ty $(=> |$db:ident| $body:expr)?;
+    )*) => {
+        impl AsyncDatabase {
+            $(
+                $(#[$attr])*
+                #[allow(clippy::too_many_arguments)]
+                #[doc = concat!("Runs [`Database::", stringify!($name), "`] on the database thread.")]
+                pub async fn $name(&self, $($arg: $ty),*) -> Result<$ret> {
+                    self.call(async_methods!(@job $name($($arg),*) $(, |$db| $body)?))
+                        .await
+                }
+            )*
+        }
+    };
+    (@job $name:ident($($arg:ident),*)) => {
+        move |db: &mut Database| converted(db.$name($($arg),*))
+    };
+    (@job $name:ident($($arg:ident),*), |$db:ident| $body:expr) => {
+        move |$db: &mut Database| converted($body)
+    };
+}
+
+/// Methods return either SQLite or crate errors
+fn converted<T, E: Into<Error>>(result: std::result::Result<T, E>) -> Result<T> {
+    result.map_err(Into::into)
+}
+
+/// Collects what a `Database` export method writes
+fn written<F>(export: F) -> Result<Vec<u8>>
+where
+    F: FnOnce(&mut Vec<u8>) -> Result<()>,
+{
+    let mut out = Vec::new();
+    export(&mut out)?;
+    Ok(out)
+}
+
+async_methods! {
+    // Schema
+    fn init_schema() -> ();
+    fn schema_version() -> i64;
+    fn migrate() -> ();
+
+    // Backups and snapshots
+    fn backup_to(path: String) -> () => |db| db.backup_to(&path);
+    fn restore_from(path: String) -> () => |db| db.restore_from(&path);
+    fn snapshot(reason: String) -> PathBuf => |db| db.snapshot(&reason);
+    fn list_snapshots() -> Vec<PathBuf>;
+    fn set_snapshot_policy(policy: Option<SnapshotPolicy>) -> () => |db| {
+        db.set_snapshot_policy(policy);
+        Ok::<_, Error>(())
+    };
+
+    // Audit log
+    fn set_actor(actor: String) -> () => |db| db.set_actor(&actor);
+    fn history(table: AuditTable, record_id: i64) -> Vec<AuditRecord>;
+
+    // Chart of accounts and assets
+    fn create_account_type(
+        name: String,
+        normal_balance: NormalBalance,
+        description: Option<String>,
+    ) -> i64;
+    fn list_account_types() -> Vec<AccountType>;
+    fn create_asset(
+        code: String,
+        name: String,
+        asset_type: AssetType,
+        decimals: i64,
+        description: Option<String>,
+    ) -> i64;
+    fn get_asset(code: String) -> Option<Asset>;
+    fn list_assets() -> Vec<Asset>;
+    fn create_account(
+        account_number: String,
+        name: String,
+        account_type_id: i64,
+        parent_account_id: Option<i64>,
+        is_active: bool,
+        opening_date: NaiveDate,
+        closing_date: Option<NaiveDate>,
+        description: Option<String>,
+    ) -> i64;
+    fn list_accounts() -> Vec<Account>;
+    fn open_account(account: NewAccount) -> i64 => |db| db.open_account(&account);
+    fn close_account(account_number: String, closing_date: NaiveDate) -> ();
+
+    // Rates and prices
+    fn create_exchange_rate(
+        from_asset_code: String,
+        to_asset_code: String,
+        rate: Decimal,
+        date: DateTime<Utc>,
+    ) -> i64;
+    fn exchange_rate_at(
+        from_asset_code: String,
+        to_asset_code: String,
+        date: DateTime<Utc>,
+    ) -> Option<Decimal>;
+    fn upsert_asset_price(
+        asset_code: String,
+        quote_asset_code: String,
+        date: NaiveDate,
+        open: Option<Money>,
+        high: Option<Money>,
+        low: Option<Money>,
+        close: Money,
+    ) -> i64;
+    fn price_at(
+        asset_code: String,
+        date: NaiveDate,
+        quote_asset_code: String,
+        lookback_days: u64,
+    ) -> Option<AssetPrice>;
+    fn import_asset_prices_csv(
+        asset_code: String,
+        quote_asset_code: String,
+        input: Vec<u8>,
+    ) -> usize => |db| db.import_asset_prices_csv(asset_code, quote_asset_code, input.as_slice());
+
+    // Journal
+    fn insert_transaction(
+        date: DateTime<Utc>,
+        description: String,
+        reference_number: String,
+        status: EntryStatus,
+        debit_account_number: String,
+        credit_account_number: String,
+        debit_asset_code: String,
+        credit_asset_code: String,
+        amount: Money,
+    ) -> i64;
+    fn create_journal_entry(entry: NewJournalEntry) -> i64
+        => |db| db.create_journal_entry(&entry);
+    fn void_journal_entry(journal_entry_id: i64) -> ();
+    fn post_journal_entry(journal_entry_id: i64) -> ();
+    fn draft_journal_entries() -> Vec<(i64, NewJournalEntry)>;
+    fn journal_entries(status: EntryStatus) -> Vec<(i64, NewJournalEntry)>;
+    fn register(account_number: String) -> Vec<RegisterLine>;
+
+    // Investments
+    fn open_lot(
+        account_number: String,
+        asset_code: String,
+        acquired_date: NaiveDate,
+        quantity: Money,
+        cost_basis: Money,
+        cost_asset_code: String,
+        journal_entry_id: Option<i64>,
+    ) -> i64;
+    fn close_lots_fifo(account_number: String, asset_code: String, quantity: Money) -> Money;
+    fn open_lots(account_number: String, asset_code: String) -> Vec<Lot>;
+    fn record_split(
+        asset_code: String,
+        date: NaiveDate,
+        ratio_new: i64,
+        ratio_old: i64,
+        description: Option<String>,
+    ) -> i64;
+    fn record_rename(
+        asset_code: String,
+        new_code: String,
+        date: NaiveDate,
+        description: Option<String>,
+    ) -> i64;
+    fn record_merger(
+        asset_code: String,
+        target_asset_code: String,
+        date: NaiveDate,
+        ratio_new: i64,
+        ratio_old: i64,
+        description: Option<String>,
+    ) -> i64;
+    fn list_corporate_actions() -> Vec<CorporateAction>;
+    fn record_dividend(
+        asset_code: String,
+        account_number: String,
+        currency_code: String,
+        gross: Money,
+        withholding: Money,
+        date: DateTime<Utc>,
+    ) -> i64;
+    fn record_interest(
+        asset_code: String,
+        account_number: String,
+        currency_code: String,
+        gross: Money,
+        withholding: Money,
+        date: DateTime<Utc>,
+    ) -> i64;
+    fn list_dividends(asset_code: String) -> Vec<Dividend>;
+
+    // Reports
+    fn get_general_balance() -> Vec<GeneralBalanceReport>;
+    fn unit_value_at(
+        asset_code: String,
+        as_of: NaiveDate,
+        reporting_asset_code: String,
+    ) -> Option<Decimal>;
+    fn get_valuation_report(
+        as_of: NaiveDate,
+        reporting_asset_code: String,
+    ) -> Vec<ValuationReport>;
+    fn portfolio_performance(
+        account_number: String,
+        from: NaiveDate,
+        to: NaiveDate,
+        reporting_asset_code: String,
+    ) -> PortfolioPerformance;
+    fn get_income_yield_report(
+        from: NaiveDate,
+        to: NaiveDate,
+        reporting_asset_code: String,
+    ) -> Vec<IncomeYieldReport>;
+
+    // Export and import of the whole book
+    fn book_document() -> BookDocument;
+    fn export_json() -> Vec<u8> => |db| written(|out| db.export_json(out));
+    fn import_json(input: Vec<u8>) -> JsonImport => |db| db.import_json(input.as_slice());
+    fn import_book_document(document: BookDocument) -> JsonImport
+        => |db| db.import_book_document(&document);
+    fn export_beancount() -> Vec<u8> => |db| written(|out| db.export_beancount(out));
+    fn export_ledger() -> Vec<u8> => |db| written(|out| db.export_ledger(out));
+    fn export_plain_text(format: PlainTextFormat) -> Vec<u8>
+        => |db| written(|out| db.export_plain_text(format, out));
+}
+
+#[cfg(feature = "importers")]
+async_methods! {
+    fn save_import_profile(profile: CsvImportProfile) -> i64
+        => |db| db.save_import_profile(&profile);
+    fn get_import_profile(name: String) -> Option<CsvImportProfile>;
+    fn list_import_profiles() -> Vec<CsvImportProfile>;
+    fn import_csv(
+        profile_name: String,
+        account_number: String,
+        asset_code: String,
+        input: Vec<u8>,
+    ) -> ImportSummary
+        => |db| db.import_csv(profile_name, account_number, asset_code, input.as_slice());
+    fn map_external_account(external_id: String, account_number: String) -> i64;
+    fn mapped_account(external_id: String) -> Option<String>;
+    fn import_statement_lines(
+        account_number: String,
+        asset_code: String,
+        lines: Vec<StatementLine>,
+    ) -> ImportSummary
+        => |db| db.import_statement_lines(account_number, asset_code, &lines);
+    fn import_ofx(input: Vec<u8>) -> ImportSummary => |db| db.import_ofx(input.as_slice());
+    fn import_camt053(input: Vec<u8>) -> StatementImport
+        => |db| db.import_camt053(input.as_slice());
+    fn import_mt940(input: Vec<u8>) -> StatementImport => |db| db.import_mt940(input.as_slice());
+    fn import_qif(
+        account_number: String,
+        asset_code: String,
+        input: Vec<u8>,
+        options: QifOptions,
+    ) -> ImportSummary
+        => |db| db.import_qif(account_number, asset_code, input.as_slice(), &options);
+    fn export_qif(account_number: String, asset_code: String, options: QifOptions) -> Vec<u8>
+        => |db| written(|out| db.export_qif(account_number, asset_code, out, &options));
+    fn import_beancount(input: Vec<u8>) -> PlainTextImport
+        => |db| db.import_beancount(input.as_slice());
+    fn import_ledger(input: Vec<u8>) -> PlainTextImport
+        => |db| db.import_ledger(input.as_slice());
+    fn import_plain_text(format: PlainTextFormat, input: Vec<u8>) -> PlainTextImport
+        => |db| db.import_plain_text(format, input.as_slice());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc as std_mpsc, time::Duration};
+
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+    use tokio::time::timeout;
+
+    use super::*;
+    use crate::{models::NewJournalEntryLine, seeding::init_sample_data};
+
+    async fn sample_book() -> Result<AsyncDatabase> {
+        let db = AsyncDatabase::open_in_memory().await?;
+        db.init_schema().await?;
+        db.transaction(init_sample_data).await?;
+        Ok(db)
+    }
+
+    /// Occupies the database thread until the returned sender is dropped
+    async fn block(db: &AsyncDatabase) -> std_mpsc::Sender<()> {
+        let (release, released) = std_mpsc::channel::<()>();
+        let (started, running) = oneshot::channel();
+        let db = db.clone();
+        tokio::spawn(async move {
+            db.call(move |_| {
+                let _ = started.send(());
+                let _ = released.recv();
+                Ok(())
+            })
+            .await
+        });
+        running.await.unwrap();
+        release
+    }
+
+    #[tokio::test]
+    async fn test_async_book() -> Result<()> {
+        let db = sample_book().await?;
+
+        let line = |account: &str, entry_type| NewJournalEntryLine {
+            account_number: account.to_string(),
+            asset_code: "USD".to_string(),
+            entry_type,
+            amount: Money::new(dec!(45.20)),
+            reference_amount: None,
+            description: None,
+        };
+        let id = db
+            .create_journal_entry(NewJournalEntry {
+                date: Utc.with_ymd_and_hms(2025, 3, 3, 0, 0, 0).unwrap(),
+                description: "Groceries".to_string(),
+                reference_number: None,
+                reference_asset_code: "USD".to_string(),
+                status: EntryStatus::Posted,
+                lines: vec![
+                    line("5301", NormalBalance::Debit),
+                    line("1101", NormalBalance::Credit),
+                ],
+            })
+            .await?;
+
+        let register = db.register("1101".to_string()).await?;
+        assert_eq!(register[0].journal_entry_id, id);
+        assert_eq!(register[0].balance, Money::new(dec!(-45.2)));
+        assert!(matches!(
+            db.void_journal_entry(99).await,
+            Err(Error::NotFound)
+        ));
+
+        let json = db.export_json().await?;
+        let copy = AsyncDatabase::open_in_memory().await?;
+        copy.init_schema().await?;
+        assert_eq!(copy.import_json(json).await?.journal_entry_ids.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rolls_back() -> Result<()> {
+        let db = sample_book().await?;
+
+        let failed = db
+            .transaction(|db| {
+                db.create_asset("XAU", "Gold", AssetType::Commodity, 4, None)?;
+                // Fails half way: the asset above must not be kept
+                db.close_account("9999", NaiveDate::from_ymd_opt(2025, 6, 30).unwrap())
+            })
+            .await;
+        assert!(matches!(failed, Err(Error::NotFound)));
+        assert!(db.get_asset("XAU".to_string()).await?.is_none());
+
+        // A panicking job is rolled back and the thread keeps serving
+        let panicked = db
+            .transaction(|db| -> Result<()> {
+                db.create_asset("XAU", "Gold", AssetType::Commodity, 4, None)?;
+                panic!("job failed");
+            })
+            .await;
+        assert!(matches!(panicked, Err(Error::Io(_))));
+        assert!(db.get_asset("XAU".to_string()).await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_jobs_are_skipped() -> Result<()> {
+        let db = sample_book().await?;
+
+        let release = block(&db).await;
+        let cancelled = db.create_asset(
+            "XAU".to_string(),
+            "Gold".to_string(),
+            AssetType::Commodity,
+            4,
+            None,
+        );
+        // Queued behind the blocking job, then dropped before it could start
+        assert!(timeout(Duration::from_millis(20), cancelled).await.is_err());
+        drop(release);
+
+        assert!(db.get_asset("XAU".to_string()).await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_queue_is_bounded() -> Result<()> {
+        let db = Database::new_in_memory()?;
+        let db = AsyncDatabase::with_capacity(db, 1).await?;
+
+        let release = block(&db).await;
+        // One job fits in the queue, the next waits for room
+        let queued = tokio::spawn({
+            let db = db.clone();
+            async move { db.schema_version().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(timeout(Duration::from_millis(20), db.schema_version())
+            .await
+            .is_err());
+
+        drop(release);
+        assert_eq!(queued.await.unwrap()?, 0);
+        assert_eq!(db.schema_version().await?, 0);
+
+        Ok(())
+    }
+}
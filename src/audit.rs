@@ -0,0 +1,340 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{
+    types::{FromSql, FromSqlError, FromSqlResult, Type, ValueRef},
+    Row,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{
+    error::Result,
+    interface::Database,
+    models::{parse_column, AuditRecord, AuditTable},
+};
+
+impl Database {
+    /// Records the changes made from now on as made by `actor`, `unknown`
+    /// until it is set.
+    ///
+    /// The actor is kept in the book rather than in the connection, so it
+    /// also applies to other connections and to changes made with other
+    /// tools, until it is set again.
+    pub fn set_actor(&mut self, actor: &str) -> Result<()> {
+        self.conn()
+            .execute("UPDATE audit_context SET actor = ?1", [actor])?;
+        Ok(())
+    }
+
+    /// Every change recorded to the row `record_id` of `table`, oldest first.
+    ///
+    /// The history of a journal entry includes the changes to its lines, so
+    /// that the whole entry can be reconstructed at any point.
+    pub fn history(&self, table: AuditTable, record_id: i64) -> Result<Vec<AuditRecord>> {
+        let mut stmt = self.conn().prepare(
+            "SELECT id, timestamp, actor, operation, table_name, record_id, before, after
+             FROM audit_log
+             WHERE (table_name = ?1 AND record_id = ?2)
+                OR (?1 = 'journal_entries' AND journal_entry_id = ?2)
+             ORDER BY id",
+        )?;
+        let records = stmt
+            .query_map(rusqlite::params![table.name(), record_id], audit_record)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(records)
+    }
+}
+
+impl AuditRecord {
+    /// The row before the change, as its model `T`
+    pub fn before_as<T: DeserializeOwned>(&self) -> Result<Option<T>> {
+        Ok(self
+            .before
+            .clone()
+            .map(serde_json::from_value)
+            .transpose()?)
+    }
+
+    /// The row after the change, as its model `T`
+    pub fn after_as<T: DeserializeOwned>(&self) -> Result<Option<T>> {
+        Ok(self.after.clone().map(serde_json::from_value).transpose()?)
+    }
+}
+
+fn audit_record(row: &Row) -> rusqlite::Result<AuditRecord> {
+    let table: AuditTable = parse_column(row, 4)?;
+    let row_as_model = |index| -> rusqlite::Result<Option<Value>> {
+        row.get::<_, Option<Value>>(index)?
+            .map(|value| serialize_dates(table, value))
+            .transpose()
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, e.into()))
+    };
+
+    Ok(AuditRecord {
+        id: row.get(0)?,
+        timestamp: row.get(1)?,
+        actor: row.get(2)?,
+        operation: parse_column(row, 3)?,
+        table,
+        record_id: row.get(5)?,
+        before: row_as_model(6)?,
+        after: row_as_model(7)?,
+    })
+}
+
+/// Rewrites the dates the triggers copied as stored the way serde writes the
+/// `DateTime<Utc>` read from them, so that the rows in the log deserialize
+/// and compare as their models
+fn serialize_dates(table: AuditTable, mut value: Value) -> FromSqlResult<Value> {
+    let fields: &[&str] = match table {
+        AuditTable::ExchangeRates => &["date"],
+        AuditTable::JournalEntries => &["date", "created_at"],
+        AuditTable::Accounts | AuditTable::Assets | AuditTable::JournalEntryLines => &[],
+    };
+    for field in fields {
+        if let Some(date) = value.get_mut(*field).filter(|date| date.is_string()) {
+            let stored = date.as_str().unwrap_or_default();
+            let parsed = DateTime::<Utc>::column_result(ValueRef::Text(stored.as_bytes()))?;
+            *date = serde_json::to_value(parsed).map_err(|e| FromSqlError::Other(e.into()))?;
+        }
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        models::{
+            Account, Asset, AuditOperation, EntryStatus, ExchangeRate, JournalEntry,
+            JournalEntryLine, NewJournalEntry, NewJournalEntryLine, NormalBalance,
+        },
+        money::Money,
+        pool::tests::TempBook,
+        seeding::init_sample_data,
+    };
+    use rusqlite::Connection;
+
+    fn sample_book() -> Result<Database> {
+        let mut db = Database::new_in_memory()?;
+        db.init_schema()?;
+        init_sample_data(&mut db)?;
+        Ok(db)
+    }
+
+    fn groceries() -> NewJournalEntry {
+        let line = |account: &str, entry_type| NewJournalEntryLine {
+            account_number: account.to_string(),
+            asset_code: "USD".to_string(),
+            entry_type,
+            amount: Money::new(dec!(45.20)),
+            reference_amount: None,
+            description: None,
+        };
+        NewJournalEntry {
+            date: Utc.with_ymd_and_hms(2025, 3, 3, 0, 0, 0).unwrap(),
+            description: "Groceries".to_string(),
+            reference_number: None,
+            reference_asset_code: "USD".to_string(),
+            status: EntryStatus::Draft,
+            lines: vec![
+                line("5301", NormalBalance::Debit),
+                line("1101", NormalBalance::Credit),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_journal_entry_history() -> Result<()> {
+        let mut db = sample_book()?;
+        db.set_actor("alice")?;
+        let id = db.create_journal_entry(&groceries())?;
+        db.set_actor("bob")?;
+        db.post_journal_entry(id)?;
+        db.void_journal_entry(id)?;
+
+        let history = db.history(AuditTable::JournalEntries, id)?;
+        let operations: Vec<_> = history
+            .iter()
+            .map(|record| (record.table.name(), record.operation))
+            .collect();
+        assert_eq!(
+            operations,
+            [
+                ("journal_entries", AuditOperation::Insert),
+                ("journal_entry_lines", AuditOperation::Insert),
+                ("journal_entry_lines", AuditOperation::Insert),
+                ("journal_entries", AuditOperation::StatusChange),
+                ("journal_entries", AuditOperation::StatusChange),
+            ]
+        );
+        let actors: Vec<_> = history.iter().map(|record| record.actor.as_str()).collect();
+        assert_eq!(actors, ["alice", "alice", "alice", "bob", "bob"]);
+
+        let posted = &history[3];
+        assert_eq!(
+            posted.before_as::<JournalEntry>()?.unwrap().status,
+            EntryStatus::Draft
+        );
+        let entry = posted.after_as::<JournalEntry>()?.unwrap();
+        assert_eq!(entry.status, EntryStatus::Posted);
+        assert_eq!(entry.date, groceries().date);
+        let line = history[1].after_as::<JournalEntryLine>()?.unwrap();
+        assert_eq!(line.journal_entry_id, id);
+        assert_eq!(line.amount, Money::new(dec!(45.20)));
+        assert!(history[1].before.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_audit_records_match_the_models() -> Result<()> {
+        let mut db = sample_book()?;
+        db.create_exchange_rate(
+            "EUR",
+            "USD",
+            dec!(1.0825),
+            Utc.with_ymd_and_hms(2025, 3, 3, 12, 30, 0).unwrap(),
+        )?;
+        db.close_account("1302", NaiveDate::from_ymd_opt(2025, 6, 30).unwrap())?;
+
+        let account = db
+            .list_accounts()?
+            .into_iter()
+            .find(|account| account.account_number == "1302")
+            .unwrap();
+        let history = db.history(AuditTable::Accounts, account.id)?;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].operation, AuditOperation::Update);
+        assert_eq!(history[1].after, Some(serde_json::to_value(&account)?));
+        assert!(history[1].before_as::<Account>()?.unwrap().is_active);
+
+        let asset = db.get_asset("EUR")?.unwrap();
+        let history = db.history(AuditTable::Assets, asset.id)?;
+        assert_eq!(history[0].after, Some(serde_json::to_value(&asset)?));
+        assert_eq!(history[0].actor, "unknown");
+
+        let history = db.history(AuditTable::ExchangeRates, 1)?;
+        let rate = history[0].after_as::<ExchangeRate>()?.unwrap();
+        assert_eq!(rate.rate, dec!(1.0825));
+        assert_eq!(
+            rate.date,
+            Utc.with_ymd_and_hms(2025, 3, 3, 12, 30, 0).unwrap()
+        );
+
+        // Changes made behind the back of the methods are recorded too
+        db.conn()
+            .execute("DELETE FROM assets WHERE code = 'ETH'", [])?;
+        let deleted = db.history(AuditTable::Assets, 5)?.pop().unwrap();
+        assert_eq!(deleted.operation, AuditOperation::Delete);
+        assert_eq!(deleted.before_as::<Asset>()?.unwrap().code, "ETH");
+        assert!(deleted.after.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_audit_rows_are_the_serialized_models() -> Result<()> {
+        let mut db = sample_book()?;
+        db.create_exchange_rate(
+            "EUR",
+            "USD",
+            dec!(1.0825),
+            Utc.with_ymd_and_hms(2025, 3, 3, 12, 30, 0).unwrap(),
+        )?;
+        db.create_exchange_rate(
+            "BTC",
+            "USD",
+            dec!(84000.5),
+            Utc.timestamp_opt(1_741_000_000, 123_456_789).unwrap(),
+        )?;
+        let id = db.create_journal_entry(&groceries())?;
+        db.post_journal_entry(id)?;
+        db.close_account("1302", NaiveDate::from_ymd_opt(2025, 6, 30).unwrap())?;
+
+        let latest = |table, id| -> Result<Option<serde_json::Value>> {
+            Ok(db.history(table, id)?.pop().unwrap().after)
+        };
+        let document = db.book_document()?;
+        for account in &document.accounts {
+            let expected = serde_json::to_value(account)?;
+            assert_eq!(latest(AuditTable::Accounts, account.id)?, Some(expected));
+        }
+        for asset in &document.assets {
+            let expected = serde_json::to_value(asset)?;
+            assert_eq!(latest(AuditTable::Assets, asset.id)?, Some(expected));
+        }
+        for rate in &document.exchange_rates {
+            let expected = serde_json::to_value(rate)?;
+            assert_eq!(latest(AuditTable::ExchangeRates, rate.id)?, Some(expected));
+        }
+        for entry in &document.journal_entries {
+            let expected = serde_json::to_value(&entry.entry)?;
+            assert_eq!(
+                latest(AuditTable::JournalEntries, entry.entry.id)?,
+                Some(expected)
+            );
+            for line in &entry.lines {
+                let expected = serde_json::to_value(line)?;
+                assert_eq!(
+                    latest(AuditTable::JournalEntryLines, line.id)?,
+                    Some(expected)
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_other_tools_are_recorded_with_the_actor() -> Result<()> {
+        let book = TempBook::new("audit-actor");
+        let mut db = Database::new(book.path())?;
+        db.init_schema()?;
+        init_sample_data(&mut db)?;
+        db.set_actor("alice")?;
+
+        // A connection without any of the crate's SQL functions, as the
+        // sqlite3 shell would open
+        let shell = Connection::open(book.path())?;
+        shell.execute(
+            "UPDATE journal_entries SET description = 'Lunch' WHERE id = ?1",
+            [db.create_journal_entry(&groceries())?],
+        )?;
+
+        let history = db.history(AuditTable::JournalEntries, 1)?;
+        let record = history.last().unwrap();
+        assert_eq!(record.operation, AuditOperation::Update);
+        assert_eq!(record.actor, "alice");
+        let entry = record.after_as::<JournalEntry>()?.unwrap();
+        assert_eq!(entry.description, "Lunch");
+        assert_eq!(entry.date, groceries().date);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_audit_log_is_append_only() -> Result<()> {
+        let db = sample_book()?;
+
+        for sql in [
+            "UPDATE audit_log SET actor = 'mallory'",
+            "DELETE FROM audit_log",
+        ] {
+            match db.conn().execute(sql, []) {
+                Err(e) => assert!(e.to_string().contains("append-only")),
+                Ok(_) => panic!("{sql} changed the audit log"),
+            }
+        }
+        assert!(matches!(
+            db.history(AuditTable::Accounts, 1)?.as_slice(),
+            [record] if record.actor == "unknown"
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,459 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{NaiveDateTime, Utc};
+use rusqlite::{backup::Backup, Connection};
+
+use crate::{
+    error::{Error, Result},
+    interface::Database,
+    journal::validate_journal_entry,
+    migrations::SCHEMA_VERSION,
+    models::EntryStatus,
+    money::register_sql_functions,
+};
+
+/// Snapshots kept by [`SnapshotPolicy::new`]
+pub const DEFAULT_SNAPSHOT_RETENTION: usize = 10;
+
+/// Pages copied by each step of the online backup, between which other
+/// connections may use the book
+const BACKUP_PAGES_PER_STEP: std::os::raw::c_int = 256;
+
+/// Time a snapshot was taken, between the name of the book and the reason
+const SNAPSHOT_TIMESTAMP: &str = "%Y%m%dT%H%M%S%.6fZ";
+
+/// Length of a [`SNAPSHOT_TIMESTAMP`], e.g. `20250303T120000.000000Z`
+const SNAPSHOT_TIMESTAMP_LEN: usize = 23;
+
+/// Where and how many snapshots of a book are kept, see
+/// [`Database::set_snapshot_policy`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotPolicy {
+    /// Directory the snapshots are written to, created if missing
+    pub directory: PathBuf,
+    /// How many of the most recent snapshots are kept; older ones are
+    /// removed as new ones are taken
+    pub keep: usize,
+}
+
+impl SnapshotPolicy {
+    /// Keeps the last [`DEFAULT_SNAPSHOT_RETENTION`] snapshots in `directory`
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        SnapshotPolicy {
+            directory: directory.into(),
+            keep: DEFAULT_SNAPSHOT_RETENTION,
+        }
+    }
+}
+
+impl Database {
+    /// Copies the book to `path` with SQLite's online backup API, replacing
+    /// any file there.
+    ///
+    /// The copy is consistent even while readers and the writer keep using
+    /// the book, and is a standalone file outside of WAL mode.
+    pub fn backup_to(&self, path: &str) -> Result<()> {
+        let mut dst = Connection::open(path)?;
+        Backup::new(self.conn(), &mut dst)?.run_to_completion(
+            BACKUP_PAGES_PER_STEP,
+            Duration::ZERO,
+            None,
+        )?;
+        dst.pragma_update_and_check(None, "journal_mode", "DELETE", |row| {
+            row.get::<_, String>(0)
+        })?;
+
+        Ok(())
+    }
+
+    /// Replaces the book with the backup at `path`.
+    ///
+    /// The backup must be at [`SCHEMA_VERSION`], pass SQLite's integrity and
+    /// foreign key checks, and every posted entry in it must balance;
+    /// otherwise the book is left untouched and [`Error::InvalidData`] says
+    /// why. A snapshot is taken first if the book has a policy.
+    pub fn restore_from(&mut self, path: &str) -> Result<()> {
+        if !Path::new(path).is_file() {
+            return Err(Error::InvalidData(format!("there is no backup at {path}")));
+        }
+        verify_backup(path)?;
+
+        self.snapshot_before("restore")?;
+        self.conn_mut().restore(
+            rusqlite::DatabaseName::Main,
+            path,
+            None::<fn(rusqlite::backup::Progress)>,
+        )?;
+
+        Ok(())
+    }
+
+    /// Takes a snapshot of the book into the directory of its policy and
+    /// removes the snapshots beyond those the policy keeps. `reason` ends
+    /// the file name, after the name of the book and the time it was taken,
+    /// with anything but letters, digits, `-` and `_` replaced by `_`.
+    pub fn snapshot(&self, reason: &str) -> Result<PathBuf> {
+        let policy = self
+            .snapshot_policy()
+            .ok_or_else(|| Error::InvalidData("this database has no snapshot policy".into()))?;
+        fs::create_dir_all(&policy.directory)?;
+
+        let reason: String = reason
+            .chars()
+            .map(|c| match c {
+                c if c.is_ascii_alphanumeric() || c == '-' || c == '_' => c,
+                _ => '_',
+            })
+            .collect();
+        let name = format!(
+            "{}-{}-{reason}.db",
+            self.snapshot_prefix(),
+            Utc::now().format(SNAPSHOT_TIMESTAMP)
+        );
+        let path = policy.directory.join(name);
+        let destination = path
+            .to_str()
+            .ok_or_else(|| Error::InvalidData(format!("{} is not a valid path", path.display())))?;
+        self.backup_to(destination)?;
+
+        let snapshots = self.list_snapshots()?;
+        let expired = snapshots.len().saturating_sub(policy.keep.max(1));
+        for snapshot in &snapshots[..expired] {
+            fs::remove_file(snapshot)?;
+        }
+
+        Ok(path)
+    }
+
+    /// The snapshots of the book, oldest first; none without a policy
+    pub fn list_snapshots(&self) -> Result<Vec<PathBuf>> {
+        let Some(policy) = self.snapshot_policy() else {
+            return Ok(Vec::new());
+        };
+        if !policy.directory.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let prefix = format!("{}-", self.snapshot_prefix());
+        let mut snapshots = Vec::new();
+        for entry in fs::read_dir(&policy.directory)? {
+            let path = entry?.path();
+            let is_snapshot = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(&prefix))
+                .is_some_and(is_snapshot_name);
+            if is_snapshot && path.is_file() {
+                snapshots.push(path);
+            }
+        }
+        // Timestamps sort in the order the snapshots were taken
+        snapshots.sort();
+
+        Ok(snapshots)
+    }
+
+    /// Takes a snapshot before `operation` if the book has a policy
+    pub(crate) fn snapshot_before(&self, operation: &str) -> Result<()> {
+        if self.snapshot_policy().is_some() {
+            self.snapshot(operation)?;
+        }
+        Ok(())
+    }
+
+    /// Snapshots are named after the book file, or `book` if it has none
+    fn snapshot_prefix(&self) -> String {
+        self.conn()
+            .path()
+            .and_then(|path| Path::new(path).file_stem())
+            .and_then(|stem| stem.to_str())
+            .filter(|stem| !stem.is_empty())
+            .unwrap_or("book")
+            .to_string()
+    }
+}
+
+/// Whether `name`, past the name of the book, is the time a snapshot was
+/// taken and its reason. Only the timestamp tells the snapshots of `family`
+/// from those of `family-2024` in the same directory.
+fn is_snapshot_name(name: &str) -> bool {
+    let (Some(timestamp), Some(rest)) = (
+        name.get(..SNAPSHOT_TIMESTAMP_LEN),
+        name.get(SNAPSHOT_TIMESTAMP_LEN..),
+    ) else {
+        return false;
+    };
+    NaiveDateTime::parse_from_str(timestamp, SNAPSHOT_TIMESTAMP).is_ok()
+        && rest.starts_with('-')
+        && rest.ends_with(".db")
+}
+
+/// Checks the backup at `path` before it replaces a book
+fn verify_backup(path: &str) -> Result<()> {
+    let conn = Connection::open(path)?;
+    register_sql_functions(&conn)?;
+    let backup = Database::from_parts(conn, None);
+
+    let version = backup.schema_version()?;
+    if version != SCHEMA_VERSION {
+        return Err(Error::InvalidData(format!(
+            "backup is at schema version {version}, not {SCHEMA_VERSION}"
+        )));
+    }
+
+    let problems = {
+        let mut stmt = backup.conn().prepare("PRAGMA integrity_check")?;
+        let problems = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        problems
+    };
+    if problems != ["ok"] {
+        return Err(Error::InvalidData(format!(
+            "backup is corrupt: {}",
+            problems.join("; ")
+        )));
+    }
+
+    let dangling: i64 =
+        backup
+            .conn()
+            .query_row("SELECT count(*) FROM pragma_foreign_key_check", [], |row| {
+                row.get(0)
+            })?;
+    if dangling > 0 {
+        return Err(Error::InvalidData(format!(
+            "backup has {dangling} rows referring to missing rows"
+        )));
+    }
+
+    for (id, entry) in backup.journal_entries(EntryStatus::Posted)? {
+        validate_journal_entry(&entry)
+            .map_err(|e| Error::InvalidData(format!("backup entry {id} does not balance: {e}")))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        migrations::drop_audit_log,
+        models::{NewJournalEntry, NewJournalEntryLine, NormalBalance},
+        money::Money,
+        pool::tests::TempBook,
+        seeding::init_sample_data,
+    };
+
+    /// A directory in the temporary directory, removed with its contents
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("mm-schema-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn sample_book(path: &str) -> Result<Database> {
+        let mut db = Database::new(path)?;
+        db.init_schema()?;
+        init_sample_data(&mut db)?;
+        Ok(db)
+    }
+
+    fn groceries(amount: Money) -> NewJournalEntry {
+        let line = |account: &str, entry_type| NewJournalEntryLine {
+            account_number: account.to_string(),
+            asset_code: "USD".to_string(),
+            entry_type,
+            amount,
+            reference_amount: None,
+            description: None,
+        };
+        NewJournalEntry {
+            date: Utc.with_ymd_and_hms(2025, 3, 3, 0, 0, 0).unwrap(),
+            description: "Groceries".to_string(),
+            reference_number: None,
+            reference_asset_code: "USD".to_string(),
+            status: EntryStatus::Posted,
+            lines: vec![
+                line("5301", NormalBalance::Debit),
+                line("1101", NormalBalance::Credit),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_backup_and_restore() -> Result<()> {
+        let book = TempBook::new("backup-source");
+        let copy = TempBook::new("backup-copy");
+        let mut db = sample_book(book.path())?;
+        db.create_journal_entry(&groceries(Money::new(dec!(45.20))))?;
+
+        // A reader in the middle of a transaction does not hold up the backup
+        let reader = db.reader()?;
+        reader.conn().execute_batch("BEGIN")?;
+        assert_eq!(reader.register("1101")?.len(), 1);
+        db.backup_to(copy.path())?;
+        reader.conn().execute_batch("COMMIT")?;
+
+        db.create_journal_entry(&groceries(Money::new(dec!(10))))?;
+        assert_eq!(db.register("1101")?.len(), 2);
+
+        db.restore_from(copy.path())?;
+        assert_eq!(db.register("1101")?.len(), 1);
+        assert_eq!(reader.register("1101")?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_rejects_bad_backups() -> Result<()> {
+        let book = TempBook::new("restore-target");
+        let backup = TempBook::new("restore-backup");
+        let mut db = sample_book(book.path())?;
+        db.create_journal_entry(&groceries(Money::new(dec!(45.20))))?;
+
+        assert!(matches!(
+            db.restore_from(backup.path()),
+            Err(Error::InvalidData(_))
+        ));
+
+        let mut other = sample_book(backup.path())?;
+        other.create_journal_entry(&groceries(Money::new(dec!(10))))?;
+        other.conn().pragma_update(None, "user_version", 2)?;
+        assert!(matches!(
+            db.restore_from(backup.path()),
+            Err(Error::InvalidData(_))
+        ));
+
+        other
+            .conn()
+            .pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        other.conn().execute(
+            "UPDATE journal_entry_lines SET amount = '1'
+             WHERE id = (SELECT min(id) FROM journal_entry_lines)",
+            [],
+        )?;
+        assert!(matches!(
+            db.restore_from(backup.path()),
+            Err(Error::InvalidData(msg)) if msg.contains("does not balance")
+        ));
+
+        // The book is left as it was
+        assert_eq!(db.register("1101")?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshots_are_rotated() -> Result<()> {
+        let book = TempBook::new("rotated");
+        let dir = TempDir::new("rotated-snapshots");
+        let mut db = sample_book(book.path())?;
+        assert!(db.snapshot("manual").is_err());
+
+        db.set_snapshot_policy(Some(SnapshotPolicy {
+            directory: dir.0.clone(),
+            keep: 3,
+        }));
+        let mut taken = Vec::new();
+        for _ in 0..5 {
+            taken.push(db.snapshot("manual")?);
+            thread::sleep(Duration::from_millis(2));
+        }
+
+        assert_eq!(db.list_snapshots()?, taken[2..]);
+        let name = taken[4].file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("mm-schema-") && name.ends_with("-manual.db"));
+
+        // A snapshot is a backup like any other
+        let restored = TempBook::new("rotated-restored");
+        let mut other = Database::new(restored.path())?;
+        other.restore_from(taken[4].to_str().unwrap())?;
+        assert_eq!(other.list_accounts()?.len(), db.list_accounts()?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshots_before_risky_operations() -> Result<()> {
+        let book = TempBook::new("risky");
+        let dir = TempDir::new("risky-snapshots");
+        let mut db = sample_book(book.path())?;
+
+        // Without a policy nothing is written
+        db.close_account("1301", Utc::now().date_naive())?;
+        assert!(!dir.0.exists());
+
+        db.set_snapshot_policy(Some(SnapshotPolicy::new(&dir.0)));
+        db.migrate()?;
+        assert!(db.list_snapshots()?.is_empty());
+
+        drop_audit_log(db.conn())?;
+        db.migrate()?;
+        db.import_book_document(&db.book_document()?)?;
+        db.close_account("1302", Utc::now().date_naive())?;
+
+        let reasons: Vec<String> = db
+            .list_snapshots()?
+            .iter()
+            .map(|path| {
+                let stem = path.file_stem().unwrap().to_str().unwrap();
+                stem.rsplit('-').next().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(reasons, ["migrate", "import", "close_account"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshots_of_books_sharing_a_directory() -> Result<()> {
+        let book = TempBook::new("family");
+        let other_book = TempBook::new("family-2024");
+        let dir = TempDir::new("family-snapshots");
+        let mut db = sample_book(book.path())?;
+        let mut other = sample_book(other_book.path())?;
+        for db in [&mut db, &mut other] {
+            db.set_snapshot_policy(Some(SnapshotPolicy {
+                directory: dir.0.clone(),
+                keep: 1,
+            }));
+        }
+
+        let theirs = other.snapshot("manual")?;
+        thread::sleep(Duration::from_millis(2));
+        let ours = db.snapshot("before 2025/03 import")?;
+        thread::sleep(Duration::from_millis(2));
+        let ours = [ours, db.snapshot("manual")?];
+
+        // Rotating one book leaves the snapshots of the other alone
+        assert_eq!(db.list_snapshots()?, ours[1..]);
+        assert_eq!(other.list_snapshots()?, [theirs]);
+        let name = ours[0].file_name().unwrap().to_str().unwrap();
+        assert!(name.ends_with("-before_2025_03_import.db"));
+        assert_eq!(ours[0].parent(), Some(dir.0.as_path()));
+
+        Ok(())
+    }
+}
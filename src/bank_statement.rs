@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use rusqlite::{named_params, Connection};
+
+use crate::{
+    error::{Error, Result},
+    import::{insert_statement_lines, ImportSummary, StatementLine},
+    interface::Database,
+    money::Money,
+};
+
+/// An account statement as delivered by a bank in camt.053 or MT940 format
+#[derive(Debug, Clone, PartialEq)]
+pub struct BankStatement {
+    /// The bank's statement id or number
+    pub id: String,
+    /// IBAN, or the bank's own account id, of the account the statement is for
+    pub account_id: String,
+    pub currency: String,
+    pub opening_balance: StatementBalance,
+    pub closing_balance: StatementBalance,
+    /// Booked entries, in statement order
+    pub entries: Vec<StatementEntry>,
+}
+
+/// A balance stated by the bank, positive when in the account holder's favour
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatementBalance {
+    pub date: NaiveDate,
+    pub amount: Money,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatementEntry {
+    /// Signed amount, positive for money coming into the account
+    pub amount: Money,
+    pub booking_date: NaiveDate,
+    /// Date the amount starts or stops earning interest
+    pub value_date: Option<NaiveDate>,
+    /// The payer of credits and the payee of debits
+    pub counterparty: Option<String>,
+    pub counterparty_iban: Option<String>,
+    /// Unstructured remittance information, or the structured creditor reference
+    pub remittance_info: Option<String>,
+    /// End-to-end or customer reference
+    pub reference: Option<String>,
+    /// The bank's unique id for the entry, if it provides one
+    pub bank_reference: Option<String>,
+}
+
+impl BankStatement {
+    /// Converts the entries to statement lines for the import pipeline,
+    /// identified by `{prefix}:` and the bank's reference, or by a fingerprint
+    /// if the bank provides none.
+    pub fn statement_lines(&self, prefix: &str) -> Vec<StatementLine> {
+        let mut occurrences: HashMap<String, usize> = HashMap::new();
+
+        self.entries
+            .iter()
+            .map(|entry| {
+                let description = match (&entry.counterparty, &entry.remittance_info) {
+                    (Some(counterparty), Some(info)) => format!("{counterparty} - {info}"),
+                    (Some(text), None) | (None, Some(text)) => text.clone(),
+                    (None, None) => String::new(),
+                };
+
+                let external_id = match &entry.bank_reference {
+                    Some(reference) => format!("{prefix}:{reference}"),
+                    None => {
+                        let fingerprint = format!(
+                            "{}|{}|{}|{}",
+                            entry.booking_date,
+                            entry.amount,
+                            entry.reference.as_deref().unwrap_or_default(),
+                            description.to_lowercase(),
+                        );
+                        let occurrence = occurrences.entry(fingerprint.clone()).or_default();
+                        *occurrence += 1;
+                        format!("{prefix}:{fingerprint}#{}", *occurrence - 1)
+                    }
+                };
+
+                StatementLine {
+                    date: entry.booking_date,
+                    amount: entry.amount,
+                    description,
+                    reference: entry.reference.clone(),
+                    external_id,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Outcome of importing bank statements
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatementImport {
+    pub summary: ImportSummary,
+    /// One per statement, in file order
+    pub balance_checks: Vec<BalanceCheck>,
+}
+
+impl StatementImport {
+    /// Whether every statement agrees with the ledger
+    pub fn is_balanced(&self) -> bool {
+        self.balance_checks.iter().all(BalanceCheck::is_balanced)
+    }
+}
+
+/// The balances a statement reports next to those of the ledger account it
+/// was imported into, including draft entries
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceCheck {
+    pub statement_id: String,
+    pub account_number: String,
+    pub opening_balance: Money,
+    /// Ledger balance at the end of the day before the statement starts
+    pub ledger_opening_balance: Money,
+    pub closing_balance: Money,
+    /// Ledger balance at the end of the statement's closing date
+    pub ledger_closing_balance: Money,
+}
+
+impl BalanceCheck {
+    pub fn is_balanced(&self) -> bool {
+        self.opening_balance == self.ledger_opening_balance
+            && self.closing_balance == self.ledger_closing_balance
+    }
+}
+
+impl Database {
+    /// Imports bank statements as draft entries on the accounts their account
+    /// ids are mapped to with [`Database::map_external_account`].
+    ///
+    /// Once the entries are recorded, the opening and closing balances of each
+    /// statement are compared with the ledger balance of its account; a
+    /// mismatch means entries are missing from, or were wrongly added to,
+    /// the ledger. See [`Database::import_statement_lines`].
+    pub fn import_bank_statements(
+        &mut self,
+        statements: &[BankStatement],
+        prefix: &str,
+    ) -> Result<StatementImport> {
+        let mut accounts = Vec::new();
+        for statement in statements {
+            let account_number = self.mapped_account(&statement.account_id)?.ok_or_else(|| {
+                Error::InvalidData(format!(
+                    "no account is mapped to bank account {:?}",
+                    statement.account_id
+                ))
+            })?;
+            accounts.push(account_number);
+        }
+
+        let mut import = StatementImport::default();
+        self.snapshot_before("import")?;
+        let t = self.transaction()?;
+
+        for (statement, account_number) in statements.iter().zip(&accounts) {
+            insert_statement_lines(
+                &t,
+                account_number,
+                &statement.currency,
+                &statement.statement_lines(prefix),
+                &mut import.summary,
+            )?;
+        }
+
+        for (statement, account_number) in statements.iter().zip(accounts) {
+            // camt.053 dates the opening balance on the first day of the
+            // statement, MT940 on the closing date of the previous one
+            let before_first_entry = statement
+                .entries
+                .iter()
+                .map(|entry| entry.booking_date)
+                .min()
+                .and_then(|date| date.pred_opt());
+            let opening_date = before_first_entry.map_or(statement.opening_balance.date, |date| {
+                date.min(statement.opening_balance.date)
+            });
+            let end = statement.closing_balance.date;
+
+            import.balance_checks.push(BalanceCheck {
+                statement_id: statement.id.clone(),
+                ledger_opening_balance: ledger_balance(
+                    &t,
+                    &account_number,
+                    &statement.currency,
+                    opening_date,
+                )?,
+                ledger_closing_balance: ledger_balance(
+                    &t,
+                    &account_number,
+                    &statement.currency,
+                    end,
+                )?,
+                opening_balance: statement.opening_balance.amount,
+                closing_balance: statement.closing_balance.amount,
+                account_number,
+            });
+        }
+
+        t.commit()?;
+
+        Ok(import)
+    }
+}
+
+/// Debit balance of `account_number` in `asset_code` at the end of `as_of`,
+/// counting draft and posted entries
+fn ledger_balance(
+    t: &Connection,
+    account_number: &str,
+    asset_code: &str,
+    as_of: NaiveDate,
+) -> Result<Money> {
+    let balance: Option<Money> = t.query_row(
+        "SELECT dec_sum(
+                CASE WHEN jel.entry_type = 'DEBIT' THEN jel.amount ELSE dec_neg(jel.amount) END
+            )
+         FROM journal_entry_lines jel
+         JOIN journal_entries je ON je.id = jel.journal_entry_id
+         WHERE jel.account_id = (SELECT id FROM accounts WHERE account_number = :account_number)
+           AND jel.asset_id = (SELECT id FROM assets WHERE code = :asset_code)
+           AND je.status != 'VOID'
+           AND date(je.date) <= :as_of",
+        named_params! {
+            ":account_number": account_number,
+            ":asset_code": asset_code,
+            ":as_of": as_of,
+        },
+        |row| row.get(0),
+    )?;
+
+    Ok(Money::new(balance.unwrap_or_default().amount().normalize()))
+}
@@ -0,0 +1,369 @@
+use std::io::Read;
+
+use chrono::NaiveDate;
+use quick_xml::{events::Event, Reader};
+
+use crate::{
+    bank_statement::{BankStatement, StatementBalance, StatementEntry, StatementImport},
+    error::{Error, Result},
+    interface::Database,
+    money::Money,
+};
+
+impl Database {
+    /// Imports an ISO 20022 camt.053 bank-to-customer statement.
+    /// See [`Database::import_bank_statements`].
+    pub fn import_camt053<R: Read>(&mut self, mut reader: R) -> Result<StatementImport> {
+        let mut xml = String::new();
+        reader.read_to_string(&mut xml)?;
+        let statements = parse_camt053(&xml)?;
+
+        self.import_bank_statements(&statements, "camt")
+    }
+}
+
+/// Parses the statements of a camt.053 document, of any version.
+///
+/// Only booked entries are read: pending and information-only entries are
+/// left out, as the bank reports them again once booked.
+pub fn parse_camt053(xml: &str) -> Result<Vec<BankStatement>> {
+    let document = parse_xml(xml)?;
+    let report = document
+        .child("BkToCstmrStmt")
+        .ok_or_else(|| Error::InvalidData("not a camt.053 document".into()))?;
+
+    report.children("Stmt").map(statement).collect()
+}
+
+fn statement(stmt: &XmlElement) -> Result<BankStatement> {
+    let account = stmt
+        .child("Acct")
+        .ok_or_else(|| Error::InvalidData("statement without Acct".into()))?;
+    let account_id = account
+        .text_at(&["Id", "IBAN"])
+        .or_else(|| account.text_at(&["Id", "Othr", "Id"]))
+        .ok_or_else(|| Error::InvalidData("statement without an account id".into()))?;
+
+    let balance = |codes: &[&str]| -> Result<Option<(StatementBalance, String)>> {
+        for bal in stmt.children("Bal") {
+            let code = bal
+                .text_at(&["Tp", "CdOrPrtry", "Cd"])
+                .or_else(|| bal.text_at(&["Tp", "CdOrPrtry", "Prtry"]))
+                .unwrap_or_default();
+            if codes.contains(&code) {
+                let (amount, currency) = amount(bal)?;
+                return Ok(Some((
+                    StatementBalance {
+                        date: date(bal, "Dt")?,
+                        amount,
+                    },
+                    currency,
+                )));
+            }
+        }
+        Ok(None)
+    };
+    // Previously closed booked balance, used instead of OPBD by some banks
+    let (opening_balance, currency) = balance(&["OPBD", "PRCD"])?
+        .ok_or_else(|| Error::InvalidData("statement without an opening balance".into()))?;
+    let (closing_balance, _) = balance(&["CLBD"])?
+        .ok_or_else(|| Error::InvalidData("statement without a closing balance".into()))?;
+
+    let entries = stmt
+        .children("Ntry")
+        .filter(|ntry| {
+            // Sts is a code up to version 7 and a choice of codes since
+            ntry.text_at(&["Sts"])
+                .or_else(|| ntry.text_at(&["Sts", "Cd"]))
+                .is_none_or(|status| status == "BOOK")
+        })
+        .map(entry)
+        .collect::<Result<_>>()?;
+
+    Ok(BankStatement {
+        id: stmt.text_at(&["Id"]).unwrap_or_default().to_string(),
+        account_id: account_id.to_string(),
+        currency: account
+            .text_at(&["Ccy"])
+            .map(String::from)
+            .unwrap_or(currency),
+        opening_balance,
+        closing_balance,
+        entries,
+    })
+}
+
+fn entry(ntry: &XmlElement) -> Result<StatementEntry> {
+    let (amount, _) = amount(ntry)?;
+    let details = ntry.child("NtryDtls").and_then(|d| d.child("TxDtls"));
+
+    // The counterparty of a credit is its debtor, of a debit its creditor
+    let (party, party_account) = match amount.signum() {
+        -1 => ("Cdtr", "CdtrAcct"),
+        _ => ("Dbtr", "DbtrAcct"),
+    };
+    let parties = details.and_then(|d| d.child("RltdPties"));
+    let counterparty = parties.and_then(|p| {
+        p.text_at(&[party, "Nm"])
+            .or_else(|| p.text_at(&[party, "Pty", "Nm"]))
+    });
+    let counterparty_iban = parties.and_then(|p| p.text_at(&[party_account, "Id", "IBAN"]));
+
+    let remittance = details.and_then(|d| d.child("RmtInf"));
+    let unstructured = remittance
+        .map(|r| {
+            r.children("Ustrd")
+                .filter_map(|u| u.text.as_deref())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|text| !text.is_empty());
+    let remittance_info = unstructured
+        .or_else(|| {
+            remittance
+                .and_then(|r| r.text_at(&["Strd", "CdtrRefInf", "Ref"]))
+                .map(String::from)
+        })
+        .or_else(|| ntry.text_at(&["AddtlNtryInf"]).map(String::from));
+
+    let reference = details
+        .and_then(|d| d.text_at(&["Refs", "EndToEndId"]))
+        .filter(|id| *id != "NOTPROVIDED");
+
+    Ok(StatementEntry {
+        amount,
+        booking_date: date(ntry, "BookgDt")?,
+        value_date: date(ntry, "ValDt").ok(),
+        counterparty: counterparty.map(String::from),
+        counterparty_iban: counterparty_iban.map(String::from),
+        remittance_info,
+        reference: reference.map(String::from),
+        bank_reference: ntry.text_at(&["AcctSvcrRef"]).map(String::from),
+    })
+}
+
+/// The signed `Amt` of a balance or entry, with its currency
+fn amount(element: &XmlElement) -> Result<(Money, String)> {
+    let amt = element
+        .child("Amt")
+        .ok_or_else(|| Error::InvalidData(format!("{} without Amt", element.name)))?;
+    let value = amt.text.as_deref().unwrap_or_default();
+    let money = value
+        .parse::<Money>()
+        .map_err(|_| Error::InvalidData(format!("invalid amount {value:?}")))?;
+    let money = Money::new(money.amount().normalize());
+
+    let signed = match element.text_at(&["CdtDbtInd"]) {
+        Some("DBIT") => -money,
+        Some("CRDT") => money,
+        other => {
+            return Err(Error::InvalidData(format!(
+                "invalid credit/debit indicator {other:?}"
+            )))
+        }
+    };
+
+    Ok((signed, amt.attribute("Ccy").unwrap_or_default().to_string()))
+}
+
+/// A `Dt` or `DtTm` date below `element`'s child `name`
+fn date(element: &XmlElement, name: &str) -> Result<NaiveDate> {
+    let value = element
+        .text_at(&[name, "Dt"])
+        .or_else(|| element.text_at(&[name, "DtTm"]))
+        .ok_or_else(|| Error::InvalidData(format!("{} without {name}", element.name)))?;
+
+    value
+        .get(..10)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .ok_or_else(|| Error::InvalidData(format!("invalid date {value:?}")))
+}
+
+/// An XML element with namespace prefixes stripped from its name
+#[derive(Debug, Default)]
+struct XmlElement {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: Option<String>,
+    elements: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.elements.iter().find(|element| element.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlElement> {
+        self.elements
+            .iter()
+            .filter(move |element| element.name == name)
+    }
+
+    fn text_at(&self, path: &[&str]) -> Option<&str> {
+        path.iter()
+            .try_fold(self, |element, name| element.child(name))?
+            .text
+            .as_deref()
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn parse_xml(xml: &str) -> Result<XmlElement> {
+    let invalid = |e: quick_xml::Error| Error::InvalidData(format!("invalid XML: {e}"));
+
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut stack = vec![XmlElement::default()];
+    loop {
+        match reader.read_event().map_err(invalid)? {
+            Event::Start(start) => stack.push(element(&start)?),
+            Event::Empty(start) => {
+                let element = element(&start)?;
+                stack.last_mut().expect("root").elements.push(element);
+            }
+            Event::Text(text) => {
+                let text = text.unescape().map_err(invalid)?;
+                stack.last_mut().expect("root").text = Some(text.into_owned());
+            }
+            Event::CData(data) => {
+                let text = String::from_utf8_lossy(&data).into_owned();
+                stack.last_mut().expect("root").text = Some(text);
+            }
+            Event::End(_) => {
+                let element = stack.pop().expect("balanced by the reader");
+                stack
+                    .last_mut()
+                    .ok_or_else(|| Error::InvalidData("unbalanced XML".into()))?
+                    .elements
+                    .push(element);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let mut root = stack
+        .pop()
+        .filter(|_| stack.is_empty())
+        .ok_or_else(|| Error::InvalidData("unterminated XML element".into()))?;
+    root.elements
+        .pop()
+        .ok_or_else(|| Error::InvalidData("empty XML document".into()))
+}
+
+fn element(start: &quick_xml::events::BytesStart) -> Result<XmlElement> {
+    let local_name = |name: &[u8]| {
+        let name = String::from_utf8_lossy(name).into_owned();
+        match name.rsplit_once(':') {
+            Some((_, local)) => local.to_string(),
+            None => name,
+        }
+    };
+
+    let attributes = start
+        .attributes()
+        .map(|attribute| {
+            let attribute =
+                attribute.map_err(|e| Error::InvalidData(format!("invalid XML attribute: {e}")))?;
+            let value = attribute
+                .unescape_value()
+                .map_err(|e| Error::InvalidData(format!("invalid XML attribute: {e}")))?;
+            Ok((local_name(attribute.key.as_ref()), value.into_owned()))
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(XmlElement {
+        name: local_name(start.name().as_ref()),
+        attributes,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_parse_camt053() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.08">
+              <BkToCstmrStmt>
+                <GrpHdr><MsgId>MSG1</MsgId><CreDtTm>2025-02-01T06:00:00</CreDtTm></GrpHdr>
+                <Stmt>
+                  <Id>STMT-2025-01</Id>
+                  <Acct><Id><IBAN>DE89370400440532013000</IBAN></Id><Ccy>EUR</Ccy></Acct>
+                  <Bal>
+                    <Tp><CdOrPrtry><Cd>PRCD</Cd></CdOrPrtry></Tp>
+                    <Amt Ccy="EUR">100.00</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+                    <Dt><Dt>2024-12-31</Dt></Dt>
+                  </Bal>
+                  <Bal>
+                    <Tp><CdOrPrtry><Cd>CLBD</Cd></CdOrPrtry></Tp>
+                    <Amt Ccy="EUR">54.80</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+                    <Dt><Dt>2025-01-31</Dt></Dt>
+                  </Bal>
+                  <Ntry>
+                    <Amt Ccy="EUR">45.20</Amt><CdtDbtInd>DBIT</CdtDbtInd>
+                    <Sts><Cd>BOOK</Cd></Sts>
+                    <BookgDt><Dt>2025-01-02</Dt></BookgDt>
+                    <ValDt><Dt>2025-01-03</Dt></ValDt>
+                    <AcctSvcrRef>REF-1</AcctSvcrRef>
+                    <NtryDtls><TxDtls>
+                      <Refs><EndToEndId>E2E-1</EndToEndId></Refs>
+                      <RltdPties>
+                        <Cdtr><Pty><Nm>Supermarket &amp; Co</Nm></Pty></Cdtr>
+                        <CdtrAcct><Id><IBAN>DE02120300000000202051</IBAN></Id></CdtrAcct>
+                      </RltdPties>
+                      <RmtInf><Ustrd>Card payment</Ustrd><Ustrd>1234</Ustrd></RmtInf>
+                    </TxDtls></NtryDtls>
+                  </Ntry>
+                  <Ntry>
+                    <Amt Ccy="EUR">10.00</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+                    <Sts><Cd>PDNG</Cd></Sts>
+                    <BookgDt><Dt>2025-01-31</Dt></BookgDt>
+                  </Ntry>
+                </Stmt>
+              </BkToCstmrStmt>
+            </Document>"#;
+
+        let statements = parse_camt053(xml).unwrap();
+        let statement = &statements[0];
+        assert_eq!(statement.id, "STMT-2025-01");
+        assert_eq!(statement.account_id, "DE89370400440532013000");
+        assert_eq!(statement.currency, "EUR");
+        assert_eq!(statement.opening_balance.amount, Money::new(dec!(100)));
+        assert_eq!(
+            statement.closing_balance.date,
+            NaiveDate::from_ymd_opt(2025, 1, 31).unwrap()
+        );
+
+        assert_eq!(statement.entries.len(), 1);
+        assert_eq!(
+            statement.entries[0],
+            StatementEntry {
+                amount: Money::new(dec!(-45.2)),
+                booking_date: NaiveDate::from_ymd_opt(2025, 1, 2).unwrap(),
+                value_date: NaiveDate::from_ymd_opt(2025, 1, 3),
+                counterparty: Some("Supermarket & Co".into()),
+                counterparty_iban: Some("DE02120300000000202051".into()),
+                remittance_info: Some("Card payment 1234".into()),
+                reference: Some("E2E-1".into()),
+                bank_reference: Some("REF-1".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_other_documents() {
+        assert!(parse_camt053("<Document><CstmrCdtTrfInitn/></Document>").is_err());
+        assert!(parse_camt053("<Document><BkToCstmrStmt>").is_err());
+    }
+}
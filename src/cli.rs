@@ -0,0 +1,915 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+};
+
+use chrono::{NaiveDate, NaiveTime, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use serde_json::value::RawValue;
+
+use mm_schema::{
+    init_sample_data, AssetType, Database, Denomination, EntryStatus, Error, Money, MoneyFormat,
+    NewAccount, NewJournalEntry, NewJournalEntryLine, NormalBalance, PlainTextFormat, QifOptions,
+    Result, SCHEMA_VERSION,
+};
+
+use crate::tui;
+
+/// Double-entry bookkeeping in a SQLite book
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// Path of the book
+    #[arg(long, global = true, default_value = "book.db")]
+    pub db: PathBuf,
+
+    /// How results are printed
+    #[arg(long, global = true, value_enum, default_value_t = Output::Table)]
+    pub output: Output,
+
+    /// Who the changes are recorded as made by in the audit log, kept in the
+    /// book until it is set again
+    #[arg(long, global = true, env = "MM_SCHEMA_ACTOR")]
+    pub actor: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    /// Aligned columns
+    Table,
+    /// An array with one object per row
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Creates the book, or upgrades an existing one to the current schema
+    Init {
+        /// Adds the sample chart of accounts and assets to a new book
+        #[arg(long)]
+        sample: bool,
+    },
+    /// Lists, adds and closes accounts
+    #[command(subcommand)]
+    Accounts(AccountsCommand),
+    /// Lists and adds assets
+    #[command(subcommand)]
+    Assets(AssetsCommand),
+    /// Records and voids journal entries
+    #[command(subcommand)]
+    Tx(TxCommand),
+    /// Prints the balance of every account with posted entries
+    Balance,
+    /// Prints the entries booked to an account with running balances
+    Register {
+        /// Account number
+        account: String,
+    },
+    /// Imports a statement or journal file
+    Import(ImportArgs),
+    /// Exports the book, or the register of one account for QIF
+    Export(ExportArgs),
+    /// Browses the book and enters transactions in a terminal UI
+    Tui,
+    /// Serves the book as a JSON API on localhost
+    #[cfg(feature = "server")]
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: std::net::SocketAddr,
+        /// Bearer token every request must carry
+        #[arg(long, env = "MM_SCHEMA_TOKEN", hide_env_values = true)]
+        token: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AccountsCommand {
+    /// Lists open accounts
+    List {
+        /// Includes closed accounts
+        #[arg(long)]
+        all: bool,
+    },
+    /// Adds an account
+    Add {
+        number: String,
+        name: String,
+        /// Name of the account type, such as Asset or Expense
+        #[arg(long = "type")]
+        account_type: String,
+        /// Number of the parent account
+        #[arg(long)]
+        parent: Option<String>,
+        /// Opening date, today if left out
+        #[arg(long)]
+        opened: Option<NaiveDate>,
+        #[arg(long)]
+        description: Option<String>,
+    },
+    /// Closes an account without a balance
+    Close {
+        number: String,
+        /// Closing date, today if left out
+        #[arg(long)]
+        date: Option<NaiveDate>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AssetsCommand {
+    /// Lists assets
+    List,
+    /// Adds an asset
+    Add {
+        code: String,
+        name: String,
+        #[arg(long = "type", value_parser = parse_asset_type)]
+        asset_type: AssetType,
+        #[arg(long, default_value_t = 2)]
+        decimals: i64,
+        #[arg(long)]
+        description: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TxCommand {
+    /// Records a journal entry given by flags, or as JSON on standard input
+    Add(TxAddArgs),
+    /// Marks a journal entry as void
+    Void {
+        /// Journal entry id
+        id: i64,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct TxAddArgs {
+    /// Reads a JSON journal entry from standard input instead of the flags
+    #[arg(long, conflicts_with_all = ["date", "description", "asset", "reference", "draft", "lines"])]
+    pub stdin: bool,
+    #[arg(long, required_unless_present = "stdin")]
+    pub date: Option<NaiveDate>,
+    #[arg(long, required_unless_present = "stdin")]
+    pub description: Option<String>,
+    /// Reference asset the entry balances in
+    #[arg(long, required_unless_present = "stdin")]
+    pub asset: Option<String>,
+    #[arg(long)]
+    pub reference: Option<String>,
+    /// Records the entry as a draft rather than posted
+    #[arg(long)]
+    pub draft: bool,
+    /// `ACCOUNT AMOUNT [ASSET] [@@ REFERENCE_AMOUNT]`, positive for debits;
+    /// repeat for each line
+    #[arg(long = "line", required_unless_present = "stdin")]
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    Csv,
+    Ofx,
+    Qif,
+    Camt053,
+    Mt940,
+    Beancount,
+    Ledger,
+    Json,
+}
+
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    #[arg(value_enum)]
+    pub format: ImportFormat,
+    /// File to read, standard input if left out
+    pub file: Option<PathBuf>,
+    /// Account the statement is imported into (csv, qif)
+    #[arg(long)]
+    pub account: Option<String>,
+    /// Currency of the statement (csv, qif)
+    #[arg(long)]
+    pub asset: Option<String>,
+    /// Saved import profile (csv)
+    #[arg(long)]
+    pub profile: Option<String>,
+    /// Dates are written day first (qif)
+    #[arg(long)]
+    pub day_first: bool,
+    /// Amounts use a decimal comma (qif)
+    #[arg(long)]
+    pub decimal_comma: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Beancount,
+    Ledger,
+    Qif,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    #[arg(value_enum)]
+    pub format: ExportFormat,
+    /// File to write, standard output if left out
+    pub file: Option<PathBuf>,
+    /// Account whose register is exported (qif)
+    #[arg(long)]
+    pub account: Option<String>,
+    /// Asset of the register (qif)
+    #[arg(long)]
+    pub asset: Option<String>,
+    /// Dates are written day first (qif)
+    #[arg(long)]
+    pub day_first: bool,
+    /// Amounts use a decimal comma (qif)
+    #[arg(long)]
+    pub decimal_comma: bool,
+}
+
+/// Opens the book and runs the command, printing results to standard output
+pub fn run(cli: &Cli) -> Result<()> {
+    let path = cli.db.to_string_lossy();
+    // Only `init` creates the book, so that a mistyped path is not left behind
+    // as an empty one
+    if !matches!(cli.command, Command::Init { .. }) && !cli.db.exists() {
+        return Err(Error::InvalidData(format!(
+            "{path} does not exist, run `init` to create it"
+        )));
+    }
+
+    let mut db = Database::new(&path)?;
+
+    if !matches!(cli.command, Command::Init { .. }) {
+        let version = db.schema_version()?;
+        if version != SCHEMA_VERSION {
+            return Err(Error::InvalidData(format!(
+                "{path} is at schema version {version} instead of {SCHEMA_VERSION}, \
+                 run `init` to create or upgrade it"
+            )));
+        }
+    }
+
+    let stdout = io::stdout();
+    execute(&mut db, cli, stdout.lock())
+}
+
+/// Runs the command against an open book
+pub fn execute<W: Write>(db: &mut Database, cli: &Cli, mut out: W) -> Result<()> {
+    let today = Utc::now().date_naive();
+    // The actor is kept in the book, which `init` may first have to create
+    if !matches!(cli.command, Command::Init { .. }) {
+        set_actor(db, cli)?;
+    }
+
+    let table = match &cli.command {
+        Command::Init { sample } => {
+            let new = db.schema_version()? == 0;
+            db.migrate()?;
+            set_actor(db, cli)?;
+            if *sample {
+                if !new {
+                    return Err(Error::InvalidData(
+                        "sample data can only be added to a new book".into(),
+                    ));
+                }
+                init_sample_data(db)?;
+            }
+            let mut table = Table::new(&["schema_version"]);
+            table.push(vec![integer(db.schema_version()?)]);
+            Some(table)
+        }
+
+        Command::Accounts(AccountsCommand::List { all }) => {
+            let types = db.list_account_types()?;
+            let accounts = db.list_accounts()?;
+            let mut table = Table::new(&[
+                "number",
+                "name",
+                "type",
+                "parent",
+                "opened",
+                "closed",
+                "description",
+            ]);
+            let mut sorted: Vec<_> = accounts.iter().filter(|a| *all || a.is_active).collect();
+            sorted.sort_by(|a, b| a.account_number.cmp(&b.account_number));
+            for account in sorted {
+                let parent = account.parent_account_id.and_then(|id| {
+                    accounts
+                        .iter()
+                        .find(|parent| parent.id == id)
+                        .map(|parent| parent.account_number.clone())
+                });
+                let account_type = types
+                    .iter()
+                    .find(|t| t.id == account.account_type_id)
+                    .map(|t| t.name.clone());
+                table.push(vec![
+                    text(&account.account_number),
+                    text(&account.name),
+                    optional(account_type),
+                    optional(parent),
+                    text(account.opening_date),
+                    optional(account.closing_date),
+                    optional(account.description.as_ref()),
+                ]);
+            }
+            Some(table)
+        }
+
+        Command::Accounts(AccountsCommand::Add {
+            number,
+            name,
+            account_type,
+            parent,
+            opened,
+            description,
+        }) => {
+            let id = db.open_account(&NewAccount {
+                account_number: number.clone(),
+                name: name.clone(),
+                account_type: account_type.clone(),
+                parent_account_number: parent.clone(),
+                opening_date: opened.unwrap_or(today),
+                description: description.clone(),
+            })?;
+            let mut table = Table::new(&["id"]);
+            table.push(vec![integer(id)]);
+            Some(table)
+        }
+
+        Command::Accounts(AccountsCommand::Close { number, date }) => {
+            db.close_account(number, date.unwrap_or(today))?;
+            None
+        }
+
+        Command::Assets(AssetsCommand::List) => {
+            let mut table = Table::new(&["code", "name", "type", "decimals", "description"]);
+            for asset in db.list_assets()? {
+                table.push(vec![
+                    text(&asset.code),
+                    text(&asset.name),
+                    text(format!("{:?}", asset.asset_type).to_uppercase()),
+                    integer(asset.decimals),
+                    optional(asset.description),
+                ]);
+            }
+            Some(table)
+        }
+
+        Command::Assets(AssetsCommand::Add {
+            code,
+            name,
+            asset_type,
+            decimals,
+            description,
+        }) => {
+            let id = db.create_asset(
+                code.as_str(),
+                name.as_str(),
+                *asset_type,
+                *decimals,
+                description.as_deref(),
+            )?;
+            let mut table = Table::new(&["id"]);
+            table.push(vec![integer(id)]);
+            Some(table)
+        }
+
+        Command::Tx(TxCommand::Add(args)) => {
+            let entry = match args.stdin {
+                true => serde_json::from_reader(io::stdin().lock())?,
+                false => journal_entry(args)?,
+            };
+            let id = db.create_journal_entry(&entry)?;
+            let mut table = Table::new(&["id"]);
+            table.push(vec![integer(id)]);
+            Some(table)
+        }
+
+        Command::Tui => {
+            tui::run(db)?;
+            None
+        }
+
+        #[cfg(feature = "server")]
+        Command::Serve { addr, token } => {
+            eprintln!("listening on http://{addr}");
+            mm_schema::serve(db, *addr, token)?;
+            None
+        }
+
+        Command::Tx(TxCommand::Void { id }) => {
+            db.void_journal_entry(*id)?;
+            None
+        }
+
+        Command::Balance => {
+            let decimals = asset_decimals(db)?;
+            let mut table = Table::new(&["number", "name", "asset", "balance"]);
+            for row in db.get_general_balance()? {
+                let decimals = decimals.get(&row.asset).copied();
+                table.push(vec![
+                    text(row.account_number),
+                    text(row.account_name),
+                    text(row.asset),
+                    amount(row.balance, decimals),
+                ]);
+            }
+            Some(table)
+        }
+
+        Command::Register { account } => {
+            let decimals = asset_decimals(db)?;
+            let mut table = Table::new(&[
+                "date",
+                "entry",
+                "reference",
+                "description",
+                "status",
+                "asset",
+                "amount",
+                "balance",
+            ]);
+            for line in db.register(account)? {
+                let decimals = decimals.get(&line.asset).copied();
+                table.push(vec![
+                    text(line.date.date_naive()),
+                    integer(line.journal_entry_id),
+                    optional(line.reference_number),
+                    text(line.description),
+                    text(format!("{:?}", line.status).to_uppercase()),
+                    text(line.asset),
+                    amount(line.amount, decimals),
+                    amount(line.balance, decimals),
+                ]);
+            }
+            Some(table)
+        }
+
+        Command::Import(args) => Some(import(db, args)?),
+
+        Command::Export(args) => {
+            let mut writer: Box<dyn Write> = match &args.file {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(&mut out),
+            };
+            match args.format {
+                ExportFormat::Json => db.export_json(&mut writer)?,
+                ExportFormat::Beancount => db.export_beancount(&mut writer)?,
+                ExportFormat::Ledger => db.export_ledger(&mut writer)?,
+                ExportFormat::Qif => db.export_qif(
+                    required(&args.account, "--account", "qif")?,
+                    required(&args.asset, "--asset", "qif")?,
+                    &mut writer,
+                    &qif_options(args.day_first, args.decimal_comma),
+                )?,
+            }
+            writer.flush()?;
+            None
+        }
+    };
+
+    if let Some(table) = table {
+        table.write(cli.output, &mut out)?;
+    }
+
+    Ok(())
+}
+
+fn set_actor(db: &mut Database, cli: &Cli) -> Result<()> {
+    match &cli.actor {
+        Some(actor) => db.set_actor(actor),
+        None => Ok(()),
+    }
+}
+
+fn import(db: &mut Database, args: &ImportArgs) -> Result<Table> {
+    let reader: Box<dyn Read> = match &args.file {
+        Some(path) => Box::new(BufReader::new(File::open(path)?)),
+        None => Box::new(io::stdin().lock()),
+    };
+    let format = format!("{:?}", args.format).to_lowercase();
+    let account = || required(&args.account, "--account", &format);
+    let asset = || required(&args.asset, "--asset", &format);
+
+    // Entries created, duplicates, skipped lines and warnings
+    let (entries, duplicates, skipped, warnings) = match args.format {
+        ImportFormat::Csv => {
+            let summary = db.import_csv(
+                required(&args.profile, "--profile", "csv")?,
+                account()?,
+                asset()?,
+                reader,
+            )?;
+            (
+                summary.entry_ids.len(),
+                summary.duplicates,
+                summary.skipped,
+                vec![],
+            )
+        }
+        ImportFormat::Ofx => {
+            let summary = db.import_ofx(reader)?;
+            (
+                summary.entry_ids.len(),
+                summary.duplicates,
+                summary.skipped,
+                vec![],
+            )
+        }
+        ImportFormat::Qif => {
+            let summary = db.import_qif(
+                account()?,
+                asset()?,
+                reader,
+                &qif_options(args.day_first, args.decimal_comma),
+            )?;
+            (
+                summary.entry_ids.len(),
+                summary.duplicates,
+                summary.skipped,
+                vec![],
+            )
+        }
+        ImportFormat::Camt053 | ImportFormat::Mt940 => {
+            let import = match args.format {
+                ImportFormat::Camt053 => db.import_camt053(reader)?,
+                _ => db.import_mt940(reader)?,
+            };
+            let warnings = import
+                .balance_checks
+                .iter()
+                .filter(|check| !check.is_balanced())
+                .map(|check| {
+                    format!(
+                        "statement {} does not match account {}: opening {} against {}, closing {} against {}",
+                        check.statement_id,
+                        check.account_number,
+                        check.opening_balance,
+                        check.ledger_opening_balance,
+                        check.closing_balance,
+                        check.ledger_closing_balance,
+                    )
+                })
+                .collect();
+            let summary = import.summary;
+            (
+                summary.entry_ids.len(),
+                summary.duplicates,
+                summary.skipped,
+                warnings,
+            )
+        }
+        ImportFormat::Beancount | ImportFormat::Ledger => {
+            let format = match args.format {
+                ImportFormat::Beancount => PlainTextFormat::Beancount,
+                _ => PlainTextFormat::Ledger,
+            };
+            let import = db.import_plain_text(format, reader)?;
+            let warnings = import
+                .warnings
+                .iter()
+                .map(|w| format!("line {}: {}: {}", w.line, w.directive, w.message))
+                .collect();
+            (import.entry_ids.len(), 0, 0, warnings)
+        }
+        ImportFormat::Json => {
+            let import = db.import_json(reader)?;
+            (import.journal_entry_ids.len(), 0, 0, vec![])
+        }
+    };
+
+    for warning in &warnings {
+        eprintln!("warning: {warning}");
+    }
+
+    let mut table = Table::new(&["entries", "duplicates", "skipped", "warnings"]);
+    table.push(vec![
+        integer(entries),
+        integer(duplicates),
+        integer(skipped),
+        integer(warnings.len()),
+    ]);
+    Ok(table)
+}
+
+/// Builds a journal entry from the `tx add` flags
+fn journal_entry(args: &TxAddArgs) -> Result<NewJournalEntry> {
+    let (Some(date), Some(description), Some(asset)) = (args.date, &args.description, &args.asset)
+    else {
+        return Err(Error::InvalidData(
+            "--date, --description and --asset are required".into(),
+        ));
+    };
+
+    Ok(NewJournalEntry {
+        date: date.and_time(NaiveTime::MIN).and_utc(),
+        description: description.clone(),
+        reference_number: args.reference.clone(),
+        reference_asset_code: asset.clone(),
+        status: match args.draft {
+            true => EntryStatus::Draft,
+            false => EntryStatus::Posted,
+        },
+        lines: args
+            .lines
+            .iter()
+            .map(|line| journal_entry_line(line, asset))
+            .collect::<Result<_>>()?,
+    })
+}
+
+/// Parses `ACCOUNT AMOUNT [ASSET] [@@ REFERENCE_AMOUNT]`, where a positive
+/// amount is a debit and the asset defaults to the reference asset
+pub(crate) fn journal_entry_line(text: &str, reference_asset: &str) -> Result<NewJournalEntryLine> {
+    let invalid = || Error::InvalidData(format!("invalid line {text:?}"));
+    let money = |text: &str| text.trim().parse::<Money>().map_err(|_| invalid());
+
+    let (posting, reference_amount) = match text.split_once("@@") {
+        Some((posting, reference)) => (posting, Some(money(reference)?.abs())),
+        None => (text, None),
+    };
+    let mut words = posting.split_whitespace();
+    let account_number = words.next().ok_or_else(invalid)?;
+    let amount = money(words.next().ok_or_else(invalid)?)?;
+    let asset_code = words.next().unwrap_or(reference_asset);
+    if words.next().is_some() {
+        return Err(invalid());
+    }
+
+    Ok(NewJournalEntryLine {
+        account_number: account_number.to_string(),
+        asset_code: asset_code.to_string(),
+        entry_type: match amount.signum() {
+            -1 => NormalBalance::Credit,
+            _ => NormalBalance::Debit,
+        },
+        amount: amount.abs(),
+        reference_amount,
+        description: None,
+    })
+}
+
+fn required<'a>(value: &'a Option<String>, flag: &str, format: &str) -> Result<&'a str> {
+    value
+        .as_deref()
+        .ok_or_else(|| Error::InvalidData(format!("{flag} is required for {format}")))
+}
+
+fn qif_options(day_first: bool, decimal_comma: bool) -> QifOptions {
+    QifOptions {
+        day_first,
+        decimal_separator: match decimal_comma {
+            true => ',',
+            false => '.',
+        },
+    }
+}
+
+fn parse_asset_type(text: &str) -> std::result::Result<AssetType, String> {
+    text.to_uppercase().parse()
+}
+
+/// Decimals of every asset keyed by code
+fn asset_decimals(db: &Database) -> Result<HashMap<String, u32>> {
+    Ok(db
+        .list_assets()?
+        .iter()
+        .map(|asset| (asset.code.clone(), Denomination::from(asset).decimals))
+        .collect())
+}
+
+fn text(value: impl ToString) -> Cell {
+    Cell::Text(value.to_string())
+}
+
+fn integer(value: impl ToString) -> Cell {
+    Cell::Number(value.to_string())
+}
+
+fn optional(value: Option<impl ToString>) -> Cell {
+    value.map_or(Cell::Null, text)
+}
+
+/// An amount written with the `decimals` of its asset, or as it is stored if
+/// the asset is unknown
+fn amount(money: Money, decimals: Option<u32>) -> Cell {
+    let format = MoneyFormat {
+        decimals,
+        ..Default::default()
+    };
+    Cell::Number(format.format(money))
+}
+
+/// A value of a [`Table`]
+#[derive(Debug, Clone)]
+enum Cell {
+    Null,
+    Text(String),
+    /// A number in plain decimal notation, written unquoted in JSON
+    Number(String),
+}
+
+impl Cell {
+    fn as_str(&self) -> &str {
+        match self {
+            Cell::Null => "",
+            Cell::Text(text) | Cell::Number(text) => text,
+        }
+    }
+}
+
+impl Serialize for Cell {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Cell::Null => serializer.serialize_none(),
+            Cell::Text(text) => serializer.serialize_str(text),
+            Cell::Number(number) => RawValue::from_string(number.clone())
+                .map_err(serde::ser::Error::custom)?
+                .serialize(serializer),
+        }
+    }
+}
+
+/// Rows of a command's result, printed as columns or as JSON objects
+struct Table {
+    columns: Vec<&'static str>,
+    rows: Vec<Vec<Cell>>,
+}
+
+/// A row of a [`Table`] as a JSON object keyed by column
+struct JsonRow<'a> {
+    columns: &'a [&'static str],
+    cells: &'a [Cell],
+}
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for (column, cell) in self.columns.iter().zip(self.cells) {
+            map.serialize_entry(column, cell)?;
+        }
+        map.end()
+    }
+}
+
+impl Table {
+    fn new(columns: &[&'static str]) -> Self {
+        Table {
+            columns: columns.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, row: Vec<Cell>) {
+        self.rows.push(row);
+    }
+
+    fn write<W: Write>(&self, output: Output, mut w: W) -> Result<()> {
+        match output {
+            Output::Table => {
+                let mut widths: Vec<usize> = self.columns.iter().map(|c| c.len()).collect();
+                for row in &self.rows {
+                    for (width, value) in widths.iter_mut().zip(row) {
+                        *width = (*width).max(value.as_str().chars().count());
+                    }
+                }
+
+                let header = self.columns.iter().map(|c| text(c.to_uppercase()));
+                for row in std::iter::once(header.collect()).chain(self.rows.clone()) {
+                    let line = row
+                        .iter()
+                        .zip(&widths)
+                        .map(|(value, width)| format!("{:width$}", value.as_str()))
+                        .collect::<Vec<_>>()
+                        .join("  ");
+                    writeln!(w, "{}", line.trim_end())?;
+                }
+            }
+            Output::Json => {
+                let rows: Vec<JsonRow> = self
+                    .rows
+                    .iter()
+                    .map(|cells| JsonRow {
+                        columns: &self.columns,
+                        cells,
+                    })
+                    .collect();
+                serde_json::to_writer_pretty(&mut w, &rows)?;
+                writeln!(w)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn run_command(db: &mut Database, args: &[&str]) -> Result<String> {
+        let cli = Cli::try_parse_from(std::iter::once("mm-schema").chain(args.iter().copied()))
+            .map_err(|e| Error::InvalidData(e.to_string()))?;
+        let mut out = Vec::new();
+        execute(db, &cli, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_journal_entry_line() {
+        let line = journal_entry_line("1201 10 AAPL @@ 1501", "USD").unwrap();
+        assert_eq!(line.account_number, "1201");
+        assert_eq!(line.asset_code, "AAPL");
+        assert_eq!(line.entry_type, NormalBalance::Debit);
+        assert_eq!(line.reference_amount, Some(Money::new(dec!(1501))));
+
+        let line = journal_entry_line("1101 -45.20", "USD").unwrap();
+        assert_eq!(line.asset_code, "USD");
+        assert_eq!(line.entry_type, NormalBalance::Credit);
+        assert_eq!(line.amount, Money::new(dec!(45.20)));
+
+        assert!(journal_entry_line("1101", "USD").is_err());
+        assert!(journal_entry_line("1101 ten", "USD").is_err());
+    }
+
+    #[test]
+    fn test_cli_commands() -> Result<()> {
+        let mut db = Database::new_in_memory()?;
+        run_command(&mut db, &["init", "--sample"])?;
+
+        let id = run_command(
+            &mut db,
+            &[
+                "--output",
+                "json",
+                "tx",
+                "add",
+                "--date",
+                "2025-03-03",
+                "--description",
+                "Groceries",
+                "--asset",
+                "USD",
+                "--line",
+                "5301 45.20",
+                "--line",
+                "1101 -45.20",
+            ],
+        )?;
+        assert!(id.contains("\"id\": 1"));
+
+        let balance = run_command(&mut db, &["balance"])?;
+        let lines: Vec<&str> = balance.lines().collect();
+        assert_eq!(lines[0], "NUMBER  NAME                   ASSET  BALANCE");
+        assert!(lines.contains(&"5301    Groceries              USD    45.20"));
+
+        // Amounts are JSON numbers written with the decimals of their asset
+        let register = run_command(&mut db, &["--output", "json", "register", "1101"])?;
+        assert!(register.contains("\"entry\": 1,"));
+        assert!(register.contains("\"balance\": -45.20"));
+        let rows: Vec<serde_json::Value> = serde_json::from_str(&register)?;
+        assert_eq!(rows[0]["amount"], -45.2);
+
+        assert!(matches!(
+            run_command(&mut db, &["accounts", "close", "1101"]),
+            Err(Error::InvalidData(_))
+        ));
+        run_command(&mut db, &["tx", "void", "1"])?;
+        run_command(
+            &mut db,
+            &["accounts", "close", "1101", "--date", "2025-12-31"],
+        )?;
+        let accounts = run_command(&mut db, &["accounts", "list"])?;
+        assert!(!accounts.contains("Main Checking Account"));
+
+        assert!(matches!(
+            run_command(&mut db, &["tx", "void", "99"]),
+            Err(Error::NotFound)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_does_not_create_a_book() {
+        let path =
+            std::env::temp_dir().join(format!("mm-schema-missing-{}.db", std::process::id()));
+        let cli =
+            Cli::try_parse_from(["mm-schema", "--db", path.to_str().unwrap(), "balance"]).unwrap();
+
+        assert!(matches!(run(&cli), Err(Error::InvalidData(_))));
+        assert!(!path.exists());
+    }
+}
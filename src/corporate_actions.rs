@@ -0,0 +1,295 @@
+use chrono::NaiveDate;
+use rusqlite::{named_params, params, Connection, Row};
+use rust_decimal::Decimal;
+
+use crate::{
+    error::{Error, Result},
+    interface::Database,
+    journal::asset_id_of,
+    models::{CorporateAction, CorporateActionType},
+    money::{Money, Rounding},
+};
+
+impl Database {
+    /// Records a split of `asset_code` effective on `date`, where every
+    /// `ratio_old` units held become `ratio_new` units (4:1 is `4, 1`, a 1:10
+    /// reverse split is `1, 10`).
+    ///
+    /// Open lots acquired before `date` are restated so that their total cost
+    /// stays the same, while journal lines are left untouched.
+    pub fn record_split<S: AsRef<str>>(
+        &mut self,
+        asset_code: S,
+        date: NaiveDate,
+        ratio_new: i64,
+        ratio_old: i64,
+        description: Option<S>,
+    ) -> Result<i64> {
+        let t = self.transaction()?;
+
+        let asset_id = asset_id_of(&t, asset_code.as_ref())?;
+        let action_id = insert_corporate_action(
+            &t,
+            asset_id,
+            CorporateActionType::Split,
+            date,
+            (ratio_new, ratio_old),
+            None,
+            None,
+            description.as_ref().map(AsRef::as_ref),
+        )?;
+        restate_lots(
+            &t, action_id, asset_id, asset_id, date, ratio_new, ratio_old,
+        )?;
+
+        t.commit()?;
+
+        Ok(action_id)
+    }
+
+    /// Records that `asset_code` changed its code to `new_code` on `date`.
+    /// The old code is kept in the corporate action for reference.
+    pub fn record_rename<S: AsRef<str>>(
+        &mut self,
+        asset_code: S,
+        new_code: S,
+        date: NaiveDate,
+        description: Option<S>,
+    ) -> Result<i64> {
+        let t = self.transaction()?;
+
+        let asset_id = asset_id_of(&t, asset_code.as_ref())?;
+        let action_id = insert_corporate_action(
+            &t,
+            asset_id,
+            CorporateActionType::Rename,
+            date,
+            (1, 1),
+            Some((asset_code.as_ref(), new_code.as_ref())),
+            None,
+            description.as_ref().map(AsRef::as_ref),
+        )?;
+
+        t.execute(
+            "UPDATE assets SET code = ?1 WHERE id = ?2",
+            params![new_code.as_ref(), asset_id],
+        )?;
+
+        t.commit()?;
+
+        Ok(action_id)
+    }
+
+    /// Records the merger of `asset_code` into `target_asset_code` on `date`,
+    /// where every `ratio_old` units held become `ratio_new` units of the target.
+    ///
+    /// Open lots acquired before `date` are moved to the target asset with
+    /// their cost basis unchanged.
+    pub fn record_merger<S: AsRef<str>>(
+        &mut self,
+        asset_code: S,
+        target_asset_code: S,
+        date: NaiveDate,
+        ratio_new: i64,
+        ratio_old: i64,
+        description: Option<S>,
+    ) -> Result<i64> {
+        let t = self.transaction()?;
+
+        let asset_id = asset_id_of(&t, asset_code.as_ref())?;
+        let target_asset_id = asset_id_of(&t, target_asset_code.as_ref())?;
+        if asset_id == target_asset_id {
+            return Err(Error::InvalidData(
+                "an asset cannot be merged into itself".into(),
+            ));
+        }
+
+        let action_id = insert_corporate_action(
+            &t,
+            asset_id,
+            CorporateActionType::Merger,
+            date,
+            (ratio_new, ratio_old),
+            None,
+            Some(target_asset_id),
+            description.as_ref().map(AsRef::as_ref),
+        )?;
+        restate_lots(
+            &t,
+            action_id,
+            asset_id,
+            target_asset_id,
+            date,
+            ratio_new,
+            ratio_old,
+        )?;
+
+        t.commit()?;
+
+        Ok(action_id)
+    }
+
+    /// Returns every recorded corporate action in the order they took effect.
+    pub fn list_corporate_actions(&self) -> Result<Vec<CorporateAction>> {
+        let mut stmt = self.conn().prepare(
+            "SELECT id, asset_id, type, date, ratio_new, ratio_old,
+                    old_code, new_code, target_asset_id, description
+             FROM corporate_actions
+             ORDER BY date, id",
+        )?;
+
+        let actions = stmt
+            .query_map([], corporate_action_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(actions)
+    }
+}
+
+/// Converts a position of `quantity` units of `asset_id` held since
+/// `held_since` into the equivalent position on `as_of`, following every split
+/// and merger that took effect in between.
+pub(crate) fn adjust_position(
+    actions: &[CorporateAction],
+    asset_id: i64,
+    quantity: Decimal,
+    held_since: NaiveDate,
+    as_of: NaiveDate,
+) -> (i64, Decimal) {
+    actions
+        .iter()
+        .filter(|action| action.date > held_since && action.date <= as_of)
+        .fold((asset_id, quantity), |(asset_id, quantity), action| {
+            if action.asset_id != asset_id {
+                return (asset_id, quantity);
+            }
+
+            let ratio = Decimal::from(action.ratio_new) / Decimal::from(action.ratio_old);
+            match action.action_type {
+                CorporateActionType::Split => (asset_id, quantity * ratio),
+                CorporateActionType::Merger => {
+                    (action.target_asset_id.unwrap_or(asset_id), quantity * ratio)
+                }
+                CorporateActionType::Rename => (asset_id, quantity),
+            }
+        })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn insert_corporate_action(
+    t: &Connection,
+    asset_id: i64,
+    action_type: CorporateActionType,
+    date: NaiveDate,
+    (ratio_new, ratio_old): (i64, i64),
+    codes: Option<(&str, &str)>,
+    target_asset_id: Option<i64>,
+    description: Option<&str>,
+) -> Result<i64> {
+    if ratio_new <= 0 || ratio_old <= 0 {
+        return Err(Error::InvalidData(format!(
+            "invalid ratio {ratio_new}:{ratio_old}"
+        )));
+    }
+
+    let id = t.query_row(
+        "INSERT INTO corporate_actions (
+            asset_id, type, date, ratio_new, ratio_old,
+            old_code, new_code, target_asset_id, description
+        ) VALUES (
+            :asset_id, :type, :date, :ratio_new, :ratio_old,
+            :old_code, :new_code, :target_asset_id, :description
+        ) RETURNING id",
+        named_params! {
+            ":asset_id": asset_id,
+            ":type": format!("{:?}", action_type).to_uppercase(),
+            ":date": date,
+            ":ratio_new": ratio_new,
+            ":ratio_old": ratio_old,
+            ":old_code": codes.map(|(old, _)| old),
+            ":new_code": codes.map(|(_, new)| new),
+            ":target_asset_id": target_asset_id,
+            ":description": description,
+        },
+        |row| row.get(0),
+    )?;
+
+    Ok(id)
+}
+
+/// Rewrites the open lots of `asset_id` acquired before `date`, recording the
+/// previous state of each lot in `lot_adjustments`.
+fn restate_lots(
+    t: &Connection,
+    action_id: i64,
+    asset_id: i64,
+    target_asset_id: i64,
+    date: NaiveDate,
+    ratio_new: i64,
+    ratio_old: i64,
+) -> Result<()> {
+    let lots = {
+        let mut stmt = t.prepare(
+            "SELECT id, quantity FROM lots
+             WHERE asset_id = ?1 AND acquired_date < ?2 AND dec_sign(quantity) != 0",
+        )?;
+        let lots = stmt
+            .query_map(params![asset_id, date], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Money>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        lots
+    };
+
+    let decimals: i64 = t.query_row(
+        "SELECT decimals FROM assets WHERE id = ?1",
+        [target_asset_id],
+        |row| row.get(0),
+    )?;
+    let decimals = decimals.clamp(0, 28) as u32;
+
+    let ratio = Decimal::from(ratio_new) / Decimal::from(ratio_old);
+    for (lot_id, quantity) in lots {
+        let restated = Money::new(quantity.amount() * ratio).round(decimals, Rounding::Bankers);
+
+        t.execute(
+            "INSERT INTO lot_adjustments (
+                lot_id, corporate_action_id, asset_id_before, asset_id_after,
+                quantity_before, quantity_after
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                lot_id,
+                action_id,
+                asset_id,
+                target_asset_id,
+                quantity,
+                restated
+            ],
+        )?;
+        t.execute(
+            "UPDATE lots SET asset_id = ?1, quantity = ?2 WHERE id = ?3",
+            params![target_asset_id, restated, lot_id],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn corporate_action_from_row(row: &Row) -> rusqlite::Result<CorporateAction> {
+    let action_type: String = row.get(2)?;
+
+    Ok(CorporateAction {
+        id: row.get(0)?,
+        asset_id: row.get(1)?,
+        action_type: action_type.parse().map_err(|e: String| {
+            rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, e.into())
+        })?,
+        date: row.get(3)?,
+        ratio_new: row.get(4)?,
+        ratio_old: row.get(5)?,
+        old_code: row.get(6)?,
+        new_code: row.get(7)?,
+        target_asset_id: row.get(8)?,
+        description: row.get(9)?,
+    })
+}
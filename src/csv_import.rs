@@ -0,0 +1,255 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read},
+};
+
+use chrono::NaiveDate;
+use rusqlite::{named_params, OptionalExtension, Row};
+
+use crate::{
+    error::{Error, Result},
+    import::{ImportSummary, StatementLine},
+    interface::Database,
+    models::{AmountColumns, CsvImportProfile},
+    money::Money,
+    money_format::MoneyFormat,
+};
+
+impl Database {
+    /// Saves `profile`, replacing the profile with the same name if there is one.
+    pub fn save_import_profile(&mut self, profile: &CsvImportProfile) -> Result<i64> {
+        let (amount_column, debit_column, credit_column) = match &profile.amount_columns {
+            AmountColumns::Signed(amount) => (Some(amount), None, None),
+            AmountColumns::DebitCredit { debit, credit } => (None, Some(debit), Some(credit)),
+        };
+
+        let t = self.transaction()?;
+
+        let id = t.query_row(
+            include_str!("sql/upsert_import_profile.sql"),
+            named_params! {
+                ":name": profile.name,
+                ":delimiter": profile.delimiter.to_string(),
+                ":has_headers": profile.has_headers,
+                ":skip_lines": profile.skip_lines as i64,
+                ":date_column": profile.date_column,
+                ":date_format": profile.date_format,
+                ":amount_column": amount_column,
+                ":debit_column": debit_column,
+                ":credit_column": credit_column,
+                ":description_column": profile.description_column,
+                ":reference_column": profile.reference_column,
+                ":decimal_separator": profile.decimal_separator.to_string(),
+                ":thousands_separator": profile.thousands_separator.map(String::from),
+            },
+            |row| row.get(0),
+        )?;
+
+        t.commit()?;
+
+        Ok(id)
+    }
+
+    pub fn get_import_profile<S: AsRef<str>>(&self, name: S) -> Result<Option<CsvImportProfile>> {
+        let profile = self
+            .conn()
+            .query_row(
+                "SELECT name, delimiter, has_headers, skip_lines, date_column, date_format,
+                        amount_column, debit_column, credit_column, description_column,
+                        reference_column, decimal_separator, thousands_separator
+                 FROM import_profiles
+                 WHERE name = ?1",
+                [name.as_ref()],
+                import_profile_from_row,
+            )
+            .optional()?;
+
+        Ok(profile)
+    }
+
+    pub fn list_import_profiles(&self) -> Result<Vec<CsvImportProfile>> {
+        let mut stmt = self.conn().prepare(
+            "SELECT name, delimiter, has_headers, skip_lines, date_column, date_format,
+                    amount_column, debit_column, credit_column, description_column,
+                    reference_column, decimal_separator, thousands_separator
+             FROM import_profiles
+             ORDER BY name",
+        )?;
+
+        let profiles = stmt
+            .query_map([], import_profile_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(profiles)
+    }
+
+    /// Imports a bank CSV export into `account_number` as draft entries, reading
+    /// it with the saved profile `profile_name`.
+    /// See [`Database::import_statement_lines`].
+    pub fn import_csv<S: AsRef<str>, R: Read>(
+        &mut self,
+        profile_name: S,
+        account_number: S,
+        asset_code: S,
+        reader: R,
+    ) -> Result<ImportSummary> {
+        let profile = self
+            .get_import_profile(profile_name)?
+            .ok_or(Error::NotFound)?;
+        let lines = parse_csv_statement(&profile, reader)?;
+
+        self.import_statement_lines(account_number, asset_code, &lines)
+    }
+}
+
+/// Reads the transactions of a bank CSV export described by `profile`.
+///
+/// CSV exports carry no transaction ids, so each line is identified by a
+/// fingerprint of its date, amount, reference and description, numbered to
+/// tell apart identical transactions on the same statement.
+pub fn parse_csv_statement<R: Read>(
+    profile: &CsvImportProfile,
+    reader: R,
+) -> Result<Vec<StatementLine>> {
+    let delimiter = u8::try_from(profile.delimiter)
+        .map_err(|_| Error::InvalidData(format!("invalid delimiter {:?}", profile.delimiter)))?;
+
+    let mut reader = BufReader::new(reader);
+    for _ in 0..profile.skip_lines {
+        reader.read_line(&mut String::new())?;
+    }
+
+    let mut csv = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(profile.has_headers)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let headers = match profile.has_headers {
+        true => Some(csv.headers()?.clone()),
+        false => None,
+    };
+    let column = |name: &str| -> Result<usize> {
+        headers
+            .as_ref()
+            .and_then(|headers| headers.iter().position(|h| h.eq_ignore_ascii_case(name)))
+            .or_else(|| name.parse().ok())
+            .ok_or_else(|| Error::InvalidData(format!("missing `{name}` column")))
+    };
+
+    let date_column = column(&profile.date_column)?;
+    let description_column = column(&profile.description_column)?;
+    let reference_column = profile
+        .reference_column
+        .as_deref()
+        .map(column)
+        .transpose()?;
+    let amount_columns = match &profile.amount_columns {
+        AmountColumns::Signed(amount) => (column(amount)?, None),
+        AmountColumns::DebitCredit { debit, credit } => (column(credit)?, Some(column(debit)?)),
+    };
+
+    let format = MoneyFormat {
+        decimal_separator: profile.decimal_separator,
+        thousands_separator: profile.thousands_separator,
+        ..Default::default()
+    };
+
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    let mut lines = Vec::new();
+
+    for record in csv.records() {
+        let record = record?;
+        let line = record.position().map_or(0, |p| p.line());
+        let field = |index: usize| record.get(index).unwrap_or_default();
+
+        let amount = |index: usize| -> Result<Money> {
+            match field(index) {
+                "" => Ok(Money::default()),
+                value => format
+                    .parse(value)
+                    .map_err(|e| Error::InvalidData(format!("line {line}: {e}"))),
+            }
+        };
+
+        let date = field(date_column);
+        let date = NaiveDate::parse_from_str(date, &profile.date_format)
+            .map_err(|e| Error::InvalidData(format!("line {line}: invalid date {date:?}: {e}")))?;
+        let amount = match amount_columns {
+            (amount_column, None) => amount(amount_column)?,
+            (credit_column, Some(debit_column)) => {
+                amount(credit_column)?.abs() - amount(debit_column)?.abs()
+            }
+        };
+        let amount = Money::new(amount.amount().normalize());
+        let description = field(description_column).to_string();
+        let reference = reference_column
+            .map(field)
+            .filter(|reference| !reference.is_empty())
+            .map(String::from);
+
+        let fingerprint = format!(
+            "{date}|{amount}|{}|{}",
+            reference.as_deref().unwrap_or_default(),
+            description
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase(),
+        );
+        let occurrence = occurrences.entry(fingerprint.clone()).or_default();
+        let external_id = format!("csv:{fingerprint}#{occurrence}");
+        *occurrence += 1;
+
+        lines.push(StatementLine {
+            date,
+            amount,
+            description,
+            reference,
+            external_id,
+        });
+    }
+
+    Ok(lines)
+}
+
+fn import_profile_from_row(row: &Row) -> rusqlite::Result<CsvImportProfile> {
+    let character = |index: usize| -> rusqlite::Result<Option<char>> {
+        Ok(row
+            .get::<_, Option<String>>(index)?
+            .and_then(|value| value.chars().next()))
+    };
+    let required = |index: usize| -> rusqlite::Result<char> {
+        character(index)?.ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                index,
+                rusqlite::types::Type::Text,
+                "empty separator".into(),
+            )
+        })
+    };
+
+    let amount_column: Option<String> = row.get(6)?;
+    let amount_columns = match amount_column {
+        Some(amount) => AmountColumns::Signed(amount),
+        None => AmountColumns::DebitCredit {
+            debit: row.get(7)?,
+            credit: row.get(8)?,
+        },
+    };
+
+    Ok(CsvImportProfile {
+        name: row.get(0)?,
+        delimiter: required(1)?,
+        has_headers: row.get(2)?,
+        skip_lines: row.get::<_, i64>(3)? as usize,
+        date_column: row.get(4)?,
+        date_format: row.get(5)?,
+        amount_columns,
+        description_column: row.get(9)?,
+        reference_column: row.get(10)?,
+        decimal_separator: required(11)?,
+        thousands_separator: character(12)?,
+    })
+}
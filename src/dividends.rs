@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{named_params, Connection, Row};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{
+    error::{Error, Result},
+    interface::Database,
+    journal::insert_journal_entry,
+    models::{
+        Dividend, EntryStatus, IncomeKind, NewJournalEntry, NewJournalEntryLine, NormalBalance,
+    },
+    money::{Money, Rounding},
+};
+
+/// Income account credited with dividends
+pub const DIVIDENDS_ACCOUNT: &str = "4201";
+/// Income account credited with interest
+pub const INTEREST_INCOME_ACCOUNT: &str = "4203";
+/// Expense account debited with taxes withheld at source
+pub const WITHHOLDING_TAX_ACCOUNT: &str = "5504";
+
+/// Represents a row in the income yield report, with amounts in the reporting asset
+#[derive(Debug, Serialize)]
+pub struct IncomeYieldReport {
+    pub asset: String,
+    pub gross: Money,
+    pub withholding: Money,
+    pub net: Money,
+    /// Value of the holding at the end of the period, `None` if it could not be priced
+    pub market_value: Option<Money>,
+    /// Gross income divided by the market value
+    pub yield_rate: Option<Decimal>,
+}
+
+impl Database {
+    /// Records a dividend paid by `asset_code` into `account_number`.
+    ///
+    /// A single balanced entry credits 4201 Dividends with the `gross` amount,
+    /// debits the receiving account with the net amount and 5504 Withholding Tax
+    /// with the tax withheld at source. Returns the id of the dividend record.
+    pub fn record_dividend<S: AsRef<str>>(
+        &mut self,
+        asset_code: S,
+        account_number: S,
+        currency_code: S,
+        gross: Money,
+        withholding: Money,
+        date: DateTime<Utc>,
+    ) -> Result<i64> {
+        self.record_income(
+            IncomeKind::Dividend,
+            asset_code.as_ref(),
+            account_number.as_ref(),
+            currency_code.as_ref(),
+            gross,
+            withholding,
+            date,
+        )
+    }
+
+    /// Records interest paid by `asset_code` (a bond or a deposit) into
+    /// `account_number`, crediting 4203 Interest Income.
+    /// See [`Database::record_dividend`].
+    pub fn record_interest<S: AsRef<str>>(
+        &mut self,
+        asset_code: S,
+        account_number: S,
+        currency_code: S,
+        gross: Money,
+        withholding: Money,
+        date: DateTime<Utc>,
+    ) -> Result<i64> {
+        self.record_income(
+            IncomeKind::Interest,
+            asset_code.as_ref(),
+            account_number.as_ref(),
+            currency_code.as_ref(),
+            gross,
+            withholding,
+            date,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn record_income(
+        &mut self,
+        kind: IncomeKind,
+        asset_code: &str,
+        account_number: &str,
+        currency_code: &str,
+        gross: Money,
+        withholding: Money,
+        date: DateTime<Utc>,
+    ) -> Result<i64> {
+        let t = self.transaction()?;
+        let (id, _) = insert_income(
+            &t,
+            kind,
+            asset_code,
+            account_number,
+            currency_code,
+            gross,
+            withholding,
+            date,
+        )?;
+        t.commit()?;
+
+        Ok(id)
+    }
+
+    /// Returns the dividends and interest paid by `asset_code`, oldest first.
+    pub fn list_dividends<S: AsRef<str>>(&self, asset_code: S) -> Result<Vec<Dividend>> {
+        let mut stmt = self.conn().prepare(
+            "SELECT id, journal_entry_id, asset_id, account_id, currency_asset_id,
+                    kind, date, gross, withholding
+             FROM dividends
+             WHERE asset_id = (SELECT id FROM assets WHERE code = ?1)
+             ORDER BY date, id",
+        )?;
+
+        let dividends = stmt
+            .query_map([asset_code.as_ref()], dividend_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(dividends)
+    }
+
+    /// Income received per holding between `from` and `to` (inclusive), converted
+    /// into `reporting_asset_code` on each payment date, together with its yield
+    /// on the market value of the holding at `to`.
+    pub fn get_income_yield_report<S: AsRef<str>>(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        reporting_asset_code: S,
+    ) -> Result<Vec<IncomeYieldReport>> {
+        let reporting_asset_code = reporting_asset_code.as_ref();
+
+        let payments = {
+            let mut stmt = self.conn().prepare(
+                "SELECT a.code, c.code, date(d.date), d.gross, d.withholding
+                 FROM dividends d
+                 JOIN assets a ON a.id = d.asset_id
+                 JOIN assets c ON c.id = d.currency_asset_id
+                 WHERE date(d.date) BETWEEN :from AND :to",
+            )?;
+            let payments = stmt
+                .query_map(named_params! { ":from": from, ":to": to }, |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, NaiveDate>(2)?,
+                        row.get::<_, Money>(3)?,
+                        row.get::<_, Money>(4)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            payments
+        };
+
+        let mut income: BTreeMap<String, (Decimal, Decimal)> = BTreeMap::new();
+        for (asset, currency, date, gross, withholding) in payments {
+            let rate = self
+                .unit_value_at(currency.as_str(), date, reporting_asset_code)?
+                .ok_or_else(|| {
+                    Error::InvalidData(format!(
+                        "no rate from {currency} to {reporting_asset_code} on {date}"
+                    ))
+                })?;
+
+            let totals = income.entry(asset).or_default();
+            totals.0 += gross.amount() * rate;
+            totals.1 += withholding.amount() * rate;
+        }
+
+        let holdings = self.get_valuation_report(to, reporting_asset_code)?;
+        let decimals = *self
+            .asset_decimals()?
+            .get(reporting_asset_code)
+            .ok_or(Error::NotFound)?;
+        let round = |value: Decimal| Money::new(value).round(decimals, Rounding::Bankers);
+
+        Ok(income
+            .into_iter()
+            .map(|(asset, (gross, withholding))| {
+                let market_value = holdings
+                    .iter()
+                    .filter(|row| row.asset == asset)
+                    .map(|row| row.value.map(|value| value.amount()))
+                    .sum::<Option<Decimal>>()
+                    .filter(|value| !value.is_zero());
+
+                IncomeYieldReport {
+                    gross: round(gross),
+                    withholding: round(withholding),
+                    net: round(gross - withholding),
+                    market_value: market_value.map(round),
+                    yield_rate: market_value.map(|value| (gross / value).round_dp(8)),
+                    asset,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Records income inside an already open transaction.
+/// Returns the ids of the dividend record and of its journal entry.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn insert_income(
+    t: &Connection,
+    kind: IncomeKind,
+    asset_code: &str,
+    account_number: &str,
+    currency_code: &str,
+    gross: Money,
+    withholding: Money,
+    date: DateTime<Utc>,
+) -> Result<(i64, i64)> {
+    if gross.amount() <= Decimal::ZERO
+        || withholding.amount() < Decimal::ZERO
+        || withholding.amount() > gross.amount()
+    {
+        return Err(Error::InvalidData(format!(
+            "invalid gross {} and withholding {}",
+            gross.amount(),
+            withholding.amount()
+        )));
+    }
+
+    let (income_account, label) = match kind {
+        IncomeKind::Dividend => (DIVIDENDS_ACCOUNT, "Dividend"),
+        IncomeKind::Interest => (INTEREST_INCOME_ACCOUNT, "Interest"),
+    };
+    let line = |account_number: &str, entry_type, amount| NewJournalEntryLine {
+        account_number: account_number.to_string(),
+        asset_code: currency_code.to_string(),
+        entry_type,
+        amount,
+        reference_amount: None,
+        description: None,
+    };
+
+    // Nothing reaches the account when all of the income is withheld
+    let mut lines = Vec::new();
+    if withholding != gross {
+        lines.push(line(
+            account_number,
+            NormalBalance::Debit,
+            gross - withholding,
+        ));
+    }
+    if !withholding.amount().is_zero() {
+        lines.push(line(
+            WITHHOLDING_TAX_ACCOUNT,
+            NormalBalance::Debit,
+            withholding,
+        ));
+    }
+    lines.push(line(income_account, NormalBalance::Credit, gross));
+
+    let entry = NewJournalEntry {
+        date,
+        description: format!("{label} {asset_code}"),
+        reference_number: None,
+        reference_asset_code: currency_code.to_string(),
+        status: EntryStatus::Posted,
+        lines,
+    };
+
+    let journal_entry_id = insert_journal_entry(t, &entry)?;
+
+    let id = t.query_row(
+        "INSERT INTO dividends (
+            journal_entry_id, asset_id, account_id, currency_asset_id,
+            kind, date, gross, withholding
+        ) VALUES (
+            :journal_entry_id,
+            (SELECT id FROM assets WHERE code = :asset_code),
+            (SELECT id FROM accounts WHERE account_number = :account_number),
+            (SELECT id FROM assets WHERE code = :currency_code),
+            :kind, :date, :gross, :withholding
+        ) RETURNING id",
+        named_params! {
+            ":journal_entry_id": journal_entry_id,
+            ":asset_code": asset_code,
+            ":account_number": account_number,
+            ":currency_code": currency_code,
+            ":kind": format!("{:?}", kind).to_uppercase(),
+            ":date": date,
+            ":gross": gross,
+            ":withholding": withholding,
+        },
+        |row| row.get(0),
+    )?;
+
+    Ok((id, journal_entry_id))
+}
+
+fn dividend_from_row(row: &Row) -> rusqlite::Result<Dividend> {
+    let kind: String = row.get(5)?;
+
+    Ok(Dividend {
+        id: row.get(0)?,
+        journal_entry_id: row.get(1)?,
+        asset_id: row.get(2)?,
+        account_id: row.get(3)?,
+        currency_asset_id: row.get(4)?,
+        kind: kind.parse().map_err(|e: String| {
+            rusqlite::Error::FromSqlConversionFailure(5, rusqlite::types::Type::Text, e.into())
+        })?,
+        date: row.get(6)?,
+        gross: row.get(7)?,
+        withholding: row.get(8)?,
+    })
+}
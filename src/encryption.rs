@@ -0,0 +1,205 @@
+use std::fs;
+
+use rusqlite::{DatabaseName, ErrorCode};
+
+use crate::{
+    error::{Error, Result},
+    interface::Database,
+    pool::DatabaseOptions,
+};
+
+impl Database {
+    /// Opens the SQLCipher-encrypted book at `path` with the passphrase
+    /// `key`, creating an encrypted book if there is no file yet.
+    ///
+    /// A wrong key, or a plaintext book, is reported as
+    /// [`Error::InvalidData`].
+    pub fn open_encrypted(path: &str, key: &str) -> Result<Self> {
+        Self::open_encrypted_with(path, key, &DatabaseOptions::default())
+    }
+
+    /// Opens an encrypted book like [`open_encrypted`](Self::open_encrypted),
+    /// with `options`. Readers taken from it are unlocked with the same key.
+    pub fn open_encrypted_with(path: &str, key: &str, options: &DatabaseOptions) -> Result<Self> {
+        Self::open_file(path, options, Some(key)).map_err(|e| match e.sqlite_error_code() {
+            Some(ErrorCode::NotADatabase) => {
+                Error::InvalidData(format!("{path} cannot be opened with this key"))
+            }
+            _ => e.into(),
+        })
+    }
+
+    /// Re-encrypts the book with `new_key`. Fails if readers taken from the
+    /// book are still in use, since they hold the old key.
+    pub fn rekey(&mut self, new_key: &str) -> Result<()> {
+        if new_key.is_empty() {
+            return Err(Error::InvalidData(
+                "an empty key would decrypt the book, use decrypt_to instead".into(),
+            ));
+        }
+
+        let rekey = || Ok(self.conn().pragma_update(None, "rekey", new_key)?);
+        match self.readers() {
+            Some(pool) => pool.change_key(new_key, rekey),
+            None => rekey(),
+        }
+    }
+
+    /// Writes an encrypted copy of the book to a new file at `path`. Together
+    /// with [`encrypt_file`](Self::encrypt_file), this is how a plaintext book
+    /// becomes an encrypted one.
+    pub fn encrypt_to(&self, path: &str, key: &str) -> Result<()> {
+        if key.is_empty() {
+            return Err(Error::InvalidData("the key cannot be empty".into()));
+        }
+        self.export_copy(path, key)
+    }
+
+    /// Writes a plaintext copy of the book to a new file at `path`.
+    pub fn decrypt_to(&self, path: &str) -> Result<()> {
+        self.export_copy(path, "")
+    }
+
+    /// Encrypts the plaintext book at `path` in place with `key`.
+    ///
+    /// The encrypted copy is written next to the book and only replaces it
+    /// once complete, so an interruption leaves the plaintext book intact.
+    pub fn encrypt_file(path: &str, key: &str) -> Result<()> {
+        let encrypted = format!("{path}.encrypted");
+        let _ = fs::remove_file(&encrypted);
+
+        {
+            let db = Self::open_with(
+                path,
+                &DatabaseOptions {
+                    max_readers: 0,
+                    ..Default::default()
+                },
+            )?;
+            db.encrypt_to(&encrypted, key)?;
+            // Folds the write-ahead log into the file before it is replaced
+            db.conn().pragma_update(None, "journal_mode", "DELETE")?;
+        }
+
+        fs::rename(&encrypted, path)?;
+
+        Ok(())
+    }
+
+    /// Copies every table and the schema version to a new file at `path`,
+    /// encrypted with `key` unless it is empty
+    fn export_copy(&self, path: &str, key: &str) -> Result<()> {
+        if fs::metadata(path).is_ok() {
+            return Err(Error::InvalidData(format!("{path} already exists")));
+        }
+
+        let conn = self.conn();
+        conn.execute("ATTACH DATABASE ?1 AS export KEY ?2", [path, key])?;
+        let exported = conn
+            .query_row("SELECT sqlcipher_export('export')", [], |_| Ok(()))
+            .and_then(|()| {
+                let version: i64 =
+                    conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+                conn.pragma_update(
+                    Some(DatabaseName::Attached("export")),
+                    "user_version",
+                    version,
+                )
+            });
+        conn.execute("DETACH DATABASE export", [])?;
+        exported?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pool::tests::TempBook, seeding::init_sample_data};
+
+    fn sample_book(path: &str, key: &str) -> Result<()> {
+        let mut db = Database::open_encrypted(path, key)?;
+        db.init_schema()?;
+        init_sample_data(&mut db)
+    }
+
+    #[test]
+    fn test_open_encrypted() -> Result<()> {
+        let book = TempBook::new("encrypted");
+        sample_book(book.path(), "correct horse")?;
+
+        let db = Database::open_encrypted(book.path(), "correct horse")?;
+        assert_eq!(
+            db.list_accounts()?.len(),
+            db.reader()?.list_accounts()?.len()
+        );
+        assert!(matches!(
+            Database::open_encrypted(book.path(), "battery staple"),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(Database::new(book.path()).is_err());
+
+        // Nothing readable is left on disk
+        drop(db);
+        let bytes = fs::read(book.path())?;
+        assert!(!bytes.windows(7).any(|window| window == b"Savings"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rekey() -> Result<()> {
+        let book = TempBook::new("rekey");
+        sample_book(book.path(), "old key")?;
+
+        let mut db = Database::open_encrypted(book.path(), "old key")?;
+        let reader = db.reader()?;
+        assert!(db.rekey("new key").is_err());
+        drop(reader);
+        db.rekey("new key")?;
+        assert!(db.rekey("").is_err());
+
+        // Readers pick up the new key
+        assert_eq!(db.reader()?.list_assets()?.len(), 6);
+        drop(db);
+
+        assert!(Database::open_encrypted(book.path(), "old key").is_err());
+        assert_eq!(
+            Database::open_encrypted(book.path(), "new key")?
+                .list_assets()?
+                .len(),
+            6
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypt_and_decrypt_files() -> Result<()> {
+        let book = TempBook::new("plaintext");
+        let copy = TempBook::new("decrypted");
+        {
+            let mut db = Database::new(book.path())?;
+            db.migrate()?;
+            init_sample_data(&mut db)?;
+        }
+
+        Database::encrypt_file(book.path(), "passphrase")?;
+        assert!(Database::new(book.path()).is_err());
+
+        let db = Database::open_encrypted(book.path(), "passphrase")?;
+        assert_eq!(db.schema_version()?, crate::migrations::SCHEMA_VERSION);
+        assert!(db.encrypt_to(book.path(), "again").is_err());
+
+        db.decrypt_to(copy.path())?;
+        let plaintext = Database::new(copy.path())?;
+        assert_eq!(plaintext.list_assets()?.len(), 6);
+        assert_eq!(
+            plaintext.schema_version()?,
+            crate::migrations::SCHEMA_VERSION
+        );
+
+        Ok(())
+    }
+}
@@ -6,6 +6,15 @@ pub enum Error {
     #[error("Database error: {0}")]
     Database(#[from] rusqlite::Error),
 
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
     #[error("Record not found")]
     NotFound,
 
@@ -13,4 +22,17 @@ pub enum Error {
     InvalidData(String),
 }
 
+impl Error {
+    /// Process exit code for the command-line interface; 2 is left to
+    /// argument errors
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Database(_) => 1,
+            Error::NotFound => 3,
+            Error::InvalidData(_) | Error::Csv(_) | Error::Json(_) => 4,
+            Error::Io(_) => 5,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,457 @@
+use chrono::{NaiveDate, NaiveTime};
+use rusqlite::{named_params, params, Connection, OptionalExtension};
+
+use crate::{
+    dividends::insert_income,
+    error::{Error, Result},
+    interface::Database,
+    journal::{account_id_of, asset_id_of, insert_journal_entry},
+    lots::{close_lots_fifo, insert_lot},
+    models::{
+        AssetType, EntryStatus, IncomeKind, NewJournalEntry, NewJournalEntryLine, NormalBalance,
+    },
+    money::Money,
+};
+
+/// Equity account imported transactions are booked against until they are
+/// reviewed and moved to their actual income or expense account
+pub const SUSPENSE_ACCOUNT: &str = "3900";
+/// Income account credited with gains realized on sales (and debited with losses)
+pub const CAPITAL_GAINS_ACCOUNT: &str = "4202";
+
+/// A transaction read from a bank statement, before it becomes a journal entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementLine {
+    pub date: NaiveDate,
+    /// Signed amount, positive for money coming into the account
+    pub amount: Money,
+    pub description: String,
+    pub reference: Option<String>,
+    /// Identifies the line when an overlapping statement is imported again:
+    /// the bank's own transaction id where there is one, a fingerprint otherwise
+    pub external_id: String,
+}
+
+/// Outcome of an import
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportSummary {
+    /// Draft entries created, in statement order
+    pub entry_ids: Vec<i64>,
+    /// Lines that had already been imported into the account
+    pub duplicates: usize,
+    /// Lines with a zero amount, which cannot be recorded
+    pub skipped: usize,
+}
+
+/// What happened in an investment transaction read from a brokerage statement
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvestmentKind {
+    /// `units` bought for `total`, fees included
+    Buy { units: Money, total: Money },
+    /// `units` sold for `total`, net of fees
+    Sell { units: Money, total: Money },
+    /// Income paid by the security, with the tax withheld at source
+    Income {
+        kind: IncomeKind,
+        total: Money,
+        withholding: Money,
+    },
+}
+
+/// An investment transaction ready to be recorded by [`insert_investment_transaction`]
+pub(crate) struct InvestmentTransaction<'a> {
+    pub account_number: &'a str,
+    /// Account paying for buys and receiving the proceeds of sales, usually
+    /// the brokerage account itself
+    pub cash_account_number: &'a str,
+    pub currency: &'a str,
+    pub asset_code: &'a str,
+    pub date: NaiveDate,
+    pub memo: Option<&'a str>,
+    pub reference: Option<&'a str>,
+    pub kind: &'a InvestmentKind,
+}
+
+impl Database {
+    /// Maps the account id a bank or broker uses in its statements (an OFX
+    /// ACCTID, an IBAN, ...) to `account_number`, replacing any previous mapping.
+    pub fn map_external_account<S: AsRef<str>>(
+        &mut self,
+        external_id: S,
+        account_number: S,
+    ) -> Result<i64> {
+        let t = self.transaction()?;
+
+        let id = t
+            .query_row(
+                "INSERT INTO external_accounts (external_id, account_id)
+                 SELECT :external_id, id FROM accounts WHERE account_number = :account_number
+                 ON CONFLICT (external_id) DO UPDATE SET account_id = excluded.account_id
+                 RETURNING id",
+                named_params! {
+                    ":external_id": external_id.as_ref(),
+                    ":account_number": account_number.as_ref(),
+                },
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or(Error::NotFound)?;
+
+        t.commit()?;
+
+        Ok(id)
+    }
+
+    /// Returns the number of the account `external_id` is mapped to.
+    pub fn mapped_account<S: AsRef<str>>(&self, external_id: S) -> Result<Option<String>> {
+        let account_number = self
+            .conn()
+            .query_row(
+                "SELECT a.account_number
+                 FROM external_accounts e
+                 JOIN accounts a ON a.id = e.account_id
+                 WHERE e.external_id = ?1",
+                [external_id.as_ref()],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(account_number)
+    }
+
+    /// Records statement `lines` of `account_number` as draft entries in
+    /// `asset_code`, balanced against [`SUSPENSE_ACCOUNT`].
+    ///
+    /// Lines whose external id was already imported into the account are
+    /// skipped, so overlapping statements can be imported safely. All entries
+    /// are written in a single transaction.
+    pub fn import_statement_lines<S: AsRef<str>>(
+        &mut self,
+        account_number: S,
+        asset_code: S,
+        lines: &[StatementLine],
+    ) -> Result<ImportSummary> {
+        let mut summary = ImportSummary::default();
+
+        self.snapshot_before("import")?;
+        let t = self.transaction()?;
+        insert_statement_lines(
+            &t,
+            account_number.as_ref(),
+            asset_code.as_ref(),
+            lines,
+            &mut summary,
+        )?;
+        t.commit()?;
+
+        Ok(summary)
+    }
+}
+
+/// Inserts statement lines inside an already open transaction, adding the
+/// outcome to `summary`. See [`Database::import_statement_lines`].
+pub(crate) fn insert_statement_lines(
+    t: &Connection,
+    account_number: &str,
+    asset_code: &str,
+    lines: &[StatementLine],
+    summary: &mut ImportSummary,
+) -> Result<()> {
+    let account_id = account_id_of(t, account_number)?;
+
+    for line in lines {
+        if is_imported(t, account_id, &line.external_id)? {
+            summary.duplicates += 1;
+            continue;
+        }
+        if line.amount.is_zero() {
+            summary.skipped += 1;
+            continue;
+        }
+
+        let (account_side, suspense_side) = if line.amount.signum() > 0 {
+            (NormalBalance::Debit, NormalBalance::Credit)
+        } else {
+            (NormalBalance::Credit, NormalBalance::Debit)
+        };
+        let entry_line = |account_number: &str, entry_type| NewJournalEntryLine {
+            account_number: account_number.to_string(),
+            asset_code: asset_code.to_string(),
+            entry_type,
+            amount: line.amount.abs(),
+            reference_amount: None,
+            description: None,
+        };
+
+        let entry = NewJournalEntry {
+            date: line.date.and_time(NaiveTime::MIN).and_utc(),
+            description: line.description.clone(),
+            reference_number: line.reference.clone(),
+            reference_asset_code: asset_code.to_string(),
+            status: EntryStatus::Draft,
+            lines: vec![
+                entry_line(account_number, account_side),
+                entry_line(SUSPENSE_ACCOUNT, suspense_side),
+            ],
+        };
+        let journal_entry_id = insert_journal_entry(t, &entry)?;
+        record_imported(t, account_id, &line.external_id, journal_entry_id)?;
+
+        summary.entry_ids.push(journal_entry_id);
+    }
+
+    Ok(())
+}
+
+/// Whether a statement line with `external_id` was already imported into the account
+pub(crate) fn is_imported(t: &Connection, account_id: i64, external_id: &str) -> Result<bool> {
+    Ok(t.query_row(
+        "SELECT 1 FROM imported_transactions
+         WHERE account_id = ?1 AND external_id = ?2",
+        params![account_id, external_id],
+        |_| Ok(()),
+    )
+    .optional()?
+    .is_some())
+}
+
+pub(crate) fn record_imported(
+    t: &Connection,
+    account_id: i64,
+    external_id: &str,
+    journal_entry_id: i64,
+) -> Result<()> {
+    t.execute(
+        "INSERT INTO imported_transactions (account_id, external_id, journal_entry_id)
+         VALUES (:account_id, :external_id, :journal_entry_id)",
+        named_params! {
+            ":account_id": account_id,
+            ":external_id": external_id,
+            ":journal_entry_id": journal_entry_id,
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Records an investment transaction as a posted entry and returns its id.
+///
+/// Buys open a lot at their total cost. Sells close lots first in, first out
+/// and book the difference between proceeds and cost to
+/// [`CAPITAL_GAINS_ACCOUNT`]. Income is recorded as a dividend or interest
+/// payment on the security.
+pub(crate) fn insert_investment_transaction(
+    t: &Connection,
+    transaction: &InvestmentTransaction,
+) -> Result<i64> {
+    let InvestmentTransaction {
+        account_number,
+        cash_account_number,
+        currency,
+        asset_code,
+        ..
+    } = *transaction;
+    let date = transaction.date.and_time(NaiveTime::MIN).and_utc();
+
+    let line = |account_number: &str, asset_code: &str, entry_type, amount, reference_amount| {
+        NewJournalEntryLine {
+            account_number: account_number.to_string(),
+            asset_code: asset_code.to_string(),
+            entry_type,
+            amount,
+            reference_amount,
+            description: None,
+        }
+    };
+    let entry = |description: String, lines| NewJournalEntry {
+        date,
+        description: match transaction.memo {
+            Some(memo) => format!("{description} - {memo}"),
+            None => description,
+        },
+        reference_number: transaction.reference.map(String::from),
+        reference_asset_code: currency.to_string(),
+        status: EntryStatus::Posted,
+        lines,
+    };
+
+    match transaction.kind {
+        InvestmentKind::Buy { units, total } => {
+            let journal_entry_id = insert_journal_entry(
+                t,
+                &entry(
+                    format!("Buy {units} {asset_code}"),
+                    vec![
+                        line(
+                            account_number,
+                            asset_code,
+                            NormalBalance::Debit,
+                            *units,
+                            Some(*total),
+                        ),
+                        line(
+                            cash_account_number,
+                            currency,
+                            NormalBalance::Credit,
+                            *total,
+                            None,
+                        ),
+                    ],
+                ),
+            )?;
+            insert_lot(
+                t,
+                account_number,
+                asset_code,
+                transaction.date,
+                *units,
+                *total,
+                currency,
+                Some(journal_entry_id),
+            )?;
+
+            Ok(journal_entry_id)
+        }
+        InvestmentKind::Sell { units, total } => {
+            let account_id = account_id_of(t, account_number)?;
+            let asset_id = asset_id_of(t, asset_code)?;
+            let (cost, cost_asset) = close_lots_fifo(t, account_id, asset_id, *units)?;
+            if cost_asset != currency {
+                return Err(Error::InvalidData(format!(
+                    "lots of {asset_code} were bought in {cost_asset}, not {currency}"
+                )));
+            }
+
+            let mut lines = vec![
+                line(
+                    cash_account_number,
+                    currency,
+                    NormalBalance::Debit,
+                    *total,
+                    None,
+                ),
+                line(
+                    account_number,
+                    asset_code,
+                    NormalBalance::Credit,
+                    *units,
+                    Some(cost),
+                ),
+            ];
+            let gain = *total - cost;
+            match gain.signum() {
+                1 => lines.push(line(
+                    CAPITAL_GAINS_ACCOUNT,
+                    currency,
+                    NormalBalance::Credit,
+                    gain,
+                    None,
+                )),
+                -1 => lines.push(line(
+                    CAPITAL_GAINS_ACCOUNT,
+                    currency,
+                    NormalBalance::Debit,
+                    -gain,
+                    None,
+                )),
+                _ => {}
+            }
+
+            insert_journal_entry(t, &entry(format!("Sell {units} {asset_code}"), lines))
+        }
+        InvestmentKind::Income {
+            kind,
+            total,
+            withholding,
+        } => {
+            let (_, journal_entry_id) = insert_income(
+                t,
+                *kind,
+                asset_code,
+                account_number,
+                currency,
+                *total,
+                *withholding,
+                date,
+            )?;
+
+            Ok(journal_entry_id)
+        }
+    }
+}
+
+/// Creates the asset `code` with `decimals` decimals unless it already exists.
+pub(crate) fn ensure_asset(
+    t: &Connection,
+    code: &str,
+    name: &str,
+    asset_type: AssetType,
+    decimals: u32,
+    description: &str,
+) -> Result<()> {
+    t.execute(
+        "INSERT INTO assets (code, name, type, decimals, description)
+         VALUES (:code, :name, :type, :decimals, :description)
+         ON CONFLICT (code) DO NOTHING",
+        named_params! {
+            ":code": code,
+            ":name": name,
+            ":type": format!("{:?}", asset_type).to_uppercase(),
+            ":decimals": decimals,
+            ":description": description,
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Picks a number for a new child of `parent_number` following the chart of
+/// accounts' numbering: the next one after the last sibling at the same step
+/// (`5500` → `5600`, `5303` → `5304`), or the first one below the parent. Falls
+/// back to `parent-n` when the numbering has no room left.
+pub(crate) fn next_child_number(
+    t: &Connection,
+    parent_id: i64,
+    parent_number: &str,
+) -> Result<String> {
+    let mut stmt = t.prepare("SELECT account_number FROM accounts WHERE parent_account_id = ?1")?;
+    let siblings = stmt
+        .query_map([parent_id], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let trailing_zeros = |number: u64| {
+        let digits = number.to_string();
+        (digits.len() - digits.trim_end_matches('0').len()) as u32
+    };
+    let taken = |number: &str| -> Result<bool> {
+        Ok(t.query_row(
+            "SELECT 1 FROM accounts WHERE account_number = ?1",
+            [number],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some())
+    };
+
+    if let Ok(parent) = parent_number.parse::<u64>() {
+        let scale = 10u64.pow(trailing_zeros(parent));
+        let last = siblings.iter().filter_map(|n| n.parse::<u64>().ok()).max();
+        let candidate = match last {
+            Some(last) => last + 10u64.pow(trailing_zeros(last)),
+            None => parent + 1,
+        };
+        // Still within the parent's range, e.g. 5301..5399 for 5300
+        if scale > 1 && candidate / scale == parent / scale && !taken(&candidate.to_string())? {
+            return Ok(candidate.to_string());
+        }
+    }
+
+    let mut n = siblings.len() + 1;
+    loop {
+        let candidate = format!("{parent_number}-{n}");
+        if !taken(&candidate)? {
+            return Ok(candidate);
+        }
+        n += 1;
+    }
+}
@@ -1,15 +1,29 @@
+use std::sync::Arc;
+
 use chrono::{DateTime, NaiveDate, Utc};
-use rusqlite::{named_params, params, Connection, Result, Transaction};
+use rusqlite::{
+    named_params, params, types::Type, Connection, OptionalExtension, Result, Savepoint,
+};
+use rust_decimal::Decimal;
+use serde::Serialize;
 
 use crate::{
-    models::{AssetType, EntryStatus, NormalBalance},
-    money::Money,
+    backup::SnapshotPolicy,
+    models::{Account, AccountType, Asset, AssetType, EntryStatus, NormalBalance},
+    money::{register_sql_functions, Money},
+    pool::{DatabaseOptions, ReaderPool},
 };
 
-pub struct Database(Connection);
+pub struct Database {
+    conn: Connection,
+    /// Read-only connections to the same file, if it is one
+    readers: Option<Arc<ReaderPool>>,
+    /// Where snapshots are taken before risky operations, if anywhere
+    snapshots: Option<SnapshotPolicy>,
+}
 
 /// Represents a row in the general balance report
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct GeneralBalanceReport {
     pub account_number: String,
     pub account_name: String,
@@ -18,14 +32,42 @@ pub struct GeneralBalanceReport {
 }
 
 impl Database {
+    /// Opens the book at `path` in WAL mode with the default
+    /// [`DatabaseOptions`]
     pub fn new(path: &str) -> Result<Self> {
-        let conn = Connection::open(path)?;
-        Ok(Self(conn))
+        Self::open_with(path, &DatabaseOptions::default())
     }
 
     pub fn new_in_memory() -> Result<Self> {
         let conn = Connection::open_in_memory()?;
-        Ok(Self(conn))
+        register_sql_functions(&conn)?;
+        Ok(Self::from_parts(conn, None))
+    }
+
+    pub(crate) fn from_parts(conn: Connection, readers: Option<Arc<ReaderPool>>) -> Self {
+        Self {
+            conn,
+            readers,
+            snapshots: None,
+        }
+    }
+
+    pub(crate) fn into_connection(self) -> Connection {
+        self.conn
+    }
+
+    pub(crate) fn readers(&self) -> Option<&Arc<ReaderPool>> {
+        self.readers.as_ref()
+    }
+
+    pub(crate) fn snapshot_policy(&self) -> Option<&SnapshotPolicy> {
+        self.snapshots.as_ref()
+    }
+
+    /// Takes snapshots of the book as `policy` says before migrations,
+    /// imports, restores and account closings, or stops taking them
+    pub fn set_snapshot_policy(&mut self, policy: Option<SnapshotPolicy>) {
+        self.snapshots = policy;
     }
 
     pub fn init_schema(&self) -> Result<()> {
@@ -33,12 +75,18 @@ impl Database {
         Ok(())
     }
 
-    pub(super) fn conn(&self) -> &Connection {
-        &self.0
+    pub(crate) fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    pub(crate) fn conn_mut(&mut self) -> &mut Connection {
+        &mut self.conn
     }
 
-    pub(super) fn transaction(&mut self) -> Result<Transaction> {
-        self.0.transaction()
+    /// Opens a savepoint, which starts a transaction of its own or nests
+    /// inside one already open, so that operations can be grouped atomically
+    pub(crate) fn transaction(&mut self) -> Result<Savepoint<'_>> {
+        self.conn.savepoint()
     }
 
     // Account Types
@@ -77,14 +125,15 @@ impl Database {
         code: S,
         name: S,
         asset_type: AssetType,
+        decimals: i64,
         description: Option<S>,
     ) -> Result<i64> {
         let t = self.transaction()?;
 
         let id = {
             let mut stmt = t.prepare(
-                "INSERT INTO assets (code, name, type, description)
-                         VALUES (?1, ?2, ?3, ?4) RETURNING id",
+                "INSERT INTO assets (code, name, type, decimals, description)
+                         VALUES (?1, ?2, ?3, ?4, ?5) RETURNING id",
             )?;
 
             stmt.query_row(
@@ -92,6 +141,7 @@ impl Database {
                     code.as_ref(),
                     name.as_ref(),
                     format!("{:?}", asset_type).to_uppercase(),
+                    decimals,
                     description.map(|d| d.as_ref().to_string())
                 ],
                 |row| row.get(0),
@@ -103,6 +153,169 @@ impl Database {
         Ok(id)
     }
 
+    pub fn get_asset<S: AsRef<str>>(&self, code: S) -> Result<Option<Asset>> {
+        self.conn()
+            .query_row(
+                "SELECT id, code, name, type, decimals, description FROM assets WHERE code = ?1",
+                [code.as_ref()],
+                |row| {
+                    let asset_type: String = row.get(3)?;
+
+                    Ok(Asset {
+                        id: row.get(0)?,
+                        code: row.get(1)?,
+                        name: row.get(2)?,
+                        asset_type: asset_type.parse().map_err(|e: String| {
+                            rusqlite::Error::FromSqlConversionFailure(3, Type::Text, e.into())
+                        })?,
+                        decimals: row.get(4)?,
+                        description: row.get(5)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    /// Every asset, in id order
+    pub fn list_assets(&self) -> Result<Vec<Asset>> {
+        let mut stmt = self.conn().prepare(
+            "SELECT id, code, name, type, decimals, description FROM assets ORDER BY id",
+        )?;
+        let assets = stmt
+            .query_map([], |row| {
+                let asset_type: String = row.get(3)?;
+
+                Ok(Asset {
+                    id: row.get(0)?,
+                    code: row.get(1)?,
+                    name: row.get(2)?,
+                    asset_type: asset_type.parse().map_err(|e: String| {
+                        rusqlite::Error::FromSqlConversionFailure(3, Type::Text, e.into())
+                    })?,
+                    decimals: row.get(4)?,
+                    description: row.get(5)?,
+                })
+            })?
+            .collect();
+
+        assets
+    }
+
+    // Exchange Rates
+    pub fn create_exchange_rate<S: AsRef<str>>(
+        &mut self,
+        from_asset_code: S,
+        to_asset_code: S,
+        rate: Decimal,
+        date: DateTime<Utc>,
+    ) -> Result<i64> {
+        let t = self.transaction()?;
+
+        let id = {
+            let mut stmt = t.prepare(
+                "INSERT INTO exchange_rates (from_asset_id, to_asset_id, rate, date)
+                 VALUES (
+                    (SELECT id FROM assets WHERE code = ?1),
+                    (SELECT id FROM assets WHERE code = ?2),
+                    ?3, ?4
+                 ) RETURNING id",
+            )?;
+
+            stmt.query_row(
+                params![
+                    from_asset_code.as_ref(),
+                    to_asset_code.as_ref(),
+                    Money::new(rate),
+                    date
+                ],
+                |row| row.get(0),
+            )?
+        };
+
+        t.commit()?;
+
+        Ok(id)
+    }
+
+    /// Returns the most recent rate converting `from` into `to` at or before `date`.
+    /// If only the opposite pair has been recorded, its inverse is used.
+    pub fn exchange_rate_at<S: AsRef<str>>(
+        &self,
+        from_asset_code: S,
+        to_asset_code: S,
+        date: DateTime<Utc>,
+    ) -> Result<Option<Decimal>> {
+        let mut stmt = self
+            .conn()
+            .prepare(include_str!("sql/exchange_rate_at.sql"))?;
+
+        let rate = stmt
+            .query_row(
+                named_params! {
+                    ":from_asset_code": from_asset_code.as_ref(),
+                    ":to_asset_code": to_asset_code.as_ref(),
+                    ":date": date,
+                },
+                |row| Ok((row.get::<_, Money>(0)?, row.get::<_, bool>(1)?)),
+            )
+            .optional()?;
+
+        Ok(rate.and_then(|(rate, inverted)| match inverted {
+            false => Some(rate.amount()),
+            true if rate.amount().is_zero() => None,
+            true => Some(Decimal::ONE / rate.amount()),
+        }))
+    }
+
+    /// Every account type, in id order
+    pub fn list_account_types(&self) -> Result<Vec<AccountType>> {
+        let mut stmt = self.conn().prepare(
+            "SELECT id, name, normal_balance, description FROM account_types ORDER BY id",
+        )?;
+        let account_types = stmt
+            .query_map([], |row| {
+                let normal_balance: String = row.get(2)?;
+
+                Ok(AccountType {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    normal_balance: normal_balance.parse().map_err(|e: String| {
+                        rusqlite::Error::FromSqlConversionFailure(2, Type::Text, e.into())
+                    })?,
+                    description: row.get(3)?,
+                })
+            })?
+            .collect();
+
+        account_types
+    }
+
+    /// Every account, in id order
+    pub fn list_accounts(&self) -> Result<Vec<Account>> {
+        let mut stmt = self.conn().prepare(
+            "SELECT id, account_number, name, account_type_id, parent_account_id,
+                    is_active, opening_date, closing_date, description
+             FROM accounts ORDER BY id",
+        )?;
+        let accounts = stmt
+            .query_map([], |row| {
+                Ok(Account {
+                    id: row.get(0)?,
+                    account_number: row.get(1)?,
+                    name: row.get(2)?,
+                    account_type_id: row.get(3)?,
+                    parent_account_id: row.get(4)?,
+                    is_active: row.get(5)?,
+                    opening_date: row.get(6)?,
+                    closing_date: row.get(7)?,
+                    description: row.get(8)?,
+                })
+            })?
+            .collect();
+
+        accounts
+    }
+
     // Account creation
     #[allow(clippy::too_many_arguments)]
     pub fn create_account<S: AsRef<str>>(
@@ -146,9 +359,11 @@ impl Database {
         Ok(id)
     }
 
+    /// Records a simple two-line journal entry moving `amount` from the credit
+    /// account to the debit account, and returns the new entry id.
     #[allow(clippy::too_many_arguments)]
-    fn insert_transaction<S: AsRef<str>>(
-        transaction: &Transaction,
+    pub fn insert_transaction<S: AsRef<str>>(
+        &mut self,
 
         date: DateTime<Utc>,
         description: S,
@@ -162,20 +377,25 @@ impl Database {
         credit_asset_code: S,
 
         amount: Money,
-    ) -> Result<()> {
-        transaction.execute(
+    ) -> Result<i64> {
+        let t = self.transaction()?;
+
+        let journal_entry_id: i64 = t.query_row(
             include_str!("sql/insert_journal_entry.sql"),
             named_params! {
                 ":date": date,
                 ":description": description.as_ref(),
                 ":reference_number": reference_number.as_ref(),
+                ":reference_asset_code": debit_asset_code.as_ref(),
                 ":status": format!("{:?}", status).to_uppercase(),
             },
+            |row| row.get(0),
         )?;
 
-        transaction.execute(
+        t.execute(
             include_str!("sql/insert_journal_entry_lines.sql"),
             named_params! {
+                ":journal_entry_id": journal_entry_id,
                 ":description": description.as_ref(),
                 ":amount": amount,
                 ":debit_account_number": debit_account_number.as_ref(),
@@ -185,7 +405,9 @@ impl Database {
             },
         )?;
 
-        Ok(())
+        t.commit()?;
+
+        Ok(journal_entry_id)
     }
 
     // General Balance Report
@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{named_params, params, Connection, OptionalExtension};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{
+    error::{Error, Result},
+    interface::Database,
+    models::{
+        parse_column, EntryStatus, NewAccount, NewJournalEntry, NewJournalEntryLine, NormalBalance,
+    },
+    money::Money,
+};
+
+/// A line of an account register, with the balance it leaves the account at
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegisterLine {
+    pub journal_entry_id: i64,
+    pub date: DateTime<Utc>,
+    pub description: String,
+    pub reference_number: Option<String>,
+    pub status: EntryStatus,
+    pub asset: String,
+    /// Positive for debits
+    pub amount: Money,
+    /// Balance in `asset` after this line, drafts included
+    pub balance: Money,
+}
+
+impl Database {
+    /// Records a multi-line journal entry and returns its id.
+    ///
+    /// The entry is rejected with [`Error::InvalidData`] unless it has at least
+    /// two lines, every amount is positive and debits equal credits in the
+    /// reference asset.
+    pub fn create_journal_entry(&mut self, entry: &NewJournalEntry) -> Result<i64> {
+        let t = self.transaction()?;
+        let id = insert_journal_entry(&t, entry)?;
+        t.commit()?;
+
+        Ok(id)
+    }
+
+    /// Marks a journal entry as void. Void entries stay in the book but no
+    /// longer count towards any balance.
+    pub fn void_journal_entry(&mut self, journal_entry_id: i64) -> Result<()> {
+        let t = self.transaction()?;
+
+        let status: String = t
+            .query_row(
+                "SELECT status FROM journal_entries WHERE id = ?1",
+                [journal_entry_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or(Error::NotFound)?;
+        if status == "VOID" {
+            return Err(Error::InvalidData(format!(
+                "journal entry {journal_entry_id} is already void"
+            )));
+        }
+        t.execute(
+            "UPDATE journal_entries SET status = 'VOID' WHERE id = ?1",
+            [journal_entry_id],
+        )?;
+
+        t.commit()?;
+
+        Ok(())
+    }
+
+    /// Posts a draft journal entry, so that it counts towards the balance.
+    pub fn post_journal_entry(&mut self, journal_entry_id: i64) -> Result<()> {
+        let t = self.transaction()?;
+
+        let status: String = t
+            .query_row(
+                "SELECT status FROM journal_entries WHERE id = ?1",
+                [journal_entry_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or(Error::NotFound)?;
+        if status != "DRAFT" {
+            return Err(Error::InvalidData(format!(
+                "journal entry {journal_entry_id} is not a draft"
+            )));
+        }
+        t.execute(
+            "UPDATE journal_entries SET status = 'POSTED' WHERE id = ?1",
+            [journal_entry_id],
+        )?;
+
+        t.commit()?;
+
+        Ok(())
+    }
+
+    /// Lists the draft journal entries awaiting review in date order, each
+    /// with its id.
+    pub fn draft_journal_entries(&self) -> Result<Vec<(i64, NewJournalEntry)>> {
+        self.journal_entries(EntryStatus::Draft)
+    }
+
+    /// Lists the journal entries with `status` in date order, each with its id.
+    pub fn journal_entries(&self, status: EntryStatus) -> Result<Vec<(i64, NewJournalEntry)>> {
+        let mut stmt = self.conn().prepare(
+            "SELECT je.id, je.date, je.description, je.reference_number, a.code
+             FROM journal_entries je
+             JOIN assets a ON a.id = je.reference_asset_id
+             WHERE je.status = ?1
+             ORDER BY je.date, je.id",
+        )?;
+        let mut entries = stmt
+            .query_map([format!("{status:?}").to_uppercase()], |row| {
+                Ok((
+                    row.get(0)?,
+                    NewJournalEntry {
+                        date: row.get(1)?,
+                        description: row.get(2)?,
+                        reference_number: row.get(3)?,
+                        reference_asset_code: row.get(4)?,
+                        status,
+                        lines: Vec::new(),
+                    },
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<(i64, NewJournalEntry)>>>()?;
+
+        let mut stmt = self.conn().prepare(
+            "SELECT acc.account_number, a.code, jel.entry_type, jel.amount,
+                    jel.reference_amount, jel.description
+             FROM journal_entry_lines jel
+             JOIN accounts acc ON acc.id = jel.account_id
+             JOIN assets a ON a.id = jel.asset_id
+             WHERE jel.journal_entry_id = ?1
+             ORDER BY jel.id",
+        )?;
+        for (id, entry) in &mut entries {
+            let lines = stmt.query_map([*id], |row| {
+                Ok(NewJournalEntryLine {
+                    account_number: row.get(0)?,
+                    asset_code: row.get(1)?,
+                    entry_type: parse_column(row, 2)?,
+                    amount: row.get(3)?,
+                    reference_amount: row.get(4)?,
+                    description: row.get(5)?,
+                })
+            })?;
+            for line in lines {
+                entry.lines.push(line?);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Opens an active account, looking up its type by name and its parent by
+    /// account number, and returns the new account id.
+    pub fn open_account(&mut self, account: &NewAccount) -> Result<i64> {
+        let account_type_id: i64 = self
+            .conn()
+            .query_row(
+                "SELECT id FROM account_types WHERE name = ?1 COLLATE NOCASE",
+                [&account.account_type],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| {
+                Error::InvalidData(format!("unknown account type {:?}", account.account_type))
+            })?;
+        let parent_account_id = match &account.parent_account_number {
+            Some(parent) => Some(
+                self.conn()
+                    .query_row(
+                        "SELECT id FROM accounts WHERE account_number = ?1",
+                        [parent],
+                        |row| row.get::<_, i64>(0),
+                    )
+                    .optional()?
+                    .ok_or_else(|| {
+                        Error::InvalidData(format!("unknown parent account {parent:?}"))
+                    })?,
+            ),
+            None => None,
+        };
+
+        Ok(self.create_account(
+            account.account_number.as_str(),
+            account.name.as_str(),
+            account_type_id,
+            parent_account_id,
+            true,
+            account.opening_date,
+            None,
+            account.description.as_deref(),
+        )?)
+    }
+
+    /// Closes an account on `closing_date`. Only accounts without a balance
+    /// in any asset, drafts included, can be closed.
+    pub fn close_account<S: AsRef<str>>(
+        &mut self,
+        account_number: S,
+        closing_date: NaiveDate,
+    ) -> Result<()> {
+        self.snapshot_before("close_account")?;
+        let t = self.transaction()?;
+
+        let account_id = account_id_of(&t, account_number.as_ref())?;
+
+        let open_balance: Option<(String, Money)> = t
+            .query_row(
+                "SELECT a.code, dec_sum(
+                        CASE WHEN jel.entry_type = 'DEBIT' THEN jel.amount ELSE dec_neg(jel.amount) END
+                    ) AS balance
+                 FROM journal_entry_lines jel
+                 JOIN journal_entries je ON je.id = jel.journal_entry_id
+                 JOIN assets a ON a.id = jel.asset_id
+                 WHERE jel.account_id = ?1 AND je.status != 'VOID'
+                 GROUP BY a.code
+                 HAVING dec_sign(balance) != 0
+                 ORDER BY a.code",
+                [account_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        if let Some((asset, balance)) = open_balance {
+            return Err(Error::InvalidData(format!(
+                "account {} still has a balance of {balance} {asset}",
+                account_number.as_ref()
+            )));
+        }
+
+        t.execute(
+            "UPDATE accounts SET closing_date = ?1, is_active = false WHERE id = ?2",
+            params![closing_date, account_id],
+        )?;
+
+        t.commit()?;
+
+        Ok(())
+    }
+
+    /// Lists the draft and posted lines booked to an account in date order,
+    /// with a running balance per asset.
+    pub fn register<S: AsRef<str>>(&self, account_number: S) -> Result<Vec<RegisterLine>> {
+        let account_id = account_id_of(self.conn(), account_number.as_ref())?;
+
+        let mut stmt = self.conn().prepare(
+            "SELECT je.id, je.date, je.description, je.reference_number, je.status, a.code,
+                    CASE WHEN jel.entry_type = 'DEBIT' THEN jel.amount ELSE dec_neg(jel.amount) END
+             FROM journal_entry_lines jel
+             JOIN journal_entries je ON je.id = jel.journal_entry_id
+             JOIN assets a ON a.id = jel.asset_id
+             WHERE jel.account_id = ?1 AND je.status != 'VOID'
+             ORDER BY je.date, je.id, jel.id",
+        )?;
+        let rows = stmt.query_map([account_id], |row| {
+            Ok(RegisterLine {
+                journal_entry_id: row.get(0)?,
+                date: row.get(1)?,
+                description: row.get(2)?,
+                reference_number: row.get(3)?,
+                status: parse_column(row, 4)?,
+                asset: row.get(5)?,
+                amount: row.get(6)?,
+                balance: Money::default(),
+            })
+        })?;
+
+        let mut balances: HashMap<String, Money> = HashMap::new();
+        let mut lines = Vec::new();
+        for line in rows {
+            let mut line = line?;
+            let balance = balances.entry(line.asset.clone()).or_default();
+            *balance += line.amount;
+            line.balance = Money::new(balance.amount().normalize());
+            lines.push(line);
+        }
+
+        Ok(lines)
+    }
+}
+
+/// The id of the account `account_number`, or [`Error::NotFound`]
+pub(crate) fn account_id_of(t: &Connection, account_number: &str) -> Result<i64> {
+    t.query_row(
+        "SELECT id FROM accounts WHERE account_number = ?1",
+        [account_number],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or(Error::NotFound)
+}
+
+/// The id of the asset `asset_code`, or [`Error::NotFound`]
+pub(crate) fn asset_id_of(t: &Connection, asset_code: &str) -> Result<i64> {
+    t.query_row(
+        "SELECT id FROM assets WHERE code = ?1",
+        [asset_code],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or(Error::NotFound)
+}
+
+/// Validates and inserts `entry` inside an already open transaction, so that it
+/// can be recorded atomically with other rows that refer to it.
+pub(crate) fn insert_journal_entry(t: &Connection, entry: &NewJournalEntry) -> Result<i64> {
+    validate_journal_entry(entry)?;
+
+    t.query_row(
+        "SELECT id FROM assets WHERE code = ?1",
+        [&entry.reference_asset_code],
+        |row| row.get::<_, i64>(0),
+    )
+    .optional()?
+    .ok_or_else(|| Error::InvalidData(format!("unknown asset {:?}", entry.reference_asset_code)))?;
+
+    let journal_entry_id: i64 = t.query_row(
+        include_str!("sql/insert_journal_entry.sql"),
+        named_params! {
+            ":date": entry.date,
+            ":description": entry.description,
+            ":reference_number": entry.reference_number,
+            ":reference_asset_code": entry.reference_asset_code,
+            ":status": format!("{:?}", entry.status).to_uppercase(),
+        },
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = t.prepare(
+        "INSERT INTO journal_entry_lines (
+            journal_entry_id, account_id, asset_id, entry_type,
+            amount, reference_amount, description
+        ) VALUES (
+            :journal_entry_id,
+            (SELECT id FROM accounts WHERE account_number = :account_number),
+            (SELECT id FROM assets WHERE code = :asset_code),
+            :entry_type,
+            :amount,
+            :reference_amount,
+            :description
+        )",
+    )?;
+
+    for line in &entry.lines {
+        stmt.execute(named_params! {
+            ":journal_entry_id": journal_entry_id,
+            ":account_number": line.account_number,
+            ":asset_code": line.asset_code,
+            ":entry_type": format!("{:?}", line.entry_type).to_uppercase(),
+            ":amount": line.amount,
+            ":reference_amount": line.reference_amount,
+            ":description": line.description,
+        })
+        .map_err(|e| match e {
+            rusqlite::Error::SqliteFailure(_, Some(ref msg)) if msg.contains("NOT NULL") => {
+                Error::InvalidData(format!(
+                    "unknown account {:?} or asset {:?}",
+                    line.account_number, line.asset_code
+                ))
+            }
+            e => e.into(),
+        })?;
+    }
+
+    Ok(journal_entry_id)
+}
+
+pub(crate) fn validate_journal_entry(entry: &NewJournalEntry) -> Result<()> {
+    if entry.lines.len() < 2 {
+        return Err(Error::InvalidData(
+            "a journal entry needs at least two lines".into(),
+        ));
+    }
+
+    let mut balance = Decimal::ZERO;
+    for line in &entry.lines {
+        if line.amount.amount() <= Decimal::ZERO {
+            return Err(Error::InvalidData(format!(
+                "line amounts must be positive, got {}",
+                line.amount.amount()
+            )));
+        }
+
+        let value = match line.reference_amount {
+            Some(reference_amount) => reference_amount.amount(),
+            None if line.asset_code == entry.reference_asset_code => line.amount.amount(),
+            None => {
+                return Err(Error::InvalidData(format!(
+                    "line in {} needs a reference amount in {}",
+                    line.asset_code, entry.reference_asset_code
+                )))
+            }
+        };
+
+        match line.entry_type {
+            NormalBalance::Debit => balance += value,
+            NormalBalance::Credit => balance -= value,
+        }
+    }
+
+    if !balance.is_zero() {
+        return Err(Error::InvalidData(format!(
+            "journal entry is unbalanced by {balance} {}",
+            entry.reference_asset_code
+        )));
+    }
+
+    Ok(())
+}
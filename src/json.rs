@@ -0,0 +1,566 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Write},
+};
+
+use rusqlite::{named_params, params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    interface::Database,
+    journal::validate_journal_entry,
+    models::{
+        parse_column, Account, AccountType, Asset, ExchangeRate, JournalEntry, JournalEntryLine,
+        NewJournalEntry, NewJournalEntryLine,
+    },
+    money::Money,
+};
+
+/// Written in the `format` field of every document
+pub const JSON_FORMAT: &str = "mm-schema";
+/// Version of the document layout written by [`Database::export_json`]
+pub const JSON_FORMAT_VERSION: u32 = 1;
+
+/// The whole book as a self-contained JSON document
+///
+/// Rows keep the ids they have in the exporting database; they are only
+/// used to link rows within the document and are remapped on import.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookDocument {
+    pub format: String,
+    pub version: u32,
+    pub account_types: Vec<AccountType>,
+    pub assets: Vec<Asset>,
+    pub accounts: Vec<Account>,
+    pub exchange_rates: Vec<ExchangeRate>,
+    pub journal_entries: Vec<BookDocumentEntry>,
+}
+
+/// A journal entry together with its lines
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookDocumentEntry {
+    #[serde(flatten)]
+    pub entry: JournalEntry,
+    pub lines: Vec<JournalEntryLine>,
+}
+
+/// Rows created by [`Database::import_json`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JsonImport {
+    pub account_types: usize,
+    pub assets: usize,
+    pub accounts: usize,
+    pub exchange_rates: usize,
+    pub journal_entry_ids: Vec<i64>,
+}
+
+impl Database {
+    /// Reads the account types, assets, accounts, exchange rates and journal
+    /// entries of the book into a [`BookDocument`].
+    pub fn book_document(&self) -> Result<BookDocument> {
+        let conn = self.conn();
+
+        let account_types = self.list_account_types()?;
+        let assets = self.list_assets()?;
+        let accounts = self.list_accounts()?;
+
+        let exchange_rates = conn
+            .prepare(
+                "SELECT id, from_asset_id, to_asset_id, rate, date FROM exchange_rates ORDER BY id",
+            )?
+            .query_map([], |row| {
+                Ok(ExchangeRate {
+                    id: row.get(0)?,
+                    from_asset_id: row.get(1)?,
+                    to_asset_id: row.get(2)?,
+                    rate: row.get::<_, Money>(3)?.amount(),
+                    date: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut lines: HashMap<i64, Vec<JournalEntryLine>> = HashMap::new();
+        let mut stmt = conn.prepare(
+            "SELECT id, journal_entry_id, account_id, asset_id, entry_type,
+                    amount, reference_amount, description
+             FROM journal_entry_lines ORDER BY id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(JournalEntryLine {
+                id: row.get(0)?,
+                journal_entry_id: row.get(1)?,
+                account_id: row.get(2)?,
+                asset_id: row.get(3)?,
+                entry_type: parse_column(row, 4)?,
+                amount: row.get(5)?,
+                reference_amount: row.get(6)?,
+                description: row.get(7)?,
+            })
+        })?;
+        for line in rows {
+            let line = line?;
+            lines.entry(line.journal_entry_id).or_default().push(line);
+        }
+
+        let journal_entries = conn
+            .prepare(
+                "SELECT id, date, description, reference_number, reference_asset_id,
+                        status, created_at
+                 FROM journal_entries ORDER BY id",
+            )?
+            .query_map([], |row| {
+                Ok(JournalEntry {
+                    id: row.get(0)?,
+                    date: row.get(1)?,
+                    description: row.get(2)?,
+                    reference_number: row.get(3)?,
+                    reference_asset_id: row.get(4)?,
+                    status: parse_column(row, 5)?,
+                    created_at: row.get(6)?,
+                })
+            })?
+            .map(|entry| {
+                entry.map(|entry| BookDocumentEntry {
+                    lines: lines.remove(&entry.id).unwrap_or_default(),
+                    entry,
+                })
+            })
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(BookDocument {
+            format: JSON_FORMAT.to_string(),
+            version: JSON_FORMAT_VERSION,
+            account_types,
+            assets,
+            accounts,
+            exchange_rates,
+            journal_entries,
+        })
+    }
+
+    /// Writes the book as a pretty-printed [`BookDocument`].
+    pub fn export_json<W: Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer_pretty(writer, &self.book_document()?)?;
+        Ok(())
+    }
+
+    /// Reads a document written by [`Database::export_json`] into the book.
+    /// See [`Database::import_book_document`].
+    pub fn import_json<R: Read>(&mut self, reader: R) -> Result<JsonImport> {
+        let document: BookDocument = serde_json::from_reader(reader)?;
+        self.import_book_document(&document)
+    }
+
+    /// Adds the contents of `document` to the book, giving every row a new id.
+    ///
+    /// Account types, assets and accounts that already exist under the same
+    /// name, code or number are reused rather than duplicated, as long as
+    /// their normal balance, asset type and decimals, or account type agree
+    /// with the document. Exchange rates already known for a pair and date
+    /// are kept. Journal entries are always added, with their status and
+    /// creation time.
+    ///
+    /// The whole document is checked before anything is written: every id
+    /// must refer to a row of the document, the account hierarchy must not
+    /// loop and every entry must balance. Nothing is imported if one check
+    /// fails.
+    pub fn import_book_document(&mut self, document: &BookDocument) -> Result<JsonImport> {
+        validate_document(self.conn(), document)?;
+
+        self.snapshot_before("import")?;
+        let t = self.transaction()?;
+        let mut import = JsonImport::default();
+
+        let mut account_types: HashMap<i64, i64> = HashMap::new();
+        for account_type in &document.account_types {
+            let id = match existing_account_type(&t, account_type)? {
+                Some(id) => id,
+                None => {
+                    import.account_types += 1;
+                    t.query_row(
+                        "INSERT INTO account_types (name, normal_balance, description)
+                         VALUES (?1, ?2, ?3) RETURNING id",
+                        params![
+                            account_type.name,
+                            format!("{:?}", account_type.normal_balance).to_uppercase(),
+                            account_type.description,
+                        ],
+                        |row| row.get(0),
+                    )?
+                }
+            };
+            account_types.insert(account_type.id, id);
+        }
+
+        let mut assets: HashMap<i64, i64> = HashMap::new();
+        for asset in &document.assets {
+            let id = match existing_asset(&t, asset)? {
+                Some(id) => id,
+                None => {
+                    import.assets += 1;
+                    t.query_row(
+                        "INSERT INTO assets (code, name, type, decimals, description)
+                         VALUES (?1, ?2, ?3, ?4, ?5) RETURNING id",
+                        params![
+                            asset.code,
+                            asset.name,
+                            format!("{:?}", asset.asset_type).to_uppercase(),
+                            asset.decimals,
+                            asset.description,
+                        ],
+                        |row| row.get(0),
+                    )?
+                }
+            };
+            assets.insert(asset.id, id);
+        }
+
+        // Parents before their children
+        let mut accounts: HashMap<i64, i64> = HashMap::new();
+        while accounts.len() < document.accounts.len() {
+            for account in &document.accounts {
+                if accounts.contains_key(&account.id) {
+                    continue;
+                }
+                let parent_id = match account.parent_account_id {
+                    Some(parent_id) => match accounts.get(&parent_id) {
+                        Some(id) => Some(*id),
+                        None => continue,
+                    },
+                    None => None,
+                };
+
+                let id = insert_account(
+                    &t,
+                    account,
+                    account_types[&account.account_type_id],
+                    parent_id,
+                    &mut import,
+                )?;
+                accounts.insert(account.id, id);
+            }
+        }
+
+        for rate in &document.exchange_rates {
+            import.exchange_rates += t.execute(
+                "INSERT INTO exchange_rates (from_asset_id, to_asset_id, rate, date)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (from_asset_id, to_asset_id, date) DO NOTHING",
+                params![
+                    assets[&rate.from_asset_id],
+                    assets[&rate.to_asset_id],
+                    Money::new(rate.rate),
+                    rate.date,
+                ],
+            )?;
+        }
+
+        let mut insert_line = t.prepare(
+            "INSERT INTO journal_entry_lines (
+                journal_entry_id, account_id, asset_id, entry_type,
+                amount, reference_amount, description
+             ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        for BookDocumentEntry { entry, lines } in &document.journal_entries {
+            let journal_entry_id: i64 = t.query_row(
+                "INSERT INTO journal_entries (
+                    date, description, reference_number, reference_asset_id, status, created_at
+                 ) VALUES (
+                    :date, :description, :reference_number, :reference_asset_id, :status, :created_at
+                 ) RETURNING id",
+                named_params! {
+                    ":date": entry.date,
+                    ":description": entry.description,
+                    ":reference_number": entry.reference_number,
+                    ":reference_asset_id": assets[&entry.reference_asset_id],
+                    ":status": format!("{:?}", entry.status).to_uppercase(),
+                    ":created_at": entry.created_at,
+                },
+                |row| row.get(0),
+            )?;
+
+            for line in lines {
+                insert_line.execute(params![
+                    journal_entry_id,
+                    accounts[&line.account_id],
+                    assets[&line.asset_id],
+                    format!("{:?}", line.entry_type).to_uppercase(),
+                    line.amount,
+                    line.reference_amount,
+                    line.description,
+                ])?;
+            }
+            import.journal_entry_ids.push(journal_entry_id);
+        }
+        drop(insert_line);
+
+        t.commit()?;
+
+        Ok(import)
+    }
+}
+
+fn insert_account(
+    t: &Connection,
+    account: &Account,
+    account_type_id: i64,
+    parent_id: Option<i64>,
+    import: &mut JsonImport,
+) -> Result<i64> {
+    if let Some(id) = existing_account(t, account, Some(account_type_id))? {
+        return Ok(id);
+    }
+
+    import.accounts += 1;
+    let id = t.query_row(
+        "INSERT INTO accounts (
+            account_number, name, account_type_id, parent_account_id,
+            is_active, opening_date, closing_date, description
+         ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8) RETURNING id",
+        params![
+            account.account_number,
+            account.name,
+            account_type_id,
+            parent_id,
+            account.is_active,
+            account.opening_date,
+            account.closing_date,
+            account.description,
+        ],
+        |row| row.get(0),
+    )?;
+
+    Ok(id)
+}
+
+/// The account type of the book named like `account_type`, which must have
+/// the same normal balance
+fn existing_account_type(conn: &Connection, account_type: &AccountType) -> Result<Option<i64>> {
+    let existing: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT id, normal_balance FROM account_types WHERE name = ?1",
+            [&account_type.name],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let normal_balance = format!("{:?}", account_type.normal_balance).to_uppercase();
+
+    match existing {
+        Some((_, existing)) if existing != normal_balance => Err(Error::InvalidData(format!(
+            "account type {:?} is {existing} in the book but {normal_balance} in the document",
+            account_type.name
+        ))),
+        existing => Ok(existing.map(|(id, _)| id)),
+    }
+}
+
+/// The asset of the book with the code of `asset`, which must have the same
+/// type and decimals
+fn existing_asset(conn: &Connection, asset: &Asset) -> Result<Option<i64>> {
+    let existing: Option<(i64, String, i64)> = conn
+        .query_row(
+            "SELECT id, type, decimals FROM assets WHERE code = ?1",
+            [&asset.code],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    let asset_type = format!("{:?}", asset.asset_type).to_uppercase();
+
+    match existing {
+        Some((_, existing_type, decimals))
+            if existing_type != asset_type || decimals != asset.decimals =>
+        {
+            Err(Error::InvalidData(format!(
+                "asset {} is {existing_type} with {decimals} decimals in the book \
+                 but {asset_type} with {} in the document",
+                asset.code, asset.decimals
+            )))
+        }
+        existing => Ok(existing.map(|(id, _, _)| id)),
+    }
+}
+
+/// The account of the book numbered like `account`, which must be of the
+/// account type `account_type_id` of the book, `None` if that type is new
+fn existing_account(
+    conn: &Connection,
+    account: &Account,
+    account_type_id: Option<i64>,
+) -> Result<Option<i64>> {
+    let existing: Option<(i64, i64)> = conn
+        .query_row(
+            "SELECT id, account_type_id FROM accounts WHERE account_number = ?1",
+            [&account.account_number],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    match existing {
+        Some((_, existing_type_id)) if Some(existing_type_id) != account_type_id => {
+            Err(Error::InvalidData(format!(
+                "account {} has another account type in the book than in the document",
+                account.account_number
+            )))
+        }
+        existing => Ok(existing.map(|(id, _)| id)),
+    }
+}
+
+/// Checks that the document is one this version can read, that its ids
+/// refer to rows of the document, that its entries balance and that the
+/// rows it shares with the book in `conn` agree with it
+fn validate_document(conn: &Connection, document: &BookDocument) -> Result<()> {
+    if document.format != JSON_FORMAT {
+        return Err(Error::InvalidData(format!(
+            "not a book document: format {:?}",
+            document.format
+        )));
+    }
+    if document.version == 0 || document.version > JSON_FORMAT_VERSION {
+        return Err(Error::InvalidData(format!(
+            "unsupported book document version {}, expected at most {JSON_FORMAT_VERSION}",
+            document.version
+        )));
+    }
+
+    let account_types = unique(
+        "account type",
+        document
+            .account_types
+            .iter()
+            .map(|t| (t.id, t.name.as_str())),
+    )?;
+    let assets = unique(
+        "asset",
+        document.assets.iter().map(|a| (a.id, a.code.as_str())),
+    )?;
+    let accounts = unique(
+        "account",
+        document
+            .accounts
+            .iter()
+            .map(|a| (a.id, a.account_number.as_str())),
+    )?;
+    unique(
+        "journal entry",
+        document.journal_entries.iter().map(|e| (e.entry.id, "")),
+    )?;
+
+    let missing = |what: &str, id: i64, from: String| {
+        Error::InvalidData(format!("{from} refers to unknown {what} {id}"))
+    };
+
+    let parents: HashMap<i64, Option<i64>> = document
+        .accounts
+        .iter()
+        .map(|account| (account.id, account.parent_account_id))
+        .collect();
+    for account in &document.accounts {
+        let from = || format!("account {}", account.account_number);
+        if !account_types.contains_key(&account.account_type_id) {
+            return Err(missing("account type", account.account_type_id, from()));
+        }
+
+        let mut seen = HashSet::from([account.id]);
+        let mut parent = account.parent_account_id;
+        while let Some(parent_id) = parent {
+            if !seen.insert(parent_id) {
+                return Err(Error::InvalidData(format!(
+                    "account {} is its own ancestor",
+                    account.account_number
+                )));
+            }
+            parent = *parents
+                .get(&parent_id)
+                .ok_or_else(|| missing("parent account", parent_id, from()))?;
+        }
+    }
+
+    for rate in &document.exchange_rates {
+        for asset_id in [rate.from_asset_id, rate.to_asset_id] {
+            if !assets.contains_key(&asset_id) {
+                return Err(missing(
+                    "asset",
+                    asset_id,
+                    format!("exchange rate {}", rate.id),
+                ));
+            }
+        }
+    }
+
+    for BookDocumentEntry { entry, lines } in &document.journal_entries {
+        let from = || format!("journal entry {}", entry.id);
+        let reference_asset_code = assets
+            .get(&entry.reference_asset_id)
+            .ok_or_else(|| missing("asset", entry.reference_asset_id, from()))?;
+
+        let mut new_lines = Vec::new();
+        for line in lines {
+            if line.journal_entry_id != entry.id {
+                return Err(Error::InvalidData(format!(
+                    "line {} is listed under journal entry {} but belongs to {}",
+                    line.id, entry.id, line.journal_entry_id
+                )));
+            }
+            new_lines.push(NewJournalEntryLine {
+                account_number: accounts
+                    .get(&line.account_id)
+                    .ok_or_else(|| missing("account", line.account_id, from()))?
+                    .to_string(),
+                asset_code: assets
+                    .get(&line.asset_id)
+                    .ok_or_else(|| missing("asset", line.asset_id, from()))?
+                    .to_string(),
+                entry_type: line.entry_type,
+                amount: line.amount,
+                reference_amount: line.reference_amount,
+                description: None,
+            });
+        }
+
+        validate_journal_entry(&NewJournalEntry {
+            date: entry.date,
+            description: entry.description.clone(),
+            reference_number: None,
+            reference_asset_code: reference_asset_code.to_string(),
+            status: entry.status,
+            lines: new_lines,
+        })
+        .map_err(|e| match e {
+            Error::InvalidData(message) => Error::InvalidData(format!("{}: {message}", from())),
+            e => e,
+        })?;
+    }
+
+    let mut book_account_types = HashMap::new();
+    for account_type in &document.account_types {
+        book_account_types.insert(account_type.id, existing_account_type(conn, account_type)?);
+    }
+    for asset in &document.assets {
+        existing_asset(conn, asset)?;
+    }
+    for account in &document.accounts {
+        existing_account(conn, account, book_account_types[&account.account_type_id])?;
+    }
+
+    Ok(())
+}
+
+/// Maps ids to keys, rejecting duplicate ids and duplicate non-empty keys
+fn unique<'a>(
+    what: &str,
+    rows: impl Iterator<Item = (i64, &'a str)>,
+) -> Result<HashMap<i64, &'a str>> {
+    let mut ids = HashMap::new();
+    let mut keys = HashSet::new();
+    for (id, key) in rows {
+        if ids.insert(id, key).is_some() {
+            return Err(Error::InvalidData(format!("duplicate {what} id {id}")));
+        }
+        if !key.is_empty() && !keys.insert(key) {
+            return Err(Error::InvalidData(format!("duplicate {what} {key:?}")));
+        }
+    }
+    Ok(ids)
+}
@@ -0,0 +1,115 @@
+//! Double-entry bookkeeping on top of SQLite.
+//!
+//! [`Database`] owns the connection and exposes every operation on the book:
+//! the chart of accounts, assets and rates, journal entries and the reports
+//! built from them. Amounts are [`Money`], an exact decimal.
+//!
+//! The core of the book is also available through [`LedgerStore`], which
+//! [`MemoryStore`] implements without SQLite for tests and embedding.
+//!
+//! Every change to accounts, assets, rates and journal entries is recorded in
+//! an append-only audit log, read back with [`Database::history`].
+//!
+//! Optional subsystems sit behind features:
+//!
+//! - `importers` (default): CSV, OFX, QIF, camt.053, MT940, beancount and
+//!   ledger importers, plus the QIF export that shares their parser
+//! - `server`: a local HTTP/JSON API served by `serve`
+//! - `async`: `AsyncDatabase`, a handle that runs the book on its own thread
+//!   for async runtimes such as tokio
+//! - `encryption`: SQLCipher-encrypted books, see `Database::open_encrypted`
+//! - `cli` (default): dependencies of the `mm-schema` binary
+
+#[cfg(feature = "async")]
+mod async_database;
+mod audit;
+mod backup;
+#[cfg(feature = "importers")]
+mod bank_statement;
+#[cfg(feature = "importers")]
+mod camt;
+mod corporate_actions;
+#[cfg(feature = "importers")]
+mod csv_import;
+mod dividends;
+#[cfg(feature = "encryption")]
+mod encryption;
+mod error;
+#[cfg(feature = "importers")]
+mod import;
+mod interface;
+mod journal;
+mod json;
+mod lots;
+mod memory;
+mod migrations;
+mod models;
+mod money;
+mod money_format;
+#[cfg(feature = "importers")]
+mod mt940;
+#[cfg(feature = "importers")]
+mod ofx;
+mod performance;
+mod plain_text;
+#[cfg(feature = "importers")]
+mod plain_text_import;
+mod pool;
+mod prices;
+#[cfg(feature = "importers")]
+mod qif;
+mod seeding;
+#[cfg(feature = "server")]
+mod server;
+mod store;
+mod valuation;
+
+#[cfg(test)]
+mod tests;
+
+pub use backup::{SnapshotPolicy, DEFAULT_SNAPSHOT_RETENTION};
+pub use dividends::{
+    IncomeYieldReport, DIVIDENDS_ACCOUNT, INTEREST_INCOME_ACCOUNT, WITHHOLDING_TAX_ACCOUNT,
+};
+pub use error::{Error, Result};
+pub use interface::{Database, GeneralBalanceReport};
+pub use journal::RegisterLine;
+pub use json::{BookDocument, BookDocumentEntry, JsonImport, JSON_FORMAT, JSON_FORMAT_VERSION};
+pub use memory::MemoryStore;
+pub use migrations::SCHEMA_VERSION;
+pub use models::{
+    Account, AccountType, AmountColumns, Asset, AssetPrice, AssetType, AuditOperation, AuditRecord,
+    AuditTable, CorporateAction, CorporateActionType, CsvImportProfile, Dividend, EntryStatus,
+    ExchangeRate, IncomeKind, JournalEntry, JournalEntryLine, Lot, NewAccount, NewJournalEntry,
+    NewJournalEntryLine, NormalBalance,
+};
+pub use money::{Amount, Denomination, Money, MoneyError, Rounding};
+pub use money_format::{MoneyFormat, NegativeStyle, SymbolPosition};
+pub use performance::{
+    AssetPerformance, MonthlyPerformance, PerformanceSummary, PortfolioPerformance,
+};
+pub use plain_text::PlainTextFormat;
+pub use pool::{DatabaseOptions, PooledReader, Synchronous};
+pub use prices::DEFAULT_PRICE_LOOKBACK_DAYS;
+pub use seeding::init_sample_data;
+pub use store::LedgerStore;
+pub use valuation::ValuationReport;
+
+#[cfg(feature = "importers")]
+pub use bank_statement::{BalanceCheck, StatementImport};
+#[cfg(feature = "importers")]
+pub use import::{
+    ImportSummary, InvestmentKind, StatementLine, CAPITAL_GAINS_ACCOUNT, SUSPENSE_ACCOUNT,
+};
+#[cfg(feature = "importers")]
+pub use ofx::BROKERAGE_ACCOUNT;
+#[cfg(feature = "importers")]
+pub use plain_text_import::{ImportWarning, PlainTextImport};
+#[cfg(feature = "importers")]
+pub use qif::QifOptions;
+
+#[cfg(feature = "server")]
+pub use server::{openapi, serve};
+
+#[cfg(feature = "async")]
+pub use async_database::{AsyncDatabase, DEFAULT_QUEUE_CAPACITY};
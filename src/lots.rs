@@ -0,0 +1,222 @@
+use chrono::NaiveDate;
+use rusqlite::{named_params, params, Connection, Row};
+
+use crate::{
+    error::{Error, Result},
+    interface::Database,
+    models::Lot,
+    money::{Money, Rounding},
+};
+
+impl Database {
+    /// Opens a new lot of `quantity` units of `asset_code` held in `account_number`,
+    /// acquired for `cost_basis` units of `cost_asset_code`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn open_lot<S: AsRef<str>>(
+        &mut self,
+        account_number: S,
+        asset_code: S,
+        acquired_date: NaiveDate,
+        quantity: Money,
+        cost_basis: Money,
+        cost_asset_code: S,
+        journal_entry_id: Option<i64>,
+    ) -> Result<i64> {
+        let t = self.transaction()?;
+        let id = insert_lot(
+            &t,
+            account_number.as_ref(),
+            asset_code.as_ref(),
+            acquired_date,
+            quantity,
+            cost_basis,
+            cost_asset_code.as_ref(),
+            journal_entry_id,
+        )?;
+        t.commit()?;
+
+        Ok(id)
+    }
+
+    /// Closes `quantity` units of `asset_code` held in `account_number`, oldest
+    /// lots first, and returns the cost basis of the closed units.
+    pub fn close_lots_fifo<S: AsRef<str>>(
+        &mut self,
+        account_number: S,
+        asset_code: S,
+        quantity: Money,
+    ) -> Result<Money> {
+        let t = self.transaction()?;
+
+        let (account_id, asset_id) = t.query_row(
+            "SELECT
+                    (SELECT id FROM accounts WHERE account_number = ?1),
+                    (SELECT id FROM assets WHERE code = ?2)",
+            [account_number.as_ref(), asset_code.as_ref()],
+            |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, Option<i64>>(1)?)),
+        )?;
+        let (Some(account_id), Some(asset_id)) = (account_id, asset_id) else {
+            return Err(Error::NotFound);
+        };
+        let (cost, _) = close_lots_fifo(&t, account_id, asset_id, quantity)?;
+
+        t.commit()?;
+
+        Ok(cost)
+    }
+
+    /// Returns the lots of `asset_code` in `account_number` that still hold a
+    /// quantity, oldest first.
+    pub fn open_lots<S: AsRef<str>>(&self, account_number: S, asset_code: S) -> Result<Vec<Lot>> {
+        let mut stmt = self.conn().prepare(
+            "SELECT l.id, l.account_id, l.asset_id, l.journal_entry_id, l.acquired_date,
+                    l.quantity, l.cost_basis, l.cost_asset_id
+             FROM lots l
+             WHERE l.account_id = (SELECT id FROM accounts WHERE account_number = :account_number)
+               AND l.asset_id = (SELECT id FROM assets WHERE code = :asset_code)
+               AND dec_sign(l.quantity) != 0
+             ORDER BY l.acquired_date, l.id",
+        )?;
+
+        let lots = stmt
+            .query_map(
+                named_params! {
+                    ":account_number": account_number.as_ref(),
+                    ":asset_code": asset_code.as_ref(),
+                },
+                lot_from_row,
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(lots)
+    }
+}
+
+/// Opens a lot inside an already open transaction. See [`Database::open_lot`].
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn insert_lot(
+    t: &Connection,
+    account_number: &str,
+    asset_code: &str,
+    acquired_date: NaiveDate,
+    quantity: Money,
+    cost_basis: Money,
+    cost_asset_code: &str,
+    journal_entry_id: Option<i64>,
+) -> Result<i64> {
+    let id = t.query_row(
+        "INSERT INTO lots (
+            account_id, asset_id, journal_entry_id, acquired_date,
+            quantity, cost_basis, cost_asset_id
+        ) VALUES (
+            (SELECT id FROM accounts WHERE account_number = :account_number),
+            (SELECT id FROM assets WHERE code = :asset_code),
+            :journal_entry_id,
+            :acquired_date,
+            :quantity,
+            :cost_basis,
+            (SELECT id FROM assets WHERE code = :cost_asset_code)
+        ) RETURNING id",
+        named_params! {
+            ":account_number": account_number,
+            ":asset_code": asset_code,
+            ":journal_entry_id": journal_entry_id,
+            ":acquired_date": acquired_date,
+            ":quantity": quantity,
+            ":cost_basis": cost_basis,
+            ":cost_asset_code": cost_asset_code,
+        },
+        |row| row.get(0),
+    )?;
+
+    Ok(id)
+}
+
+/// Closes `quantity` units of `asset_id` held in `account_id` inside an already
+/// open transaction, taking them from the oldest open lots first.
+///
+/// Returns the cost basis of the closed units and the code of the asset it is
+/// expressed in. Partially closed lots keep the rest of their cost basis.
+pub(crate) fn close_lots_fifo(
+    t: &Connection,
+    account_id: i64,
+    asset_id: i64,
+    quantity: Money,
+) -> Result<(Money, String)> {
+    let lots = {
+        let mut stmt = t.prepare(
+            "SELECT l.id, l.quantity, l.cost_basis, c.code, c.decimals
+             FROM lots l
+             JOIN assets c ON c.id = l.cost_asset_id
+             WHERE l.account_id = ?1 AND l.asset_id = ?2 AND dec_sign(l.quantity) > 0
+             ORDER BY l.acquired_date, l.id",
+        )?;
+        let lots = stmt
+            .query_map(params![account_id, asset_id], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Money>(1)?,
+                    row.get::<_, Money>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, i64>(4)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        lots
+    };
+
+    let held: Money = lots.iter().map(|(_, quantity, ..)| *quantity).sum();
+    if held < quantity {
+        return Err(Error::InvalidData(format!(
+            "cannot close {quantity} units, only {held} are held in open lots"
+        )));
+    }
+    let cost_asset = match lots.first() {
+        Some((_, _, _, code, _)) if lots.iter().all(|lot| &lot.3 == code) => code.clone(),
+        Some(_) => {
+            return Err(Error::InvalidData(
+                "open lots have cost bases in different assets".into(),
+            ))
+        }
+        None => return Ok((Money::default(), String::new())),
+    };
+
+    let mut remaining = quantity;
+    let mut cost = Money::default();
+    for (lot_id, lot_quantity, lot_cost, _, decimals) in lots {
+        if remaining.is_zero() {
+            break;
+        }
+
+        let closed = remaining.min(lot_quantity);
+        let closed_cost = if closed == lot_quantity {
+            lot_cost
+        } else {
+            (lot_cost * (closed.amount() / lot_quantity.amount()))
+                .round(decimals.clamp(0, 28) as u32, Rounding::default())
+        };
+
+        t.execute(
+            "UPDATE lots SET quantity = ?1, cost_basis = ?2 WHERE id = ?3",
+            params![lot_quantity - closed, lot_cost - closed_cost, lot_id],
+        )?;
+
+        remaining -= closed;
+        cost += closed_cost;
+    }
+
+    Ok((cost, cost_asset))
+}
+
+pub(crate) fn lot_from_row(row: &Row) -> rusqlite::Result<Lot> {
+    Ok(Lot {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        asset_id: row.get(2)?,
+        journal_entry_id: row.get(3)?,
+        acquired_date: row.get(4)?,
+        quantity: row.get(5)?,
+        cost_basis: row.get(6)?,
+        cost_asset_id: row.get(7)?,
+    })
+}
@@ -1,39 +1,18 @@
-mod error;
-mod interface;
-mod models;
-mod money;
-mod seeding;
+mod cli;
+mod tui;
 
-#[cfg(test)]
-mod tests;
+use std::process::ExitCode;
 
-use chrono::Utc;
-use money::Money;
-use rust_decimal_macros::dec;
+use clap::Parser;
 
-use crate::interface::Database;
+fn main() -> ExitCode {
+    let cli = cli::Cli::parse();
 
-fn main() -> error::Result<()> {
-    // Example usage
-    let mut db = Database::new("./ciao.db")?;
-
-    db.init_schema()?;
-    seeding::init_sample_data(&mut db).unwrap();
-
-    // db.insert_transaction(
-    //     Utc::now(),
-    //     "Salary",
-    //     "SALARY-01",
-    //     models::EntryStatus::Posted,
-    //     "1101",
-    //     "4100",
-    //     "EUR",
-    //     "EUR",
-    //     Money::new(dec!(3000.0)),
-    // )?;
-
-    let balance = db.get_general_balance()?;
-    dbg!(balance);
-
-    Ok(())
+    match cli::run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::from(e.exit_code())
+        }
+    }
 }
@@ -0,0 +1,442 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+use crate::{
+    error::{Error, Result},
+    interface::GeneralBalanceReport,
+    journal::{validate_journal_entry, RegisterLine},
+    models::{
+        Account, AccountType, Asset, AssetType, EntryStatus, ExchangeRate, NewJournalEntry,
+        NormalBalance,
+    },
+    money::Money,
+    store::LedgerStore,
+};
+
+/// A [`LedgerStore`] kept entirely in memory, for fast tests and for
+/// embedding where SQLite is not available.
+///
+/// Rows live in ordered maps keyed by id, with ids handed out in sequence as
+/// SQLite does. Nothing is persisted: dropping the store drops the book.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStore {
+    account_types: BTreeMap<i64, AccountType>,
+    assets: BTreeMap<i64, Asset>,
+    accounts: BTreeMap<i64, Account>,
+    exchange_rates: BTreeMap<i64, ExchangeRate>,
+    journal_entries: BTreeMap<i64, NewJournalEntry>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn asset_id(&self, code: &str) -> Option<i64> {
+        self.assets
+            .values()
+            .find(|asset| asset.code == code)
+            .map(|asset| asset.id)
+    }
+
+    fn account(&self, account_number: &str) -> Result<&Account> {
+        self.accounts
+            .values()
+            .find(|account| account.account_number == account_number)
+            .ok_or(Error::NotFound)
+    }
+
+    fn entry_mut(&mut self, journal_entry_id: i64) -> Result<&mut NewJournalEntry> {
+        self.journal_entries
+            .get_mut(&journal_entry_id)
+            .ok_or(Error::NotFound)
+    }
+
+    /// Debit-positive amounts of the lines booked to `account_number` in
+    /// entries matching `include`, in date order
+    fn account_lines<'a>(
+        &'a self,
+        account_number: &'a str,
+        include: impl Fn(EntryStatus) -> bool + 'a,
+    ) -> impl Iterator<Item = (i64, &'a NewJournalEntry, &'a str, Money)> + 'a {
+        let mut entries: Vec<_> = self
+            .journal_entries
+            .iter()
+            .filter(move |(_, entry)| include(entry.status))
+            .collect();
+        entries.sort_by_key(|(id, entry)| (entry.date, **id));
+
+        entries.into_iter().flat_map(move |(id, entry)| {
+            entry
+                .lines
+                .iter()
+                .filter(move |line| line.account_number == account_number)
+                .map(move |line| {
+                    let amount = match line.entry_type {
+                        NormalBalance::Debit => line.amount,
+                        NormalBalance::Credit => -line.amount,
+                    };
+                    (*id, entry, line.asset_code.as_str(), amount)
+                })
+        })
+    }
+}
+
+/// The next id in a table, as SQLite assigns them
+fn next_id<T>(table: &BTreeMap<i64, T>) -> i64 {
+    table.keys().next_back().map_or(1, |id| id + 1)
+}
+
+/// Money as it reads back from SQLite, which stores it normalized
+fn normalized(money: Money) -> Money {
+    Money::new(money.amount().normalize())
+}
+
+impl LedgerStore for MemoryStore {
+    fn create_account_type(
+        &mut self,
+        name: &str,
+        normal_balance: NormalBalance,
+        description: Option<&str>,
+    ) -> Result<i64> {
+        if self.account_types.values().any(|t| t.name == name) {
+            return Err(Error::InvalidData(format!(
+                "account type {name:?} already exists"
+            )));
+        }
+
+        let id = next_id(&self.account_types);
+        self.account_types.insert(
+            id,
+            AccountType {
+                id,
+                name: name.to_string(),
+                normal_balance,
+                description: description.map(str::to_string),
+            },
+        );
+
+        Ok(id)
+    }
+
+    fn list_account_types(&self) -> Result<Vec<AccountType>> {
+        Ok(self.account_types.values().cloned().collect())
+    }
+
+    fn create_asset(
+        &mut self,
+        code: &str,
+        name: &str,
+        asset_type: AssetType,
+        decimals: i64,
+        description: Option<&str>,
+    ) -> Result<i64> {
+        if self.asset_id(code).is_some() {
+            return Err(Error::InvalidData(format!("asset {code:?} already exists")));
+        }
+        if !(0..=28).contains(&decimals) {
+            return Err(Error::InvalidData(format!(
+                "asset decimals must be between 0 and 28, got {decimals}"
+            )));
+        }
+
+        let id = next_id(&self.assets);
+        self.assets.insert(
+            id,
+            Asset {
+                id,
+                code: code.to_string(),
+                name: name.to_string(),
+                asset_type,
+                decimals,
+                description: description.map(str::to_string),
+            },
+        );
+
+        Ok(id)
+    }
+
+    fn get_asset(&self, code: &str) -> Result<Option<Asset>> {
+        Ok(self
+            .asset_id(code)
+            .and_then(|id| self.assets.get(&id))
+            .cloned())
+    }
+
+    fn list_assets(&self) -> Result<Vec<Asset>> {
+        Ok(self.assets.values().cloned().collect())
+    }
+
+    fn create_account(
+        &mut self,
+        account_number: &str,
+        name: &str,
+        account_type_id: i64,
+        parent_account_id: Option<i64>,
+        is_active: bool,
+        opening_date: NaiveDate,
+        closing_date: Option<NaiveDate>,
+        description: Option<&str>,
+    ) -> Result<i64> {
+        if self.account(account_number).is_ok() {
+            return Err(Error::InvalidData(format!(
+                "account {account_number:?} already exists"
+            )));
+        }
+        if !self.account_types.contains_key(&account_type_id) {
+            return Err(Error::InvalidData(format!(
+                "unknown account type {account_type_id}"
+            )));
+        }
+        if let Some(parent) = parent_account_id.filter(|id| !self.accounts.contains_key(id)) {
+            return Err(Error::InvalidData(format!(
+                "unknown parent account {parent}"
+            )));
+        }
+
+        let id = next_id(&self.accounts);
+        self.accounts.insert(
+            id,
+            Account {
+                id,
+                account_number: account_number.to_string(),
+                name: name.to_string(),
+                account_type_id,
+                parent_account_id,
+                is_active,
+                opening_date,
+                closing_date,
+                description: description.map(str::to_string),
+            },
+        );
+
+        Ok(id)
+    }
+
+    fn list_accounts(&self) -> Result<Vec<Account>> {
+        Ok(self.accounts.values().cloned().collect())
+    }
+
+    fn close_account(&mut self, account_number: &str, closing_date: NaiveDate) -> Result<()> {
+        let id = self.account(account_number)?.id;
+
+        let mut balances: BTreeMap<&str, Money> = BTreeMap::new();
+        for (_, _, asset, amount) in
+            self.account_lines(account_number, |status| status != EntryStatus::Void)
+        {
+            *balances.entry(asset).or_default() += amount;
+        }
+        if let Some((asset, balance)) = balances.iter().find(|(_, balance)| !balance.is_zero()) {
+            return Err(Error::InvalidData(format!(
+                "account {account_number} still has a balance of {} {asset}",
+                normalized(*balance)
+            )));
+        }
+
+        let account = self.accounts.get_mut(&id).expect("account was just found");
+        account.closing_date = Some(closing_date);
+        account.is_active = false;
+
+        Ok(())
+    }
+
+    fn create_exchange_rate(
+        &mut self,
+        from_asset_code: &str,
+        to_asset_code: &str,
+        rate: Decimal,
+        date: DateTime<Utc>,
+    ) -> Result<i64> {
+        let asset_id = |code: &str| {
+            self.asset_id(code)
+                .ok_or_else(|| Error::InvalidData(format!("unknown asset {code:?}")))
+        };
+        let from_asset_id = asset_id(from_asset_code)?;
+        let to_asset_id = asset_id(to_asset_code)?;
+
+        if self.exchange_rates.values().any(|rate| {
+            (rate.from_asset_id, rate.to_asset_id, rate.date) == (from_asset_id, to_asset_id, date)
+        }) {
+            return Err(Error::InvalidData(format!(
+                "a {from_asset_code}/{to_asset_code} rate for {date} already exists"
+            )));
+        }
+
+        let id = next_id(&self.exchange_rates);
+        self.exchange_rates.insert(
+            id,
+            ExchangeRate {
+                id,
+                from_asset_id,
+                to_asset_id,
+                rate: rate.normalize(),
+                date,
+            },
+        );
+
+        Ok(id)
+    }
+
+    fn exchange_rate_at(
+        &self,
+        from_asset_code: &str,
+        to_asset_code: &str,
+        date: DateTime<Utc>,
+    ) -> Result<Option<Decimal>> {
+        let (Some(from), Some(to)) = (self.asset_id(from_asset_code), self.asset_id(to_asset_code))
+        else {
+            return Ok(None);
+        };
+
+        // Latest first; on the same date a direct rate wins over an inverse one
+        let rate = self
+            .exchange_rates
+            .values()
+            .filter(|rate| rate.date <= date)
+            .filter_map(|rate| match (rate.from_asset_id, rate.to_asset_id) {
+                pair if pair == (from, to) => Some((rate.date, false, rate.rate)),
+                pair if pair == (to, from) => Some((rate.date, true, rate.rate)),
+                _ => None,
+            })
+            .min_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        Ok(rate.and_then(|(_, inverted, rate)| match inverted {
+            false => Some(rate),
+            true if rate.is_zero() => None,
+            true => Some(Decimal::ONE / rate),
+        }))
+    }
+
+    fn create_journal_entry(&mut self, entry: &NewJournalEntry) -> Result<i64> {
+        validate_journal_entry(entry)?;
+
+        if self.asset_id(&entry.reference_asset_code).is_none() {
+            return Err(Error::InvalidData(format!(
+                "unknown asset {:?}",
+                entry.reference_asset_code
+            )));
+        }
+        for line in &entry.lines {
+            if self.account(&line.account_number).is_err()
+                || self.asset_id(&line.asset_code).is_none()
+            {
+                return Err(Error::InvalidData(format!(
+                    "unknown account {:?} or asset {:?}",
+                    line.account_number, line.asset_code
+                )));
+            }
+        }
+
+        let mut entry = entry.clone();
+        for line in &mut entry.lines {
+            line.amount = normalized(line.amount);
+            line.reference_amount = line.reference_amount.map(normalized);
+        }
+
+        let id = next_id(&self.journal_entries);
+        self.journal_entries.insert(id, entry);
+
+        Ok(id)
+    }
+
+    fn post_journal_entry(&mut self, journal_entry_id: i64) -> Result<()> {
+        let entry = self.entry_mut(journal_entry_id)?;
+        if entry.status != EntryStatus::Draft {
+            return Err(Error::InvalidData(format!(
+                "journal entry {journal_entry_id} is not a draft"
+            )));
+        }
+        entry.status = EntryStatus::Posted;
+
+        Ok(())
+    }
+
+    fn void_journal_entry(&mut self, journal_entry_id: i64) -> Result<()> {
+        let entry = self.entry_mut(journal_entry_id)?;
+        if entry.status == EntryStatus::Void {
+            return Err(Error::InvalidData(format!(
+                "journal entry {journal_entry_id} is already void"
+            )));
+        }
+        entry.status = EntryStatus::Void;
+
+        Ok(())
+    }
+
+    fn journal_entries(&self, status: EntryStatus) -> Result<Vec<(i64, NewJournalEntry)>> {
+        let mut entries: Vec<_> = self
+            .journal_entries
+            .iter()
+            .filter(|(_, entry)| entry.status == status)
+            .map(|(id, entry)| (*id, entry.clone()))
+            .collect();
+        entries.sort_by_key(|(id, entry)| (entry.date, *id));
+
+        Ok(entries)
+    }
+
+    fn register(&self, account_number: &str) -> Result<Vec<RegisterLine>> {
+        self.account(account_number)?;
+
+        let mut balances: HashMap<&str, Money> = HashMap::new();
+        let lines = self
+            .account_lines(account_number, |status| status != EntryStatus::Void)
+            .map(|(id, entry, asset, amount)| {
+                let balance = balances.entry(asset).or_default();
+                *balance += amount;
+
+                RegisterLine {
+                    journal_entry_id: id,
+                    date: entry.date,
+                    description: entry.description.clone(),
+                    reference_number: entry.reference_number.clone(),
+                    status: entry.status,
+                    asset: asset.to_string(),
+                    amount: normalized(amount),
+                    balance: normalized(*balance),
+                }
+            })
+            .collect();
+
+        Ok(lines)
+    }
+
+    fn get_general_balance(&self) -> Result<Vec<GeneralBalanceReport>> {
+        let mut accounts: Vec<&Account> = self.accounts.values().collect();
+        accounts.sort_by(|a, b| a.account_number.cmp(&b.account_number));
+
+        let mut report = Vec::new();
+        for account in accounts {
+            let Some(account_type) = self.account_types.get(&account.account_type_id) else {
+                continue;
+            };
+
+            // Grouped by asset id, like the SQL report
+            let mut balances: BTreeMap<i64, (&str, Money)> = BTreeMap::new();
+            for (_, _, asset, amount) in self.account_lines(&account.account_number, |status| {
+                status == EntryStatus::Posted
+            }) {
+                let id = self
+                    .asset_id(asset)
+                    .expect("line assets are checked on entry");
+                balances.entry(id).or_insert((asset, Money::default())).1 += amount;
+            }
+
+            for (asset, balance) in balances.into_values() {
+                report.push(GeneralBalanceReport {
+                    account_number: account.account_number.clone(),
+                    account_name: account.name.clone(),
+                    asset: asset.to_string(),
+                    balance: normalized(match account_type.normal_balance {
+                        NormalBalance::Debit => balance,
+                        NormalBalance::Credit => -balance,
+                    }),
+                });
+            }
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::BTreeSet;
+
+use rusqlite::Connection;
+
+use crate::{
+    error::{Error, Result},
+    interface::Database,
+};
+
+/// Version of the schema created by [`Database::init_schema`], kept in
+/// SQLite's `user_version` pragma
+pub const SCHEMA_VERSION: i64 = 4;
+
+/// Scripts bringing a database from the previous version to the given one.
+/// Version 1 is reached by [`migrate_to_text_amounts`], which rebuilds every
+/// table straight into the current schema.
+const MIGRATIONS: &[(i64, &str)] = &[
+    (2, include_str!("sql/migrations/v2_imports.sql")),
+    (3, include_str!("sql/migrations/v3_external_accounts.sql")),
+    (4, include_str!("sql/migrations/v4_audit_log.sql")),
+];
+
+/// Columns that version 0 stored as integers with eight implied decimal places
+const LEGACY_MONEY_COLUMNS: &[(&str, &str)] = &[
+    ("exchange_rates", "rate"),
+    ("journal_entry_lines", "amount"),
+    ("journal_entry_lines", "reference_amount"),
+    ("asset_prices", "open"),
+    ("asset_prices", "high"),
+    ("asset_prices", "low"),
+    ("asset_prices", "close"),
+    ("lots", "quantity"),
+    ("lots", "cost_basis"),
+    ("lot_adjustments", "quantity_before"),
+    ("lot_adjustments", "quantity_after"),
+    ("dividends", "gross"),
+    ("dividends", "withholding"),
+];
+
+impl Database {
+    /// Returns the schema version of the database, 0 for databases created
+    /// before versioning was introduced.
+    pub fn schema_version(&self) -> Result<i64> {
+        Ok(self
+            .conn()
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?)
+    }
+
+    /// Brings the schema up to [`SCHEMA_VERSION`], creating it in an empty
+    /// database. The whole migration runs in a single transaction, after a
+    /// snapshot of the book if it has a [`SnapshotPolicy`](crate::SnapshotPolicy).
+    pub fn migrate(&mut self) -> Result<()> {
+        let version = self.schema_version()?;
+        if version > SCHEMA_VERSION {
+            return Err(Error::InvalidData(format!(
+                "schema version {version} is newer than the supported version {SCHEMA_VERSION}"
+            )));
+        }
+        if version == SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        let empty = table_names(self.conn())?.is_empty();
+        if !empty {
+            self.snapshot_before("migrate")?;
+        }
+
+        let t = self.transaction()?;
+        if empty {
+            t.execute_batch(include_str!("sql/schema.sql"))?;
+        } else if version == 0 {
+            migrate_to_text_amounts(&t)?;
+        } else {
+            for (_, script) in MIGRATIONS.iter().filter(|(to, _)| *to > version) {
+                t.execute_batch(script)?;
+            }
+        }
+        t.commit()?;
+
+        Ok(())
+    }
+}
+
+/// Version 0 -> 1: amounts move from scaled integers to lossless TEXT decimals.
+/// Later versions come along, as the tables are recreated from `schema.sql`.
+///
+/// SQLite cannot change the type of a column, so every table of the current
+/// schema is renamed, recreated from `schema.sql` and refilled. Columns that
+/// did not exist yet take their default, which also covers databases created
+/// before the later tables and columns were added.
+fn migrate_to_text_amounts(t: &Connection) -> Result<()> {
+    let schema_tables = schema_table_names()?;
+    let legacy_tables: Vec<String> = table_names(t)?
+        .into_iter()
+        .filter(|table| schema_tables.contains(table))
+        .collect();
+
+    // Keep foreign keys pointing at the original names while tables are renamed,
+    // and only check them once every table has been refilled
+    t.execute_batch("PRAGMA legacy_alter_table = ON; PRAGMA defer_foreign_keys = ON;")?;
+
+    for table in &legacy_tables {
+        let indexes = {
+            let mut stmt = t.prepare(
+                "SELECT name FROM sqlite_master
+                 WHERE type = 'index' AND tbl_name = ?1 AND sql IS NOT NULL",
+            )?;
+            let indexes = stmt
+                .query_map([table], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            indexes
+        };
+        for index in indexes {
+            t.execute_batch(&format!("DROP INDEX \"{index}\""))?;
+        }
+        t.execute_batch(&format!("ALTER TABLE \"{table}\" RENAME TO \"{table}_v0\""))?;
+    }
+
+    t.execute_batch(include_str!("sql/schema.sql"))?;
+
+    // Refilling the tables is no change to the book, so the audit triggers
+    // are only put back once it is done
+    let audit_triggers = {
+        let mut stmt = t.prepare(
+            "SELECT name, sql FROM sqlite_master
+             WHERE type = 'trigger' AND name LIKE 'audit_%' AND tbl_name != 'audit_log'",
+        )?;
+        let triggers = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        triggers
+    };
+    for (trigger, _) in &audit_triggers {
+        t.execute_batch(&format!("DROP TRIGGER \"{trigger}\""))?;
+    }
+
+    for table in &legacy_tables {
+        let legacy = format!("{table}_v0");
+        let legacy_columns = column_names(t, &legacy)?;
+        let columns: Vec<String> = column_names(t, table)?
+            .into_iter()
+            .filter(|column| legacy_columns.contains(column))
+            .collect();
+        let values: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                if LEGACY_MONEY_COLUMNS.contains(&(table.as_str(), column.as_str())) {
+                    format!("dec_from_legacy(\"{column}\")")
+                } else {
+                    format!("\"{column}\"")
+                }
+            })
+            .collect();
+
+        t.execute_batch(&format!(
+            "INSERT INTO \"{table}\" ({}) SELECT {} FROM \"{legacy}\";
+             DROP TABLE \"{legacy}\";",
+            columns
+                .iter()
+                .map(|column| format!("\"{column}\""))
+                .collect::<Vec<_>>()
+                .join(", "),
+            values.join(", "),
+        ))?;
+
+        if table == "assets" && !legacy_columns.contains("decimals") {
+            t.execute(
+                "UPDATE assets SET decimals = CASE type WHEN 'FIAT' THEN 2 ELSE 8 END",
+                [],
+            )?;
+        }
+    }
+
+    // Entries recorded before reference assets existed balance in the asset
+    // of their first debit line
+    t.execute(
+        "UPDATE journal_entries
+         SET reference_asset_id = (
+             SELECT asset_id FROM journal_entry_lines
+             WHERE journal_entry_id = journal_entries.id AND entry_type = 'DEBIT'
+             ORDER BY id
+             LIMIT 1
+         )
+         WHERE reference_asset_id IS NULL",
+        [],
+    )?;
+
+    for (_, sql) in &audit_triggers {
+        t.execute_batch(sql)?;
+    }
+    t.execute_batch("PRAGMA legacy_alter_table = OFF")?;
+
+    Ok(())
+}
+
+fn table_names(conn: &Connection) -> Result<BTreeSet<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+    )?;
+    let tables = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(tables)
+}
+
+/// Tables created by the current `schema.sql`
+fn schema_table_names() -> Result<BTreeSet<String>> {
+    let conn = Connection::open_in_memory()?;
+    conn.execute_batch(include_str!("sql/schema.sql"))?;
+
+    table_names(&conn)
+}
+
+fn column_names(conn: &Connection, table: &str) -> Result<BTreeSet<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+    let columns = stmt
+        .query_map([table], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(columns)
+}
+
+/// Takes a book back to version 3 by dropping the audit log, its actor and
+/// its triggers
+#[cfg(test)]
+pub(crate) fn drop_audit_log(conn: &Connection) -> Result<()> {
+    let triggers = {
+        let mut stmt = conn.prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'trigger' AND name LIKE 'audit_%'",
+        )?;
+        let triggers = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        triggers
+    };
+    for trigger in triggers {
+        conn.execute_batch(&format!("DROP TRIGGER \"{trigger}\""))?;
+    }
+    conn.execute_batch("DROP TABLE audit_log; DROP TABLE audit_context; PRAGMA user_version = 3;")?;
+
+    Ok(())
+}
@@ -1,4 +1,7 @@
+use std::str::FromStr;
+
 use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{types::Type, Row};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
@@ -16,6 +19,25 @@ pub struct AccountType {
     pub description: Option<String>,
 }
 
+/// The root an account type or root account name stands for (`Assets`,
+/// `Liabilities`, `Equity`, `Income` or `Expenses`), unless it is none of them
+pub(crate) fn classify_root(name: &str) -> Option<&'static str> {
+    let name = name.to_lowercase();
+    if name.starts_with("asset") {
+        Some("Assets")
+    } else if name.starts_with("liabilit") {
+        Some("Liabilities")
+    } else if name.starts_with("equity") {
+        Some("Equity")
+    } else if name.starts_with("income") || name.starts_with("revenue") {
+        Some("Income")
+    } else if name.starts_with("expense") {
+        Some("Expenses")
+    } else {
+        None
+    }
+}
+
 /// Represents a financial asset or currency in the system
 ///
 /// Assets can be various types like fiat currencies, stocks, cryptocurrencies,
@@ -43,6 +65,23 @@ pub struct ExchangeRate {
     pub date: DateTime<Utc>,
 }
 
+/// Represents the market price of an asset on a given day
+///
+/// Prices are quoted in another asset (e.g. AAPL in USD) and are kept separate
+/// from exchange rates, which only cover fiat currency pairs. Open, high and low
+/// are optional so that close-only price feeds can be stored as well.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetPrice {
+    pub id: i64,
+    pub asset_id: i64,
+    pub quote_asset_id: i64,
+    pub date: NaiveDate,
+    pub open: Option<Money>,
+    pub high: Option<Money>,
+    pub low: Option<Money>,
+    pub close: Money,
+}
+
 /// Represents an individual account in the chart of accounts
 ///
 /// Accounts are hierarchical (can have parent accounts) and track financial activity
@@ -88,9 +127,150 @@ pub struct JournalEntryLine {
     pub asset_id: i64,
     pub entry_type: NormalBalance,
     pub amount: Money,
+    pub reference_amount: Option<Money>,
+    pub description: Option<String>,
+}
+
+/// Describes a journal entry to be recorded, together with its lines
+///
+/// Accounts and assets are referred to by account number and asset code. The
+/// entry must balance in its reference asset: lines in another asset carry their
+/// value in the reference asset in `reference_amount`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewJournalEntry {
+    pub date: DateTime<Utc>,
+    pub description: String,
+    pub reference_number: Option<String>,
+    pub reference_asset_code: String,
+    pub status: EntryStatus,
+    pub lines: Vec<NewJournalEntryLine>,
+}
+
+/// Describes an account to open with [`Database::open_account`], with its
+/// type referred to by name and its parent by account number
+///
+/// [`Database::open_account`]: crate::Database::open_account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewAccount {
+    pub account_number: String,
+    pub name: String,
+    /// Name of the account type, matched regardless of case
+    pub account_type: String,
+    pub parent_account_number: Option<String>,
+    pub opening_date: NaiveDate,
+    pub description: Option<String>,
+}
+
+/// Describes a single line of a [`NewJournalEntry`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewJournalEntryLine {
+    pub account_number: String,
+    pub asset_code: String,
+    pub entry_type: NormalBalance,
+    pub amount: Money,
+    pub reference_amount: Option<Money>,
+    pub description: Option<String>,
+}
+
+/// Represents dividend or interest income received from a holding
+///
+/// Links the journal entry that booked the income to the asset that paid it,
+/// keeping the gross amount and the tax withheld at source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dividend {
+    pub id: i64,
+    pub journal_entry_id: i64,
+    pub asset_id: i64,
+    pub account_id: i64,
+    pub currency_asset_id: i64,
+    pub kind: IncomeKind,
+    pub date: DateTime<Utc>,
+    pub gross: Money,
+    pub withholding: Money,
+}
+
+/// Represents an open position in an asset acquired at a known cost
+///
+/// Lots track the remaining quantity of an acquisition together with the cost
+/// of that quantity, so that gains can be computed and corporate actions can
+/// restate holdings without touching the journal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lot {
+    pub id: i64,
+    pub account_id: i64,
+    pub asset_id: i64,
+    pub journal_entry_id: Option<i64>,
+    pub acquired_date: NaiveDate,
+    pub quantity: Money,
+    pub cost_basis: Money,
+    pub cost_asset_id: i64,
+}
+
+/// Represents a corporate action affecting an asset
+///
+/// Splits and mergers convert `ratio_old` units of the asset into `ratio_new`
+/// units (of `target_asset_id` for mergers). Renames only change the asset code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorporateAction {
+    pub id: i64,
+    pub asset_id: i64,
+    pub action_type: CorporateActionType,
+    pub date: NaiveDate,
+    pub ratio_new: i64,
+    pub ratio_old: i64,
+    pub old_code: Option<String>,
+    pub new_code: Option<String>,
+    pub target_asset_id: Option<i64>,
     pub description: Option<String>,
 }
 
+/// Represents a saved column mapping for a bank's CSV export
+///
+/// Columns are referred to by header name, or by zero-based position when the
+/// file has no header row. Dates are parsed with a chrono format string such as
+/// `%d.%m.%Y`, and amounts with the given decimal and thousands separators.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CsvImportProfile {
+    pub name: String,
+    pub delimiter: char,
+    pub has_headers: bool,
+    /// Lines to skip before the header row, for exports that start with a preamble
+    pub skip_lines: usize,
+    pub date_column: String,
+    pub date_format: String,
+    pub amount_columns: AmountColumns,
+    pub description_column: String,
+    pub reference_column: Option<String>,
+    pub decimal_separator: char,
+    pub thousands_separator: Option<char>,
+}
+
+/// Where a CSV export keeps the transaction amount
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AmountColumns {
+    /// A single signed column, positive for money coming in
+    Signed(String),
+    /// Money going out and money coming in, in separate unsigned columns
+    DebitCredit { debit: String, credit: String },
+}
+
+/// A change to an audited row, recorded in the `audit_log` table
+///
+/// `before` and `after` hold the row as its model serializes it, e.g. an
+/// [`Account`] for the accounts table; `before` is `None` for inserts and
+/// `after` for deletes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub actor: String,
+    pub operation: AuditOperation,
+    pub table: AuditTable,
+    pub record_id: i64,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
 /// Represents the normal balance type of an account
 ///
 /// In accounting, accounts naturally maintain either a debit or credit balance.
@@ -131,3 +311,170 @@ pub enum EntryStatus {
     Posted,
     Void,
 }
+
+/// Kind of corporate action recorded for an asset
+///
+/// - Split: Quantities are multiplied by the split ratio (also used for reverse splits)
+/// - Rename: The asset changes its ticker or code
+/// - Merger: Holdings are converted into another asset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum CorporateActionType {
+    Split,
+    Rename,
+    Merger,
+}
+
+/// Kind of investment income paid by a holding
+///
+/// - Dividend: Booked to 4201 Dividends
+/// - Interest: Booked to 4203 Interest Income
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum IncomeKind {
+    Dividend,
+    Interest,
+}
+
+/// Kind of change recorded in the audit log
+///
+/// - StatusChange: A journal entry was posted or voided
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuditOperation {
+    Insert,
+    Update,
+    StatusChange,
+    Delete,
+}
+
+/// Tables whose changes are recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditTable {
+    Accounts,
+    Assets,
+    ExchangeRates,
+    JournalEntries,
+    JournalEntryLines,
+}
+
+impl AuditTable {
+    /// Name of the table in the schema
+    pub fn name(self) -> &'static str {
+        match self {
+            AuditTable::Accounts => "accounts",
+            AuditTable::Assets => "assets",
+            AuditTable::ExchangeRates => "exchange_rates",
+            AuditTable::JournalEntries => "journal_entries",
+            AuditTable::JournalEntryLines => "journal_entry_lines",
+        }
+    }
+}
+
+impl std::str::FromStr for AssetType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "FIAT" => Ok(AssetType::Fiat),
+            "STOCK" => Ok(AssetType::Stock),
+            "BOND" => Ok(AssetType::Bond),
+            "ETF" => Ok(AssetType::Etf),
+            "ETC" => Ok(AssetType::Etc),
+            "ETN" => Ok(AssetType::Etn),
+            "CRYPTO" => Ok(AssetType::Crypto),
+            "COMMODITY" => Ok(AssetType::Commodity),
+            other => Err(format!("unknown asset type: {other}")),
+        }
+    }
+}
+
+impl std::str::FromStr for CorporateActionType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "SPLIT" => Ok(CorporateActionType::Split),
+            "RENAME" => Ok(CorporateActionType::Rename),
+            "MERGER" => Ok(CorporateActionType::Merger),
+            other => Err(format!("unknown corporate action type: {other}")),
+        }
+    }
+}
+
+impl std::str::FromStr for IncomeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "DIVIDEND" => Ok(IncomeKind::Dividend),
+            "INTEREST" => Ok(IncomeKind::Interest),
+            other => Err(format!("unknown income kind: {other}")),
+        }
+    }
+}
+
+impl std::str::FromStr for NormalBalance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "DEBIT" => Ok(NormalBalance::Debit),
+            "CREDIT" => Ok(NormalBalance::Credit),
+            other => Err(format!("unknown normal balance: {other}")),
+        }
+    }
+}
+
+impl std::str::FromStr for EntryStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "DRAFT" => Ok(EntryStatus::Draft),
+            "POSTED" => Ok(EntryStatus::Posted),
+            "VOID" => Ok(EntryStatus::Void),
+            other => Err(format!("unknown entry status: {other}")),
+        }
+    }
+}
+
+impl std::str::FromStr for AuditOperation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "INSERT" => Ok(AuditOperation::Insert),
+            "UPDATE" => Ok(AuditOperation::Update),
+            "STATUS_CHANGE" => Ok(AuditOperation::StatusChange),
+            "DELETE" => Ok(AuditOperation::Delete),
+            other => Err(format!("unknown audit operation: {other}")),
+        }
+    }
+}
+
+impl std::str::FromStr for AuditTable {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "accounts" => Ok(AuditTable::Accounts),
+            "assets" => Ok(AuditTable::Assets),
+            "exchange_rates" => Ok(AuditTable::ExchangeRates),
+            "journal_entries" => Ok(AuditTable::JournalEntries),
+            "journal_entry_lines" => Ok(AuditTable::JournalEntryLines),
+            other => Err(format!("unknown audited table: {other}")),
+        }
+    }
+}
+
+/// Reads the text column `index` of `row` with the `FromStr` impl of `T`
+pub(crate) fn parse_column<T: FromStr<Err = String>>(
+    row: &Row,
+    index: usize,
+) -> rusqlite::Result<T> {
+    row.get::<_, String>(index)?
+        .parse()
+        .map_err(|e: String| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, e.into()))
+}
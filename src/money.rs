@@ -1,34 +1,178 @@
-use rusqlite::types::{FromSql, ToSql};
-use rust_decimal::{prelude::ToPrimitive, Decimal};
+use rusqlite::{
+    functions::{Aggregate, Context, FunctionFlags},
+    types::{FromSql, FromSqlError, ToSql, ValueRef},
+    Connection,
+};
+use rust_decimal::{prelude::ToPrimitive, Decimal, RoundingStrategy};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-/// Rust => Decimal type (float backed by an i128)
-/// SQLite => 64 bit integer with eight decimal places (fixed precision)
-#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+use crate::models::Asset;
+
+/// Errors raised by [`Money`] and [`Amount`] operations
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum MoneyError {
+    #[error("{0} is outside of the range that can be represented")]
+    Overflow(Decimal),
+
+    #[error("Cannot combine amounts in {0} and {1}")]
+    AssetMismatch(String, String),
+
+    #[error("Division by zero")]
+    DivisionByZero,
+
+    #[error("Allocation weights must be non-negative and add up to more than zero")]
+    InvalidWeights,
+
+    #[error("Invalid amount {0:?}")]
+    Parse(String),
+
+    #[error("{0} has more decimals than {1} keeps")]
+    Precision(Decimal, String),
+}
+
+/// How to round amounts that have more decimals than their asset allows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rounding {
+    /// Round half to even (banker's rounding): 0.125 -> 0.12, 0.135 -> 0.14
+    #[default]
+    Bankers,
+    /// Round half away from zero: 0.125 -> 0.13, -0.125 -> -0.13
+    HalfUp,
+    /// Round half towards zero: 0.125 -> 0.12
+    HalfDown,
+    /// Truncate towards zero
+    Down,
+    /// Round away from zero
+    Up,
+}
+
+impl From<Rounding> for RoundingStrategy {
+    fn from(rounding: Rounding) -> Self {
+        match rounding {
+            Rounding::Bankers => RoundingStrategy::MidpointNearestEven,
+            Rounding::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            Rounding::HalfDown => RoundingStrategy::MidpointTowardZero,
+            Rounding::Down => RoundingStrategy::ToZero,
+            Rounding::Up => RoundingStrategy::AwayFromZero,
+        }
+    }
+}
+
+/// Rust => Decimal type (96 bit mantissa with up to 28 decimal places)
+/// SQLite => TEXT holding the normalized decimal, so that 18-decimal crypto
+/// amounts and large fiat balances are stored without loss.
+///
+/// Stored amounts must be aggregated with the `dec_*` SQL functions installed
+/// on every [`Database`](crate::Database) connection: SQLite's own `SUM` and
+/// arithmetic operators would coerce them to floating point.
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default, Serialize, Deserialize,
+)]
 pub struct Money(Decimal);
 
 impl ToSql for Money {
     fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
-        // Convert Decimal to i64 with 8 decimal places
-        // Example: 0.12345678 -> 12345678
-        let scaled = self.0 * Decimal::new(100000000, 0);
-
-        match scaled.to_i64() {
-            Some(value) => Ok(rusqlite::types::ToSqlOutput::from(value)),
-            None => Err(rusqlite::Error::ToSqlConversionFailure(Box::new(
-                std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "Cannot convert decimal to i64",
-                ),
-            ))),
-        }
+        // Normalized so that equal amounts are stored as equal strings
+        // Example: 1.50 -> "1.5", -0 -> "0"
+        Ok(rusqlite::types::ToSqlOutput::from(
+            self.0.normalize().to_string(),
+        ))
     }
 }
 
 impl FromSql for Money {
     fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
-        let val = i64::column_result(value)?;
-        Ok(Money::from_sqlite_repr(val))
+        match value {
+            ValueRef::Text(text) => std::str::from_utf8(text)
+                .map_err(|e| FromSqlError::Other(Box::new(e)))?
+                .parse()
+                .map(Money)
+                .map_err(|e| FromSqlError::Other(Box::new(e))),
+            // Whole amounts computed in SQL, e.g. `dec_sign()` or a literal
+            ValueRef::Integer(value) => Ok(Money(Decimal::from(value))),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+/// Installs the SQL functions used to work with stored [`Money`] values:
+///
+/// - `dec_sum(x)`: aggregate sum, `NULL` over no rows like `SUM`
+/// - `dec_add(x, y)`, `dec_sub(x, y)` and `dec_neg(x)`
+/// - `dec_sign(x)`: -1, 0 or 1
+/// - `dec_from_legacy(x)`: converts the old integer encoding with eight
+///   implied decimal places, used by the schema migration
+///
+/// Every function returns `NULL` when one of its arguments is `NULL`.
+pub fn register_sql_functions(conn: &Connection) -> rusqlite::Result<()> {
+    let flags = || FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+
+    conn.create_aggregate_function("dec_sum", 1, flags(), MoneySum)?;
+    conn.create_scalar_function("dec_add", 2, flags(), |ctx| {
+        binary_op(ctx, Decimal::checked_add)
+    })?;
+    conn.create_scalar_function("dec_sub", 2, flags(), |ctx| {
+        binary_op(ctx, Decimal::checked_sub)
+    })?;
+    conn.create_scalar_function("dec_neg", 1, flags(), |ctx| {
+        Ok(ctx.get::<Option<Money>>(0)?.map(|value| Money(-value.0)))
+    })?;
+    conn.create_scalar_function("dec_sign", 1, flags(), |ctx| {
+        Ok(ctx.get::<Option<Money>>(0)?.map(|value| {
+            if value.0.is_zero() {
+                0
+            } else if value.0.is_sign_negative() {
+                -1
+            } else {
+                1
+            }
+        }))
+    })?;
+    conn.create_scalar_function("dec_from_legacy", 1, flags(), |ctx| {
+        Ok(ctx.get::<Option<i64>>(0)?.map(Money::from_legacy_repr))
+    })?;
+
+    Ok(())
+}
+
+fn binary_op(
+    ctx: &Context<'_>,
+    op: fn(Decimal, Decimal) -> Option<Decimal>,
+) -> rusqlite::Result<Option<Money>> {
+    let (Some(left), Some(right)) = (ctx.get::<Option<Money>>(0)?, ctx.get::<Option<Money>>(1)?)
+    else {
+        return Ok(None);
+    };
+
+    op(left.0, right.0)
+        .map(|value| Some(Money(value)))
+        .ok_or_else(|| rusqlite::Error::UserFunctionError(Box::new(MoneyError::Overflow(left.0))))
+}
+
+struct MoneySum;
+
+impl Aggregate<Decimal, Option<Money>> for MoneySum {
+    fn init(&self, _: &mut Context<'_>) -> rusqlite::Result<Decimal> {
+        Ok(Decimal::ZERO)
+    }
+
+    fn step(&self, ctx: &mut Context<'_>, sum: &mut Decimal) -> rusqlite::Result<()> {
+        if let Some(value) = ctx.get::<Option<Money>>(0)? {
+            *sum = sum.checked_add(value.0).ok_or_else(|| {
+                rusqlite::Error::UserFunctionError(Box::new(MoneyError::Overflow(*sum)))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn finalize(
+        &self,
+        _: &mut Context<'_>,
+        sum: Option<Decimal>,
+    ) -> rusqlite::Result<Option<Money>> {
+        Ok(sum.map(Money))
     }
 }
 
@@ -38,10 +182,6 @@ impl Money {
         Money(amount)
     }
 
-    pub fn from_str(s: &str) -> Result<Self, rust_decimal::Error> {
-        Ok(Money(s.parse()?))
-    }
-
     pub fn amount(&self) -> Decimal {
         self.0
     }
@@ -50,15 +190,138 @@ impl Money {
         self.0.to_f64().unwrap()
     }
 
-    /// Convert from an i64 stored into SQLite to the [`Money`] type.
-    /// Please note that the i64 has eight decimal places.
-    fn from_sqlite_repr(value: i64) -> Self {
+    /// Rounds to `decimals` decimal places using `rounding`.
+    pub fn round(&self, decimals: u32, rounding: Rounding) -> Self {
+        Money(self.0.round_dp_with_strategy(decimals, rounding.into()))
+    }
+
+    pub fn abs(&self) -> Self {
+        Money(self.0.abs())
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    /// Returns -1, 0 or 1 depending on the sign of the amount.
+    pub fn signum(&self) -> i32 {
+        if self.0.is_zero() {
+            0
+        } else if self.0.is_sign_negative() {
+            -1
+        } else {
+            1
+        }
+    }
+
+    pub fn checked_add(&self, other: Money) -> Result<Self, MoneyError> {
+        self.0
+            .checked_add(other.0)
+            .map(Money)
+            .ok_or(MoneyError::Overflow(self.0))
+    }
+
+    pub fn checked_sub(&self, other: Money) -> Result<Self, MoneyError> {
+        self.0
+            .checked_sub(other.0)
+            .map(Money)
+            .ok_or(MoneyError::Overflow(self.0))
+    }
+
+    pub fn checked_mul(&self, factor: Decimal) -> Result<Self, MoneyError> {
+        self.0
+            .checked_mul(factor)
+            .map(Money)
+            .ok_or(MoneyError::Overflow(self.0))
+    }
+
+    pub fn checked_div(&self, divisor: Decimal) -> Result<Self, MoneyError> {
+        if divisor.is_zero() {
+            return Err(MoneyError::DivisionByZero);
+        }
+
+        self.0
+            .checked_div(divisor)
+            .map(Money)
+            .ok_or(MoneyError::Overflow(self.0))
+    }
+
+    /// Splits the amount in proportion to `weights` into parts with as many
+    /// decimals as the amount itself, which add back up to exactly the amount.
+    ///
+    /// Units left over by rounding each part down go to the parts that lost
+    /// the most, earlier parts first on ties:
+    /// 100.00 split three ways is 33.34, 33.33 and 33.33.
+    pub fn allocate(&self, weights: &[Decimal]) -> Result<Vec<Money>, MoneyError> {
+        let total = weights
+            .iter()
+            .try_fold(Decimal::ZERO, |total, weight| total.checked_add(*weight))
+            .ok_or(MoneyError::InvalidWeights)?;
+        if total <= Decimal::ZERO || weights.iter().any(|weight| weight.is_sign_negative()) {
+            return Err(MoneyError::InvalidWeights);
+        }
+
+        // Work in whole units of the last decimal place, e.g. cents for 100.00
+        let scale = self.0.scale();
+        let units = Decimal::from_i128_with_scale(self.0.mantissa().abs(), 0);
+
+        let mut parts = Vec::with_capacity(weights.len());
+        for weight in weights {
+            let exact = weight
+                .checked_div(total)
+                .and_then(|ratio| units.checked_mul(ratio))
+                .ok_or(MoneyError::Overflow(self.0))?;
+            parts.push((exact.floor(), exact - exact.floor()));
+        }
+
+        let mut order: Vec<usize> = (0..parts.len()).collect();
+        order.sort_by(|a, b| parts[*b].1.cmp(&parts[*a].1));
+
+        let allocated: Decimal = parts.iter().map(|(part, _)| *part).sum();
+        let mut left_over = units - allocated;
+        for index in order.iter().cycle() {
+            if left_over <= Decimal::ZERO {
+                break;
+            }
+            parts[*index].0 += Decimal::ONE;
+            left_over -= Decimal::ONE;
+        }
+
+        Ok(parts
+            .into_iter()
+            .map(|(part, _)| {
+                let mut part = Decimal::from_i128_with_scale(part.normalize().mantissa(), scale);
+                part.set_sign_negative(self.0.is_sign_negative());
+                Money(part)
+            })
+            .collect())
+    }
+
+    /// Convert from the i64 that older databases stored into SQLite to the
+    /// [`Money`] type. Please note that the i64 has eight decimal places.
+    fn from_legacy_repr(value: i64) -> Self {
         // Convert from i64 with 8 decimal places to Decimal
         // Example: 12345678 -> 0.12345678
         Money(Decimal::new(value, 8))
     }
 }
 
+impl std::str::FromStr for Money {
+    type Err = rust_decimal::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Money(s.parse()?))
+    }
+}
+
+/// Plain decimal notation (`-1234.56`), honouring the formatter's precision.
+/// Use [`MoneyFormat`](crate::money_format::MoneyFormat) for locale-aware output.
+impl std::fmt::Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
 impl std::ops::Add for Money {
     type Output = Self;
 
@@ -75,6 +338,187 @@ impl std::ops::Sub for Money {
     }
 }
 
+impl std::ops::Neg for Money {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Money(-self.0)
+    }
+}
+
+impl std::ops::Mul<Decimal> for Money {
+    type Output = Self;
+
+    fn mul(self, factor: Decimal) -> Self {
+        Money(self.0 * factor)
+    }
+}
+
+impl std::ops::Div<Decimal> for Money {
+    type Output = Self;
+
+    fn div(self, divisor: Decimal) -> Self {
+        Money(self.0 / divisor)
+    }
+}
+
+impl std::ops::AddAssign for Money {
+    fn add_assign(&mut self, other: Self) {
+        self.0 += other.0;
+    }
+}
+
+impl std::ops::SubAssign for Money {
+    fn sub_assign(&mut self, other: Self) {
+        self.0 -= other.0;
+    }
+}
+
+impl std::iter::Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Self {
+        Money(iter.map(|money| money.0).sum())
+    }
+}
+
+impl<'a> std::iter::Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Money>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+/// The asset an [`Amount`] is denominated in, with the number of decimals its
+/// amounts are kept at (2 for EUR, 8 for BTC, 18 for ETH, ...)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Denomination {
+    pub code: String,
+    pub decimals: u32,
+}
+
+impl Denomination {
+    pub fn new<S: Into<String>>(code: S, decimals: u32) -> Self {
+        Denomination {
+            code: code.into(),
+            decimals,
+        }
+    }
+}
+
+impl From<&Asset> for Denomination {
+    fn from(asset: &Asset) -> Self {
+        Denomination::new(asset.code.clone(), asset.decimals.clamp(0, 28) as u32)
+    }
+}
+
+/// A [`Money`] value denominated in an asset and rounded to that asset's precision
+///
+/// Amounts in different assets cannot be combined: [`Amount::checked_add`] and
+/// [`Amount::checked_sub`] return [`MoneyError::AssetMismatch`] instead.
+///
+/// Deserializing fails with [`MoneyError::Precision`] for a value with more
+/// decimals than its asset keeps, rather than rounding it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "AmountFields")]
+pub struct Amount {
+    money: Money,
+    asset: Denomination,
+}
+
+/// The serialized fields of an [`Amount`], checked before they become one
+#[derive(Deserialize)]
+struct AmountFields {
+    money: Money,
+    asset: Denomination,
+}
+
+impl TryFrom<AmountFields> for Amount {
+    type Error = MoneyError;
+
+    fn try_from(fields: AmountFields) -> Result<Self, Self::Error> {
+        let amount = Amount::new(fields.money, fields.asset, Rounding::Down);
+        if amount.money != fields.money {
+            return Err(MoneyError::Precision(
+                fields.money.amount(),
+                amount.asset.code,
+            ));
+        }
+
+        Ok(amount)
+    }
+}
+
+impl Amount {
+    /// Creates an amount, rounding `money` to the asset's decimals with
+    /// `rounding`. Assets keep at most 28 decimals, the most a [`Money`] holds.
+    pub fn new(money: Money, mut asset: Denomination, rounding: Rounding) -> Self {
+        asset.decimals = asset.decimals.min(28);
+        Amount {
+            money: money.round(asset.decimals, rounding),
+            asset,
+        }
+    }
+
+    pub fn money(&self) -> Money {
+        self.money
+    }
+
+    pub fn asset(&self) -> &Denomination {
+        &self.asset
+    }
+
+    pub fn checked_add(&self, other: &Amount) -> Result<Amount, MoneyError> {
+        self.ensure_same_asset(other)?;
+        Ok(Amount {
+            money: self.money.checked_add(other.money)?,
+            asset: self.asset.clone(),
+        })
+    }
+
+    pub fn checked_sub(&self, other: &Amount) -> Result<Amount, MoneyError> {
+        self.ensure_same_asset(other)?;
+        Ok(Amount {
+            money: self.money.checked_sub(other.money)?,
+            asset: self.asset.clone(),
+        })
+    }
+
+    /// Splits the amount in proportion to `weights` into parts kept at the
+    /// asset's decimals. See [`Money::allocate`].
+    pub fn allocate(&self, weights: &[Decimal]) -> Result<Vec<Amount>, MoneyError> {
+        let mut value = self.money.amount();
+        value.rescale(self.asset.decimals);
+
+        Ok(Money(value)
+            .allocate(weights)?
+            .into_iter()
+            .map(|money| Amount {
+                money,
+                asset: self.asset.clone(),
+            })
+            .collect())
+    }
+
+    fn ensure_same_asset(&self, other: &Amount) -> Result<(), MoneyError> {
+        if self.asset.code != other.asset.code {
+            return Err(MoneyError::AssetMismatch(
+                self.asset.code.clone(),
+                other.asset.code.clone(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats the amount with exactly as many decimals as its asset, followed by
+/// the asset code (e.g. `1234.50 EUR`).
+impl std::fmt::Display for Amount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut value = self.money.amount();
+        value.rescale(self.asset.decimals);
+        write!(f, "{} {}", value, self.asset.code)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -112,7 +556,7 @@ mod tests {
         ];
 
         for (input, should_succeed) in valid_cases {
-            let result = Money::from_str(input);
+            let result = input.parse::<Money>();
             assert_eq!(
                 result.is_ok(),
                 should_succeed,
@@ -123,7 +567,7 @@ mod tests {
     }
 
     #[test]
-    fn test_money_from_i64() {
+    fn test_money_from_legacy_i64() {
         let cases = vec![
             (12345678, "0.12345678"),
             (100000000, "1.00000000"),
@@ -138,7 +582,7 @@ mod tests {
 
         for (i64_value, expected_str) in cases {
             let expected = Decimal::from_str(expected_str).unwrap();
-            let money = Money::from_sqlite_repr(i64_value);
+            let money = Money::from_legacy_repr(i64_value);
             assert_eq!(money.0, expected, "Failed for i64 value: {}", i64_value);
         }
     }
@@ -158,8 +602,10 @@ mod tests {
     #[test]
     fn test_money_precision() {
         // Test with maximum precision
-        let large_amount = Money::from_str("9999999999999999.9999999999999999").unwrap();
-        let small_amount = Money::from_str("0.0000000000000001").unwrap();
+        let large_amount = "9999999999999999.9999999999999999"
+            .parse::<Money>()
+            .unwrap();
+        let small_amount = "0.0000000000000001".parse::<Money>().unwrap();
 
         let result = large_amount + small_amount;
         assert_eq!(
@@ -175,12 +621,13 @@ mod tests {
         // Test ToSql
         let sql_value = original.to_sql().unwrap();
         let value = match sql_value {
-            ToSqlOutput::Owned(Value::Integer(i)) => i,
+            ToSqlOutput::Owned(Value::Text(text)) => text,
             _ => panic!("Expected Text output"),
         };
+        assert_eq!(value, "123.45");
 
         // Test FromSql
-        let value = Value::Integer(value);
+        let value = Value::Text(value);
         let roundtrip = Money::column_result(ValueRef::from(&value)).unwrap();
 
         assert_eq!(original, roundtrip);
@@ -188,52 +635,47 @@ mod tests {
 
     #[test]
     fn test_money_max_values() {
-        // Maximum positive value for i64 is 9,223,372,036,854,775,807
-        // With 8 decimal places, this means our maximum decimal is 92,233,720,368.54775807
-        let max_money = Money::from_str("92233720368.54775807").unwrap();
-        let min_money = Money::from_str("-92233720368.54775807").unwrap();
+        // The old i64 encoding with 8 decimal places topped out at 92,233,720,368.54775807
+        // and truncated anything past the 8th decimal; both limits are gone now
+        let cases = [
+            "92233720368.54775807",
+            "-92233720368.54775807",
+            "92233720368.54775808",
+            "-92233720368.54775809",
+            "79228162514264337593543950335",
+            "-79228162514264337593543950335",
+            "0.000000000000000001",
+            "123456789.123456789012345678",
+            "0.0000000000000000000000000001",
+        ];
 
         let conn = Connection::open_in_memory().unwrap();
         conn.execute(
-            "CREATE TABLE test_max_money (id INTEGER PRIMARY KEY, amount INTEGER NOT NULL)",
+            "CREATE TABLE test_max_money (id INTEGER PRIMARY KEY, amount TEXT NOT NULL)",
             [],
         )
         .unwrap();
 
-        // Test inserting maximum values
-        conn.execute(
-            "INSERT INTO test_max_money (amount) VALUES (?)",
-            [&max_money],
-        )
-        .unwrap();
-        conn.execute(
-            "INSERT INTO test_max_money (amount) VALUES (?)",
-            [&min_money],
-        )
-        .unwrap();
+        let originals: Vec<Money> = cases
+            .iter()
+            .map(|case| case.parse::<Money>().unwrap())
+            .collect();
+        for money in &originals {
+            conn.execute("INSERT INTO test_max_money (amount) VALUES (?)", [money])
+                .unwrap();
+        }
 
         // Verify retrieved values
         let mut stmt = conn
             .prepare("SELECT amount FROM test_max_money ORDER BY id")
             .unwrap();
-        let money_iter = stmt
-            .query_map([], |row| {
-                let value: i64 = row.get(0)?;
-                Ok(Money::from_sqlite_repr(value))
-            })
-            .unwrap();
+        let results: Vec<Money> = stmt
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
 
-        let results: Vec<Money> = money_iter.map(|r| r.unwrap()).collect();
-        assert_eq!(results[0], max_money);
-        assert_eq!(results[1], min_money);
-
-        // Test that exceeding the maximum value fails
-        let too_large = Money::from_str("92233720368.54775808").unwrap();
-        let result = conn.execute(
-            "INSERT INTO test_max_money (amount) VALUES (?)",
-            [&too_large],
-        );
-        assert!(result.is_err());
+        assert_eq!(results, originals);
     }
 
     #[test]
@@ -242,7 +684,7 @@ mod tests {
 
         // Create a test table
         conn.execute(
-            "CREATE TABLE test_money (id INTEGER PRIMARY KEY, amount INTEGER NOT NULL)",
+            "CREATE TABLE test_money (id INTEGER PRIMARY KEY, amount TEXT NOT NULL)",
             [],
         )
         .unwrap();
@@ -263,12 +705,7 @@ mod tests {
         let mut stmt = conn
             .prepare("SELECT amount FROM test_money ORDER BY id")
             .unwrap();
-        let money_iter = stmt
-            .query_map([], |row| {
-                let value: i64 = row.get(0)?;
-                Ok(Money::from_sqlite_repr(value))
-            })
-            .unwrap();
+        let money_iter = stmt.query_map([], |row| row.get::<_, Money>(0)).unwrap();
 
         for (stored, original) in money_iter.zip(test_amounts) {
             assert_eq!(stored.unwrap(), original);
@@ -278,10 +715,11 @@ mod tests {
     #[test]
     fn test_money_db_arithmetic() {
         let conn = Connection::open_in_memory().unwrap();
+        register_sql_functions(&conn).unwrap();
 
         // Create a test table
         conn.execute(
-            "CREATE TABLE test_money_arithmetic (id INTEGER PRIMARY KEY, amount INTEGER NOT NULL)",
+            "CREATE TABLE test_money_arithmetic (id INTEGER PRIMARY KEY, amount TEXT NOT NULL)",
             [],
         )
         .unwrap();
@@ -297,13 +735,13 @@ mod tests {
 
         // Perform arithmetic operations in the database
         conn.execute(
-            "UPDATE test_money_arithmetic SET amount = amount + ? WHERE id = 1",
+            "UPDATE test_money_arithmetic SET amount = dec_add(amount, ?) WHERE id = 1",
             [&m2],
         )
         .unwrap();
 
         conn.execute(
-            "UPDATE test_money_arithmetic SET amount = amount - ? WHERE id = 2",
+            "UPDATE test_money_arithmetic SET amount = dec_sub(amount, ?) WHERE id = 2",
             [&m1],
         )
         .unwrap();
@@ -312,57 +750,279 @@ mod tests {
         let mut stmt = conn
             .prepare("SELECT amount FROM test_money_arithmetic ORDER BY id")
             .unwrap();
-        let money_iter = stmt
-            .query_map([], |row| {
-                let value: i64 = row.get(0)?;
-                Ok(Money::from_sqlite_repr(value))
-            })
-            .unwrap();
+        let money_iter = stmt.query_map([], |row| row.get::<_, Money>(0)).unwrap();
 
         let results: Vec<Money> = money_iter.map(|r| r.unwrap()).collect();
         assert_eq!(results[0], m1 + m2);
         assert_eq!(results[1], m2 - m1);
+
+        let (sum, negated, sign): (Money, Money, i64) = conn
+            .query_row(
+                "SELECT dec_sum(amount), dec_neg(dec_sum(amount)), dec_sign(dec_sum(amount))
+                 FROM test_money_arithmetic",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(sum.amount(), dec!(100.50));
+        assert_eq!(negated.amount(), dec!(-100.50));
+        assert_eq!(sign, 1);
+
+        let empty: Option<Money> = conn
+            .query_row(
+                "SELECT dec_sum(amount) FROM test_money_arithmetic WHERE id > 2",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(empty, None);
     }
 
     #[test]
-    fn test_money_to_sql_conversion_failure() {
-        // This would result in an integer that is too big and can´t be represented in an i64
-        let too_large = Money(Decimal::from_str("92233720368.54775808").unwrap());
-        let result = too_large.to_sql();
+    fn test_money_sql_sum_overflow_error() {
+        let conn = Connection::open_in_memory().unwrap();
+        register_sql_functions(&conn).unwrap();
+
+        let max = Money(Decimal::MAX);
+        let result = conn.query_row(
+            "SELECT dec_sum(amount) FROM (SELECT ?1 AS amount UNION ALL SELECT ?1)",
+            [&max],
+            |row| row.get::<_, Money>(0),
+        );
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_money_sql_from_legacy_repr() {
+        let conn = Connection::open_in_memory().unwrap();
+        register_sql_functions(&conn).unwrap();
+
+        let converted: Money = conn
+            .query_row("SELECT dec_from_legacy(?1)", [i64::MAX], |row| row.get(0))
+            .unwrap();
+        assert_eq!(converted.amount(), dec!(92233720368.54775807));
+    }
+
+    #[test]
+    fn test_money_sql_rejects_real() {
+        let value = Value::Real(0.1);
+        assert!(Money::column_result(ValueRef::from(&value)).is_err());
+    }
+
+    #[test]
+    fn test_money_rounding_strategies() {
+        let cases = [
+            (dec!(0.125), Rounding::Bankers, dec!(0.12)),
+            (dec!(0.135), Rounding::Bankers, dec!(0.14)),
+            (dec!(0.125), Rounding::HalfUp, dec!(0.13)),
+            (dec!(-0.125), Rounding::HalfUp, dec!(-0.13)),
+            (dec!(0.125), Rounding::HalfDown, dec!(0.12)),
+            (dec!(0.129), Rounding::Down, dec!(0.12)),
+            (dec!(0.121), Rounding::Up, dec!(0.13)),
+        ];
+
+        for (value, rounding, expected) in cases {
+            assert_eq!(
+                Money::new(value).round(2, rounding).amount(),
+                expected,
+                "Failed for {} with {:?}",
+                value,
+                rounding
+            );
+        }
+    }
+
+    #[test]
+    fn test_amount_deserialization_is_checked() {
+        let amount: Amount = serde_json::from_str(
+            r#"{ "money": "1234.50", "asset": { "code": "EUR", "decimals": 2 } }"#,
+        )
+        .unwrap();
+        assert_eq!(amount.to_string(), "1234.50 EUR");
+
+        let error = serde_json::from_str::<Amount>(
+            r#"{ "money": "1234.505", "asset": { "code": "EUR", "decimals": 2 } }"#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("more decimals than EUR keeps"));
+
+        let amount: Amount = serde_json::from_str(
+            r#"{ "money": "0.1", "asset": { "code": "XYZ", "decimals": 40 } }"#,
+        )
+        .unwrap();
+        assert_eq!(amount.asset().decimals, 28);
+    }
+
+    #[test]
+    fn test_amount_precision_and_display() {
+        let eur = Denomination::new("EUR", 2);
+        let eth = Denomination::new("ETH", 18);
+
+        let price = Amount::new(Money::new(dec!(1234.5)), eur.clone(), Rounding::Bankers);
+        assert_eq!(price.to_string(), "1234.50 EUR");
+
+        let fee = Amount::new(Money::new(dec!(0.005)), eur, Rounding::HalfUp);
+        assert_eq!(fee.money().amount(), dec!(0.01));
+        assert_eq!(price.checked_add(&fee).unwrap().to_string(), "1234.51 EUR");
+
+        let gas = Amount::new(
+            Money::new(dec!(0.000000000000000001)),
+            eth,
+            Rounding::Bankers,
+        );
+        assert_eq!(gas.to_string(), "0.000000000000000001 ETH");
+
+        assert_eq!(
+            price.checked_sub(&gas),
+            Err(MoneyError::AssetMismatch("EUR".into(), "ETH".into()))
+        );
+
+        let units = Denomination::new("UNIT", 0);
+        let max = Amount::new(Money::new(Decimal::MAX), units.clone(), Rounding::Bankers);
+        let one = Amount::new(Money::new(dec!(1)), units, Rounding::Bankers);
+        assert_eq!(
+            max.checked_add(&one),
+            Err(MoneyError::Overflow(Decimal::MAX))
+        );
+        let min = Amount::new(
+            Money::new(Decimal::MIN),
+            max.asset().clone(),
+            Rounding::Bankers,
+        );
+        assert_eq!(
+            min.checked_sub(&one),
+            Err(MoneyError::Overflow(Decimal::MIN))
+        );
+    }
+
     #[test]
     fn test_money_sql_i64_min_and_max() {
-        let original = Money(Decimal::new(i64::MAX, 8)); // 92233720368.54775807
+        for original in [
+            Money(Decimal::new(i64::MAX, 8)), // 92233720368.54775807
+            Money(Decimal::new(i64::MIN, 8)), // -92233720368.54775808
+        ] {
+            // Test ToSql
+            let sql_value = original.to_sql().unwrap();
+            let value = match sql_value {
+                ToSqlOutput::Owned(Value::Text(text)) => text,
+                _ => panic!("Expected Text output"),
+            };
 
-        // Test ToSql
-        let sql_value = original.to_sql().unwrap();
-        let value = match sql_value {
-            ToSqlOutput::Owned(Value::Integer(i)) => i,
-            _ => panic!("Expected Integer output"),
-        };
+            // Test FromSql
+            let value = Value::Text(value);
+            let roundtrip = Money::column_result(ValueRef::from(&value)).unwrap();
 
-        // Test FromSql
-        let value = Value::Integer(value);
-        let roundtrip = Money::column_result(ValueRef::from(&value)).unwrap();
+            assert_eq!(original, roundtrip);
+        }
+    }
 
-        assert_eq!(original, roundtrip);
+    #[test]
+    fn test_money_operators_and_ordering() {
+        let mut total = Money::new(dec!(10.50));
+        total += Money::new(dec!(4.50));
+        total -= Money::new(dec!(5));
+        assert_eq!(total.amount(), dec!(10.00));
 
-        let original = Money(Decimal::new(i64::MIN, 8)); // -92233720368.54775807
+        assert_eq!((-total).amount(), dec!(-10));
+        assert_eq!((total * dec!(1.5)).amount(), dec!(15));
+        assert_eq!((total / dec!(4)).amount(), dec!(2.5));
 
-        // Test ToSql
-        let sql_value = original.to_sql().unwrap();
-        let value = match sql_value {
-            ToSqlOutput::Owned(Value::Integer(i)) => i,
-            _ => panic!("Expected Integer output"),
-        };
+        let amounts = [
+            Money::new(dec!(3)),
+            Money::new(dec!(-1)),
+            Money::new(dec!(2)),
+        ];
+        assert_eq!(amounts.iter().sum::<Money>().amount(), dec!(4));
+        assert_eq!(amounts.into_iter().max(), Some(Money::new(dec!(3))));
+        assert!(Money::new(dec!(-1)) < Money::new(dec!(0.01)));
+        assert_eq!(Money::new(dec!(1.0)), Money::new(dec!(1.00)));
 
-        // Test FromSql
-        let value = Value::Integer(value);
-        let roundtrip = Money::column_result(ValueRef::from(&value)).unwrap();
+        assert_eq!(Money::new(dec!(-2.5)).abs().amount(), dec!(2.5));
+        assert_eq!(Money::new(dec!(-2.5)).signum(), -1);
+        assert_eq!(Money::new(dec!(0.00)).signum(), 0);
+        assert!(Money::new(dec!(0.00)).is_zero());
+    }
 
-        assert_eq!(original, roundtrip);
+    #[test]
+    fn test_money_checked_arithmetic() {
+        let max = Money::new(Decimal::MAX);
+
+        assert_eq!(
+            max.checked_add(Money::new(dec!(1))),
+            Err(MoneyError::Overflow(Decimal::MAX))
+        );
+        assert_eq!(
+            (-max).checked_sub(Money::new(dec!(1))),
+            Err(MoneyError::Overflow(Decimal::MIN))
+        );
+        assert_eq!(
+            max.checked_mul(dec!(2)),
+            Err(MoneyError::Overflow(Decimal::MAX))
+        );
+        assert_eq!(
+            max.checked_div(Decimal::ZERO),
+            Err(MoneyError::DivisionByZero)
+        );
+        assert_eq!(
+            Money::new(dec!(10)).checked_div(dec!(4)),
+            Ok(Money::new(dec!(2.5)))
+        );
+    }
+
+    #[test]
+    fn test_money_allocate() {
+        let parts = Money::new(dec!(100.00))
+            .allocate(&[dec!(1), dec!(1), dec!(1)])
+            .unwrap();
+        assert_eq!(
+            parts
+                .iter()
+                .map(|part| part.amount().to_string())
+                .collect::<Vec<_>>(),
+            ["33.34", "33.33", "33.33"]
+        );
+
+        // 70/30 split of a fee, remainder going to the largest fractional part
+        let parts = Money::new(dec!(-0.05))
+            .allocate(&[dec!(0.7), dec!(0.3)])
+            .unwrap();
+        assert_eq!(parts, [Money::new(dec!(-0.04)), Money::new(dec!(-0.01))]);
+
+        let parts = Money::new(dec!(10))
+            .allocate(&[dec!(2), dec!(0), dec!(1)])
+            .unwrap();
+        assert_eq!(parts.iter().sum::<Money>(), Money::new(dec!(10)));
+        assert_eq!(parts[1], Money::new(dec!(0)));
+
+        assert_eq!(
+            Money::new(dec!(1)).allocate(&[]),
+            Err(MoneyError::InvalidWeights)
+        );
+        assert_eq!(
+            Money::new(dec!(1)).allocate(&[dec!(1), dec!(-1)]),
+            Err(MoneyError::InvalidWeights)
+        );
+
+        // Amounts read back from the database are normalized, so split them in asset units
+        let bill = Amount::new(
+            Money::new(dec!(100)),
+            Denomination::new("EUR", 2),
+            Rounding::Bankers,
+        );
+        let shares = bill.allocate(&[dec!(1), dec!(1), dec!(1)]).unwrap();
+        assert_eq!(
+            shares
+                .iter()
+                .map(|share| share.to_string())
+                .collect::<Vec<_>>(),
+            ["33.34 EUR", "33.33 EUR", "33.33 EUR"]
+        );
+    }
+
+    #[test]
+    fn test_money_display() {
+        assert_eq!(Money::new(dec!(-1234.5)).to_string(), "-1234.5");
+        assert_eq!(format!("{:.2}", Money::new(dec!(1234.5))), "1234.50");
     }
 
     #[test]
@@ -0,0 +1,439 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::money::{Amount, Denomination, Money, MoneyError, Rounding};
+
+/// Where the currency symbol or asset code goes relative to the number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymbolPosition {
+    /// `$1,234.56`
+    Prefix,
+    /// `1.234,56 €`
+    Suffix,
+}
+
+/// How negative amounts are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NegativeStyle {
+    /// `-$1,234.56`, `-1.234,56 €`
+    #[default]
+    Minus,
+    /// `1,234.56-`, common in bank statements
+    TrailingMinus,
+    /// `($1,234.56)`, the accounting convention
+    Parentheses,
+}
+
+/// Locale conventions used to format and parse [`Money`] values.
+///
+/// The default is the plain Rust decimal notation (`-1234.56`). Presets for
+/// common locales can be adjusted with the `with_*` methods:
+///
+/// ```
+/// use mm_schema::{Money, MoneyError, MoneyFormat};
+/// use rust_decimal_macros::dec;
+///
+/// let format = MoneyFormat::de_de().with_symbol("€");
+/// assert_eq!(format.parse("1.234,56 €")?, Money::new(dec!(1234.56)));
+/// assert_eq!(format.format(Money::new(dec!(-1234.5))), "-1.234,50 €");
+/// # Ok::<(), MoneyError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MoneyFormat {
+    pub decimal_separator: char,
+    pub thousands_separator: Option<char>,
+    /// Currency symbol or asset code, e.g. `€`, `$` or `CHF`
+    pub symbol: Option<String>,
+    pub symbol_position: SymbolPosition,
+    /// Whether a space separates a symbol such as `€` from the number
+    pub symbol_spacing: bool,
+    pub negative_style: NegativeStyle,
+    /// Decimals to show and to accept, `None` to keep those of the amount
+    pub decimals: Option<u32>,
+    pub rounding: Rounding,
+}
+
+impl Default for MoneyFormat {
+    fn default() -> Self {
+        MoneyFormat {
+            decimal_separator: '.',
+            thousands_separator: None,
+            symbol: None,
+            symbol_position: SymbolPosition::Suffix,
+            symbol_spacing: true,
+            negative_style: NegativeStyle::Minus,
+            decimals: None,
+            rounding: Rounding::default(),
+        }
+    }
+}
+
+impl MoneyFormat {
+    /// `$1,234.56`
+    pub fn en_us() -> Self {
+        MoneyFormat {
+            thousands_separator: Some(','),
+            symbol_position: SymbolPosition::Prefix,
+            symbol_spacing: false,
+            decimals: Some(2),
+            ..Default::default()
+        }
+    }
+
+    /// `1.234,56 €`, also used in Italy, Spain and the Netherlands
+    pub fn de_de() -> Self {
+        MoneyFormat {
+            decimal_separator: ',',
+            thousands_separator: Some('.'),
+            decimals: Some(2),
+            ..Default::default()
+        }
+    }
+
+    /// `1 234,56 €`, with a narrow no-break space between groups
+    pub fn fr_fr() -> Self {
+        MoneyFormat {
+            decimal_separator: ',',
+            thousands_separator: Some('\u{202f}'),
+            decimals: Some(2),
+            ..Default::default()
+        }
+    }
+
+    /// `CHF 1'234.56`
+    pub fn de_ch() -> Self {
+        MoneyFormat {
+            thousands_separator: Some('\''),
+            symbol_position: SymbolPosition::Prefix,
+            decimals: Some(2),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_symbol<S: Into<String>>(mut self, symbol: S) -> Self {
+        self.symbol = Some(symbol.into());
+        self
+    }
+
+    pub fn with_negative_style(mut self, negative_style: NegativeStyle) -> Self {
+        self.negative_style = negative_style;
+        self
+    }
+
+    pub fn with_decimals(mut self, decimals: u32) -> Self {
+        self.decimals = Some(decimals);
+        self
+    }
+
+    /// Uses the decimals of `asset`, and its code unless a symbol is already set.
+    pub fn for_asset(mut self, asset: &Denomination) -> Self {
+        self.decimals = Some(asset.decimals);
+        if self.symbol.is_none() {
+            self.symbol = Some(asset.code.clone());
+        }
+        self
+    }
+
+    pub fn format(&self, money: Money) -> String {
+        let mut value = money.amount();
+        if let Some(decimals) = self.decimals {
+            value = value.round_dp_with_strategy(decimals, self.rounding.into());
+            value.rescale(decimals);
+        }
+
+        let digits = value.abs().to_string();
+        let (integer, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+
+        let mut number = String::new();
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                if let Some(separator) = self.thousands_separator {
+                    number.push(separator);
+                }
+            }
+            number.push(digit);
+        }
+        if !fraction.is_empty() {
+            number.push(self.decimal_separator);
+            number.push_str(fraction);
+        }
+
+        // Codes such as `USD` are always set apart from the number
+        let spacing = match &self.symbol {
+            Some(symbol) if self.symbol_spacing || symbol.chars().all(char::is_alphabetic) => " ",
+            _ => "",
+        };
+        let text = match (&self.symbol, self.symbol_position) {
+            (None, _) => number,
+            (Some(symbol), SymbolPosition::Prefix) => format!("{symbol}{spacing}{number}"),
+            (Some(symbol), SymbolPosition::Suffix) => format!("{number}{spacing}{symbol}"),
+        };
+
+        if !value.is_sign_negative() || value.is_zero() {
+            return text;
+        }
+        match self.negative_style {
+            NegativeStyle::Minus => format!("-{text}"),
+            NegativeStyle::TrailingMinus => format!("{text}-"),
+            NegativeStyle::Parentheses => format!("({text})"),
+        }
+    }
+
+    /// Formats `amount` with the decimals and code of its asset.
+    pub fn format_amount(&self, amount: &Amount) -> String {
+        self.clone()
+            .for_asset(amount.asset())
+            .format(amount.money())
+    }
+
+    /// Parses an amount written in this format.
+    ///
+    /// Parsing is lenient about where the symbol and the minus sign are and
+    /// accepts every negative style, but rejects more than one of them,
+    /// misplaced separators and more decimals than the format allows.
+    pub fn parse(&self, input: &str) -> Result<Money, MoneyError> {
+        let invalid = || MoneyError::Parse(input.to_string());
+
+        // Parentheses, a leading or trailing minus or one after the symbol
+        // each mark a negative amount, and at most one of them may be used
+        const MINUS: [char; 2] = ['-', '\u{2212}'];
+        let mut markers = 0;
+        let mut text = input.trim();
+
+        if let Some(inner) = text
+            .strip_prefix('(')
+            .and_then(|text| text.strip_suffix(')'))
+        {
+            markers += 1;
+            text = inner.trim();
+        }
+        if let Some(rest) = text.strip_prefix(MINUS) {
+            markers += 1;
+            text = rest.trim_start();
+        }
+        if let Some(rest) = text.strip_suffix(MINUS) {
+            markers += 1;
+            text = rest.trim_end();
+        }
+
+        if let Some(symbol) = &self.symbol {
+            if let Some(rest) = text.strip_prefix(symbol.as_str()) {
+                text = rest;
+            } else if let Some(rest) = text.strip_suffix(symbol.as_str()) {
+                text = rest;
+            }
+        }
+
+        // The minus sign may also sit between the symbol and the number: `$-1.00`
+        let mut text = text.trim();
+        if let Some(rest) = text.strip_prefix(MINUS) {
+            markers += 1;
+            text = rest;
+        }
+        if markers > 1 {
+            return Err(invalid());
+        }
+        let negative = markers == 1;
+
+        let (integer, fraction) = match text.split_once(self.decimal_separator) {
+            Some((integer, fraction)) => (integer.to_string(), Some(fraction.to_string())),
+            None => (text.to_string(), None),
+        };
+
+        let groups: Vec<&str> = match self.thousands_separator {
+            Some(separator) if separator.is_whitespace() => integer
+                .split(|c: char| c == separator || c.is_whitespace())
+                .collect(),
+            Some(separator) => integer.split(separator).collect(),
+            None => vec![integer.as_str()],
+        };
+        let grouped = groups.len() > 1;
+        if groups.is_empty()
+            || groups[0].is_empty()
+            || (grouped && (groups[0].len() > 3 || groups[1..].iter().any(|g| g.len() != 3)))
+        {
+            return Err(invalid());
+        }
+
+        let integer = groups.concat();
+        let fraction = fraction.unwrap_or_default();
+        if !integer.chars().all(|c| c.is_ascii_digit())
+            || !fraction.chars().all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        if let Some(decimals) = self.decimals {
+            if fraction.len() > decimals as usize {
+                return Err(invalid());
+            }
+        }
+
+        let mut value: Decimal = if fraction.is_empty() {
+            integer.parse()
+        } else {
+            format!("{integer}.{fraction}").parse()
+        }
+        .map_err(|_| invalid())?;
+        value.set_sign_negative(negative && !value.is_zero());
+
+        Ok(Money::new(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_format_locales() {
+        let amount = Money::new(dec!(1234567.891));
+
+        assert_eq!(MoneyFormat::default().format(amount), "1234567.891");
+        assert_eq!(
+            MoneyFormat::en_us().with_symbol("$").format(amount),
+            "$1,234,567.89"
+        );
+        assert_eq!(
+            MoneyFormat::de_de().with_symbol("€").format(amount),
+            "1.234.567,89 €"
+        );
+        assert_eq!(
+            MoneyFormat::fr_fr().with_symbol("€").format(amount),
+            "1\u{202f}234\u{202f}567,89 €"
+        );
+        assert_eq!(
+            MoneyFormat::de_ch().with_symbol("CHF").format(amount),
+            "CHF 1'234'567.89"
+        );
+        assert_eq!(MoneyFormat::en_us().format(Money::new(dec!(999))), "999.00");
+    }
+
+    #[test]
+    fn test_format_negative_styles() {
+        let amount = Money::new(dec!(-1234.5));
+        let format = MoneyFormat::en_us().with_symbol("$");
+
+        assert_eq!(format.format(amount), "-$1,234.50");
+        assert_eq!(
+            format
+                .clone()
+                .with_negative_style(NegativeStyle::Parentheses)
+                .format(amount),
+            "($1,234.50)"
+        );
+        assert_eq!(
+            format
+                .with_negative_style(NegativeStyle::TrailingMinus)
+                .format(amount),
+            "$1,234.50-"
+        );
+        assert_eq!(
+            MoneyFormat::en_us().format(Money::new(dec!(-0.001))),
+            "0.00"
+        );
+    }
+
+    #[test]
+    fn test_format_asset_decimals() {
+        let eth = Denomination::new("ETH", 18);
+        let gas = Amount::new(
+            Money::new(dec!(0.000000000000000001)),
+            eth,
+            Rounding::Bankers,
+        );
+        assert_eq!(
+            MoneyFormat::en_us().format_amount(&gas),
+            "ETH 0.000000000000000001"
+        );
+
+        let jpy = Denomination::new("JPY", 0);
+        assert_eq!(
+            MoneyFormat::en_us()
+                .with_symbol("¥")
+                .for_asset(&jpy)
+                .format(Money::new(dec!(1234.5))),
+            "¥1,234"
+        );
+    }
+
+    #[test]
+    fn test_parse_locales() {
+        let de = MoneyFormat::de_de().with_symbol("€");
+        assert_eq!(de.parse("1.234,56 €"), Ok(Money::new(dec!(1234.56))));
+        assert_eq!(de.parse("1234,5"), Ok(Money::new(dec!(1234.5))));
+        assert_eq!(de.parse("-1.234,56 €"), Ok(Money::new(dec!(-1234.56))));
+        assert_eq!(de.parse("€ 12"), Ok(Money::new(dec!(12))));
+
+        let us = MoneyFormat::en_us().with_symbol("$");
+        assert_eq!(us.parse("$1,234.56"), Ok(Money::new(dec!(1234.56))));
+        assert_eq!(us.parse("($1,234.56)"), Ok(Money::new(dec!(-1234.56))));
+        assert_eq!(us.parse("-$0.50"), Ok(Money::new(dec!(-0.50))));
+        assert_eq!(us.parse("$-0.50"), Ok(Money::new(dec!(-0.50))));
+        assert_eq!(us.parse("1,234.56-"), Ok(Money::new(dec!(-1234.56))));
+
+        let fr = MoneyFormat::fr_fr().with_symbol("€");
+        assert_eq!(fr.parse("1 234,56 €"), Ok(Money::new(dec!(1234.56))));
+        assert_eq!(
+            fr.parse("1\u{202f}234,56\u{a0}€"),
+            Ok(Money::new(dec!(1234.56)))
+        );
+
+        let ch = MoneyFormat::de_ch().with_symbol("CHF");
+        assert_eq!(ch.parse("CHF 1'234.56"), Ok(Money::new(dec!(1234.56))));
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_input() {
+        let us = MoneyFormat::en_us().with_symbol("$");
+        for input in [
+            "",
+            "$",
+            "1.234,56",
+            "12,34.00",
+            "1,2345.00",
+            "1.005",
+            "abc",
+            "1.2.3",
+            "(-5.00)",
+            "--5",
+            "-5-",
+            "-$-5",
+        ] {
+            assert_eq!(
+                us.parse(input),
+                Err(MoneyError::Parse(input.to_string())),
+                "Failed for input: {}",
+                input
+            );
+        }
+
+        let eth = MoneyFormat::default().for_asset(&Denomination::new("ETH", 18));
+        assert_eq!(
+            eth.parse("0.000000000000000001 ETH"),
+            Ok(Money::new(dec!(0.000000000000000001)))
+        );
+    }
+
+    #[test]
+    fn test_format_parse_roundtrip() {
+        let formats = [
+            MoneyFormat::default(),
+            MoneyFormat::en_us()
+                .with_symbol("$")
+                .with_negative_style(NegativeStyle::Parentheses),
+            MoneyFormat::de_de().with_symbol("EUR"),
+            MoneyFormat::fr_fr()
+                .with_symbol("€")
+                .with_negative_style(NegativeStyle::TrailingMinus),
+            MoneyFormat::de_ch().with_symbol("CHF"),
+        ];
+
+        for format in formats {
+            for value in [dec!(0), dec!(0.01), dec!(-1234567.89), dec!(999.99)] {
+                let text = format.format(Money::new(value));
+                assert_eq!(format.parse(&text), Ok(Money::new(value)), "{text}");
+            }
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use std::io::Read;
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::{
+    bank_statement::{BankStatement, StatementBalance, StatementEntry, StatementImport},
+    error::{Error, Result},
+    interface::Database,
+    money::Money,
+};
+
+impl Database {
+    /// Imports SWIFT MT940 customer statements.
+    /// See [`Database::import_bank_statements`].
+    pub fn import_mt940<R: Read>(&mut self, mut reader: R) -> Result<StatementImport> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        // MT940 is restricted to a subset of ASCII, but banks often send Latin-1
+        let statements = parse_mt940(&String::from_utf8_lossy(&bytes))?;
+
+        self.import_bank_statements(&statements, "mt940")
+    }
+}
+
+/// Parses the statements of an MT940 file.
+///
+/// The `:86:` information field is read in the structured `?nn` layout used by
+/// German banks when present, and as free text otherwise.
+pub fn parse_mt940(input: &str) -> Result<Vec<BankStatement>> {
+    let mut statements = Vec::new();
+    let mut statement: Option<PartialStatement> = None;
+
+    for (tag, value) in fields(input) {
+        let invalid = |message: &str| Error::InvalidData(format!(":{tag}: {message}: {value:?}"));
+
+        match tag.as_str() {
+            "20" => {
+                if let Some(statement) = statement.take() {
+                    statements.push(statement.finish()?);
+                }
+                statement = Some(PartialStatement {
+                    id: value.trim().to_string(),
+                    ..Default::default()
+                });
+            }
+            _ => {
+                let Some(statement) = statement.as_mut() else {
+                    // Fields before the first :20: belong to the envelope
+                    continue;
+                };
+
+                match tag.as_str() {
+                    "25" => statement.account_id = Some(value.trim().to_string()),
+                    "28C" => {
+                        // Statement number/sequence, kept with the reference
+                        statement.id = format!("{} {}", statement.id, value.trim());
+                    }
+                    "60F" | "60M" => {
+                        let (balance, currency) =
+                            balance(&value).ok_or_else(|| invalid("invalid balance"))?;
+                        statement.opening_balance = Some(balance);
+                        statement.currency = Some(currency);
+                    }
+                    "62F" | "62M" => {
+                        let (balance, _) =
+                            balance(&value).ok_or_else(|| invalid("invalid balance"))?;
+                        statement.closing_balance = Some(balance);
+                    }
+                    "61" => {
+                        let entry = statement_line(&value)
+                            .ok_or_else(|| invalid("invalid statement line"))?;
+                        statement.entries.push(entry);
+                    }
+                    "86" => {
+                        if let Some(entry) = statement.entries.last_mut() {
+                            information(entry, &value);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    if let Some(statement) = statement {
+        statements.push(statement.finish()?);
+    }
+
+    Ok(statements)
+}
+
+#[derive(Default)]
+struct PartialStatement {
+    id: String,
+    account_id: Option<String>,
+    currency: Option<String>,
+    opening_balance: Option<StatementBalance>,
+    closing_balance: Option<StatementBalance>,
+    entries: Vec<StatementEntry>,
+}
+
+impl PartialStatement {
+    fn finish(self) -> Result<BankStatement> {
+        let missing =
+            |tag: &str| Error::InvalidData(format!("statement {:?} without :{tag}:", self.id));
+
+        Ok(BankStatement {
+            account_id: self.account_id.clone().ok_or_else(|| missing("25"))?,
+            currency: self.currency.clone().ok_or_else(|| missing("60F"))?,
+            opening_balance: self.opening_balance.ok_or_else(|| missing("60F"))?,
+            closing_balance: self.closing_balance.ok_or_else(|| missing("62F"))?,
+            id: self.id,
+            entries: self.entries,
+        })
+    }
+}
+
+/// Splits a file into `(tag, value)` fields, joining continuation lines and
+/// dropping the SWIFT envelope and message separators
+fn fields(input: &str) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+
+    for line in input.lines() {
+        let line = line.trim_end_matches('\r');
+        // {1:F01...}{2:O940...}{4: opens the message text and -} closes it
+        let line = match line.rfind("{4:") {
+            Some(start) => &line[start + 3..],
+            None => line,
+        };
+        if line == "-" || line == "-}" || line.starts_with("{5:") || line.is_empty() {
+            continue;
+        }
+
+        let tag = line
+            .strip_prefix(':')
+            .and_then(|rest| rest.split_once(':'))
+            .filter(|(tag, _)| tag.len() <= 3 && tag.chars().all(|c| c.is_ascii_alphanumeric()));
+        match tag {
+            Some((tag, value)) => fields.push((tag.to_string(), value.to_string())),
+            None => {
+                if let Some((_, value)) = fields.last_mut() {
+                    value.push('\n');
+                    value.push_str(line);
+                }
+            }
+        }
+    }
+
+    fields
+}
+
+/// `C250131EUR1234,56`: credit or debit mark, date, currency and amount
+fn balance(value: &str) -> Option<(StatementBalance, String)> {
+    let value = value.trim();
+    let sign = value.get(..1)?;
+    let date = date(value.get(1..7)?)?;
+    let currency = value.get(7..10)?.to_string();
+    let amount = amount(value.get(10..)?)?;
+
+    let amount = match sign {
+        "C" => amount,
+        "D" => -amount,
+        _ => return None,
+    };
+
+    Some((StatementBalance { date, amount }, currency))
+}
+
+/// `2502010201D12,50NTRFNONREF//B0201XYZ`: value date, optional booking
+/// date, mark, optional funds code, amount, transaction type, customer
+/// reference, bank reference and supplementary details on the next line
+fn statement_line(value: &str) -> Option<StatementEntry> {
+    let (first, supplementary) = match value.split_once('\n') {
+        Some((first, rest)) => (first, Some(rest.trim())),
+        None => (value, None),
+    };
+
+    let value_date = date(first.get(..6)?)?;
+    let mut rest = &first[6..];
+
+    let booking_date = match rest
+        .get(..4)
+        .filter(|s| s.chars().all(|c| c.is_ascii_digit()))
+    {
+        Some(month_day) => {
+            rest = &rest[4..];
+            let month: u32 = month_day[..2].parse().ok()?;
+            let day: u32 = month_day[2..].parse().ok()?;
+            // Entries booked in January for a December value date, and vice versa
+            let year = match (value_date.month(), month) {
+                (12, 1) => value_date.year() + 1,
+                (1, 12) => value_date.year() - 1,
+                _ => value_date.year(),
+            };
+            NaiveDate::from_ymd_opt(year, month, day)?
+        }
+        None => value_date,
+    };
+
+    // Reversals (RC, RD) undo an entry of the opposite sign
+    let (negative, mark_length) = match rest.get(..2)? {
+        "RC" => (true, 2),
+        "RD" => (false, 2),
+        mark if mark.starts_with('C') => (false, 1),
+        mark if mark.starts_with('D') => (true, 1),
+        _ => return None,
+    };
+    rest = &rest[mark_length..];
+
+    // Third character of the currency code, for funds codes
+    if rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        rest = &rest[1..];
+    }
+
+    let amount_length = rest.find(|c: char| !c.is_ascii_digit() && c != ',')?;
+    let amount = amount(&rest[..amount_length])?;
+    rest = &rest[amount_length..];
+
+    // Transaction type: N, F or S followed by a three character code
+    let references = rest.get(4..).unwrap_or_default();
+    let (customer_reference, bank_reference) = match references.split_once("//") {
+        Some((customer, bank)) => (customer, Some(bank)),
+        None => (references, None),
+    };
+    let reference = |value: &str| {
+        Some(value.trim().to_string()).filter(|value| !value.is_empty() && value != "NONREF")
+    };
+
+    Some(StatementEntry {
+        amount: match negative {
+            true => -amount,
+            false => amount,
+        },
+        booking_date,
+        value_date: Some(value_date),
+        reference: reference(customer_reference),
+        bank_reference: bank_reference.and_then(reference),
+        remittance_info: supplementary.and_then(reference),
+        ..Default::default()
+    })
+}
+
+/// Fills `entry` from an `:86:` information field
+fn information(entry: &mut StatementEntry, value: &str) {
+    let value = value.replace('\n', "");
+
+    // Structured: a three digit transaction code followed by ?nn subfields
+    let separator = value.chars().nth(3).filter(|c| {
+        value
+            .get(..3)
+            .is_some_and(|code| code.chars().all(|c| c.is_ascii_digit()))
+            && !c.is_alphanumeric()
+            && !c.is_whitespace()
+    });
+    let Some(separator) = separator else {
+        entry.remittance_info = Some(value.trim().to_string()).filter(|v| !v.is_empty());
+        return;
+    };
+
+    let mut remittance = String::new();
+    let mut name = String::new();
+    for subfield in value[4..].split(separator) {
+        let (Some(Ok(code)), Some(text)) =
+            (subfield.get(..2).map(str::parse::<u8>), subfield.get(2..))
+        else {
+            continue;
+        };
+        match code {
+            20..=29 | 60..=63 => remittance.push_str(text),
+            31 => entry.counterparty_iban = Some(text.trim().to_string()),
+            32 | 33 => name.push_str(text),
+            _ => {}
+        }
+    }
+
+    if !remittance.trim().is_empty() {
+        entry.remittance_info = Some(remittance.trim().to_string());
+    }
+    if !name.trim().is_empty() {
+        entry.counterparty = Some(name.trim().to_string());
+    }
+}
+
+fn date(value: &str) -> Option<NaiveDate> {
+    let year: i32 = value.get(..2)?.parse().ok()?;
+    let month = value.get(2..4)?.parse().ok()?;
+    let day = value.get(4..6)?.parse().ok()?;
+
+    NaiveDate::from_ymd_opt(2000 + year, month, day)
+}
+
+/// SWIFT amounts use a comma as the decimal separator and no grouping
+fn amount(value: &str) -> Option<Money> {
+    let money = value.trim().replace(',', ".").parse::<Money>().ok()?;
+    Some(Money::new(money.amount().normalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    const STATEMENT: &str =
+        "{1:F01BANKDEFFXXXX0000000000}{2:O9400000000000BANKDEFFXXXX00000000000000000000N}{4:\r\n\
+        :20:STARTUMSE\r\n\
+        :25:10020030/1234567\r\n\
+        :28C:00001/001\r\n\
+        :60F:C241231EUR100,00\r\n\
+        :61:2501020102D45,20NTRFNONREF//B0102XYZ\r\n\
+        :86:106?00KARTENZAHLUNG?20Supermarkt Filiale 12?21Karte 1234?30DEUTDEFF\r\n\
+        ?31DE02120300000000202051?32SUPERMARKT GMBH\r\n\
+        :61:2501311231CR2500,NTRFREF123\r\n\
+        /Gehalt Januar\r\n\
+        :86:Salary January\r\n\
+        :62F:C250131EUR2554,80\r\n\
+        -}";
+
+    #[test]
+    fn test_parse_mt940() {
+        let statements = parse_mt940(STATEMENT).unwrap();
+        let statement = &statements[0];
+        assert_eq!(statement.id, "STARTUMSE 00001/001");
+        assert_eq!(statement.account_id, "10020030/1234567");
+        assert_eq!(statement.currency, "EUR");
+        assert_eq!(statement.opening_balance.amount, Money::new(dec!(100)));
+        assert_eq!(statement.closing_balance.amount, Money::new(dec!(2554.8)));
+
+        assert_eq!(
+            statement.entries[0],
+            StatementEntry {
+                amount: Money::new(dec!(-45.2)),
+                booking_date: NaiveDate::from_ymd_opt(2025, 1, 2).unwrap(),
+                value_date: NaiveDate::from_ymd_opt(2025, 1, 2),
+                counterparty: Some("SUPERMARKT GMBH".into()),
+                counterparty_iban: Some("DE02120300000000202051".into()),
+                remittance_info: Some("Supermarkt Filiale 12Karte 1234".into()),
+                reference: None,
+                bank_reference: Some("B0102XYZ".into()),
+            }
+        );
+
+        // Booked on the last day of the year for a January value date
+        let salary = &statement.entries[1];
+        assert_eq!(salary.amount, Money::new(dec!(2500)));
+        assert_eq!(
+            salary.booking_date,
+            NaiveDate::from_ymd_opt(2024, 12, 31).unwrap()
+        );
+        assert_eq!(salary.reference.as_deref(), Some("REF123"));
+        assert_eq!(salary.remittance_info.as_deref(), Some("Salary January"));
+    }
+
+    #[test]
+    fn test_parse_mt940_requires_balances() {
+        assert!(parse_mt940(":20:X\n:25:ACCOUNT\n:61:2501020102D45,20NTRFNONREF\n").is_err());
+    }
+}
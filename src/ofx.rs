@@ -0,0 +1,651 @@
+use std::{collections::HashMap, io::Read};
+
+use chrono::NaiveDate;
+use rusqlite::Connection;
+
+use crate::{
+    error::{Error, Result},
+    import::{
+        ensure_asset, insert_investment_transaction, insert_statement_lines, is_imported,
+        record_imported, ImportSummary, InvestmentKind, InvestmentTransaction, StatementLine,
+    },
+    interface::Database,
+    journal::account_id_of,
+    models::{AssetType, IncomeKind},
+    money::Money,
+};
+
+/// Account investment statements are imported into unless their broker
+/// account is mapped to another one
+pub const BROKERAGE_ACCOUNT: &str = "1201";
+
+/// A parsed OFX document
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OfxDocument {
+    /// Bank and credit card statements (`STMTRS`, `CCSTMTRS`)
+    pub statements: Vec<OfxStatement>,
+    /// Brokerage statements (`INVSTMTRS`)
+    pub investment_statements: Vec<OfxInvestmentStatement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OfxStatement {
+    /// The bank's `ACCTID`
+    pub account_id: String,
+    pub currency: String,
+    pub lines: Vec<StatementLine>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OfxInvestmentStatement {
+    /// The broker's `ACCTID`
+    pub account_id: String,
+    pub currency: String,
+    pub transactions: Vec<OfxInvestmentTransaction>,
+    /// Deposits, withdrawals and fees on the cash balance (`INVBANKTRAN`)
+    pub cash: Vec<StatementLine>,
+    /// Transactions of kinds that are not imported, such as transfers or splits
+    pub unsupported: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OfxInvestmentTransaction {
+    pub fitid: String,
+    pub date: NaiveDate,
+    pub memo: Option<String>,
+    pub security: OfxSecurity,
+    pub kind: InvestmentKind,
+}
+
+/// A security from the document's `SECLIST`
+#[derive(Debug, Clone, PartialEq)]
+pub struct OfxSecurity {
+    /// CUSIP, ISIN or whatever `UNIQUEIDTYPE` the broker uses
+    pub unique_id: String,
+    pub ticker: Option<String>,
+    pub name: Option<String>,
+    pub asset_type: AssetType,
+}
+
+impl OfxSecurity {
+    /// Code of the asset the security is recorded as
+    pub fn asset_code(&self) -> &str {
+        self.ticker.as_deref().unwrap_or(&self.unique_id)
+    }
+}
+
+impl Database {
+    /// Imports an OFX 1.x (SGML) or 2.x (XML) file.
+    ///
+    /// Bank and credit card transactions become draft entries on the account
+    /// their `ACCTID` is mapped to with [`Database::map_external_account`].
+    /// Investment buys, sells and income are recorded as posted entries on
+    /// [`BROKERAGE_ACCOUNT`] (or the mapped account) and open and close lots
+    /// first in, first out. Transactions are deduplicated on their `FITID`,
+    /// and the whole file is imported in a single transaction.
+    pub fn import_ofx<R: Read>(&mut self, mut reader: R) -> Result<ImportSummary> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        // OFX 1.x files are often in a Windows code page rather than UTF-8
+        let document = parse_ofx(&String::from_utf8_lossy(&bytes))?;
+
+        let mut statements = Vec::new();
+        for statement in &document.statements {
+            let account_number = self.mapped_account(&statement.account_id)?.ok_or_else(|| {
+                Error::InvalidData(format!(
+                    "no account is mapped to OFX account {:?}",
+                    statement.account_id
+                ))
+            })?;
+            statements.push((account_number, statement));
+        }
+        let mut investment_statements = Vec::new();
+        for statement in &document.investment_statements {
+            let account_number = self
+                .mapped_account(&statement.account_id)?
+                .unwrap_or_else(|| BROKERAGE_ACCOUNT.to_string());
+            investment_statements.push((account_number, statement));
+        }
+
+        let mut summary = ImportSummary::default();
+        self.snapshot_before("import")?;
+        let t = self.transaction()?;
+
+        for (account_number, statement) in statements {
+            insert_statement_lines(
+                &t,
+                &account_number,
+                &statement.currency,
+                &statement.lines,
+                &mut summary,
+            )?;
+        }
+        for (account_number, statement) in investment_statements {
+            insert_statement_lines(
+                &t,
+                &account_number,
+                &statement.currency,
+                &statement.cash,
+                &mut summary,
+            )?;
+
+            let account_id = account_id_of(&t, &account_number)?;
+            for transaction in &statement.transactions {
+                let external_id = format!("ofx:{}", transaction.fitid);
+                if is_imported(&t, account_id, &external_id)? {
+                    summary.duplicates += 1;
+                    continue;
+                }
+
+                let journal_entry_id =
+                    insert_ofx_investment(&t, &account_number, &statement.currency, transaction)?;
+                record_imported(&t, account_id, &external_id, journal_entry_id)?;
+                summary.entry_ids.push(journal_entry_id);
+            }
+            summary.skipped += statement.unsupported;
+        }
+
+        t.commit()?;
+
+        Ok(summary)
+    }
+}
+
+/// Records a buy, sell or income transaction and returns its journal entry id.
+fn insert_ofx_investment(
+    t: &Connection,
+    account_number: &str,
+    currency: &str,
+    transaction: &OfxInvestmentTransaction,
+) -> Result<i64> {
+    let security = &transaction.security;
+    let asset_code = security.asset_code();
+    ensure_asset(
+        t,
+        asset_code,
+        security.name.as_deref().unwrap_or(asset_code),
+        security.asset_type,
+        8,
+        &format!("Imported from OFX ({})", security.unique_id),
+    )?;
+
+    insert_investment_transaction(
+        t,
+        &InvestmentTransaction {
+            account_number,
+            cash_account_number: account_number,
+            currency,
+            asset_code,
+            date: transaction.date,
+            memo: transaction.memo.as_deref(),
+            reference: Some(&transaction.fitid),
+            kind: &transaction.kind,
+        },
+    )
+}
+
+/// Parses an OFX 1.x (SGML) or 2.x (XML) document.
+pub fn parse_ofx(input: &str) -> Result<OfxDocument> {
+    let root = parse_elements(input)?;
+    let securities = parse_securities(&root);
+
+    let mut document = OfxDocument::default();
+
+    for statement in root
+        .descendants("STMTRS")
+        .into_iter()
+        .chain(root.descendants("CCSTMTRS"))
+    {
+        let account = statement
+            .find("BANKACCTFROM")
+            .or_else(|| statement.find("CCACCTFROM"))
+            .and_then(|account| account.value_of("ACCTID"))
+            .ok_or_else(|| Error::InvalidData("statement without ACCTID".into()))?;
+
+        document.statements.push(OfxStatement {
+            account_id: account.to_string(),
+            currency: required(statement, "CURDEF")?.to_string(),
+            lines: statement
+                .find("BANKTRANLIST")
+                .map(|list| list.children_named("STMTTRN"))
+                .unwrap_or_default()
+                .into_iter()
+                .map(statement_line)
+                .collect::<Result<_>>()?,
+        });
+    }
+
+    for statement in root.descendants("INVSTMTRS") {
+        let account = statement
+            .find("INVACCTFROM")
+            .and_then(|account| account.value_of("ACCTID"))
+            .ok_or_else(|| Error::InvalidData("investment statement without ACCTID".into()))?;
+
+        let mut transactions = Vec::new();
+        let mut cash = Vec::new();
+        let mut unsupported = 0;
+        for transaction in statement
+            .find("INVTRANLIST")
+            .map(|list| list.children.iter().collect::<Vec<_>>())
+            .unwrap_or_default()
+        {
+            match investment_transaction(transaction, &securities)? {
+                Some(transaction) => transactions.push(transaction),
+                None if transaction.name == "INVBANKTRAN" => {
+                    if let Some(line) = transaction.find("STMTTRN") {
+                        cash.push(statement_line(line)?);
+                    }
+                }
+                None if transaction.value.is_none() => unsupported += 1,
+                None => {}
+            }
+        }
+
+        document.investment_statements.push(OfxInvestmentStatement {
+            account_id: account.to_string(),
+            currency: required(statement, "CURDEF")?.to_string(),
+            transactions,
+            cash,
+            unsupported,
+        });
+    }
+
+    Ok(document)
+}
+
+fn statement_line(transaction: &OfxElement) -> Result<StatementLine> {
+    let fitid = required(transaction, "FITID")?;
+    let description = match (transaction.value_of("NAME"), transaction.value_of("MEMO")) {
+        (Some(name), Some(memo)) if name != memo => format!("{name} - {memo}"),
+        (Some(name), _) => name.to_string(),
+        (None, Some(memo)) => memo.to_string(),
+        (None, None) => required(transaction, "TRNTYPE")?.to_string(),
+    };
+
+    Ok(StatementLine {
+        date: parse_date(required(transaction, "DTPOSTED")?)?,
+        amount: parse_amount(required(transaction, "TRNAMT")?)?,
+        description,
+        reference: transaction
+            .value_of("CHECKNUM")
+            .or_else(|| transaction.value_of("REFNUM"))
+            .map(String::from),
+        external_id: format!("ofx:{fitid}"),
+    })
+}
+
+/// Reads a buy, sell or income transaction, `None` for any other kind.
+fn investment_transaction(
+    transaction: &OfxElement,
+    securities: &HashMap<String, OfxSecurity>,
+) -> Result<Option<OfxInvestmentTransaction>> {
+    let (details, kind) = match transaction.name.as_str() {
+        "BUYDEBT" | "BUYMF" | "BUYOPT" | "BUYOTHER" | "BUYSTOCK" => {
+            let details = transaction.child("INVBUY").ok_or_else(|| {
+                Error::InvalidData(format!("{} without INVBUY", transaction.name))
+            })?;
+            let units = parse_amount(required(details, "UNITS")?)?.abs();
+            let total = match details.value_of("TOTAL") {
+                Some(total) => parse_amount(total)?.abs(),
+                None => {
+                    units * parse_amount(required(details, "UNITPRICE")?)?.amount()
+                        + optional_amount(details, "COMMISSION")?
+                        + optional_amount(details, "FEES")?
+                }
+            };
+
+            (details, InvestmentKind::Buy { units, total })
+        }
+        "SELLDEBT" | "SELLMF" | "SELLOPT" | "SELLOTHER" | "SELLSTOCK" => {
+            let details = transaction.child("INVSELL").ok_or_else(|| {
+                Error::InvalidData(format!("{} without INVSELL", transaction.name))
+            })?;
+            let units = parse_amount(required(details, "UNITS")?)?.abs();
+            let total = match details.value_of("TOTAL") {
+                Some(total) => parse_amount(total)?.abs(),
+                None => {
+                    units * parse_amount(required(details, "UNITPRICE")?)?.amount()
+                        - optional_amount(details, "COMMISSION")?
+                        - optional_amount(details, "FEES")?
+                }
+            };
+
+            (details, InvestmentKind::Sell { units, total })
+        }
+        "INCOME" => {
+            let kind = match transaction.value_of("INCOMETYPE") {
+                Some("INTEREST") => IncomeKind::Interest,
+                // Dividends and fund distributions (CGLONG, CGSHORT, MISC)
+                _ => IncomeKind::Dividend,
+            };
+
+            (
+                transaction,
+                InvestmentKind::Income {
+                    kind,
+                    total: parse_amount(required(transaction, "TOTAL")?)?.abs(),
+                    withholding: optional_amount(transaction, "WITHHOLDING")?.abs(),
+                },
+            )
+        }
+        _ => return Ok(None),
+    };
+
+    let details_transaction = details
+        .child("INVTRAN")
+        .ok_or_else(|| Error::InvalidData(format!("{} without INVTRAN", transaction.name)))?;
+    let unique_id = details
+        .find("SECID")
+        .and_then(|secid| secid.value_of("UNIQUEID"))
+        .ok_or_else(|| Error::InvalidData(format!("{} without SECID", transaction.name)))?;
+    let security = securities.get(unique_id).cloned().unwrap_or(OfxSecurity {
+        unique_id: unique_id.to_string(),
+        ticker: None,
+        name: None,
+        asset_type: AssetType::Stock,
+    });
+
+    Ok(Some(OfxInvestmentTransaction {
+        fitid: required(details_transaction, "FITID")?.to_string(),
+        date: parse_date(required(details_transaction, "DTTRADE")?)?,
+        memo: details_transaction.value_of("MEMO").map(String::from),
+        security,
+        kind,
+    }))
+}
+
+/// Securities described in the `SECLIST`, by unique id
+fn parse_securities(root: &OfxElement) -> HashMap<String, OfxSecurity> {
+    let Some(list) = root.find("SECLIST") else {
+        return HashMap::new();
+    };
+
+    list.children
+        .iter()
+        .filter_map(|info| {
+            let asset_type = match info.name.as_str() {
+                "DEBTINFO" => AssetType::Bond,
+                "MFINFO" => AssetType::Etf,
+                _ => AssetType::Stock,
+            };
+            let secinfo = info.child("SECINFO")?;
+            let unique_id = secinfo.find("SECID")?.value_of("UNIQUEID")?;
+
+            Some((
+                unique_id.to_string(),
+                OfxSecurity {
+                    unique_id: unique_id.to_string(),
+                    ticker: secinfo.value_of("TICKER").map(String::from),
+                    name: secinfo.value_of("SECNAME").map(String::from),
+                    asset_type,
+                },
+            ))
+        })
+        .collect()
+}
+
+fn required<'a>(element: &'a OfxElement, name: &str) -> Result<&'a str> {
+    element
+        .value_of(name)
+        .ok_or_else(|| Error::InvalidData(format!("{} without {name}", element.name)))
+}
+
+fn optional_amount(element: &OfxElement, name: &str) -> Result<Money> {
+    element
+        .value_of(name)
+        .map(parse_amount)
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+/// OFX dates are `YYYYMMDD`, optionally followed by a time and a time zone
+fn parse_date(value: &str) -> Result<NaiveDate> {
+    value
+        .get(..8)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+        .ok_or_else(|| Error::InvalidData(format!("invalid OFX date {value:?}")))
+}
+
+/// OFX amounts use a dot, or in some locales a comma, as the decimal separator
+fn parse_amount(value: &str) -> Result<Money> {
+    let normalized = value.trim().trim_start_matches('+').replace(',', ".");
+    normalized
+        .parse::<Money>()
+        .map(|money| Money::new(money.amount().normalize()))
+        .map_err(|_| Error::InvalidData(format!("invalid OFX amount {value:?}")))
+}
+
+/// An OFX aggregate, or an element with a value
+#[derive(Debug, Clone, Default, PartialEq)]
+struct OfxElement {
+    name: String,
+    value: Option<String>,
+    children: Vec<OfxElement>,
+}
+
+impl OfxElement {
+    fn child(&self, name: &str) -> Option<&OfxElement> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children_named(&self, name: &str) -> Vec<&OfxElement> {
+        self.children
+            .iter()
+            .filter(|child| child.name == name)
+            .collect()
+    }
+
+    fn value_of(&self, name: &str) -> Option<&str> {
+        self.child(name)?.value.as_deref()
+    }
+
+    /// First element named `name` below this one, depth first
+    fn find(&self, name: &str) -> Option<&OfxElement> {
+        self.children.iter().find_map(|child| {
+            (child.name == name)
+                .then_some(child)
+                .or_else(|| child.find(name))
+        })
+    }
+
+    /// Every element named `name` below this one, without looking inside matches
+    fn descendants(&self, name: &str) -> Vec<&OfxElement> {
+        let mut found = Vec::new();
+        for child in &self.children {
+            if child.name == name {
+                found.push(child);
+            } else {
+                found.extend(child.descendants(name));
+            }
+        }
+        found
+    }
+}
+
+/// Builds the element tree of an OFX document.
+///
+/// OFX 1.x is SGML where elements holding a value are never closed, while
+/// OFX 2.x is XML. Both are handled by treating a tag followed by text as a
+/// value element, ignoring its closing tag if there is one, and any other tag
+/// as an aggregate that lasts until its closing tag.
+fn parse_elements(input: &str) -> Result<OfxElement> {
+    let start = input
+        .find("<OFX>")
+        .ok_or_else(|| Error::InvalidData("not an OFX document".into()))?;
+
+    let mut stack = vec![OfxElement::default()];
+    let mut rest = &input[start..];
+
+    while let Some(open) = rest.find('<') {
+        let close = rest[open..]
+            .find('>')
+            .map(|close| open + close)
+            .ok_or_else(|| Error::InvalidData("unterminated OFX tag".into()))?;
+        let tag = rest[open + 1..close].trim();
+        rest = &rest[close + 1..];
+
+        // XML declaration, OFX processing instruction or comment
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim().to_uppercase();
+            if stack.iter().skip(1).any(|element| element.name == name) {
+                while let Some(element) = stack.pop() {
+                    let done = element.name == name;
+                    stack
+                        .last_mut()
+                        .expect("the document element is never closed")
+                        .children
+                        .push(element);
+                    if done {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+
+        let (name, empty) = match tag.strip_suffix('/') {
+            Some(name) => (name.trim().to_uppercase(), true),
+            None => (tag.to_uppercase(), false),
+        };
+        let text_end = rest.find('<').unwrap_or(rest.len());
+        let text = decode_entities(rest[..text_end].trim());
+        rest = &rest[text_end..];
+
+        let top = stack
+            .last_mut()
+            .expect("the document element is never closed");
+        if empty {
+            top.children.push(OfxElement {
+                name,
+                ..Default::default()
+            });
+        } else if !text.is_empty() {
+            top.children.push(OfxElement {
+                name,
+                value: Some(text),
+                children: Vec::new(),
+            });
+        } else {
+            stack.push(OfxElement {
+                name,
+                ..Default::default()
+            });
+        }
+    }
+
+    // Unclosed aggregates in a truncated document
+    while stack.len() > 1 {
+        let element = stack.pop().expect("checked above");
+        stack
+            .last_mut()
+            .expect("checked above")
+            .children
+            .push(element);
+    }
+
+    Ok(stack.pop().expect("the document element is never closed"))
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    const SGML: &str = "OFXHEADER:100\r\nDATA:OFXSGML\r\nVERSION:102\r\n\r\n\
+        <OFX><SIGNONMSGSRSV1><SONRS><STATUS><CODE>0<SEVERITY>INFO</STATUS></SONRS></SIGNONMSGSRSV1>\
+        <BANKMSGSRSV1><STMTTRNRS><STMTRS><CURDEF>EUR\
+        <BANKACCTFROM><BANKID>12345<ACCTID>DE0001<ACCTTYPE>CHECKING</BANKACCTFROM>\
+        <BANKTRANLIST><DTSTART>20250101<DTEND>20250131\
+        <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20250102120000[+1:CET]<TRNAMT>-45,20<FITID>A1<NAME>Supermarket &amp; Co<MEMO>Card 1234</STMTTRN>\
+        <STMTTRN><TRNTYPE>CHECK<DTPOSTED>20250110<TRNAMT>-100.00<FITID>A2<CHECKNUM>1001<NAME>Check</STMTTRN>\
+        </BANKTRANLIST><LEDGERBAL><BALAMT>1000.00<DTASOF>20250131</LEDGERBAL></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>";
+
+    #[test]
+    fn test_parse_sgml_bank_statement() {
+        let document = parse_ofx(SGML).unwrap();
+        assert!(document.investment_statements.is_empty());
+
+        let statement = &document.statements[0];
+        assert_eq!(statement.account_id, "DE0001");
+        assert_eq!(statement.currency, "EUR");
+        assert_eq!(
+            statement.lines[0],
+            StatementLine {
+                date: NaiveDate::from_ymd_opt(2025, 1, 2).unwrap(),
+                amount: Money::new(dec!(-45.2)),
+                description: "Supermarket & Co - Card 1234".into(),
+                reference: None,
+                external_id: "ofx:A1".into(),
+            }
+        );
+        assert_eq!(statement.lines[1].reference.as_deref(), Some("1001"));
+    }
+
+    #[test]
+    fn test_parse_xml_investment_statement() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <?OFX OFXHEADER="200" VERSION="220"?>
+            <OFX>
+              <INVSTMTMSGSRSV1><INVSTMTTRNRS><INVSTMTRS>
+                <CURDEF>USD</CURDEF>
+                <INVACCTFROM><BROKERID>broker.example</BROKERID><ACCTID>U123</ACCTID></INVACCTFROM>
+                <INVTRANLIST>
+                  <DTSTART>20250101</DTSTART><DTEND>20250630</DTEND>
+                  <BUYSTOCK>
+                    <INVBUY>
+                      <INVTRAN><FITID>B1</FITID><DTTRADE>20250110</DTTRADE></INVTRAN>
+                      <SECID><UNIQUEID>US0378331005</UNIQUEID><UNIQUEIDTYPE>ISIN</UNIQUEIDTYPE></SECID>
+                      <UNITS>10</UNITS><UNITPRICE>150.00</UNITPRICE><COMMISSION>1.00</COMMISSION>
+                    </INVBUY>
+                    <BUYTYPE>BUY</BUYTYPE>
+                  </BUYSTOCK>
+                  <TRANSFER><INVTRAN><FITID>T1</FITID><DTTRADE>20250111</DTTRADE></INVTRAN></TRANSFER>
+                  <INVBANKTRAN>
+                    <STMTTRN><TRNTYPE>CREDIT</TRNTYPE><DTPOSTED>20250105</DTPOSTED><TRNAMT>5000</TRNAMT><FITID>C1</FITID><NAME>Deposit</NAME></STMTTRN>
+                    <SUBACCTFUND>CASH</SUBACCTFUND>
+                  </INVBANKTRAN>
+                </INVTRANLIST>
+              </INVSTMTRS></INVSTMTTRNRS></INVSTMTMSGSRSV1>
+              <SECLISTMSGSRSV1><SECLIST>
+                <STOCKINFO><SECINFO>
+                  <SECID><UNIQUEID>US0378331005</UNIQUEID><UNIQUEIDTYPE>ISIN</UNIQUEIDTYPE></SECID>
+                  <SECNAME>Apple Inc.</SECNAME><TICKER>AAPL</TICKER>
+                </SECINFO></STOCKINFO>
+              </SECLIST></SECLISTMSGSRSV1>
+            </OFX>"#;
+
+        let document = parse_ofx(xml).unwrap();
+        let statement = &document.investment_statements[0];
+        assert_eq!(statement.account_id, "U123");
+        assert_eq!(statement.unsupported, 1);
+        assert_eq!(statement.cash[0].amount, Money::new(dec!(5000)));
+
+        let buy = &statement.transactions[0];
+        assert_eq!(buy.security.asset_code(), "AAPL");
+        assert_eq!(
+            buy.kind,
+            InvestmentKind::Buy {
+                units: Money::new(dec!(10)),
+                total: Money::new(dec!(1501)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_non_ofx() {
+        assert!(parse_ofx("Date;Amount\n").is_err());
+    }
+}
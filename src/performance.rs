@@ -0,0 +1,582 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use chrono::{Datelike, Days, Months, NaiveDate};
+use rusqlite::named_params;
+use rust_decimal::{
+    prelude::{FromPrimitive, ToPrimitive},
+    Decimal,
+};
+use serde::Serialize;
+
+use crate::{
+    corporate_actions::adjust_position,
+    error::{Error, Result},
+    interface::Database,
+    models::classify_root,
+    money::{Money, Rounding},
+};
+
+/// Roots whose postings are returns of the portfolio (dividends, gains, fees)
+/// rather than money moved into or out of it
+const RETURN_ROOTS: [&str; 2] = ["Income", "Expenses"];
+
+/// Performance figures over a period, with amounts in the reporting asset
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PerformanceSummary {
+    pub start_value: Money,
+    pub end_value: Money,
+    /// Contributions minus withdrawals
+    pub net_contributions: Money,
+    /// End value minus start value and net contributions
+    pub absolute_gain: Money,
+    /// Time-weighted return, `None` if nothing was invested during the period
+    pub time_weighted_return: Option<Decimal>,
+    /// Annualized money-weighted return (XIRR), `None` if it has no solution
+    pub money_weighted_return: Option<Decimal>,
+}
+
+/// Performance of a single asset held in the portfolio
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetPerformance {
+    pub asset: String,
+    /// Quantity held at the end of the period
+    pub quantity: Money,
+    /// Cost of the open lots at the end of the period, `None` if it could not be valued
+    pub cost_basis: Option<Money>,
+    pub summary: PerformanceSummary,
+}
+
+/// Performance of the portfolio over a calendar month (or the part of it in the period)
+#[derive(Debug, Clone, Serialize)]
+pub struct MonthlyPerformance {
+    /// First day of the month
+    pub month: NaiveDate,
+    pub summary: PerformanceSummary,
+}
+
+/// Performance of an account subtree over a period
+#[derive(Debug, Clone, Serialize)]
+pub struct PortfolioPerformance {
+    pub account_number: String,
+    pub reporting_asset: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub total: PerformanceSummary,
+    pub by_asset: Vec<AssetPerformance>,
+    pub by_month: Vec<MonthlyPerformance>,
+}
+
+/// A valuation point: date, value at the end of that day and the external flow
+/// that happened on that day
+type Point = (NaiveDate, Decimal, Decimal);
+
+impl Database {
+    /// Computes the performance of `account_number` and every account below it
+    /// between `from` and `to` (inclusive), valued in `reporting_asset_code`.
+    ///
+    /// Contributions and withdrawals are postings whose counter-accounts are
+    /// outside of the portfolio and are not income or expense accounts, so that
+    /// dividends, realized gains and fees count as returns. Flows are assumed to
+    /// happen at the end of the day they are booked on.
+    pub fn portfolio_performance<S: AsRef<str>>(
+        &self,
+        account_number: S,
+        from: NaiveDate,
+        to: NaiveDate,
+        reporting_asset_code: S,
+    ) -> Result<PortfolioPerformance> {
+        let (account_number, reporting_asset_code) =
+            (account_number.as_ref(), reporting_asset_code.as_ref());
+
+        if from > to {
+            return Err(Error::InvalidData(format!(
+                "period starts after it ends: {from} > {to}"
+            )));
+        }
+
+        if self.account_subtree(account_number)?.is_empty() {
+            return Err(Error::NotFound);
+        }
+
+        let decimals = self.asset_decimals()?;
+        let reporting_decimals = *decimals.get(reporting_asset_code).ok_or(Error::NotFound)?;
+
+        let start = from.pred_opt().unwrap_or(from);
+        let (flows, asset_flows) =
+            self.portfolio_flows(account_number, from, to, reporting_asset_code)?;
+
+        // Value the portfolio on every day with a flow and at the end of every month
+        let mut dates: BTreeSet<NaiveDate> = flows.keys().copied().collect();
+        dates.extend(asset_flows.keys().map(|(_, date)| *date));
+        dates.extend(month_ends(from, to));
+        dates.insert(start);
+        dates.insert(to);
+
+        let quantities = self.portfolio_quantities(account_number, &dates)?;
+        let mut values: BTreeMap<NaiveDate, BTreeMap<String, Decimal>> = BTreeMap::new();
+        let mut assets: BTreeSet<String> = asset_flows.keys().map(|(a, _)| a.clone()).collect();
+        for (&date, held) in &quantities {
+            let mut holdings = BTreeMap::new();
+            for (asset, quantity) in held {
+                let value = quantity * self.unit_value(asset, date, reporting_asset_code)?;
+                holdings.insert(asset.clone(), value);
+            }
+            assets.extend(holdings.keys().cloned());
+            values.insert(date, holdings);
+        }
+
+        let series = |value: &dyn Fn(&BTreeMap<String, Decimal>) -> Decimal,
+                      flow: &dyn Fn(NaiveDate) -> Decimal|
+         -> Vec<Point> {
+            values
+                .iter()
+                .map(|(&date, holdings)| {
+                    let flow = if date == start {
+                        Decimal::ZERO
+                    } else {
+                        flow(date)
+                    };
+                    (date, value(holdings), flow)
+                })
+                .collect()
+        };
+
+        let total = series(&|holdings| holdings.values().sum(), &|date| {
+            flows.get(&date).copied().unwrap_or_default()
+        });
+
+        let quantities = &quantities[&to];
+        let cost_basis = self.portfolio_cost_basis(account_number, to, reporting_asset_code)?;
+
+        let by_asset = assets
+            .into_iter()
+            .map(|asset| {
+                let points = series(
+                    &|holdings| holdings.get(&asset).copied().unwrap_or_default(),
+                    &|date| {
+                        asset_flows
+                            .get(&(asset.clone(), date))
+                            .copied()
+                            .unwrap_or_default()
+                    },
+                );
+
+                AssetPerformance {
+                    quantity: Money::new(quantities.get(&asset).copied().unwrap_or_default())
+                        .round(
+                            decimals.get(&asset).copied().unwrap_or(28),
+                            Rounding::Bankers,
+                        ),
+                    cost_basis: cost_basis.get(&asset).copied().flatten().map(Money::new),
+                    summary: summarize(&points, reporting_decimals),
+                    asset,
+                }
+            })
+            .collect();
+
+        let by_month = month_starts(from, to)
+            .into_iter()
+            .map(|month| {
+                let month_end = month + Months::new(1) - Days::new(1);
+                let month_start = month.max(from);
+                let first = total.iter().rposition(|(date, _, _)| *date < month_start);
+                let last = total.iter().rposition(|(date, _, _)| *date <= month_end);
+
+                let points = match (first, last) {
+                    (Some(first), Some(last)) => &total[first..=last],
+                    _ => &[],
+                };
+
+                MonthlyPerformance {
+                    month,
+                    summary: summarize(points, reporting_decimals),
+                }
+            })
+            .collect();
+
+        Ok(PortfolioPerformance {
+            account_number: account_number.to_string(),
+            reporting_asset: reporting_asset_code.to_string(),
+            from,
+            to,
+            total: summarize(&total, reporting_decimals),
+            by_asset,
+            by_month,
+        })
+    }
+
+    /// Account numbers of `account_number` and all of its descendants
+    pub(crate) fn account_subtree(&self, account_number: &str) -> Result<HashSet<String>> {
+        let mut stmt = self
+            .conn()
+            .prepare(include_str!("sql/account_subtree.sql"))?;
+
+        let accounts = stmt
+            .query_map(named_params! { ":account_number": account_number }, |row| {
+                row.get(0)
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(accounts)
+    }
+
+    /// External flows into the portfolio per day, and the value added to each
+    /// asset per day by everything except returns
+    #[allow(clippy::type_complexity)]
+    fn portfolio_flows(
+        &self,
+        account_number: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+        reporting_asset_code: &str,
+    ) -> Result<(
+        BTreeMap<NaiveDate, Decimal>,
+        BTreeMap<(String, NaiveDate), Decimal>,
+    )> {
+        let mut stmt = self
+            .conn()
+            .prepare(include_str!("sql/portfolio_entry_lines.sql"))?;
+
+        let lines = stmt
+            .query_map(
+                named_params! {
+                    ":account_number": account_number,
+                    ":from": from,
+                    ":to": to,
+                },
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, NaiveDate>(1)?,
+                        row.get::<_, bool>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, String>(4)?,
+                        row.get::<_, String>(5)?,
+                        row.get::<_, Money>(6)?,
+                    ))
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut flows = BTreeMap::new();
+        let mut asset_flows = BTreeMap::new();
+
+        for entry in lines.chunk_by(|a, b| a.0 == b.0) {
+            let date = entry[0].1;
+            let is_return = |account_type: &str| {
+                classify_root(account_type).is_some_and(|root| RETURN_ROOTS.contains(&root))
+            };
+
+            let has_external = entry.iter().any(|line| !line.2 && !is_return(&line.3));
+            let internal_only = entry.iter().all(|line| line.2);
+            if !has_external && !internal_only {
+                // Only income and expense counter-accounts: the entry is a return
+                continue;
+            }
+
+            for (_, _, in_portfolio, account_type, asset, entry_type, amount) in entry {
+                let value = amount.amount() * self.unit_value(asset, date, reporting_asset_code)?;
+                let value = match entry_type.as_str() {
+                    "DEBIT" => value,
+                    _ => -value,
+                };
+
+                if *in_portfolio {
+                    *asset_flows.entry((asset.clone(), date)).or_default() += value;
+                } else if !is_return(account_type) {
+                    // Credits on external accounts fund the portfolio
+                    *flows.entry(date).or_default() -= value;
+                }
+            }
+        }
+
+        Ok((flows, asset_flows))
+    }
+
+    /// Quantity per asset held in the subtree of `account_number` at the end
+    /// of each of `dates`, restated for corporate actions. The daily balances
+    /// of the subtree are read once and carried forward in date order.
+    fn portfolio_quantities(
+        &self,
+        account_number: &str,
+        dates: &BTreeSet<NaiveDate>,
+    ) -> Result<BTreeMap<NaiveDate, BTreeMap<String, Decimal>>> {
+        let Some(&last) = dates.last() else {
+            return Ok(BTreeMap::new());
+        };
+        let actions = self.list_corporate_actions()?;
+        let asset_codes = self.asset_codes()?;
+
+        let mut stmt = self
+            .conn()
+            .prepare(include_str!("sql/portfolio_balances.sql"))?;
+        let balances = stmt
+            .query_map(
+                named_params! { ":account_number": account_number, ":to": last },
+                |row| {
+                    Ok((
+                        row.get::<_, NaiveDate>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, Money>(2)?,
+                    ))
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut balances = balances.into_iter().peekable();
+        let mut actions = actions.iter().peekable();
+        let mut held: BTreeMap<i64, Decimal> = BTreeMap::new();
+        let mut quantities = BTreeMap::new();
+        for &date in dates {
+            loop {
+                let action_date = actions.peek().map(|action| action.date);
+                let day = balances.peek().map(|(day, _, _)| *day);
+                match (action_date, day) {
+                    // An action restates what was held before its day
+                    (Some(action_date), _)
+                        if action_date <= date && day.is_none_or(|day| action_date <= day) =>
+                    {
+                        let action = std::slice::from_ref(actions.next().unwrap());
+                        for (asset_id, quantity) in std::mem::take(&mut held) {
+                            let (asset_id, quantity) = adjust_position(
+                                action,
+                                asset_id,
+                                quantity,
+                                NaiveDate::MIN,
+                                action_date,
+                            );
+                            *held.entry(asset_id).or_default() += quantity;
+                        }
+                    }
+                    (_, Some(day)) if day <= date => {
+                        let (_, asset_id, quantity) = balances.next().unwrap();
+                        *held.entry(asset_id).or_default() += quantity.amount();
+                    }
+                    _ => break,
+                }
+            }
+
+            let mut holdings = BTreeMap::new();
+            for (asset_id, quantity) in held.iter().filter(|(_, q)| !q.is_zero()) {
+                let asset = asset_codes.get(asset_id).cloned().ok_or(Error::NotFound)?;
+                *holdings.entry(asset).or_insert(Decimal::ZERO) += quantity;
+            }
+            quantities.insert(date, holdings);
+        }
+
+        Ok(quantities)
+    }
+
+    /// Cost of the open lots per asset, `None` for assets whose cost could not be valued
+    fn portfolio_cost_basis(
+        &self,
+        account_number: &str,
+        as_of: NaiveDate,
+        reporting_asset_code: &str,
+    ) -> Result<BTreeMap<String, Option<Decimal>>> {
+        let mut stmt = self
+            .conn()
+            .prepare(include_str!("sql/portfolio_cost_basis.sql"))?;
+
+        let rows = stmt
+            .query_map(
+                named_params! { ":account_number": account_number, ":as_of": as_of },
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, Money>(2)?,
+                    ))
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut cost_basis: BTreeMap<String, Option<Decimal>> = BTreeMap::new();
+        for (asset, cost_asset, cost) in rows {
+            let value = self
+                .unit_value_at(cost_asset.as_str(), as_of, reporting_asset_code)?
+                .map(|rate| cost.amount() * rate);
+            let total = cost_basis.entry(asset).or_insert(Some(Decimal::ZERO));
+            *total = total.zip(value).map(|(total, value)| total + value);
+        }
+
+        Ok(cost_basis)
+    }
+
+    fn unit_value(
+        &self,
+        asset: &str,
+        date: NaiveDate,
+        reporting_asset_code: &str,
+    ) -> Result<Decimal> {
+        self.unit_value_at(asset, date, reporting_asset_code)?
+            .ok_or_else(|| {
+                Error::InvalidData(format!(
+                    "cannot value {asset} in {reporting_asset_code} on {date}"
+                ))
+            })
+    }
+}
+
+/// Summarizes `points`, rounding amounts to the `decimals` of the reporting asset
+fn summarize(points: &[Point], decimals: u32) -> PerformanceSummary {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return PerformanceSummary {
+            start_value: Money::new(Decimal::ZERO),
+            end_value: Money::new(Decimal::ZERO),
+            net_contributions: Money::new(Decimal::ZERO),
+            absolute_gain: Money::new(Decimal::ZERO),
+            time_weighted_return: None,
+            money_weighted_return: None,
+        };
+    };
+
+    let net_contributions: Decimal = points[1..].iter().map(|(_, _, flow)| flow).sum();
+
+    // Chain the returns of the sub-periods between two flows
+    let mut growth = Decimal::ONE;
+    let mut invested = false;
+    for window in points.windows(2) {
+        let ((_, previous, _), (_, value, flow)) = (window[0], window[1]);
+        if !previous.is_zero() {
+            growth *= (value - flow) / previous;
+            invested = true;
+        }
+    }
+
+    let round = |value: Decimal| Money::new(value).round(decimals, Rounding::Bankers);
+    let mut cash_flows = vec![(first.0, -first.1)];
+    cash_flows.extend(points[1..].iter().map(|&(date, _, flow)| (date, -flow)));
+    cash_flows.push((last.0, last.1));
+
+    PerformanceSummary {
+        start_value: round(first.1),
+        end_value: round(last.1),
+        net_contributions: round(net_contributions),
+        absolute_gain: round(last.1 - first.1 - net_contributions),
+        time_weighted_return: invested.then(|| (growth - Decimal::ONE).round_dp(8)),
+        money_weighted_return: xirr(&cash_flows),
+    }
+}
+
+/// Solves the annualized rate at which the net present value of `cash_flows`
+/// is zero, using bisection. Returns `None` unless there are both inflows and
+/// outflows on different dates.
+fn xirr(cash_flows: &[(NaiveDate, Decimal)]) -> Option<Decimal> {
+    let flows = cash_flows
+        .iter()
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(date, amount)| Some((*date, amount.to_f64()?)))
+        .collect::<Option<Vec<_>>>()?;
+
+    let first = flows.iter().map(|(date, _)| *date).min()?;
+    let last = flows.iter().map(|(date, _)| *date).max()?;
+    if first == last
+        || !flows.iter().any(|(_, amount)| *amount > 0.0)
+        || !flows.iter().any(|(_, amount)| *amount < 0.0)
+    {
+        return None;
+    }
+
+    let npv = |rate: f64| -> f64 {
+        flows
+            .iter()
+            .map(|(date, amount)| {
+                let years = (*date - first).num_days() as f64 / 365.0;
+                amount / (1.0 + rate).powf(years)
+            })
+            .sum()
+    };
+
+    let (mut low, mut high) = (-0.999_999_9, 1.0);
+    while npv(low).signum() == npv(high).signum() {
+        high *= 2.0;
+        if high > 1e9 {
+            return None;
+        }
+    }
+
+    for _ in 0..200 {
+        let mid = (low + high) / 2.0;
+        if npv(mid).signum() == npv(low).signum() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    Decimal::from_f64((low + high) / 2.0).map(|rate| rate.round_dp(6))
+}
+
+fn month_starts(from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+    let mut month = from.with_day(1).unwrap_or(from);
+    let mut months = Vec::new();
+    while month <= to {
+        months.push(month);
+        month = month + Months::new(1);
+    }
+
+    months
+}
+
+fn month_ends(from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+    month_starts(from, to)
+        .into_iter()
+        .map(|month| month + Months::new(1) - Days::new(1))
+        .filter(|end| *end >= from && *end <= to)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_xirr_one_year() {
+        let flows = [
+            (date(2024, 1, 1), dec!(-1000)),
+            (date(2024, 12, 31), dec!(1100)),
+        ];
+        assert_eq!(xirr(&flows), Some(dec!(0.1)));
+    }
+
+    #[test]
+    fn test_xirr_needs_inflows_and_outflows() {
+        assert_eq!(xirr(&[(date(2024, 1, 1), dec!(-1000))]), None);
+        assert_eq!(
+            xirr(&[
+                (date(2024, 1, 1), dec!(-1000)),
+                (date(2024, 1, 1), dec!(1000))
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_time_weighted_return_ignores_flows() {
+        // +10% on 1000, then 1000 more is contributed, then -10% on 2100
+        let points = [
+            (date(2024, 1, 31), dec!(1000), dec!(0)),
+            (date(2024, 2, 15), dec!(2100), dec!(1000)),
+            (date(2024, 2, 29), dec!(1890), dec!(0)),
+        ];
+        let summary = summarize(&points, 2);
+
+        assert_eq!(summary.time_weighted_return, Some(dec!(-0.01)));
+        assert_eq!(summary.net_contributions, Money::new(dec!(1000)));
+        assert_eq!(summary.absolute_gain, Money::new(dec!(-110)));
+    }
+
+    #[test]
+    fn test_month_ends() {
+        assert_eq!(
+            month_ends(date(2024, 1, 15), date(2024, 3, 30)),
+            vec![date(2024, 1, 31), date(2024, 2, 29)]
+        );
+    }
+}
@@ -0,0 +1,523 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::Write,
+};
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::{
+    error::Result,
+    interface::Database,
+    models::{classify_root, parse_column, EntryStatus, NormalBalance},
+    money::Money,
+};
+
+/// Plain-text accounting syntaxes the book can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlainTextFormat {
+    Beancount,
+    /// ledger, also read by hledger
+    Ledger,
+}
+
+impl Database {
+    /// Writes the whole book in beancount syntax. See [`Database::export_plain_text`].
+    pub fn export_beancount<W: Write>(&self, writer: W) -> Result<()> {
+        self.export_plain_text(PlainTextFormat::Beancount, writer)
+    }
+
+    /// Writes the whole book in ledger syntax, which hledger reads as well.
+    /// See [`Database::export_plain_text`].
+    pub fn export_ledger<W: Write>(&self, writer: W) -> Result<()> {
+        self.export_plain_text(PlainTextFormat::Ledger, writer)
+    }
+
+    /// Writes commodities, accounts with their opening and closing dates,
+    /// prices and every journal entry to `writer`.
+    ///
+    /// Account names are built from the account hierarchy below a root named
+    /// after the account type (`Assets`, `Liabilities`, `Equity`, `Income`,
+    /// `Expenses`). Posted entries are cleared (`*`), drafts pending (`!`) and
+    /// void entries are written commented out. Lines in another asset than the
+    /// entry's reference asset are priced at their reference amount.
+    ///
+    /// The output only depends on the contents of the book: everything is
+    /// sorted, so exports of the same data are identical and diff cleanly.
+    pub fn export_plain_text<W: Write>(
+        &self,
+        format: PlainTextFormat,
+        mut writer: W,
+    ) -> Result<()> {
+        let book = self.load_book(format)?;
+        let w = &mut writer;
+
+        let first_date = book
+            .accounts
+            .iter()
+            .map(|account| account.opening_date)
+            .min()
+            .unwrap_or_default();
+
+        for asset in &book.assets {
+            let code = commodity(format, &asset.code);
+            match format {
+                PlainTextFormat::Beancount => {
+                    writeln!(w, "{first_date} commodity {code}")?;
+                    writeln!(w, "  name: {}", quoted(&asset.name))?;
+                }
+                PlainTextFormat::Ledger => {
+                    writeln!(w, "commodity {code}")?;
+                    writeln!(w, "    ; name: {}", asset.name)?;
+                    writeln!(
+                        w,
+                        "    format {} {code}",
+                        number(Money::default(), asset.decimals)
+                    )?;
+                }
+            }
+        }
+        writeln!(w)?;
+
+        for account in &book.accounts {
+            match format {
+                PlainTextFormat::Beancount => {
+                    writeln!(w, "{} open {}", account.opening_date, account.name)?;
+                    writeln!(w, "  number: {}", quoted(&account.number))?;
+                    if let Some(description) = &account.description {
+                        writeln!(w, "  description: {}", quoted(description))?;
+                    }
+                }
+                PlainTextFormat::Ledger => {
+                    writeln!(w, "account {}", account.name)?;
+                    writeln!(w, "    ; number: {}", account.number)?;
+                    writeln!(w, "    ; opened: {}", account.opening_date)?;
+                    if let Some(closing_date) = account.closing_date {
+                        writeln!(w, "    ; closed: {closing_date}")?;
+                    }
+                    if let Some(description) = &account.description {
+                        writeln!(w, "    ; description: {description}")?;
+                    }
+                }
+            }
+        }
+        if format == PlainTextFormat::Beancount {
+            for account in &book.accounts {
+                if let Some(closing_date) = account.closing_date {
+                    writeln!(w, "{closing_date} close {}", account.name)?;
+                }
+            }
+        }
+        writeln!(w)?;
+
+        for (date, base, rate, quote) in &book.prices {
+            let (base, quote) = (commodity(format, base), commodity(format, quote));
+            match format {
+                PlainTextFormat::Beancount => writeln!(w, "{date} price {base} {rate} {quote}")?,
+                PlainTextFormat::Ledger => writeln!(w, "P {date} {base} {rate} {quote}")?,
+            }
+        }
+        if !book.prices.is_empty() {
+            writeln!(w)?;
+        }
+
+        for entry in &book.entries {
+            let mut text = Vec::new();
+            write_entry(&mut text, format, &book, entry)?;
+            let text = String::from_utf8(text).expect("written from strings");
+
+            for line in text.lines() {
+                match entry.status {
+                    EntryStatus::Void => writeln!(w, "; {line}")?,
+                    _ => writeln!(w, "{line}")?,
+                }
+            }
+            writeln!(w)?;
+        }
+
+        Ok(())
+    }
+
+    fn load_book(&self, format: PlainTextFormat) -> Result<Book> {
+        let conn = self.conn();
+
+        let assets = conn
+            .prepare("SELECT id, code, name, decimals FROM assets ORDER BY code")?
+            .query_map([], |row| {
+                Ok(BookAsset {
+                    id: row.get(0)?,
+                    code: row.get(1)?,
+                    name: row.get(2)?,
+                    decimals: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let rows = conn
+            .prepare(
+                "SELECT a.id, a.account_number, a.name, a.parent_account_id, a.opening_date,
+                        a.closing_date, a.description, at.name, at.normal_balance
+                 FROM accounts a
+                 JOIN account_types at ON at.id = a.account_type_id
+                 ORDER BY a.account_number",
+            )?
+            .query_map([], |row| {
+                Ok(AccountRow {
+                    id: row.get(0)?,
+                    number: row.get(1)?,
+                    name: row.get(2)?,
+                    parent_id: row.get(3)?,
+                    opening_date: row.get(4)?,
+                    closing_date: row.get(5)?,
+                    description: row.get(6)?,
+                    account_type: row.get(7)?,
+                    normal_balance: parse_column(row, 8)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let accounts = account_names(format, &rows);
+
+        let mut prices = BTreeSet::new();
+        let mut stmt = conn.prepare(
+            "SELECT date(er.date), f.code, er.rate, t.code
+             FROM exchange_rates er
+             JOIN assets f ON f.id = er.from_asset_id
+             JOIN assets t ON t.id = er.to_asset_id
+             UNION
+             SELECT date(p.date), a.code, p.close, q.code
+             FROM asset_prices p
+             JOIN assets a ON a.id = p.asset_id
+             JOIN assets q ON q.id = p.quote_asset_id",
+        )?;
+        for price in stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, NaiveDate>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Money>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })? {
+            let (date, base, rate, quote) = price?;
+            prices.insert((date, base, rate.amount().normalize().to_string(), quote));
+        }
+
+        let mut entries = conn
+            .prepare(
+                "SELECT je.id, date(je.date), je.description, je.reference_number, je.status,
+                        a.code
+                 FROM journal_entries je
+                 LEFT JOIN assets a ON a.id = je.reference_asset_id
+                 ORDER BY je.date, je.id",
+            )?
+            .query_map([], |row| {
+                Ok(BookEntry {
+                    id: row.get(0)?,
+                    date: row.get(1)?,
+                    description: row.get(2)?,
+                    reference_number: row.get(3)?,
+                    status: parse_column(row, 4)?,
+                    reference_asset: row.get(5)?,
+                    lines: Vec::new(),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT journal_entry_id, account_id, asset_id, entry_type, amount, reference_amount,
+                    description
+             FROM journal_entry_lines
+             ORDER BY journal_entry_id, id",
+        )?;
+        let mut lines: HashMap<i64, Vec<BookLine>> = HashMap::new();
+        for line in stmt.query_map([], |row| {
+            let entry_type: String = row.get(3)?;
+            let amount: Money = row.get(4)?;
+            Ok((
+                row.get::<_, i64>(0)?,
+                BookLine {
+                    account_id: row.get(1)?,
+                    asset_id: row.get(2)?,
+                    amount: match entry_type.as_str() {
+                        "DEBIT" => amount,
+                        _ => -amount,
+                    },
+                    reference_amount: row.get(5)?,
+                    description: row.get(6)?,
+                },
+            ))
+        })? {
+            let (journal_entry_id, line) = line?;
+            lines.entry(journal_entry_id).or_default().push(line);
+        }
+        for entry in &mut entries {
+            entry.lines = lines.remove(&entry.id).unwrap_or_default();
+        }
+
+        Ok(Book {
+            assets,
+            accounts,
+            prices: prices.into_iter().collect(),
+            entries,
+        })
+    }
+}
+
+struct Book {
+    assets: Vec<BookAsset>,
+    accounts: Vec<BookAccount>,
+    prices: Vec<(NaiveDate, String, String, String)>,
+    entries: Vec<BookEntry>,
+}
+
+struct BookAsset {
+    id: i64,
+    code: String,
+    name: String,
+    decimals: u32,
+}
+
+struct AccountRow {
+    id: i64,
+    number: String,
+    name: String,
+    parent_id: Option<i64>,
+    opening_date: NaiveDate,
+    closing_date: Option<NaiveDate>,
+    description: Option<String>,
+    account_type: String,
+    normal_balance: NormalBalance,
+}
+
+struct BookAccount {
+    id: i64,
+    number: String,
+    /// Full name, e.g. `Assets:Cash-and-Bank:Main-Checking-Account`
+    name: String,
+    opening_date: NaiveDate,
+    closing_date: Option<NaiveDate>,
+    description: Option<String>,
+}
+
+struct BookEntry {
+    id: i64,
+    date: NaiveDate,
+    description: String,
+    reference_number: Option<String>,
+    status: EntryStatus,
+    reference_asset: Option<String>,
+    lines: Vec<BookLine>,
+}
+
+struct BookLine {
+    account_id: i64,
+    asset_id: i64,
+    /// Positive for debits
+    amount: Money,
+    reference_amount: Option<Money>,
+    description: Option<String>,
+}
+
+fn write_entry<W: Write>(
+    w: &mut W,
+    format: PlainTextFormat,
+    book: &Book,
+    entry: &BookEntry,
+) -> Result<()> {
+    let flag = match entry.status {
+        EntryStatus::Draft => "!",
+        EntryStatus::Posted | EntryStatus::Void => "*",
+    };
+
+    match format {
+        PlainTextFormat::Beancount => {
+            writeln!(w, "{} {flag} {}", entry.date, quoted(&entry.description))?;
+            if let Some(reference) = &entry.reference_number {
+                writeln!(w, "  reference: {}", quoted(reference))?;
+            }
+            if entry.status == EntryStatus::Void {
+                writeln!(w, "  status: \"void\"")?;
+            }
+        }
+        PlainTextFormat::Ledger => {
+            let code = match &entry.reference_number {
+                Some(reference) => format!(" ({reference})"),
+                None => String::new(),
+            };
+            writeln!(w, "{} {flag}{code} {}", entry.date, entry.description)?;
+            if entry.status == EntryStatus::Void {
+                writeln!(w, "    ; status: void")?;
+            }
+        }
+    }
+
+    let indent = match format {
+        PlainTextFormat::Beancount => "  ",
+        PlainTextFormat::Ledger => "    ",
+    };
+    for line in &entry.lines {
+        let account = book
+            .accounts
+            .iter()
+            .find(|account| account.id == line.account_id)
+            .map_or("", |account| account.name.as_str());
+        let asset = book.assets.iter().find(|asset| asset.id == line.asset_id);
+        let (code, decimals) = asset.map_or(("", 0), |asset| (asset.code.as_str(), asset.decimals));
+
+        let mut posting = format!(
+            "{indent}{account}  {} {}",
+            number(line.amount, decimals),
+            commodity(format, code)
+        );
+        if let (Some(reference_amount), Some(reference_asset)) =
+            (line.reference_amount, &entry.reference_asset)
+        {
+            if reference_asset != code {
+                let reference_decimals = book
+                    .assets
+                    .iter()
+                    .find(|asset| &asset.code == reference_asset)
+                    .map_or(0, |asset| asset.decimals);
+                posting.push_str(&format!(
+                    " @@ {} {}",
+                    number(reference_amount.abs(), reference_decimals),
+                    commodity(format, reference_asset)
+                ));
+            }
+        }
+
+        match (&line.description, format) {
+            (Some(description), PlainTextFormat::Beancount) => {
+                writeln!(w, "{posting}")?;
+                writeln!(w, "{indent}  memo: {}", quoted(description))?;
+            }
+            (Some(description), PlainTextFormat::Ledger) => {
+                writeln!(w, "{posting}  ; {description}")?
+            }
+            (None, _) => writeln!(w, "{posting}")?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Full names of the accounts, in account number order.
+///
+/// Top level accounts stand for the root of their type and their descendants
+/// are named by their path below it. Beancount names only allow letters,
+/// digits and dashes, so other characters are replaced; names that end up
+/// the same get their account number appended.
+fn account_names(format: PlainTextFormat, rows: &[AccountRow]) -> Vec<BookAccount> {
+    let by_id: HashMap<i64, &AccountRow> = rows.iter().map(|row| (row.id, row)).collect();
+
+    let component = |name: &str| match format {
+        PlainTextFormat::Beancount => {
+            let mut component = String::new();
+            for c in name.chars() {
+                match c {
+                    c if c.is_ascii_alphanumeric() => component.push(c),
+                    _ if !component.ends_with('-') => component.push('-'),
+                    _ => {}
+                }
+            }
+            let component = component.trim_matches('-');
+            let mut chars = component.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => "X".to_string(),
+            }
+        }
+        // Colons separate components, and two spaces end the account name
+        PlainTextFormat::Ledger => name
+            .replace(':', "-")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+
+    let mut names: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let mut path = Vec::new();
+            let mut current = row;
+            while let Some(parent) = current.parent_id.and_then(|id| by_id.get(&id)) {
+                path.push(component(&current.name));
+                current = parent;
+            }
+            path.push(root_name(&current.account_type, current.normal_balance).to_string());
+            path.reverse();
+            path.join(":")
+        })
+        .collect();
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for name in &names {
+        *counts.entry(name.clone()).or_default() += 1;
+    }
+    for (name, row) in names.iter_mut().zip(rows) {
+        if counts[name.as_str()] > 1 {
+            let separator = match format {
+                PlainTextFormat::Beancount => "-",
+                PlainTextFormat::Ledger => " ",
+            };
+            *name = format!("{name}{separator}{}", row.number);
+        }
+    }
+
+    rows.iter()
+        .zip(names)
+        .map(|(row, name)| BookAccount {
+            id: row.id,
+            number: row.number.clone(),
+            name,
+            opening_date: row.opening_date,
+            closing_date: row.closing_date,
+            description: row.description.clone(),
+        })
+        .collect()
+}
+
+/// The plain-text root for an account type
+pub(crate) fn root_name(account_type: &str, normal_balance: NormalBalance) -> &'static str {
+    classify_root(account_type).unwrap_or(match normal_balance {
+        NormalBalance::Debit => "Assets",
+        NormalBalance::Credit => "Liabilities",
+    })
+}
+
+/// An asset code as a commodity name: beancount only allows capital
+/// letters, digits and `'._-`, ledger needs anything else quoted
+fn commodity(format: PlainTextFormat, code: &str) -> String {
+    match format {
+        PlainTextFormat::Beancount => {
+            let commodity: String = code
+                .to_uppercase()
+                .chars()
+                .map(|c| match c {
+                    'A'..='Z' | '0'..='9' | '\'' | '.' | '_' | '-' => c,
+                    _ => '-',
+                })
+                .collect();
+            match commodity.starts_with(|c: char| c.is_ascii_uppercase()) {
+                true => commodity,
+                false => format!("X{commodity}"),
+            }
+        }
+        PlainTextFormat::Ledger => match code.chars().all(char::is_alphabetic) {
+            true => code.to_string(),
+            false => format!("\"{}\"", code.replace('"', "")),
+        },
+    }
+}
+
+/// An amount with at least the asset's number of decimals
+fn number(money: Money, decimals: u32) -> String {
+    let mut value: Decimal = money.amount().normalize();
+    if value.scale() < decimals {
+        value.rescale(decimals);
+    }
+    value.to_string()
+}
+
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,1763 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    io::Read,
+    str::FromStr,
+};
+
+use chrono::{NaiveDate, NaiveTime, Utc};
+use rusqlite::{named_params, params, Connection, OptionalExtension};
+use rust_decimal::Decimal;
+
+use crate::{
+    error::{Error, Result},
+    import::{ensure_asset, next_child_number},
+    interface::Database,
+    journal::{account_id_of, asset_id_of, insert_journal_entry},
+    lots::{close_lots_fifo, insert_lot},
+    models::{
+        classify_root, AssetType, EntryStatus, NewJournalEntry, NewJournalEntryLine, NormalBalance,
+    },
+    money::{Money, Rounding},
+    plain_text::{root_name, PlainTextFormat},
+};
+
+/// Outcome of importing a plain-text journal
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlainTextImport {
+    /// Numbers of the accounts created, including parents created on the way
+    pub accounts: Vec<String>,
+    /// Codes of the assets created
+    pub assets: Vec<String>,
+    /// Number of prices recorded as exchange rates
+    pub prices: usize,
+    /// Entries created, in date order
+    pub entry_ids: Vec<i64>,
+    /// Everything in the journal that was left out of the book
+    pub warnings: Vec<ImportWarning>,
+}
+
+/// A directive, or part of one, that the book has no room for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportWarning {
+    /// Line of the journal it was found on, starting at 1
+    pub line: usize,
+    /// The directive's keyword, such as `balance` or `include`, or `posting`
+    pub directive: String,
+    pub message: String,
+}
+
+impl Database {
+    /// Imports a beancount ledger. See [`Database::import_plain_text`].
+    pub fn import_beancount<R: Read>(&mut self, reader: R) -> Result<PlainTextImport> {
+        self.import_plain_text(PlainTextFormat::Beancount, reader)
+    }
+
+    /// Imports a ledger or hledger journal. See [`Database::import_plain_text`].
+    pub fn import_ledger<R: Read>(&mut self, reader: R) -> Result<PlainTextImport> {
+        self.import_plain_text(PlainTextFormat::Ledger, reader)
+    }
+
+    /// Imports the accounts, commodities, prices and transactions of a
+    /// plain-text journal.
+    ///
+    /// Accounts are matched to the chart of accounts by their `number`
+    /// metadata, or else by name below the account of their type; missing
+    /// ones are created. Unknown commodities become assets and prices become
+    /// exchange rates. Cleared and unmarked transactions are posted, pending
+    /// ones (`!`) are drafts. Postings held at cost open a lot when they add
+    /// units and close lots first in, first out when they remove them.
+    ///
+    /// Directives without a counterpart in the book, such as balance
+    /// assertions, `pad` or automated transactions, are skipped and reported
+    /// as warnings. Syntax errors and transactions that do not balance abort
+    /// the import, leaving the book untouched.
+    pub fn import_plain_text<R: Read>(
+        &mut self,
+        format: PlainTextFormat,
+        mut reader: R,
+    ) -> Result<PlainTextImport> {
+        let mut input = String::new();
+        reader.read_to_string(&mut input)?;
+
+        let (mut directives, warnings) = parse_journal(format, &input)?;
+        // Undated declarations first, then in date order so lots are
+        // opened before they are closed
+        directives.sort_by_key(|directive| (directive.date(), directive.rank()));
+
+        let mut import = Importer::new(format, &directives);
+        import.result.warnings = warnings;
+
+        self.snapshot_before("import")?;
+        let t = self.transaction()?;
+        for directive in &directives {
+            import.apply(&t, directive)?;
+        }
+        t.commit()?;
+
+        Ok(import.result)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Directive {
+    line: usize,
+    kind: DirectiveKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum DirectiveKind {
+    Open(AccountDeclaration),
+    Close {
+        date: NaiveDate,
+        account: String,
+    },
+    Commodity(CommodityDeclaration),
+    Price {
+        date: NaiveDate,
+        base: String,
+        rate: Amount,
+    },
+    Transaction(JournalTransaction),
+}
+
+impl Directive {
+    fn date(&self) -> Option<NaiveDate> {
+        match &self.kind {
+            DirectiveKind::Open(account) => account.date,
+            DirectiveKind::Close { date, .. } | DirectiveKind::Price { date, .. } => Some(*date),
+            DirectiveKind::Commodity(_) => None,
+            DirectiveKind::Transaction(transaction) => Some(transaction.date),
+        }
+    }
+
+    /// Order of directives on the same day
+    fn rank(&self) -> u8 {
+        match &self.kind {
+            DirectiveKind::Commodity(_) => 0,
+            DirectiveKind::Open(_) => 1,
+            DirectiveKind::Price { .. } => 2,
+            DirectiveKind::Transaction(_) => 3,
+            DirectiveKind::Close { .. } => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct AccountDeclaration {
+    account: String,
+    /// Missing from ledger `account` directives
+    date: Option<NaiveDate>,
+    number: Option<String>,
+    description: Option<String>,
+    closing_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct CommodityDeclaration {
+    code: String,
+    name: Option<String>,
+    decimals: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct JournalTransaction {
+    date: NaiveDate,
+    status: EntryStatus,
+    description: String,
+    reference: Option<String>,
+    postings: Vec<Posting>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Posting {
+    line: usize,
+    account: String,
+    /// Missing when the posting takes whatever balances the transaction
+    amount: Option<Amount>,
+    cost: Option<Cost>,
+    price: Option<Price>,
+    memo: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Amount {
+    number: Decimal,
+    commodity: String,
+}
+
+/// A `{...}` cost annotation
+#[derive(Debug, Clone, PartialEq)]
+struct Cost {
+    /// Missing in `{}`, which sells from whatever lots are held
+    amount: Option<Amount>,
+    /// `{{...}}`: the cost of all units rather than of one
+    total: bool,
+    date: Option<NaiveDate>,
+}
+
+/// An `@` or `@@` price annotation
+#[derive(Debug, Clone, PartialEq)]
+struct Price {
+    amount: Amount,
+    /// `@@`: the price of all units rather than of one
+    total: bool,
+}
+
+fn invalid(line: usize, message: impl Display) -> Error {
+    Error::InvalidData(format!("line {line}: {message}"))
+}
+
+fn warning(line: usize, directive: &str, message: impl Display) -> ImportWarning {
+    ImportWarning {
+        line,
+        directive: directive.to_string(),
+        message: message.to_string(),
+    }
+}
+
+/// A line starting in the first column and the indented lines below it
+struct Block<'a> {
+    line: usize,
+    header: &'a str,
+    body: Vec<(usize, &'a str)>,
+}
+
+fn blocks(format: PlainTextFormat, input: &str) -> Vec<Block<'_>> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut in_comment = false;
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        let number = index + 1;
+
+        if in_comment {
+            in_comment = !matches!(line.trim(), "end comment" | "end test");
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with(char::is_whitespace) {
+            if let Some(block) = blocks.last_mut() {
+                block.body.push((number, line));
+            }
+            continue;
+        }
+        // Comments, and org-mode headings in beancount files
+        if line.starts_with([';', '#', '%', '|', '*']) {
+            continue;
+        }
+        if format == PlainTextFormat::Ledger && matches!(line.trim(), "comment" | "test") {
+            in_comment = true;
+            continue;
+        }
+
+        blocks.push(Block {
+            line: number,
+            header: line,
+            body: Vec::new(),
+        });
+    }
+
+    blocks
+}
+
+fn parse_journal(
+    format: PlainTextFormat,
+    input: &str,
+) -> Result<(Vec<Directive>, Vec<ImportWarning>)> {
+    let mut directives = Vec::new();
+    let mut warnings = Vec::new();
+
+    for block in blocks(format, input) {
+        let kind = match format {
+            PlainTextFormat::Beancount => beancount_directive(&block, &mut warnings)?,
+            PlainTextFormat::Ledger => ledger_directive(&block, &mut warnings)?,
+        };
+        if let Some(kind) = kind {
+            directives.push(Directive {
+                line: block.line,
+                kind,
+            });
+        }
+    }
+
+    Ok((directives, warnings))
+}
+
+fn beancount_directive(
+    block: &Block,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<Option<DirectiveKind>> {
+    let (header, _) = split_comment(block.header);
+    let mut words = header.split_whitespace();
+    let first = words.next().unwrap_or_default();
+
+    let Some(date) = parse_date(first) else {
+        warnings.push(warning(
+            block.line,
+            first,
+            "directive is not supported and was skipped",
+        ));
+        return Ok(None);
+    };
+    let keyword = words.next().unwrap_or_default();
+    let rest = header
+        .trim_start()
+        .get(first.len()..)
+        .unwrap_or_default()
+        .trim_start()
+        .get(keyword.len()..)
+        .unwrap_or_default()
+        .trim();
+
+    let metadata = |key: &str| {
+        block.body.iter().find_map(|(_, line)| {
+            let (line, _) = split_comment(line);
+            metadata(line)
+                .filter(|(k, _)| *k == key)
+                .map(|(_, value)| value)
+        })
+    };
+
+    let kind = match keyword {
+        "open" => DirectiveKind::Open(AccountDeclaration {
+            account: rest
+                .split_whitespace()
+                .next()
+                .ok_or_else(|| invalid(block.line, "open without an account"))?
+                .to_string(),
+            date: Some(date),
+            number: metadata("number"),
+            description: metadata("description"),
+            closing_date: None,
+        }),
+        "close" => DirectiveKind::Close {
+            date,
+            account: rest
+                .split_whitespace()
+                .next()
+                .ok_or_else(|| invalid(block.line, "close without an account"))?
+                .to_string(),
+        },
+        "commodity" => DirectiveKind::Commodity(CommodityDeclaration {
+            code: rest.to_string(),
+            name: metadata("name"),
+            decimals: None,
+        }),
+        "price" => {
+            let (base, rate) = rest
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| invalid(block.line, "price without a rate"))?;
+            DirectiveKind::Price {
+                date,
+                base: base.to_string(),
+                rate: parse_amount(rate)
+                    .ok_or_else(|| invalid(block.line, format!("invalid price {rate:?}")))?,
+            }
+        }
+        "balance" | "pad" | "note" | "document" | "event" | "query" | "custom" => {
+            warnings.push(warning(
+                block.line,
+                keyword,
+                "directive is not supported and was skipped",
+            ));
+            return Ok(None);
+        }
+        flag if flag == "txn" || flag.chars().count() == 1 => {
+            let status = match flag {
+                "!" => EntryStatus::Draft,
+                _ => EntryStatus::Posted,
+            };
+            DirectiveKind::Transaction(beancount_transaction(block, date, status, rest, warnings)?)
+        }
+        other => return Err(invalid(block.line, format!("unknown directive {other:?}"))),
+    };
+
+    Ok(Some(kind))
+}
+
+fn beancount_transaction(
+    block: &Block,
+    date: NaiveDate,
+    status: EntryStatus,
+    header: &str,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<JournalTransaction> {
+    let strings = quoted_strings(header);
+    let description = match strings.as_slice() {
+        [payee, narration, ..] if !payee.is_empty() => format!("{payee} - {narration}"),
+        [_, narration, ..] | [narration] => narration.clone(),
+        [] => String::new(),
+    };
+
+    let mut transaction = JournalTransaction {
+        date,
+        status,
+        description,
+        reference: None,
+        postings: Vec::new(),
+    };
+    let mut posting_indent = None;
+
+    for &(line, text) in &block.body {
+        let (code, _) = split_comment(text);
+        if code.trim().is_empty() {
+            continue;
+        }
+        let indent = code.len() - code.trim_start().len();
+
+        if let Some((key, value)) = metadata(code) {
+            // Metadata indented below a posting belongs to the posting
+            match (transaction.postings.last_mut(), posting_indent) {
+                (Some(posting), Some(posting_indent)) if indent > posting_indent => {
+                    if key == "memo" {
+                        posting.memo = Some(value);
+                    }
+                }
+                _ => {
+                    if key == "reference" {
+                        transaction.reference = Some(value);
+                    }
+                }
+            }
+            continue;
+        }
+
+        let code = code.trim();
+        // Posting flags
+        let code = match code.split_once(' ') {
+            Some((flag, rest)) if flag.chars().count() == 1 => rest.trim_start(),
+            _ => code,
+        };
+        let (account, amounts) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+        let (amount, cost, price) =
+            posting_amounts(PlainTextFormat::Beancount, line, amounts, warnings)?;
+
+        posting_indent = Some(indent);
+        transaction.postings.push(Posting {
+            line,
+            account: account.to_string(),
+            amount,
+            cost,
+            price,
+            memo: None,
+        });
+    }
+
+    Ok(transaction)
+}
+
+fn ledger_directive(
+    block: &Block,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<Option<DirectiveKind>> {
+    let (header, header_comment) = split_comment(block.header);
+    let header = header.trim();
+    let (keyword, rest) = header
+        .split_once(char::is_whitespace)
+        .map_or((header, ""), |(keyword, rest)| (keyword, rest.trim()));
+
+    // `; key: value` tags and subdirectives below the header
+    let tags: Vec<(&str, String)> = block
+        .body
+        .iter()
+        .filter_map(|(_, line)| split_comment(line).1)
+        .chain(header_comment)
+        .filter_map(|comment| {
+            comment
+                .split_once(':')
+                .map(|(key, value)| (key.trim(), value.trim().to_string()))
+        })
+        .collect();
+    let tag = |key: &str| {
+        tags.iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value.clone())
+    };
+    let subdirective = |name: &str| {
+        block.body.iter().find_map(|(_, line)| {
+            let (line, _) = split_comment(line);
+            line.trim()
+                .strip_prefix(name)
+                .filter(|value| value.starts_with(char::is_whitespace))
+                .map(|value| value.trim().to_string())
+        })
+    };
+    let date_tag = |key: &str| -> Result<Option<NaiveDate>> {
+        tag(key)
+            .map(|value| {
+                parse_date(&value)
+                    .ok_or_else(|| invalid(block.line, format!("invalid date {value:?}")))
+            })
+            .transpose()
+    };
+
+    if keyword.starts_with(|c: char| c.is_ascii_digit()) {
+        return ledger_transaction(block, header, warnings)
+            .map(|transaction| Some(DirectiveKind::Transaction(transaction)));
+    }
+
+    let kind = match keyword {
+        "account" => DirectiveKind::Open(AccountDeclaration {
+            account: rest.to_string(),
+            date: date_tag("opened")?,
+            number: tag("number"),
+            description: tag("description").or_else(|| subdirective("note")),
+            closing_date: date_tag("closed")?,
+        }),
+        "commodity" => {
+            // Either a bare symbol or a sample amount such as `$1,000.00`
+            let sample = parse_amount(rest);
+            let format = subdirective("format").and_then(|format| parse_amount(&format));
+            DirectiveKind::Commodity(CommodityDeclaration {
+                code: sample
+                    .as_ref()
+                    .map_or_else(|| unquote(rest), |amount| amount.commodity.clone()),
+                name: tag("name").or_else(|| subdirective("note")),
+                decimals: format.or(sample).map(|amount| amount.number.scale()),
+            })
+        }
+        "P" => {
+            let mut words = rest.split_whitespace();
+            let date = words
+                .next()
+                .and_then(parse_date)
+                .ok_or_else(|| invalid(block.line, "price without a valid date"))?;
+            let mut rest = rest
+                .split_once(char::is_whitespace)
+                .map_or("", |(_, rest)| rest)
+                .trim();
+            // Optional time of day
+            if words.next().is_some_and(|word| word.contains(':')) {
+                rest = rest
+                    .split_once(char::is_whitespace)
+                    .map_or("", |(_, rest)| rest)
+                    .trim();
+            }
+            let (base, rate) = match rest.strip_prefix('"') {
+                Some(quoted) => {
+                    let (base, rate) = quoted
+                        .split_once('"')
+                        .ok_or_else(|| invalid(block.line, "unterminated commodity"))?;
+                    (base.to_string(), rate)
+                }
+                None => {
+                    let (base, rate) = rest
+                        .split_once(char::is_whitespace)
+                        .ok_or_else(|| invalid(block.line, "price without a rate"))?;
+                    (commodity_code(base), rate)
+                }
+            };
+            DirectiveKind::Price {
+                date,
+                base,
+                rate: parse_amount(rate)
+                    .ok_or_else(|| invalid(block.line, format!("invalid price {rate:?}")))?,
+            }
+        }
+        "=" | "~" => {
+            warnings.push(warning(
+                block.line,
+                keyword,
+                "automated and periodic transactions are not supported and were skipped",
+            ));
+            return Ok(None);
+        }
+        _ => {
+            warnings.push(warning(
+                block.line,
+                keyword,
+                "directive is not supported and was skipped",
+            ));
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(kind))
+}
+
+fn ledger_transaction(
+    block: &Block,
+    header: &str,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<JournalTransaction> {
+    let (date, mut rest) = header
+        .split_once(char::is_whitespace)
+        .map_or((header, ""), |(date, rest)| (date, rest.trim()));
+    // The auxiliary date after `=` is not kept
+    let date = date.split('=').next().unwrap_or_default();
+    let date =
+        parse_date(date).ok_or_else(|| invalid(block.line, format!("invalid date {date:?}")))?;
+
+    let status = match rest.chars().next() {
+        Some('!') => EntryStatus::Draft,
+        _ => EntryStatus::Posted,
+    };
+    rest = rest.trim_start_matches(['*', '!']).trim_start();
+
+    let mut reference = None;
+    if let Some(code) = rest.strip_prefix('(') {
+        let (code, description) = code
+            .split_once(')')
+            .ok_or_else(|| invalid(block.line, "unterminated transaction code"))?;
+        reference = Some(code.trim().to_string()).filter(|code| !code.is_empty());
+        rest = description.trim();
+    }
+
+    let mut transaction = JournalTransaction {
+        date,
+        status,
+        description: rest.to_string(),
+        reference,
+        postings: Vec::new(),
+    };
+
+    for &(line, text) in &block.body {
+        let (code, comment) = split_comment(text);
+        let code = code.trim();
+        if code.is_empty() {
+            continue;
+        }
+        let code = code.trim_start_matches(['*', '!']).trim_start();
+
+        // The account name ends at two spaces or a tab
+        let end = [code.find("  "), code.find('\t')]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(code.len());
+        let (account, amounts) = (&code[..end], code[end..].trim());
+
+        if account.starts_with(['(', '[']) {
+            warnings.push(warning(
+                line,
+                "posting",
+                format!("virtual posting to {account} was skipped"),
+            ));
+            continue;
+        }
+
+        let (amount, cost, price) =
+            posting_amounts(PlainTextFormat::Ledger, line, amounts, warnings)?;
+        transaction.postings.push(Posting {
+            line,
+            account: account.to_string(),
+            amount,
+            cost,
+            price,
+            memo: comment
+                .map(str::to_string)
+                .filter(|comment| !comment.is_empty()),
+        });
+    }
+
+    Ok(transaction)
+}
+
+/// Reads the amount, cost and price of a posting; balance assertions are
+/// reported and dropped
+fn posting_amounts(
+    format: PlainTextFormat,
+    line: usize,
+    text: &str,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<(Option<Amount>, Option<Cost>, Option<Price>)> {
+    let mut text = text.trim();
+
+    if let Some(index) = find_outside_braces(text, '=') {
+        warnings.push(warning(
+            line,
+            "assertion",
+            format!(
+                "balance assertion {:?} was not checked",
+                text[index..].trim()
+            ),
+        ));
+        text = text[..index].trim();
+    }
+
+    let mut price = None;
+    if let Some(index) = find_outside_braces(text, '@') {
+        let (total, amount) = match text[index + 1..].strip_prefix('@') {
+            Some(amount) => (true, amount),
+            None => (false, &text[index + 1..]),
+        };
+        let amount = parse_amount(amount)
+            .ok_or_else(|| invalid(line, format!("invalid price {amount:?}")))?;
+        price = Some(Price { amount, total });
+        text = text[..index].trim();
+    }
+
+    let mut cost = None;
+    if let Some(start) = text.find('{') {
+        let total = text[start..].starts_with("{{");
+        let (open, close) = match total {
+            true => ("{{", "}}"),
+            false => ("{", "}"),
+        };
+        let end = start
+            + text[start..]
+                .find(close)
+                .ok_or_else(|| invalid(line, "unterminated cost"))?;
+        let inside = text[start + open.len()..end].trim().trim_start_matches('=');
+        let after = text[end + close.len()..].trim();
+
+        let mut amount = None;
+        let mut date = None;
+        match format {
+            // Components are the cost, the acquisition date and a label
+            PlainTextFormat::Beancount => {
+                for component in inside.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+                    if let Some(component_date) = parse_date(component) {
+                        date = Some(component_date);
+                    } else if component.starts_with('"') {
+                        continue;
+                    } else {
+                        amount =
+                            Some(parse_amount(component).ok_or_else(|| {
+                                invalid(line, format!("invalid cost {component:?}"))
+                            })?);
+                    }
+                }
+            }
+            // Lot dates and notes follow the cost: `{10 USD} [2025/01/31] (note)`
+            PlainTextFormat::Ledger => {
+                if !inside.is_empty() {
+                    amount = Some(
+                        parse_amount(inside)
+                            .ok_or_else(|| invalid(line, format!("invalid cost {inside:?}")))?,
+                    );
+                }
+                if let Some(lot_date) = after.strip_prefix('[').and_then(|d| d.split_once(']')) {
+                    date = Some(parse_date(lot_date.0.trim()).ok_or_else(|| {
+                        invalid(line, format!("invalid lot date {:?}", lot_date.0))
+                    })?);
+                }
+            }
+        }
+
+        cost = Some(Cost {
+            amount,
+            total,
+            date,
+        });
+        text = text[..start].trim();
+    }
+
+    let amount = match text {
+        "" => None,
+        text => Some(
+            parse_amount(text).ok_or_else(|| invalid(line, format!("invalid amount {text:?}")))?,
+        ),
+    };
+
+    Ok((amount, cost, price))
+}
+
+fn find_outside_braces(text: &str, needle: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_string = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => depth -= 1,
+            c if c == needle && depth == 0 && !in_string => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits a line at the `;` that starts its comment, outside strings
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '\\' if in_string && !escaped => {
+                escaped = true;
+                continue;
+            }
+            '"' if !escaped => in_string = !in_string,
+            ';' if !in_string => return (&line[..index], Some(line[index + 1..].trim())),
+            _ => {}
+        }
+        escaped = false;
+    }
+    (line, None)
+}
+
+/// A beancount `key: value` metadata line, with strings unquoted
+fn metadata(line: &str) -> Option<(&str, String)> {
+    let (key, value) = line.trim().split_once(':')?;
+    let valid_key = key.starts_with(|c: char| c.is_ascii_lowercase())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid_key || !(value.is_empty() || value.starts_with(char::is_whitespace)) {
+        return None;
+    }
+    Some((key, unquote(value.trim())))
+}
+
+/// The contents of the double-quoted strings in `text`, unescaped
+fn quoted_strings(text: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match (&mut current, c) {
+            (None, '"') => current = Some(String::new()),
+            (None, _) => {}
+            (Some(_), '"') => strings.extend(current.take()),
+            (Some(string), '\\') => string.extend(chars.next()),
+            (Some(string), c) => string.push(c),
+        }
+    }
+
+    strings
+}
+
+fn unquote(text: &str) -> String {
+    match text.starts_with('"') {
+        true => quoted_strings(text).into_iter().next().unwrap_or_default(),
+        false => text.to_string(),
+    }
+}
+
+/// `2025-01-31`, `2025/01/31` or `2025.01.31`
+fn parse_date(text: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(&text.replace(['/', '.'], "-"), "%Y-%m-%d").ok()
+}
+
+/// An amount with its commodity before or after the number, such as
+/// `-1,234.50 EUR`, `$-12.00` or `10 "VWCE.DE"`
+fn parse_amount(text: &str) -> Option<Amount> {
+    let text = text.trim();
+    let (text, quoted) = match text.find('"') {
+        Some(start) => {
+            let end = start + 1 + text[start + 1..].find('"')?;
+            (
+                format!("{} {}", &text[..start], &text[end + 1..]),
+                Some(text[start + 1..end].to_string()),
+            )
+        }
+        None => (text.to_string(), None),
+    };
+
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let (number, commodity) = match (tokens.as_slice(), quoted) {
+        ([number], Some(commodity)) => (number.to_string(), commodity),
+        ([token], None) => {
+            let (number, symbol) = split_symbol(token)?;
+            (number, commodity_code(&symbol))
+        }
+        ([first, second], None) => match parse_number(first) {
+            Some(_) => (first.to_string(), commodity_code(second)),
+            None => (second.to_string(), commodity_code(first)),
+        },
+        _ => return None,
+    };
+
+    if commodity.is_empty() {
+        return None;
+    }
+
+    Some(Amount {
+        number: parse_number(&number)?,
+        commodity,
+    })
+}
+
+/// Splits `-$12.00` or `12.00€` into the signed number and the symbol
+fn split_symbol(token: &str) -> Option<(String, String)> {
+    let (sign, rest) = match token.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", token.strip_prefix('+').unwrap_or(token)),
+    };
+    let is_number = |c: char| c.is_ascii_digit() || matches!(c, '.' | ',' | '-' | '+');
+    let start = rest.find(is_number)?;
+    let end = rest[start..]
+        .find(|c: char| !is_number(c))
+        .map_or(rest.len(), |end| start + end);
+    let (prefix, number, suffix) = (&rest[..start], &rest[start..end], &rest[end..]);
+
+    let symbol = match (prefix, suffix) {
+        (symbol, "") | ("", symbol) => symbol,
+        _ => return None,
+    };
+    Some((format!("{sign}{number}"), symbol.to_string()))
+}
+
+fn parse_number(text: &str) -> Option<Decimal> {
+    let text = text.replace(',', "");
+    Decimal::from_str(text.strip_prefix('+').unwrap_or(&text)).ok()
+}
+
+/// Asset code of a commodity, spelling out currency symbols
+fn commodity_code(symbol: &str) -> String {
+    match symbol {
+        "$" => "USD".to_string(),
+        "€" => "EUR".to_string(),
+        "£" => "GBP".to_string(),
+        "¥" => "JPY".to_string(),
+        symbol => unquote(symbol),
+    }
+}
+
+/// Fiat for ISO 4217 style codes, crypto for the best known coins and stock
+/// for anything else
+fn guess_asset_type(code: &str) -> AssetType {
+    match code {
+        "BTC" | "ETH" | "USDT" | "USDC" => AssetType::Crypto,
+        code if code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase()) => AssetType::Fiat,
+        _ => AssetType::Stock,
+    }
+}
+
+/// Name components compare equal regardless of case and punctuation, so
+/// `Rent-Mortgage-Payment` finds `Rent/Mortgage Payment`
+fn match_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Writes the directives of one journal to the book
+struct Importer<'a> {
+    format: PlainTextFormat,
+    result: PlainTextImport,
+    /// Account numbers by journal account name
+    accounts: HashMap<String, String>,
+    /// Asset codes by journal commodity
+    assets: HashMap<String, String>,
+    commodities: HashMap<String, &'a CommodityDeclaration>,
+    /// Most decimals an amount of each commodity is written with
+    decimals: HashMap<String, u32>,
+    /// Date of the first transaction using each account
+    first_use: HashMap<String, NaiveDate>,
+    first_date: NaiveDate,
+}
+
+impl<'a> Importer<'a> {
+    fn new(format: PlainTextFormat, directives: &'a [Directive]) -> Self {
+        let mut decimals: HashMap<String, u32> = HashMap::new();
+        let mut first_use: HashMap<String, NaiveDate> = HashMap::new();
+        let mut commodities = HashMap::new();
+
+        for directive in directives {
+            match &directive.kind {
+                DirectiveKind::Commodity(commodity) => {
+                    commodities.insert(commodity.code.clone(), commodity);
+                }
+                DirectiveKind::Transaction(transaction) => {
+                    for posting in &transaction.postings {
+                        first_use
+                            .entry(posting.account.clone())
+                            .or_insert(transaction.date);
+
+                        // Totals only: unit costs and prices carry extra decimals
+                        let totals = [
+                            posting.amount.as_ref(),
+                            posting
+                                .cost
+                                .as_ref()
+                                .filter(|c| c.total)
+                                .and_then(|c| c.amount.as_ref()),
+                            posting
+                                .price
+                                .as_ref()
+                                .filter(|p| p.total)
+                                .map(|p| &p.amount),
+                        ];
+                        for amount in totals.into_iter().flatten() {
+                            let seen = decimals.entry(amount.commodity.clone()).or_default();
+                            *seen = (*seen).max(amount.number.scale());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            format,
+            result: PlainTextImport::default(),
+            accounts: HashMap::new(),
+            assets: HashMap::new(),
+            commodities,
+            decimals,
+            first_use,
+            first_date: directives
+                .iter()
+                .filter_map(Directive::date)
+                .min()
+                .unwrap_or_else(|| Utc::now().date_naive()),
+        }
+    }
+
+    fn apply(&mut self, t: &Connection, directive: &Directive) -> Result<()> {
+        let line = directive.line;
+
+        match &directive.kind {
+            DirectiveKind::Open(declaration) => {
+                let date = declaration
+                    .date
+                    .or_else(|| self.first_use.get(&declaration.account).copied())
+                    .unwrap_or(self.first_date);
+                let account_number =
+                    self.account(t, line, &declaration.account, date, Some(declaration))?;
+                if let Some(closing_date) = declaration.closing_date {
+                    close_account(t, &account_number, closing_date)?;
+                }
+            }
+            DirectiveKind::Close { date, account } => {
+                let account_number = self.account(t, line, account, *date, None)?;
+                close_account(t, &account_number, *date)?;
+            }
+            DirectiveKind::Commodity(commodity) => {
+                self.asset(t, &commodity.code)?;
+            }
+            DirectiveKind::Price { date, base, rate } => {
+                let base = self.asset(t, base)?;
+                let quote = self.asset(t, &rate.commodity)?;
+                t.execute(
+                    "INSERT INTO exchange_rates (from_asset_id, to_asset_id, rate, date)
+                     VALUES (
+                        (SELECT id FROM assets WHERE code = :base),
+                        (SELECT id FROM assets WHERE code = :quote),
+                        :rate, :date
+                     )
+                     ON CONFLICT (from_asset_id, to_asset_id, date) DO UPDATE SET rate = excluded.rate",
+                    named_params! {
+                        ":base": base,
+                        ":quote": quote,
+                        ":rate": Money::new(rate.number.normalize()),
+                        ":date": date.and_time(NaiveTime::MIN).and_utc(),
+                    },
+                )?;
+                self.result.prices += 1;
+            }
+            DirectiveKind::Transaction(transaction) => {
+                let id = self.transaction(t, line, transaction)?;
+                self.result.entry_ids.push(id);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Number of the account a journal account name stands for, creating it
+    /// and any missing parents
+    fn account(
+        &mut self,
+        t: &Connection,
+        line: usize,
+        name: &str,
+        date: NaiveDate,
+        declaration: Option<&AccountDeclaration>,
+    ) -> Result<String> {
+        if let Some(number) = self.accounts.get(name) {
+            return Ok(number.clone());
+        }
+
+        let number_hint = declaration.and_then(|declaration| declaration.number.as_deref());
+        if let Some(number) = number_hint {
+            if account_exists(t, number)? {
+                self.accounts.insert(name.to_string(), number.to_string());
+                return Ok(number.to_string());
+            }
+        }
+
+        let created = self.result.accounts.len();
+        let components: Vec<&str> = name.split(':').collect();
+        let root = classify_root(components[0]).ok_or_else(|| {
+            invalid(
+                line,
+                format!(
+                    "account {name} is not below Assets, Liabilities, Equity, Income or Expenses"
+                ),
+            )
+        })?;
+        let account_type_id = self.account_type(t, root)?;
+        let leaf = components.len() - 1;
+
+        let (mut parent_id, mut parent_number) = {
+            let mut stmt = t.prepare(
+                "SELECT id, account_number, name FROM accounts
+                 WHERE parent_account_id IS NULL AND account_type_id = ?1
+                 ORDER BY account_number",
+            )?;
+            let roots = stmt
+                .query_map([account_type_id], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let existing = roots
+                .iter()
+                .find(|(.., name)| match_key(name) == match_key(components[0]))
+                .or(roots.first());
+
+            match existing {
+                Some((id, number, _)) => (*id, number.clone()),
+                None => {
+                    let index = ["Assets", "Liabilities", "Equity", "Income", "Expenses"]
+                        .iter()
+                        .position(|r| *r == root)
+                        .unwrap_or_default();
+                    let number = match (leaf, number_hint) {
+                        (0, Some(number)) => number.to_string(),
+                        _ => free_number(t, &format!("{}000", index + 1))?,
+                    };
+                    let id = self.create_account(
+                        t,
+                        &number,
+                        &self.display_name(components[0]),
+                        account_type_id,
+                        None,
+                        date,
+                        declaration.filter(|_| leaf == 0),
+                    )?;
+                    (id, number)
+                }
+            }
+        };
+
+        for (index, component) in components.iter().enumerate().skip(1) {
+            let existing = {
+                let mut stmt = t.prepare(
+                    "SELECT id, account_number, name FROM accounts WHERE parent_account_id = ?1",
+                )?;
+                let children = stmt
+                    .query_map([parent_id], |row| {
+                        Ok((
+                            row.get::<_, i64>(0)?,
+                            row.get::<_, String>(1)?,
+                            row.get::<_, String>(2)?,
+                        ))
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                children
+                    .into_iter()
+                    .find(|(.., name)| match_key(name) == match_key(component))
+            };
+
+            (parent_id, parent_number) = match existing {
+                Some((id, number, _)) => (id, number),
+                None => {
+                    let number = match number_hint.filter(|_| index == leaf) {
+                        Some(number) => number.to_string(),
+                        None => next_child_number(t, parent_id, &parent_number)?,
+                    };
+                    let id = self.create_account(
+                        t,
+                        &number,
+                        &self.display_name(component),
+                        account_type_id,
+                        Some(parent_id),
+                        date,
+                        declaration.filter(|_| index == leaf),
+                    )?;
+                    (id, number)
+                }
+            };
+        }
+
+        // Beancount requires accounts to be opened before they are used
+        if declaration.is_none()
+            && self.format == PlainTextFormat::Beancount
+            && self.result.accounts.len() > created
+        {
+            self.result.warnings.push(warning(
+                line,
+                "open",
+                format!("account {name} is used without being opened"),
+            ));
+        }
+
+        self.accounts
+            .insert(name.to_string(), parent_number.clone());
+        Ok(parent_number)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_account(
+        &mut self,
+        t: &Connection,
+        number: &str,
+        name: &str,
+        account_type_id: i64,
+        parent_id: Option<i64>,
+        date: NaiveDate,
+        declaration: Option<&AccountDeclaration>,
+    ) -> Result<i64> {
+        let id = t.query_row(
+            "INSERT INTO accounts (
+                account_number, name, account_type_id, parent_account_id,
+                is_active, opening_date, description
+             ) VALUES (:number, :name, :account_type_id, :parent_id, true, :date, :description)
+             RETURNING id",
+            named_params! {
+                ":number": number,
+                ":name": name,
+                ":account_type_id": account_type_id,
+                ":parent_id": parent_id,
+                ":date": date,
+                ":description": declaration.and_then(|declaration| declaration.description.as_deref()),
+            },
+            |row| row.get(0),
+        )?;
+        self.result.accounts.push(number.to_string());
+
+        Ok(id)
+    }
+
+    /// Beancount names cannot contain spaces, so dashes most likely stand for them
+    fn display_name(&self, component: &str) -> String {
+        match self.format {
+            PlainTextFormat::Beancount => component.replace('-', " "),
+            PlainTextFormat::Ledger => component.to_string(),
+        }
+    }
+
+    /// Id of the account type a root stands for, creating it if needed
+    fn account_type(&self, t: &Connection, root: &str) -> Result<i64> {
+        let types = {
+            let mut stmt =
+                t.prepare("SELECT id, name, normal_balance FROM account_types ORDER BY id")?;
+            let types = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            types
+        };
+        for (id, name, normal_balance) in types {
+            let normal_balance =
+                NormalBalance::from_str(&normal_balance).map_err(Error::InvalidData)?;
+            if root_name(&name, normal_balance) == root {
+                return Ok(id);
+            }
+        }
+
+        let (name, normal_balance) = match root {
+            "Assets" => ("Asset", NormalBalance::Debit),
+            "Liabilities" => ("Liability", NormalBalance::Credit),
+            "Equity" => ("Equity", NormalBalance::Credit),
+            "Income" => ("Income", NormalBalance::Credit),
+            _ => ("Expense", NormalBalance::Debit),
+        };
+        let id = t.query_row(
+            "INSERT INTO account_types (name, normal_balance) VALUES (?1, ?2) RETURNING id",
+            params![name, format!("{:?}", normal_balance).to_uppercase()],
+            |row| row.get(0),
+        )?;
+
+        Ok(id)
+    }
+
+    /// Code of the asset a journal commodity stands for, creating it if needed
+    fn asset(&mut self, t: &Connection, commodity: &str) -> Result<String> {
+        if let Some(code) = self.assets.get(commodity) {
+            return Ok(code.clone());
+        }
+
+        let code = commodity_code(commodity);
+        let exists = t
+            .query_row("SELECT 1 FROM assets WHERE code = ?1", [&code], |_| Ok(()))
+            .optional()?
+            .is_some();
+
+        if !exists {
+            let declaration = self.commodities.get(commodity).copied();
+            let asset_type = guess_asset_type(&code);
+            let decimals = match declaration.and_then(|declaration| declaration.decimals) {
+                Some(decimals) => decimals,
+                None => {
+                    let seen = self.decimals.get(commodity).copied().unwrap_or_default();
+                    match asset_type {
+                        AssetType::Fiat => seen.max(2),
+                        _ => seen,
+                    }
+                }
+            };
+            let source = match self.format {
+                PlainTextFormat::Beancount => "Imported from beancount",
+                PlainTextFormat::Ledger => "Imported from ledger",
+            };
+            ensure_asset(
+                t,
+                &code,
+                declaration
+                    .and_then(|declaration| declaration.name.as_deref())
+                    .unwrap_or(&code),
+                asset_type,
+                decimals,
+                source,
+            )?;
+            self.result.assets.push(code.clone());
+        }
+
+        self.assets.insert(commodity.to_string(), code.clone());
+        Ok(code)
+    }
+
+    fn transaction(
+        &mut self,
+        t: &Connection,
+        line: usize,
+        transaction: &JournalTransaction,
+    ) -> Result<i64> {
+        /// A posting with its weight: what it is worth towards balancing
+        struct Leg {
+            account_number: String,
+            asset_code: String,
+            units: Decimal,
+            weight: Decimal,
+            weight_asset: String,
+            /// Whether the weight comes from a cost or price annotation
+            priced: bool,
+            /// Date, total cost and cost asset of the lot it opens
+            lot: Option<(NaiveDate, Decimal, String)>,
+            memo: Option<String>,
+        }
+
+        let mut legs = Vec::new();
+        let mut elided = None;
+        let mut reference_asset = None;
+
+        for posting in &transaction.postings {
+            let account_number =
+                self.account(t, posting.line, &posting.account, transaction.date, None)?;
+            let Some(amount) = &posting.amount else {
+                if elided.replace((account_number, posting)).is_some() {
+                    return Err(invalid(
+                        posting.line,
+                        "only one posting per transaction can leave out its amount",
+                    ));
+                }
+                continue;
+            };
+            let asset_code = self.asset(t, &amount.commodity)?;
+            let units = amount.number;
+            let sign = match units.is_sign_negative() {
+                true => Decimal::NEGATIVE_ONE,
+                false => Decimal::ONE,
+            };
+
+            let mut lot = None;
+            let (weight, weight_asset, priced) = match (&posting.cost, &posting.price) {
+                (Some(cost), _) => {
+                    let (total, cost_asset) = match &cost.amount {
+                        Some(cost_amount) => {
+                            let cost_asset = self.asset(t, &cost_amount.commodity)?;
+                            let total = match cost.total {
+                                true => cost_amount.number.abs(),
+                                false => {
+                                    self.round(t, &cost_asset, units.abs() * cost_amount.number)?
+                                }
+                            };
+                            if units.is_sign_negative() {
+                                let account_id = account_id_of(t, &account_number)?;
+                                match close_lots_fifo(
+                                    t,
+                                    account_id,
+                                    asset_id_of(t, &asset_code)?,
+                                    Money::new(units.abs()),
+                                ) {
+                                    Ok(_) => {}
+                                    Err(Error::InvalidData(message)) => {
+                                        self.result.warnings.push(warning(
+                                            posting.line,
+                                            "posting",
+                                            format!("lots were not closed: {message}"),
+                                        ))
+                                    }
+                                    Err(e) => return Err(e),
+                                }
+                            }
+                            (total, cost_asset)
+                        }
+                        None if units.is_sign_negative() => {
+                            let account_id = account_id_of(t, &account_number)?;
+                            let (total, cost_asset) = close_lots_fifo(
+                                t,
+                                account_id,
+                                asset_id_of(t, &asset_code)?,
+                                Money::new(units.abs()),
+                            )
+                            .map_err(|e| match e {
+                                Error::InvalidData(message) => invalid(posting.line, message),
+                                e => e,
+                            })?;
+                            if cost_asset.is_empty() {
+                                return Err(invalid(
+                                    posting.line,
+                                    format!("no open lots of {asset_code} in {}", posting.account),
+                                ));
+                            }
+                            (total.amount(), cost_asset)
+                        }
+                        None => {
+                            return Err(invalid(posting.line, "units added to a lot need a cost"))
+                        }
+                    };
+
+                    if units.is_sign_positive() {
+                        let date = cost.date.unwrap_or(transaction.date);
+                        lot = Some((date, total, cost_asset.clone()));
+                    }
+                    (sign * total, cost_asset, true)
+                }
+                (None, Some(price)) => {
+                    let price_asset = self.asset(t, &price.amount.commodity)?;
+                    let total = match price.total {
+                        true => price.amount.number.abs(),
+                        false => self.round(t, &price_asset, units.abs() * price.amount.number)?,
+                    };
+                    (sign * total, price_asset, true)
+                }
+                (None, None) => (units, asset_code.clone(), false),
+            };
+
+            if priced && reference_asset.is_none() {
+                reference_asset = Some(weight_asset.clone());
+            }
+            legs.push(Leg {
+                account_number,
+                asset_code,
+                units,
+                weight,
+                weight_asset,
+                priced,
+                lot,
+                memo: posting.memo.clone(),
+            });
+        }
+
+        // The elided posting takes up the remainder in each asset
+        if let Some((account_number, posting)) = elided {
+            let mut remainders: BTreeMap<&str, Decimal> = BTreeMap::new();
+            for leg in &legs {
+                *remainders.entry(&leg.weight_asset).or_default() -= leg.weight;
+            }
+            let remainders: Vec<(String, Decimal)> = remainders
+                .into_iter()
+                .filter(|(_, remainder)| !remainder.is_zero())
+                .map(|(asset, remainder)| (asset.to_string(), remainder))
+                .collect();
+            for (asset_code, remainder) in remainders {
+                legs.push(Leg {
+                    account_number: account_number.clone(),
+                    weight_asset: asset_code.clone(),
+                    asset_code,
+                    units: remainder,
+                    weight: remainder,
+                    priced: false,
+                    lot: None,
+                    memo: posting.memo.clone(),
+                });
+            }
+        }
+
+        legs.retain(|leg| !leg.units.is_zero());
+        let reference_asset = reference_asset
+            .or_else(|| legs.first().map(|leg| leg.asset_code.clone()))
+            .ok_or_else(|| invalid(line, "transaction without amounts"))?;
+
+        let mut balances: BTreeMap<&str, Decimal> = BTreeMap::new();
+        for leg in &legs {
+            *balances.entry(&leg.weight_asset).or_default() += leg.weight;
+        }
+
+        let mut lines = Vec::new();
+        let mut residual = Decimal::ZERO;
+        for leg in &legs {
+            let reference_amount = if leg.asset_code == reference_asset {
+                residual += leg.units;
+                None
+            } else if leg.weight_asset == reference_asset {
+                residual += leg.weight;
+                Some(leg.weight.abs())
+            } else if balances[leg.weight_asset.as_str()].is_zero() {
+                // Moves between accounts that balance on their own carry no value
+                Some(Decimal::ZERO)
+            } else {
+                return Err(invalid(
+                    line,
+                    format!(
+                        "{} {} cannot be valued in {reference_asset}",
+                        leg.units, leg.asset_code
+                    ),
+                ));
+            };
+
+            lines.push(NewJournalEntryLine {
+                account_number: leg.account_number.clone(),
+                asset_code: leg.asset_code.clone(),
+                entry_type: match leg.units.is_sign_positive() {
+                    true => NormalBalance::Debit,
+                    false => NormalBalance::Credit,
+                },
+                amount: Money::new(leg.units.abs().normalize()),
+                reference_amount: reference_amount.map(|amount| Money::new(amount.normalize())),
+                description: leg.memo.clone(),
+            });
+        }
+
+        // Unit prices rounded to the reference asset leave a few cents over,
+        // which go to the first priced line like beancount's tolerance
+        if !residual.is_zero() {
+            let decimals = asset_decimals(t, &reference_asset)?;
+            let priced = legs
+                .iter()
+                .filter(|leg| leg.priced && leg.weight_asset == reference_asset)
+                .count();
+            let tolerance = Decimal::new(priced as i64, decimals);
+            let target = legs
+                .iter()
+                .position(|leg| leg.priced && leg.weight_asset == reference_asset);
+
+            match target {
+                Some(index) if residual.abs() <= tolerance => {
+                    let leg = &legs[index];
+                    let adjusted = (leg.weight - residual).abs();
+                    lines[index].reference_amount = Some(Money::new(adjusted.normalize()));
+                }
+                _ => {
+                    return Err(invalid(
+                        line,
+                        format!("transaction is unbalanced by {residual} {reference_asset}"),
+                    ))
+                }
+            }
+        }
+
+        let journal_entry_id = insert_journal_entry(
+            t,
+            &NewJournalEntry {
+                date: transaction.date.and_time(NaiveTime::MIN).and_utc(),
+                description: transaction.description.clone(),
+                reference_number: transaction.reference.clone(),
+                reference_asset_code: reference_asset,
+                status: transaction.status,
+                lines,
+            },
+        )
+        .map_err(|e| match e {
+            Error::InvalidData(message) => invalid(line, message),
+            e => e,
+        })?;
+
+        for leg in &legs {
+            if let Some((date, cost, cost_asset)) = &leg.lot {
+                insert_lot(
+                    t,
+                    &leg.account_number,
+                    &leg.asset_code,
+                    *date,
+                    Money::new(leg.units.normalize()),
+                    Money::new(cost.normalize()),
+                    cost_asset,
+                    Some(journal_entry_id),
+                )?;
+            }
+        }
+
+        Ok(journal_entry_id)
+    }
+
+    /// Rounds a value computed from a unit price to the asset's decimals
+    fn round(&self, t: &Connection, asset_code: &str, value: Decimal) -> Result<Decimal> {
+        let decimals = asset_decimals(t, asset_code)?;
+        Ok(Money::new(value)
+            .round(decimals, Rounding::default())
+            .amount())
+    }
+}
+
+fn account_exists(t: &Connection, account_number: &str) -> Result<bool> {
+    Ok(t.query_row(
+        "SELECT 1 FROM accounts WHERE account_number = ?1",
+        [account_number],
+        |_| Ok(()),
+    )
+    .optional()?
+    .is_some())
+}
+
+/// `number`, or `number-n` for the first `n` that is not taken
+fn free_number(t: &Connection, number: &str) -> Result<String> {
+    if !account_exists(t, number)? {
+        return Ok(number.to_string());
+    }
+    let mut n = 1;
+    loop {
+        let candidate = format!("{number}-{n}");
+        if !account_exists(t, &candidate)? {
+            return Ok(candidate);
+        }
+        n += 1;
+    }
+}
+
+fn close_account(t: &Connection, account_number: &str, closing_date: NaiveDate) -> Result<()> {
+    t.execute(
+        "UPDATE accounts SET closing_date = ?1, is_active = false WHERE account_number = ?2",
+        params![closing_date, account_number],
+    )?;
+    Ok(())
+}
+
+fn asset_decimals(t: &Connection, asset_code: &str) -> Result<u32> {
+    let decimals: i64 = t.query_row(
+        "SELECT decimals FROM assets WHERE code = ?1",
+        [asset_code],
+        |row| row.get(0),
+    )?;
+    Ok(decimals.clamp(0, 28) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_parse_amount() {
+        let amount = |number: Decimal, commodity: &str| Amount {
+            number,
+            commodity: commodity.to_string(),
+        };
+
+        assert_eq!(
+            parse_amount("-1,234.50 EUR"),
+            Some(amount(dec!(-1234.50), "EUR"))
+        );
+        assert_eq!(parse_amount("$-12.00"), Some(amount(dec!(-12.00), "USD")));
+        assert_eq!(parse_amount("-$12"), Some(amount(dec!(-12), "USD")));
+        assert_eq!(parse_amount("USD 5"), Some(amount(dec!(5), "USD")));
+        assert_eq!(
+            parse_amount("10 \"VWCE.DE\""),
+            Some(amount(dec!(10), "VWCE.DE"))
+        );
+        assert_eq!(parse_amount("12.50"), None);
+    }
+
+    #[test]
+    fn test_parse_beancount() {
+        let input = r#"
+option "title" "Book"
+2025-01-01 open Assets:Broker USD,AAPL "FIFO"
+  number: "1201"
+2025-03-02 * "Broker" "Buy \"AAPL\"" #stocks
+  reference: "T-1"
+  Assets:Broker      10 AAPL {150.10 USD, 2025-03-01}
+  Assets:Cash       -1501.00 USD
+    memo: "settlement"
+2025-04-01 ! "Sell"
+  Assets:Broker     -5 AAPL {} @ 160 USD
+  Assets:Cash
+2025-04-02 balance Assets:Cash 0 USD
+"#;
+        let (directives, warnings) = parse_journal(PlainTextFormat::Beancount, input).unwrap();
+
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|w| w.directive.as_str())
+                .collect::<Vec<_>>(),
+            ["option", "balance"]
+        );
+        assert_eq!(directives.len(), 3);
+
+        let DirectiveKind::Transaction(buy) = &directives[1].kind else {
+            panic!("expected a transaction");
+        };
+        assert_eq!(buy.description, "Broker - Buy \"AAPL\"");
+        assert_eq!(buy.reference.as_deref(), Some("T-1"));
+        assert_eq!(
+            buy.postings[0].cost,
+            Some(Cost {
+                amount: Some(Amount {
+                    number: dec!(150.10),
+                    commodity: "USD".into()
+                }),
+                total: false,
+                date: NaiveDate::from_ymd_opt(2025, 3, 1),
+            })
+        );
+        assert_eq!(buy.postings[1].memo.as_deref(), Some("settlement"));
+
+        let DirectiveKind::Transaction(sell) = &directives[2].kind else {
+            panic!("expected a transaction");
+        };
+        assert_eq!(sell.status, EntryStatus::Draft);
+        assert_eq!(sell.postings[0].cost.as_ref().unwrap().amount, None);
+        assert_eq!(sell.postings[1].amount, None);
+    }
+
+    #[test]
+    fn test_parse_ledger() {
+        let input = "\
+account Assets:Checking
+    ; number: 1101
+commodity $1,000.00
+
+2025/01/05=2025/01/06 * (1234) Grocery store  ; weekly
+    Expenses:Groceries      $45.20  ; food
+    [Budget:Food]           $-45.20
+    Assets:Checking         = $954.80
+
+comment
+this is not a transaction
+end comment
+~ monthly
+    Expenses:Rent  $1000
+    Assets:Checking
+";
+        let (directives, warnings) = parse_journal(PlainTextFormat::Ledger, input).unwrap();
+
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|w| w.directive.as_str())
+                .collect::<Vec<_>>(),
+            ["posting", "assertion", "~"]
+        );
+        assert_eq!(
+            directives[1].kind,
+            DirectiveKind::Commodity(CommodityDeclaration {
+                code: "USD".into(),
+                name: None,
+                decimals: Some(2),
+            })
+        );
+
+        let DirectiveKind::Transaction(transaction) = &directives[2].kind else {
+            panic!("expected a transaction");
+        };
+        assert_eq!(
+            transaction.date,
+            NaiveDate::from_ymd_opt(2025, 1, 5).unwrap()
+        );
+        assert_eq!(transaction.reference.as_deref(), Some("1234"));
+        assert_eq!(transaction.description, "Grocery store");
+        assert_eq!(transaction.postings.len(), 2);
+        assert_eq!(transaction.postings[0].memo.as_deref(), Some("food"));
+        assert_eq!(transaction.postings[1].amount, None);
+    }
+}
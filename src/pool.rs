@@ -0,0 +1,441 @@
+use std::{
+    ops::Deref,
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    time::Duration,
+};
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::{
+    error::{Error, Result},
+    interface::Database,
+    money::register_sql_functions,
+};
+
+/// How a file-backed [`Database`] is opened, see [`Database::open_with`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DatabaseOptions {
+    /// Write-ahead logging, which lets readers work alongside the writer
+    pub wal: bool,
+    /// How long a connection waits for a lock before giving up with
+    /// `SQLITE_BUSY`
+    pub busy_timeout: Duration,
+    /// How often the writer waits for its changes to reach the disk, see
+    /// [`Synchronous`]
+    pub synchronous: Synchronous,
+    /// Read-only connections handed out by [`Database::reader`], opened as
+    /// they are first needed
+    pub max_readers: usize,
+}
+
+impl Default for DatabaseOptions {
+    fn default() -> Self {
+        DatabaseOptions {
+            wal: true,
+            busy_timeout: Duration::from_secs(5),
+            synchronous: Synchronous::Normal,
+            max_readers: 4,
+        }
+    }
+}
+
+/// SQLite's `synchronous` setting: how often the writer waits for data to
+/// reach the disk. `Normal` is safe from corruption in WAL mode, but may lose
+/// the last commits on power loss; `Full` does not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+/// Read-only connections to the file of a [`Database`], shared by the
+/// database and every reader taken from it
+pub(crate) struct ReaderPool {
+    path: String,
+    busy_timeout: Duration,
+    max_readers: usize,
+    state: Mutex<PoolState>,
+    returned: Condvar,
+}
+
+#[derive(Default)]
+struct PoolState {
+    idle: Vec<Connection>,
+    open: usize,
+    /// SQLCipher key of the book
+    key: Option<String>,
+}
+
+/// A read-only [`Database`] borrowed from the pool of a writer, see
+/// [`Database::reader`]. Its connection goes back to the pool on drop.
+///
+/// Readers see the book as of the start of each query or transaction and
+/// never block the writer, nor are blocked by it. Only methods that take
+/// `&self` are available, and those that write still fail.
+pub struct PooledReader {
+    db: Option<Database>,
+    pool: Arc<ReaderPool>,
+}
+
+impl Database {
+    /// Opens the book at `path` with `options`.
+    ///
+    /// The connection returned is the only writer; read-only connections to
+    /// the same file are taken with [`reader`](Self::reader).
+    pub fn open_with(path: &str, options: &DatabaseOptions) -> rusqlite::Result<Self> {
+        Self::open_file(path, options, None)
+    }
+
+    /// Opens the book at `path`, first unlocking it with the SQLCipher `key`
+    /// if there is one
+    pub(crate) fn open_file(
+        path: &str,
+        options: &DatabaseOptions,
+        key: Option<&str>,
+    ) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        if let Some(key) = key {
+            conn.pragma_update(None, "key", key)?;
+        }
+        register_sql_functions(&conn)?;
+
+        conn.busy_timeout(options.busy_timeout)?;
+        if options.wal {
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| {
+                row.get::<_, String>(0)
+            })?;
+        }
+        conn.pragma_update(
+            None,
+            "synchronous",
+            format!("{:?}", options.synchronous).to_uppercase(),
+        )?;
+
+        // In-memory and temporary books have no file for readers to share
+        let file = conn.path().filter(|file| !file.is_empty());
+        let readers = file.filter(|_| options.max_readers > 0).map(|file| {
+            Arc::new(ReaderPool {
+                path: file.to_string(),
+                busy_timeout: options.busy_timeout,
+                max_readers: options.max_readers,
+                state: Mutex::new(PoolState {
+                    key: key.map(str::to_string),
+                    ..Default::default()
+                }),
+                returned: Condvar::new(),
+            })
+        });
+
+        Ok(Database::from_parts(conn, readers))
+    }
+
+    /// Takes a read-only connection from the pool, waiting for one to be
+    /// returned if all of them are in use.
+    ///
+    /// Readers are independent of `self` and can be sent to other threads, so
+    /// that long reports run while entries are being written. In-memory
+    /// books and readers themselves have no pool.
+    pub fn reader(&self) -> Result<PooledReader> {
+        let pool = self.readers().ok_or_else(|| {
+            Error::InvalidData("this database has no read-only connections".into())
+        })?;
+
+        let mut state = pool.state.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            if let Some(conn) = state.idle.pop() {
+                return Ok(PooledReader::new(conn, pool));
+            }
+            if state.open < pool.max_readers {
+                state.open += 1;
+                let key = state.key.clone();
+                drop(state);
+
+                return match pool.connect(key.as_deref()) {
+                    Ok(conn) => Ok(PooledReader::new(conn, pool)),
+                    Err(e) => {
+                        let mut state = pool.state.lock().unwrap_or_else(PoisonError::into_inner);
+                        state.open -= 1;
+                        pool.returned.notify_one();
+                        Err(e.into())
+                    }
+                };
+            }
+            state = pool
+                .returned
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+impl ReaderPool {
+    fn connect(&self, key: Option<&str>) -> rusqlite::Result<Connection> {
+        let conn = Connection::open_with_flags(
+            &self.path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX
+                | OpenFlags::SQLITE_OPEN_URI,
+        )?;
+        if let Some(key) = key {
+            conn.pragma_update(None, "key", key)?;
+        }
+        register_sql_functions(&conn)?;
+        conn.busy_timeout(self.busy_timeout)?;
+
+        Ok(conn)
+    }
+
+    /// Runs `rekey` to change the key of the book to `key` while no reader
+    /// is in use, then drops the idle readers still holding the old key
+    #[cfg(feature = "encryption")]
+    pub(crate) fn change_key<F>(&self, key: &str, rekey: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.idle.len() != state.open {
+            return Err(Error::InvalidData(
+                "the key cannot change while readers are in use".into(),
+            ));
+        }
+
+        rekey()?;
+        state.idle.clear();
+        state.open = 0;
+        state.key = Some(key.to_string());
+
+        Ok(())
+    }
+}
+
+impl PooledReader {
+    fn new(conn: Connection, pool: &Arc<ReaderPool>) -> Self {
+        PooledReader {
+            db: Some(Database::from_parts(conn, None)),
+            pool: Arc::clone(pool),
+        }
+    }
+}
+
+impl Deref for PooledReader {
+    type Target = Database;
+
+    fn deref(&self) -> &Database {
+        self.db
+            .as_ref()
+            .expect("reader is only taken apart on drop")
+    }
+}
+
+impl Drop for PooledReader {
+    fn drop(&mut self) {
+        if let Some(db) = self.db.take() {
+            let conn = db.into_connection();
+            // A transaction left open would pin its snapshot for whoever
+            // takes the reader next; a reader that cannot end it is dropped
+            let reusable = conn.is_autocommit() || conn.execute_batch("ROLLBACK").is_ok();
+            let mut state = self
+                .pool
+                .state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if reusable {
+                state.idle.push(conn);
+            } else {
+                state.open -= 1;
+            }
+            self.pool.returned.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{fs, path::PathBuf, sync::mpsc, thread};
+
+    use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        models::{EntryStatus, NewJournalEntry, NewJournalEntryLine, NormalBalance},
+        money::Money,
+        seeding::init_sample_data,
+    };
+
+    /// A book file in the temporary directory, removed with its WAL files
+    /// and any copy an interrupted `encrypt_file` left behind
+    pub(crate) struct TempBook(PathBuf);
+
+    impl TempBook {
+        pub(crate) fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("mm-schema-{}-{name}.db", std::process::id()));
+            let book = TempBook(path);
+            book.remove();
+            book
+        }
+
+        pub(crate) fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+
+        fn remove(&self) {
+            for suffix in ["", "-wal", "-shm", ".encrypted"] {
+                let _ = fs::remove_file(format!("{}{suffix}", self.path()));
+            }
+        }
+    }
+
+    impl Drop for TempBook {
+        fn drop(&mut self) {
+            self.remove();
+        }
+    }
+
+    fn groceries(amount: Money) -> NewJournalEntry {
+        let line = |account: &str, entry_type| NewJournalEntryLine {
+            account_number: account.to_string(),
+            asset_code: "USD".to_string(),
+            entry_type,
+            amount,
+            reference_amount: None,
+            description: None,
+        };
+        NewJournalEntry {
+            date: Utc.with_ymd_and_hms(2025, 3, 3, 0, 0, 0).unwrap(),
+            description: "Groceries".to_string(),
+            reference_number: None,
+            reference_asset_code: "USD".to_string(),
+            status: EntryStatus::Posted,
+            lines: vec![
+                line("5301", NormalBalance::Debit),
+                line("1101", NormalBalance::Credit),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_open_with_options() -> Result<()> {
+        let book = TempBook::new("options");
+        let options = DatabaseOptions {
+            busy_timeout: Duration::from_millis(250),
+            synchronous: Synchronous::Full,
+            ..Default::default()
+        };
+        let db = Database::open_with(book.path(), &options)?;
+
+        let conn = db.conn();
+        let journal_mode: String =
+            conn.pragma_query_value(None, "journal_mode", |row| row.get(0))?;
+        assert_eq!(journal_mode, "wal");
+        let busy_timeout: i64 = conn.pragma_query_value(None, "busy_timeout", |row| row.get(0))?;
+        assert_eq!(busy_timeout, 250);
+        let synchronous: i64 = conn.pragma_query_value(None, "synchronous", |row| row.get(0))?;
+        assert_eq!(synchronous, 2);
+
+        // Readers are only available on files
+        assert!(Database::new_in_memory()?.reader().is_err());
+        assert!(Database::new(":memory:")?.reader().is_err());
+        assert!(db.reader()?.reader().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_readers_run_alongside_the_writer() -> Result<()> {
+        let book = TempBook::new("readers");
+        let mut db = Database::new(book.path())?;
+        db.init_schema()?;
+        init_sample_data(&mut db)?;
+        db.create_journal_entry(&groceries(Money::new(dec!(45.20))))?;
+
+        // A reader inside a long transaction keeps its snapshot while the
+        // writer commits
+        let reader = db.reader()?;
+        reader.conn().execute_batch("BEGIN")?;
+        assert_eq!(reader.get_general_balance()?.len(), 2);
+
+        db.create_journal_entry(&groceries(Money::new(dec!(10))))?;
+        assert_eq!(reader.register("1101")?.len(), 1);
+        reader.conn().execute_batch("COMMIT")?;
+        assert_eq!(reader.register("1101")?.len(), 2);
+
+        // and cannot write
+        assert!(reader
+            .conn()
+            .execute("DELETE FROM journal_entries", [])
+            .is_err());
+
+        // Reports run on another thread while the writer carries on
+        let report = thread::spawn(move || reader.get_general_balance());
+        db.create_journal_entry(&groceries(Money::new(dec!(1))))?;
+        let checking = report.join().unwrap()?[0].balance;
+        assert!([dec!(-55.2), dec!(-56.2)].contains(&checking.amount()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_readers_are_reused() -> Result<()> {
+        let book = TempBook::new("reuse");
+        let options = DatabaseOptions {
+            max_readers: 1,
+            ..Default::default()
+        };
+        let db = Database::open_with(book.path(), &options)?;
+        db.init_schema()?;
+
+        let reader = db.reader()?;
+        let (start_tx, start_rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        let waiting = thread::spawn({
+            let pool = Arc::clone(db.readers().unwrap());
+            move || -> Result<()> {
+                let db = Database::from_parts(Connection::open_in_memory()?, Some(pool));
+                start_tx.send(()).unwrap();
+                // Blocks until the first reader is returned
+                result_tx.send(db.reader()?.schema_version()?).unwrap();
+                Ok(())
+            }
+        });
+        start_rx.recv().unwrap();
+        assert!(result_rx.recv_timeout(Duration::from_millis(50)).is_err());
+
+        drop(reader);
+        assert!(result_rx.recv().unwrap() > 0);
+        waiting.join().unwrap()?;
+
+        let state = db.readers().unwrap().state.lock().unwrap();
+        assert_eq!((state.open, state.idle.len()), (1, 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_readers_dropped_inside_a_transaction() -> Result<()> {
+        let book = TempBook::new("dropped");
+        let options = DatabaseOptions {
+            max_readers: 1,
+            ..Default::default()
+        };
+        let mut db = Database::open_with(book.path(), &options)?;
+        db.init_schema()?;
+        init_sample_data(&mut db)?;
+
+        let reader = db.reader()?;
+        reader.conn().execute_batch("BEGIN")?;
+        assert!(reader.register("1101")?.is_empty());
+        drop(reader);
+
+        // The next reader sees what the writer committed since
+        db.create_journal_entry(&groceries(Money::new(dec!(45.20))))?;
+        let reader = db.reader()?;
+        assert!(reader.conn().is_autocommit());
+        assert_eq!(reader.register("1101")?.len(), 1);
+
+        Ok(())
+    }
+}
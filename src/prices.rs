@@ -0,0 +1,194 @@
+use std::io::Read;
+
+use chrono::{Days, NaiveDate};
+use rusqlite::{named_params, OptionalExtension};
+
+use crate::{
+    error::{Error, Result},
+    interface::Database,
+    models::AssetPrice,
+    money::Money,
+};
+
+/// Number of days [`Database::price_at`] callers usually allow when walking back
+/// over weekends and market holidays to find the last known quote.
+pub const DEFAULT_PRICE_LOOKBACK_DAYS: u64 = 7;
+
+impl Database {
+    /// Stores the price of `asset_code` quoted in `quote_asset_code` for `date`.
+    /// An existing price for the same day is replaced.
+    #[allow(clippy::too_many_arguments)]
+    pub fn upsert_asset_price<S: AsRef<str>>(
+        &mut self,
+        asset_code: S,
+        quote_asset_code: S,
+        date: NaiveDate,
+        open: Option<Money>,
+        high: Option<Money>,
+        low: Option<Money>,
+        close: Money,
+    ) -> Result<i64> {
+        let t = self.transaction()?;
+
+        let id = t.query_row(
+            include_str!("sql/upsert_asset_price.sql"),
+            named_params! {
+                ":asset_code": asset_code.as_ref(),
+                ":quote_asset_code": quote_asset_code.as_ref(),
+                ":date": date,
+                ":open": open,
+                ":high": high,
+                ":low": low,
+                ":close": close,
+            },
+            |row| row.get(0),
+        )?;
+
+        t.commit()?;
+
+        Ok(id)
+    }
+
+    /// Returns the most recent price of `asset_code` in `quote_asset_code` on or
+    /// before `date`, looking back at most `lookback_days` days to bridge gaps
+    /// such as weekends and holidays.
+    pub fn price_at<S: AsRef<str>>(
+        &self,
+        asset_code: S,
+        date: NaiveDate,
+        quote_asset_code: S,
+        lookback_days: u64,
+    ) -> Result<Option<AssetPrice>> {
+        let earliest = date
+            .checked_sub_days(Days::new(lookback_days))
+            .unwrap_or(NaiveDate::MIN);
+
+        let mut stmt = self.conn().prepare(
+            "SELECT p.id, p.asset_id, p.quote_asset_id, p.date, p.open, p.high, p.low, p.close
+             FROM asset_prices p
+             WHERE p.asset_id = (SELECT id FROM assets WHERE code = :asset_code)
+               AND p.quote_asset_id = (SELECT id FROM assets WHERE code = :quote_asset_code)
+               AND p.date BETWEEN :earliest AND :date
+             ORDER BY p.date DESC
+             LIMIT 1",
+        )?;
+
+        let price = stmt
+            .query_row(
+                named_params! {
+                    ":asset_code": asset_code.as_ref(),
+                    ":quote_asset_code": quote_asset_code.as_ref(),
+                    ":earliest": earliest,
+                    ":date": date,
+                },
+                |row| {
+                    Ok(AssetPrice {
+                        id: row.get(0)?,
+                        asset_id: row.get(1)?,
+                        quote_asset_id: row.get(2)?,
+                        date: row.get(3)?,
+                        open: row.get(4)?,
+                        high: row.get(5)?,
+                        low: row.get(6)?,
+                        close: row.get(7)?,
+                    })
+                },
+            )
+            .optional()?;
+
+        Ok(price)
+    }
+
+    /// Returns the codes of every asset `asset_code` has been quoted in.
+    pub(crate) fn price_quote_assets<S: AsRef<str>>(&self, asset_code: S) -> Result<Vec<String>> {
+        let mut stmt = self.conn().prepare(
+            "SELECT DISTINCT q.code
+             FROM asset_prices p
+             JOIN assets q ON q.id = p.quote_asset_id
+             WHERE p.asset_id = (SELECT id FROM assets WHERE code = ?1)
+             ORDER BY q.code",
+        )?;
+
+        let codes = stmt
+            .query_map([asset_code.as_ref()], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(codes)
+    }
+
+    /// Bulk imports daily prices from CSV data with a header row.
+    ///
+    /// The `date` (`YYYY-MM-DD`) and `close` columns are required, while `open`,
+    /// `high` and `low` are optional. Column names are matched case-insensitively.
+    /// All rows are written in a single transaction and the number of imported
+    /// prices is returned.
+    pub fn import_asset_prices_csv<S: AsRef<str>, R: Read>(
+        &mut self,
+        asset_code: S,
+        quote_asset_code: S,
+        reader: R,
+    ) -> Result<usize> {
+        let mut csv = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+
+        let headers = csv.headers()?.clone();
+        let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+
+        let date_column =
+            column("date").ok_or_else(|| Error::InvalidData("missing `date` column".into()))?;
+        let close_column =
+            column("close").ok_or_else(|| Error::InvalidData("missing `close` column".into()))?;
+        let (open_column, high_column, low_column) =
+            (column("open"), column("high"), column("low"));
+
+        self.snapshot_before("import")?;
+        let t = self.transaction()?;
+        let mut imported = 0;
+
+        {
+            let mut stmt = t.prepare(include_str!("sql/upsert_asset_price.sql"))?;
+
+            for record in csv.records() {
+                let record = record?;
+                let line = record.position().map_or(0, |p| p.line());
+
+                let field = |index: Option<usize>| -> Result<Option<Money>> {
+                    match index.and_then(|i| record.get(i)).filter(|v| !v.is_empty()) {
+                        Some(value) => value.parse::<Money>().map(Some).map_err(|e| {
+                            Error::InvalidData(format!("line {line}: invalid price {value:?}: {e}"))
+                        }),
+                        None => Ok(None),
+                    }
+                };
+
+                let date = record.get(date_column).unwrap_or_default();
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| {
+                    Error::InvalidData(format!("line {line}: invalid date {date:?}: {e}"))
+                })?;
+                let close = field(Some(close_column))?.ok_or_else(|| {
+                    Error::InvalidData(format!("line {line}: missing close price"))
+                })?;
+
+                stmt.query_row(
+                    named_params! {
+                        ":asset_code": asset_code.as_ref(),
+                        ":quote_asset_code": quote_asset_code.as_ref(),
+                        ":date": date,
+                        ":open": field(open_column)?,
+                        ":high": field(high_column)?,
+                        ":low": field(low_column)?,
+                        ":close": close,
+                    },
+                    |row| row.get::<_, i64>(0),
+                )?;
+
+                imported += 1;
+            }
+        }
+
+        t.commit()?;
+
+        Ok(imported)
+    }
+}
@@ -0,0 +1,1068 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use chrono::NaiveDate;
+use rusqlite::{named_params, params, Connection, OptionalExtension};
+
+use crate::{
+    error::{Error, Result},
+    import::{
+        ensure_asset, insert_investment_transaction, is_imported, next_child_number,
+        record_imported, ImportSummary, InvestmentKind, InvestmentTransaction, SUSPENSE_ACCOUNT,
+    },
+    interface::Database,
+    journal::{account_id_of, insert_journal_entry},
+    models::{
+        AssetType, EntryStatus, IncomeKind, NewJournalEntry, NewJournalEntryLine, NormalBalance,
+    },
+    money::Money,
+    money_format::MoneyFormat,
+};
+
+/// How dates and amounts are written in a QIF file, which depends on the
+/// locale of the program that exported it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QifOptions {
+    /// Dates are written `31/12/2024` rather than `12/31/2024`
+    pub day_first: bool,
+    pub decimal_separator: char,
+}
+
+impl Default for QifOptions {
+    fn default() -> Self {
+        QifOptions {
+            day_first: false,
+            decimal_separator: '.',
+        }
+    }
+}
+
+impl QifOptions {
+    fn money_format(&self) -> MoneyFormat {
+        MoneyFormat {
+            decimal_separator: self.decimal_separator,
+            thousands_separator: Some(match self.decimal_separator {
+                ',' => '.',
+                _ => ',',
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+/// A parsed QIF file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QifFile {
+    pub accounts: Vec<QifAccount>,
+    /// Categories listed in `!Type:Cat` sections
+    pub categories: Vec<QifCategory>,
+    /// Securities listed in `!Type:Security` sections
+    pub securities: Vec<QifSecurity>,
+}
+
+/// The register of one account
+#[derive(Debug, Clone, PartialEq)]
+pub struct QifAccount {
+    /// Name from the preceding `!Account` record, if there is one
+    pub name: Option<String>,
+    pub account_type: QifAccountType,
+    pub transactions: Vec<QifTransaction>,
+    pub investments: Vec<QifInvestment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QifAccountType {
+    Bank,
+    Cash,
+    CCard,
+    Invst,
+    OtherAsset,
+    OtherLiability,
+}
+
+impl std::str::FromStr for QifAccountType {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bank" => Ok(QifAccountType::Bank),
+            "cash" => Ok(QifAccountType::Cash),
+            "ccard" => Ok(QifAccountType::CCard),
+            "invst" => Ok(QifAccountType::Invst),
+            "oth a" => Ok(QifAccountType::OtherAsset),
+            "oth l" => Ok(QifAccountType::OtherLiability),
+            _ => Err(format!("Invalid QIF account type: {}", s)),
+        }
+    }
+}
+
+/// A bank, cash or credit card transaction
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QifTransaction {
+    pub date: NaiveDate,
+    /// Signed amount, positive for money coming into the account
+    pub amount: Money,
+    /// Check or reference number (`N`)
+    pub number: Option<String>,
+    pub payee: Option<String>,
+    pub memo: Option<String>,
+    /// `Category:Subcategory`, or `[Account]` for a transfer
+    pub category: Option<String>,
+    pub splits: Vec<QifSplit>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QifSplit {
+    pub category: Option<String>,
+    pub memo: Option<String>,
+    /// Signed like the transaction amount
+    pub amount: Money,
+}
+
+/// A transaction of an investment register
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QifInvestment {
+    pub date: NaiveDate,
+    /// `Buy`, `SellX`, `Div`, `ReinvDiv`, ...
+    pub action: String,
+    /// Security name (`Y`)
+    pub security: Option<String>,
+    pub price: Option<Money>,
+    pub quantity: Option<Money>,
+    pub total: Option<Money>,
+    pub commission: Option<Money>,
+    /// Account the cash comes from or goes to for the `X` actions
+    pub transfer: Option<String>,
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QifCategory {
+    pub name: String,
+    pub income: bool,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QifSecurity {
+    pub name: String,
+    pub symbol: Option<String>,
+    /// `Stock`, `Mutual Fund`, `Bond`, ...
+    pub security_type: Option<String>,
+}
+
+impl Database {
+    /// Imports a QIF file.
+    ///
+    /// Each register is imported into the account its `!Account` name is
+    /// mapped to with [`Database::map_external_account`], or into
+    /// `account_number` otherwise. Transactions become journal entries with a
+    /// line per split, booked to the Expense or Income account named by their
+    /// category: the first part of `Category:Subcategory` is looked up by name
+    /// anywhere in the Income and Expense hierarchies and missing accounts are
+    /// created below it. `[Account]` transfers are booked to the account
+    /// mapped to, or named, `Account`. Amounts without a category go to
+    /// [`SUSPENSE_ACCOUNT`] and leave their entry a draft to be reviewed.
+    ///
+    /// Investment registers record buys, sells, income and reinvested income
+    /// like [`Database::import_ofx`]; other actions are skipped.
+    ///
+    /// Transactions are deduplicated by fingerprint, as for CSV imports, and
+    /// the whole file is imported in a single transaction.
+    pub fn import_qif<S: AsRef<str>, R: Read>(
+        &mut self,
+        account_number: S,
+        asset_code: S,
+        mut reader: R,
+        options: &QifOptions,
+    ) -> Result<ImportSummary> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        // Older programs export QIF in a Windows code page rather than UTF-8
+        let file = parse_qif(&String::from_utf8_lossy(&bytes), options)?;
+
+        let mut registers = Vec::new();
+        for account in &file.accounts {
+            let mapped = match &account.name {
+                Some(name) => self.mapped_account(name)?,
+                None => None,
+            };
+            registers.push((
+                mapped.unwrap_or_else(|| account_number.as_ref().to_string()),
+                account,
+            ));
+        }
+
+        let asset_code = asset_code.as_ref();
+        let mut summary = ImportSummary::default();
+        self.snapshot_before("import")?;
+        let t = self.transaction()?;
+
+        for category in &file.categories {
+            category_account(&t, &category.name, category.income)?;
+        }
+
+        for (account_number, account) in registers {
+            let account_id = account_id_of(&t, &account_number)?;
+            let mut occurrences: HashMap<String, usize> = HashMap::new();
+            let mut external_id = |fingerprint: String| {
+                let occurrence = occurrences.entry(fingerprint.clone()).or_default();
+                *occurrence += 1;
+                format!("qif:{fingerprint}#{}", *occurrence - 1)
+            };
+
+            for transaction in &account.transactions {
+                let external_id = external_id(format!(
+                    "{}|{}|{}|{}",
+                    transaction.date,
+                    transaction.amount,
+                    transaction.number.as_deref().unwrap_or_default(),
+                    normalize(transaction.payee.as_deref().unwrap_or_default()),
+                ));
+                if is_imported(&t, account_id, &external_id)? {
+                    summary.duplicates += 1;
+                    continue;
+                }
+
+                match insert_qif_transaction(&t, &account_number, asset_code, transaction)? {
+                    Some(journal_entry_id) => {
+                        record_imported(&t, account_id, &external_id, journal_entry_id)?;
+                        summary.entry_ids.push(journal_entry_id);
+                    }
+                    None => summary.skipped += 1,
+                }
+            }
+
+            for investment in &account.investments {
+                let external_id = external_id(format!(
+                    "{}|{}|{}|{}|{}",
+                    investment.date,
+                    investment.action.to_lowercase(),
+                    normalize(investment.security.as_deref().unwrap_or_default()),
+                    investment.quantity.unwrap_or_default(),
+                    investment.total.unwrap_or_default(),
+                ));
+                if is_imported(&t, account_id, &external_id)? {
+                    summary.duplicates += 1;
+                    continue;
+                }
+
+                let entry_ids = insert_qif_investment(
+                    &t,
+                    &account_number,
+                    asset_code,
+                    investment,
+                    &file.securities,
+                )?;
+                match entry_ids.last() {
+                    Some(&journal_entry_id) => {
+                        record_imported(&t, account_id, &external_id, journal_entry_id)?;
+                        summary.entry_ids.extend(entry_ids);
+                    }
+                    None => summary.skipped += 1,
+                }
+            }
+        }
+
+        t.commit()?;
+
+        Ok(summary)
+    }
+
+    /// Writes the register of `account_number` in `asset_code` as a QIF file.
+    ///
+    /// Liability accounts are written as credit card registers and any other
+    /// account as a bank register. The other lines of each entry become its
+    /// category, or its splits when there are several: Income and Expense
+    /// accounts as their `Category:Subcategory` path below the root account,
+    /// any other account as an `[Account]` transfer. Amounts booked to
+    /// [`SUSPENSE_ACCOUNT`] are left uncategorized. Void entries are left out.
+    pub fn export_qif<S: AsRef<str>, W: Write>(
+        &self,
+        account_number: S,
+        asset_code: S,
+        mut writer: W,
+        options: &QifOptions,
+    ) -> Result<()> {
+        let accounts = self.account_tree()?;
+        let (account_id, account) = accounts
+            .iter()
+            .find(|(_, account)| account.number == account_number.as_ref())
+            .ok_or(Error::NotFound)?;
+        let (asset_id, decimals): (i64, u32) = self
+            .conn()
+            .query_row(
+                "SELECT id, decimals FROM assets WHERE code = ?1",
+                [asset_code.as_ref()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or(Error::NotFound)?;
+
+        let account_type = match account.account_type.as_str() {
+            "Liability" => "CCard",
+            _ => "Bank",
+        };
+        writeln!(writer, "!Account\nN{}\nT{account_type}\n^", account.name)?;
+        writeln!(writer, "!Type:{account_type}")?;
+
+        let format = MoneyFormat {
+            decimal_separator: options.decimal_separator,
+            decimals: Some(decimals),
+            ..Default::default()
+        };
+        let date_format = match options.day_first {
+            true => "%d/%m/%Y",
+            false => "%m/%d/%Y",
+        };
+
+        let mut entries = self.conn().prepare(
+            "SELECT DISTINCT je.id, date(je.date), je.description, je.reference_number
+             FROM journal_entries je
+             JOIN journal_entry_lines jel ON jel.journal_entry_id = je.id
+             WHERE jel.account_id = ?1 AND jel.asset_id = ?2 AND je.status != 'VOID'
+             ORDER BY je.date, je.id",
+        )?;
+        let mut lines = self.conn().prepare(
+            "SELECT account_id, asset_id, entry_type, amount, reference_amount, description
+             FROM journal_entry_lines
+             WHERE journal_entry_id = ?1
+             ORDER BY id",
+        )?;
+
+        let entries = entries
+            .query_map(params![account_id, asset_id], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, NaiveDate>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for (journal_entry_id, date, description, reference_number) in entries {
+            let mut amount = Money::default();
+            let mut splits = Vec::new();
+
+            let entry_lines = lines
+                .query_map([journal_entry_id], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, Money>(3)?,
+                        row.get::<_, Option<Money>>(4)?,
+                        row.get::<_, Option<String>>(5)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            for (line_account_id, line_asset_id, entry_type, line_amount, reference, memo) in
+                entry_lines
+            {
+                // From the register's point of view: debits bring money in
+                let signed = |amount: Money| match entry_type.as_str() {
+                    "DEBIT" => amount,
+                    _ => -amount,
+                };
+
+                if line_account_id == *account_id && line_asset_id == asset_id {
+                    amount += signed(line_amount);
+                } else if accounts[&line_account_id].number == SUSPENSE_ACCOUNT {
+                    // Left uncategorized, so it is imported as a draft again
+                    continue;
+                } else {
+                    let line_amount = match line_asset_id == asset_id {
+                        true => line_amount,
+                        false => reference.unwrap_or(line_amount),
+                    };
+                    splits.push((
+                        category_name(&accounts, line_account_id),
+                        memo,
+                        -signed(line_amount),
+                    ));
+                }
+            }
+
+            writeln!(writer, "D{}", date.format(date_format))?;
+            writeln!(writer, "T{}", format.format(amount))?;
+            if let Some(number) = reference_number {
+                writeln!(writer, "N{number}")?;
+            }
+            writeln!(writer, "P{description}")?;
+            match splits.as_slice() {
+                [] => {}
+                [(category, _, _)] => writeln!(writer, "L{category}")?,
+                splits => {
+                    for (category, memo, amount) in splits {
+                        writeln!(writer, "S{category}")?;
+                        if let Some(memo) = memo {
+                            writeln!(writer, "E{memo}")?;
+                        }
+                        writeln!(writer, "${}", format.format(*amount))?;
+                    }
+                }
+            }
+            writeln!(writer, "^")?;
+        }
+
+        Ok(())
+    }
+
+    fn account_tree(&self) -> Result<HashMap<i64, TreeAccount>> {
+        let mut stmt = self.conn().prepare(
+            "SELECT a.id, a.account_number, a.name, a.parent_account_id, at.name
+             FROM accounts a
+             JOIN account_types at ON at.id = a.account_type_id",
+        )?;
+
+        let accounts = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    TreeAccount {
+                        number: row.get(1)?,
+                        name: row.get(2)?,
+                        parent_id: row.get(3)?,
+                        account_type: row.get(4)?,
+                    },
+                ))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(accounts)
+    }
+}
+
+struct TreeAccount {
+    number: String,
+    name: String,
+    parent_id: Option<i64>,
+    account_type: String,
+}
+
+/// QIF category of an account: its path below the root for Income and
+/// Expense accounts, a transfer to it for any other account
+fn category_name(accounts: &HashMap<i64, TreeAccount>, account_id: i64) -> String {
+    let Some(account) = accounts.get(&account_id) else {
+        return String::new();
+    };
+    if account.account_type != "Income" && account.account_type != "Expense" {
+        return format!("[{}]", account.name);
+    }
+
+    let mut path = Vec::new();
+    let mut current = Some(account);
+    while let Some(account) = current {
+        let parent = account.parent_id.and_then(|id| accounts.get(&id));
+        if parent.is_none() && !path.is_empty() {
+            // The root account (Income, Expenses) is implied by the category
+            break;
+        }
+        path.push(account.name.as_str());
+        current = parent;
+    }
+    path.reverse();
+
+    path.join(":")
+}
+
+/// Records a bank transaction with a line per split, or returns `None` if
+/// all its amounts are zero.
+fn insert_qif_transaction(
+    t: &Connection,
+    account_number: &str,
+    asset_code: &str,
+    transaction: &QifTransaction,
+) -> Result<Option<i64>> {
+    let line =
+        |account_number: &str, amount: Money, description: Option<&String>| NewJournalEntryLine {
+            account_number: account_number.to_string(),
+            asset_code: asset_code.to_string(),
+            entry_type: match amount.signum() {
+                1 => NormalBalance::Debit,
+                _ => NormalBalance::Credit,
+            },
+            amount: amount.abs(),
+            reference_amount: None,
+            description: description.cloned(),
+        };
+
+    let splits = match transaction.splits.is_empty() {
+        true => vec![QifSplit {
+            category: transaction.category.clone(),
+            memo: None,
+            amount: transaction.amount,
+        }],
+        false => transaction.splits.clone(),
+    };
+
+    let mut lines = vec![line(account_number, transaction.amount, None)];
+    let mut uncategorized = transaction.amount;
+    for split in splits.iter().filter(|split| !split.amount.is_zero()) {
+        let category = split
+            .category
+            .as_deref()
+            .map(|category| category.split('/').next().unwrap_or_default().trim())
+            .filter(|category| !category.is_empty());
+        let Some(category) = category else {
+            continue;
+        };
+
+        let account_number = match category.strip_prefix('[') {
+            Some(transfer) => transfer_account(t, transfer.trim_end_matches(']'))?,
+            None => category_account(t, category, split.amount.signum() > 0)?,
+        };
+        lines.push(line(&account_number, -split.amount, split.memo.as_ref()));
+        uncategorized -= split.amount;
+    }
+    if !uncategorized.is_zero() {
+        lines.push(line(SUSPENSE_ACCOUNT, -uncategorized, None));
+    }
+
+    lines.retain(|line| !line.amount.is_zero());
+    if lines.len() < 2 {
+        return Ok(None);
+    }
+
+    let status = match uncategorized.is_zero() {
+        true => EntryStatus::Posted,
+        false => EntryStatus::Draft,
+    };
+    let description = match (&transaction.payee, &transaction.memo) {
+        (Some(payee), Some(memo)) => format!("{payee} - {memo}"),
+        (Some(text), None) | (None, Some(text)) => text.clone(),
+        (None, None) => String::new(),
+    };
+
+    let journal_entry_id = insert_journal_entry(
+        t,
+        &NewJournalEntry {
+            date: transaction.date.and_hms_opt(0, 0, 0).unwrap().and_utc(),
+            description,
+            reference_number: transaction.number.clone(),
+            reference_asset_code: asset_code.to_string(),
+            status,
+            lines,
+        },
+    )?;
+
+    Ok(Some(journal_entry_id))
+}
+
+/// Records an investment action, returning no entry for unsupported actions
+/// and two for reinvested income.
+fn insert_qif_investment(
+    t: &Connection,
+    account_number: &str,
+    currency: &str,
+    investment: &QifInvestment,
+    securities: &[QifSecurity],
+) -> Result<Vec<i64>> {
+    let action = investment.action.to_lowercase();
+    let (base, transfer) = match action.strip_suffix('x') {
+        Some(base) => (base, true),
+        None => (action.as_str(), false),
+    };
+    let supported = matches!(
+        base,
+        "buy"
+            | "sell"
+            | "div"
+            | "intinc"
+            | "cglong"
+            | "cgshort"
+            | "reinvdiv"
+            | "reinvint"
+            | "reinvlg"
+            | "reinvsh"
+    );
+    // Income transferred straight to another account never reaches the register
+    if !supported || (transfer && !matches!(base, "buy" | "sell")) {
+        return Ok(Vec::new());
+    }
+
+    let name = investment
+        .security
+        .as_deref()
+        .ok_or_else(|| Error::InvalidData(format!("{} without a security", investment.action)))?;
+    let asset_code = security_asset(t, name, securities)?;
+
+    let cash_account_number = match (transfer, investment.transfer.as_deref()) {
+        (true, Some(category)) => {
+            transfer_account(t, category.trim_start_matches('[').trim_end_matches(']'))?
+        }
+        _ => account_number.to_string(),
+    };
+
+    let units = investment.quantity.unwrap_or_default().abs();
+    let price = investment.price.unwrap_or_default().amount();
+    let commission = investment.commission.unwrap_or_default();
+    let total = investment.total.map(|total| total.abs());
+
+    let mut kinds = Vec::new();
+    match base {
+        "buy" => kinds.push(InvestmentKind::Buy {
+            units,
+            total: total.unwrap_or(units * price + commission),
+        }),
+        "sell" => kinds.push(InvestmentKind::Sell {
+            units,
+            total: total.unwrap_or(units * price - commission),
+        }),
+        _ => {
+            let total = total.ok_or_else(|| {
+                Error::InvalidData(format!("{} without an amount", investment.action))
+            })?;
+            let kind = match base {
+                "intinc" | "reinvint" => IncomeKind::Interest,
+                _ => IncomeKind::Dividend,
+            };
+            kinds.push(InvestmentKind::Income {
+                kind,
+                total,
+                withholding: Money::default(),
+            });
+            if base.starts_with("reinv") {
+                kinds.push(InvestmentKind::Buy { units, total });
+            }
+        }
+    }
+
+    kinds
+        .iter()
+        .map(|kind| {
+            insert_investment_transaction(
+                t,
+                &InvestmentTransaction {
+                    account_number,
+                    cash_account_number: &cash_account_number,
+                    currency,
+                    asset_code: &asset_code,
+                    date: investment.date,
+                    memo: investment.memo.as_deref(),
+                    reference: None,
+                    kind,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Code of the asset for the security named `name`: its symbol from the
+/// file's security list, an existing asset with that name, or the name itself
+/// for a new asset.
+fn security_asset(t: &Connection, name: &str, securities: &[QifSecurity]) -> Result<String> {
+    let security = securities
+        .iter()
+        .find(|security| security.name.eq_ignore_ascii_case(name));
+
+    let existing: Option<String> = t
+        .query_row(
+            "SELECT code FROM assets WHERE name = ?1 COLLATE NOCASE OR code = ?1 ORDER BY id",
+            [name],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let code = match (
+        security.and_then(|security| security.symbol.clone()),
+        existing,
+    ) {
+        (Some(symbol), _) => symbol,
+        (None, Some(code)) => return Ok(code),
+        (None, None) => name.to_string(),
+    };
+
+    let asset_type = match security
+        .and_then(|security| security.security_type.as_deref())
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("mutual fund") => AssetType::Etf,
+        Some("bond") => AssetType::Bond,
+        _ => AssetType::Stock,
+    };
+    ensure_asset(t, &code, name, asset_type, 8, "Imported from QIF")?;
+
+    Ok(code)
+}
+
+/// Number of the account a `[name]` transfer refers to: the account `name` is
+/// mapped to, or the account called `name`.
+fn transfer_account(t: &Connection, name: &str) -> Result<String> {
+    t.query_row(
+        "SELECT a.account_number
+         FROM external_accounts e
+         JOIN accounts a ON a.id = e.account_id
+         WHERE e.external_id = ?1
+         UNION ALL
+         SELECT account_number FROM accounts WHERE name = ?1 COLLATE NOCASE
+         LIMIT 1",
+        [name],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or_else(|| Error::InvalidData(format!("no account for QIF transfer [{name}]")))
+}
+
+/// Number of the account for category `path`, creating the accounts of the
+/// path that do not exist yet. New top level categories go below the Income
+/// root account if `income` is set, below the Expense root otherwise.
+fn category_account(t: &Connection, path: &str, income: bool) -> Result<String> {
+    let preferred = match income {
+        true => "Income",
+        false => "Expense",
+    };
+    let mut segments = path
+        .split(':')
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty());
+    let Some(first) = segments.next() else {
+        return Ok(SUSPENSE_ACCOUNT.to_string());
+    };
+
+    let existing: Option<(i64, String)> = t
+        .query_row(
+            "SELECT a.id, a.account_number
+             FROM accounts a
+             JOIN account_types at ON at.id = a.account_type_id
+             WHERE at.name IN ('Income', 'Expense') AND a.name = ?1 COLLATE NOCASE
+             ORDER BY at.name = ?2 DESC, a.account_number
+             LIMIT 1",
+            params![first, preferred],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let mut account = match existing {
+        Some(account) => account,
+        None => {
+            let root: (i64, String) = t
+                .query_row(
+                    "SELECT a.id, a.account_number
+                     FROM accounts a
+                     JOIN account_types at ON at.id = a.account_type_id
+                     WHERE at.name = ?1 AND a.parent_account_id IS NULL
+                     ORDER BY a.account_number
+                     LIMIT 1",
+                    [preferred],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?
+                .ok_or_else(|| Error::InvalidData(format!("no {preferred} root account")))?;
+            child_account(t, root, first)?
+        }
+    };
+
+    for segment in segments {
+        account = child_account(t, account, segment)?;
+    }
+
+    Ok(account.1)
+}
+
+/// The child of `parent` called `name`, created if it does not exist
+fn child_account(t: &Connection, parent: (i64, String), name: &str) -> Result<(i64, String)> {
+    let (parent_id, parent_number) = parent;
+
+    let existing = t
+        .query_row(
+            "SELECT id, account_number FROM accounts
+             WHERE parent_account_id = ?1 AND name = ?2 COLLATE NOCASE",
+            params![parent_id, name],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    if let Some(account) = existing {
+        return Ok(account);
+    }
+
+    let account_number = next_child_number(t, parent_id, &parent_number)?;
+    let id = t.query_row(
+        "INSERT INTO accounts (
+            account_number, name, account_type_id, parent_account_id,
+            is_active, opening_date, description
+         )
+         SELECT :account_number, :name, account_type_id, id, true, opening_date, :description
+         FROM accounts WHERE id = :parent_id
+         RETURNING id",
+        named_params! {
+            ":account_number": account_number,
+            ":name": name,
+            ":description": "Created by QIF import",
+            ":parent_id": parent_id,
+        },
+        |row| row.get(0),
+    )?;
+
+    Ok((id, account_number))
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+enum Section {
+    Account,
+    Register(usize),
+    Category,
+    Security,
+    /// Memorized transactions, classes, prices and other lists that are not imported
+    Other,
+}
+
+/// Parses a QIF file.
+pub fn parse_qif(input: &str, options: &QifOptions) -> Result<QifFile> {
+    let mut file = QifFile::default();
+    let mut section = Section::Other;
+    let mut account_name: Option<String> = None;
+    let mut fields: Vec<(char, String)> = Vec::new();
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        let line_number = index + 1;
+        let invalid =
+            |message: String| Error::InvalidData(format!("line {line_number}: {message}"));
+
+        if let Some(header) = line.strip_prefix('!') {
+            let header = header.trim();
+            section = match header.split_once(':') {
+                _ if header.eq_ignore_ascii_case("Account") => Section::Account,
+                Some((kind, name)) if kind.eq_ignore_ascii_case("Type") => {
+                    match name.to_lowercase().as_str() {
+                        "cat" => Section::Category,
+                        "security" => Section::Security,
+                        _ => match name.parse::<QifAccountType>() {
+                            Ok(account_type) => {
+                                file.accounts.push(QifAccount {
+                                    name: account_name.clone(),
+                                    account_type,
+                                    transactions: Vec::new(),
+                                    investments: Vec::new(),
+                                });
+                                Section::Register(file.accounts.len() - 1)
+                            }
+                            Err(_) => Section::Other,
+                        },
+                    }
+                }
+                // !Option:AutoSwitch, !Clear:AutoSwitch
+                _ => match section {
+                    Section::Account => Section::Account,
+                    _ => Section::Other,
+                },
+            };
+            fields.clear();
+            continue;
+        }
+
+        if !line.starts_with('^') {
+            let mut chars = line.chars();
+            if let Some(code) = chars.next() {
+                fields.push((code, chars.as_str().trim().to_string()));
+            }
+            continue;
+        }
+
+        let record = std::mem::take(&mut fields);
+        let value = |code: char| {
+            record
+                .iter()
+                .find(|(c, _)| *c == code)
+                .map(|(_, value)| value.clone())
+                .filter(|value| !value.is_empty())
+        };
+        let amount = |value: &str| -> Result<Money> {
+            let money = options
+                .money_format()
+                .parse(value)
+                .map_err(|e| invalid(e.to_string()))?;
+            Ok(Money::new(money.amount().normalize()))
+        };
+        let date = || -> Result<NaiveDate> {
+            let value = value('D').ok_or_else(|| invalid("transaction without a date".into()))?;
+            parse_date(&value, options.day_first)
+                .ok_or_else(|| invalid(format!("invalid date {value:?}")))
+        };
+
+        match section {
+            Section::Account => account_name = value('N'),
+            Section::Category => {
+                if let Some(name) = value('N') {
+                    file.categories.push(QifCategory {
+                        name,
+                        income: record.iter().any(|(c, _)| *c == 'I'),
+                        description: value('D'),
+                    });
+                }
+            }
+            Section::Security => {
+                if let Some(name) = value('N') {
+                    file.securities.push(QifSecurity {
+                        name,
+                        symbol: value('S'),
+                        security_type: value('T'),
+                    });
+                }
+            }
+            Section::Register(index)
+                if file.accounts[index].account_type == QifAccountType::Invst =>
+            {
+                let optional = |code: char| value(code).map(|value| amount(&value)).transpose();
+
+                file.accounts[index].investments.push(QifInvestment {
+                    date: date()?,
+                    action: value('N')
+                        .ok_or_else(|| invalid("investment without an action".into()))?,
+                    security: value('Y'),
+                    price: optional('I')?,
+                    quantity: optional('Q')?,
+                    total: match optional('T')? {
+                        Some(total) => Some(total),
+                        None => optional('U')?,
+                    },
+                    commission: optional('O')?,
+                    transfer: value('L'),
+                    memo: value('M'),
+                });
+            }
+            Section::Register(index) => {
+                let mut splits: Vec<QifSplit> = Vec::new();
+                for (code, value) in &record {
+                    match code {
+                        'S' => splits.push(QifSplit {
+                            category: Some(value.clone()).filter(|v| !v.is_empty()),
+                            ..Default::default()
+                        }),
+                        'E' | '$' => {
+                            if splits.is_empty() {
+                                splits.push(QifSplit::default());
+                            }
+                            let split = splits.last_mut().expect("pushed above");
+                            match code {
+                                'E' => split.memo = Some(value.clone()),
+                                _ => split.amount = amount(value)?,
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
+                let total = value('T')
+                    .or_else(|| value('U'))
+                    .ok_or_else(|| invalid("transaction without an amount".into()))?;
+
+                file.accounts[index].transactions.push(QifTransaction {
+                    date: date()?,
+                    amount: amount(&total)?,
+                    number: value('N'),
+                    payee: value('P'),
+                    memo: value('M'),
+                    category: value('L'),
+                    splits,
+                });
+            }
+            Section::Other => {}
+        }
+    }
+
+    Ok(file)
+}
+
+/// Parses QIF dates such as `1/15/2025`, `01/15'25`, `1/ 5' 5` or
+/// `15.01.2025`. An apostrophe before the year means the 2000s.
+fn parse_date(value: &str, day_first: bool) -> Option<NaiveDate> {
+    let parts: Vec<&str> = value.split(['/', '-', '.', '\'']).map(str::trim).collect();
+    let [a, b, year] = parts.as_slice() else {
+        return None;
+    };
+    let (a, b, year): (u32, u32, i32) = (a.parse().ok()?, b.parse().ok()?, year.parse().ok()?);
+
+    // ISO dates
+    if a > 31 {
+        return NaiveDate::from_ymd_opt(a as i32, b, year as u32);
+    }
+
+    let year = match year {
+        0..=99 if value.contains('\'') => 2000 + year,
+        0..=49 => 2000 + year,
+        50..=99 => 1900 + year,
+        _ => year,
+    };
+    let (month, day) = match day_first {
+        true => (b, a),
+        false => (a, b),
+    };
+
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_parse_dates() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d);
+
+        assert_eq!(parse_date("1/15/2025", false), date(2025, 1, 15));
+        assert_eq!(parse_date("01/15'25", false), date(2025, 1, 15));
+        assert_eq!(parse_date("1/ 5' 5", false), date(2005, 1, 5));
+        assert_eq!(parse_date("12/31/98", false), date(1998, 12, 31));
+        assert_eq!(parse_date("31.12.2024", true), date(2024, 12, 31));
+        assert_eq!(parse_date("2024-12-31", false), date(2024, 12, 31));
+        assert_eq!(parse_date("31/12/2024", false), None);
+    }
+
+    #[test]
+    fn test_parse_splits_and_investments() {
+        let qif = "!Type:Bank\n\
+            D1/15'25\n\
+            T-1,234.56\n\
+            PHardware Store\n\
+            SHousing:Maintenance\n\
+            EPaint\n\
+            $-1,000.00\n\
+            SHobbies\n\
+            $-234.56\n\
+            ^\n\
+            !Account\n\
+            NBroker\n\
+            TInvst\n\
+            ^\n\
+            !Type:Invst\n\
+            D1/20'25\n\
+            NBuyX\n\
+            YApple Inc.\n\
+            I150\n\
+            Q10\n\
+            T1501\n\
+            O1\n\
+            L[Main Checking Account]\n\
+            ^\n";
+
+        let file = parse_qif(qif, &QifOptions::default()).unwrap();
+        let bank = &file.accounts[0];
+        assert_eq!(bank.name, None);
+        assert_eq!(bank.transactions[0].amount, Money::new(dec!(-1234.56)));
+        assert_eq!(
+            bank.transactions[0].splits[0],
+            QifSplit {
+                category: Some("Housing:Maintenance".into()),
+                memo: Some("Paint".into()),
+                amount: Money::new(dec!(-1000)),
+            }
+        );
+
+        let broker = &file.accounts[1];
+        assert_eq!(broker.name.as_deref(), Some("Broker"));
+        assert_eq!(broker.account_type, QifAccountType::Invst);
+        assert_eq!(broker.investments[0].action, "BuyX");
+        assert_eq!(broker.investments[0].total, Some(Money::new(dec!(1501))));
+    }
+}
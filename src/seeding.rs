@@ -5,9 +5,11 @@ use crate::{
     models::{AssetType, NormalBalance},
 };
 
-use super::Database;
+use crate::store::LedgerStore;
 
-pub(crate) fn init_sample_data(db: &mut Database) -> Result<()> {
+/// Creates the sample account types, assets and chart of accounts in an
+/// empty book, with every account opened on 2025-01-01.
+pub fn init_sample_data<S: LedgerStore + ?Sized>(db: &mut S) -> Result<()> {
     init_account_types(db)?;
     init_assets(db)?;
 
@@ -42,16 +44,16 @@ const SAMPLE_ACCOUNT_TYPES: [(&str, NormalBalance, Option<&str>); 5] = [
     ("Expense", NormalBalance::Debit, Some("Costs and losses")),
 ];
 
-const SAMPLE_ASSETS: [(&str, &str, AssetType, Option<&str>); 6] = [
-    ("USD", "US Dollar", AssetType::Fiat, None),
-    ("EUR", "Euro", AssetType::Fiat, None),
-    ("AAPL", "Apple Inc.", AssetType::Stock, None),
-    ("VWCE", "FTSE All-World", AssetType::Etf, None),
-    ("ETH", "Ethereum", AssetType::Crypto, None),
-    ("BTC", "Bitcoin", AssetType::Crypto, None),
+const SAMPLE_ASSETS: [(&str, &str, AssetType, i64, Option<&str>); 6] = [
+    ("USD", "US Dollar", AssetType::Fiat, 2, None),
+    ("EUR", "Euro", AssetType::Fiat, 2, None),
+    ("AAPL", "Apple Inc.", AssetType::Stock, 8, None),
+    ("VWCE", "FTSE All-World", AssetType::Etf, 8, None),
+    ("ETH", "Ethereum", AssetType::Crypto, 18, None),
+    ("BTC", "Bitcoin", AssetType::Crypto, 8, None),
 ];
 
-fn init_account_types(db: &mut Database) -> Result<()> {
+fn init_account_types<S: LedgerStore + ?Sized>(db: &mut S) -> Result<()> {
     for (name, normal_balance, description) in SAMPLE_ACCOUNT_TYPES {
         db.create_account_type(name, normal_balance, description)?;
     }
@@ -59,14 +61,17 @@ fn init_account_types(db: &mut Database) -> Result<()> {
     Ok(())
 }
 
-fn init_assets(db: &mut Database) -> Result<()> {
-    for (code, name, asset_type, description) in SAMPLE_ASSETS {
-        db.create_asset(code, name, asset_type, description)?;
+fn init_assets<S: LedgerStore + ?Sized>(db: &mut S) -> Result<()> {
+    for (code, name, asset_type, decimals, description) in SAMPLE_ASSETS {
+        db.create_asset(code, name, asset_type, decimals, description)?;
     }
     Ok(())
 }
 
-fn init_asset_accounts(db: &mut Database, opening_date: NaiveDate) -> Result<i64> {
+fn init_asset_accounts<S: LedgerStore + ?Sized>(
+    db: &mut S,
+    opening_date: NaiveDate,
+) -> Result<i64> {
     let assets_id = db.create_account("1000", "Assets", 1, None, true, opening_date, None, None)?;
 
     // Cash and Bank accounts
@@ -151,7 +156,10 @@ fn init_asset_accounts(db: &mut Database, opening_date: NaiveDate) -> Result<i64
     Ok(assets_id)
 }
 
-fn init_liability_accounts(db: &mut Database, opening_date: NaiveDate) -> Result<i64> {
+fn init_liability_accounts<S: LedgerStore + ?Sized>(
+    db: &mut S,
+    opening_date: NaiveDate,
+) -> Result<i64> {
     let liabilities_id = db.create_account(
         "2000",
         "Liabilities",
@@ -212,10 +220,17 @@ fn init_liability_accounts(db: &mut Database, opening_date: NaiveDate) -> Result
     Ok(liabilities_id)
 }
 
-fn init_equity_accounts(db: &mut Database, opening_date: NaiveDate) -> Result<i64> {
+fn init_equity_accounts<S: LedgerStore + ?Sized>(
+    db: &mut S,
+    opening_date: NaiveDate,
+) -> Result<i64> {
     let equity_id = db.create_account("3000", "Equity", 3, None, true, opening_date, None, None)?;
 
-    for (number, name) in [("3100", "Opening Balance"), ("3200", "Retained Earnings")] {
+    for (number, name) in [
+        ("3100", "Opening Balance"),
+        ("3200", "Retained Earnings"),
+        ("3900", "Uncategorized"),
+    ] {
         db.create_account(
             number,
             name,
@@ -231,7 +246,10 @@ fn init_equity_accounts(db: &mut Database, opening_date: NaiveDate) -> Result<i6
     Ok(equity_id)
 }
 
-fn init_income_accounts(db: &mut Database, opening_date: NaiveDate) -> Result<i64> {
+fn init_income_accounts<S: LedgerStore + ?Sized>(
+    db: &mut S,
+    opening_date: NaiveDate,
+) -> Result<i64> {
     let income_id = db.create_account("4000", "Income", 4, None, true, opening_date, None, None)?;
 
     db.create_account(
@@ -287,7 +305,10 @@ fn init_income_accounts(db: &mut Database, opening_date: NaiveDate) -> Result<i6
     Ok(income_id)
 }
 
-fn init_expense_accounts(db: &mut Database, opening_date: NaiveDate) -> Result<i64> {
+fn init_expense_accounts<S: LedgerStore + ?Sized>(
+    db: &mut S,
+    opening_date: NaiveDate,
+) -> Result<i64> {
     let expenses_id =
         db.create_account("5000", "Expenses", 5, None, true, opening_date, None, None)?;
 
@@ -414,6 +435,7 @@ fn init_expense_accounts(db: &mut Database, opening_date: NaiveDate) -> Result<i
         ("5501", "Bank Fees"),
         ("5502", "Credit Card Interest"),
         ("5503", "Investment Fees"),
+        ("5504", "Withholding Tax"),
     ] {
         db.create_account(
             number,
@@ -0,0 +1,835 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    net::SocketAddr,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tiny_http::{Header, Server};
+
+use crate::{
+    error::{Error, Result},
+    interface::Database,
+    models::{AssetType, EntryStatus, NewAccount, NewJournalEntry},
+    money::Money,
+    valuation::end_of_day,
+};
+
+/// Largest request body the server reads, in bytes
+const MAX_BODY_BYTES: usize = 1 << 20;
+
+/// Serves the JSON API on `addr` until the process is stopped.
+///
+/// Requests are handled one at a time on the calling thread, so every write
+/// goes through the single connection of `db`. Only loopback addresses are
+/// accepted, and every request must carry `token` as
+/// `Authorization: Bearer <token>` so that web pages open in a browser on the
+/// same machine cannot call the API. Bodies are only read once the token is
+/// checked, and at most [`MAX_BODY_BYTES`] of them.
+pub fn serve(db: &mut Database, addr: SocketAddr, token: &str) -> Result<()> {
+    if !addr.ip().is_loopback() {
+        return Err(Error::InvalidData(format!(
+            "the server only listens on localhost, not on {addr}"
+        )));
+    }
+    if token.is_empty() {
+        return Err(Error::InvalidData("the server needs a token".into()));
+    }
+    let server = Server::http(addr).map_err(|e| Error::Io(io::Error::other(e)))?;
+
+    for mut request in server.incoming_requests() {
+        let authorization = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Authorization"))
+            .map(|header| header.value.as_str().to_string());
+
+        // Refuse unauthorized requests before reading anything they sent
+        let length = request.body_length();
+        let response = match authorize(token, authorization.as_deref()) {
+            Some(response) => response,
+            None => match read_body(request.as_reader(), length) {
+                Ok(body) => handle(
+                    db,
+                    token,
+                    &ApiRequest {
+                        method: request.method().as_str(),
+                        url: request.url(),
+                        authorization: authorization.as_deref(),
+                        body: &body,
+                    },
+                ),
+                Err(response) => response,
+            },
+        };
+
+        let mut reply = tiny_http::Response::from_string(response.body.to_string())
+            .with_status_code(response.status)
+            .with_header(json_header());
+        if response.status == 401 {
+            reply.add_header(
+                Header::from_bytes(&b"WWW-Authenticate"[..], &b"Bearer"[..])
+                    .expect("static header"),
+            );
+        }
+        // A client that went away has nobody left to tell
+        let _ = request.respond(reply);
+    }
+
+    Ok(())
+}
+
+/// Reads a request body of at most [`MAX_BODY_BYTES`], given its declared
+/// `length` if there is one
+fn read_body<R: Read>(
+    reader: R,
+    length: Option<usize>,
+) -> std::result::Result<Vec<u8>, ApiResponse> {
+    let too_large = || ApiResponse {
+        status: 413,
+        body: json!({ "error": format!("request body is larger than {MAX_BODY_BYTES} bytes") }),
+    };
+    if length.is_some_and(|length| length > MAX_BODY_BYTES) {
+        return Err(too_large());
+    }
+
+    let mut body = Vec::new();
+    reader
+        .take(MAX_BODY_BYTES as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| ApiResponse::error(&Error::Io(e)))?;
+    if body.len() > MAX_BODY_BYTES {
+        return Err(too_large());
+    }
+
+    Ok(body)
+}
+
+fn json_header() -> Header {
+    Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).expect("static header")
+}
+
+/// An HTTP request, independent of the server library
+pub(crate) struct ApiRequest<'a> {
+    pub method: &'a str,
+    /// Path and query string
+    pub url: &'a str,
+    pub authorization: Option<&'a str>,
+    pub body: &'a [u8],
+}
+
+#[derive(Debug)]
+pub(crate) struct ApiResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl ApiResponse {
+    fn ok<T: Serialize>(value: T) -> Result<Self> {
+        Ok(ApiResponse {
+            status: 200,
+            body: serde_json::to_value(value)?,
+        })
+    }
+
+    fn created(id: i64) -> Result<Self> {
+        Ok(ApiResponse {
+            status: 201,
+            body: json!({ "id": id }),
+        })
+    }
+
+    fn no_content() -> Result<Self> {
+        Ok(ApiResponse {
+            status: 204,
+            body: Value::Null,
+        })
+    }
+
+    fn error(error: &Error) -> Self {
+        let status = match error {
+            Error::NotFound => 404,
+            Error::InvalidData(_) | Error::Json(_) | Error::Csv(_) => 400,
+            Error::Database(rusqlite::Error::SqliteFailure(e, _))
+                if e.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                409
+            }
+            Error::Database(_) | Error::Io(_) => 500,
+        };
+        ApiResponse {
+            status,
+            body: json!({ "error": error.to_string() }),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CloseAccount {
+    closing_date: NaiveDate,
+}
+
+#[derive(Debug, Deserialize)]
+struct NewAsset {
+    code: String,
+    name: String,
+    asset_type: AssetType,
+    decimals: i64,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NewExchangeRate {
+    from: String,
+    to: String,
+    rate: Money,
+    date: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+struct EntryWithId {
+    id: i64,
+    #[serde(flatten)]
+    entry: NewJournalEntry,
+}
+
+/// Routes a request to the matching `Database` operation
+pub(crate) fn handle(db: &mut Database, token: &str, request: &ApiRequest) -> ApiResponse {
+    authorize(token, request.authorization)
+        .unwrap_or_else(|| route(db, request).unwrap_or_else(|e| ApiResponse::error(&e)))
+}
+
+/// The response refusing a request whose `authorization` does not carry
+/// `token`, if it does not
+fn authorize(token: &str, authorization: Option<&str>) -> Option<ApiResponse> {
+    let expected = format!("Bearer {token}");
+    if authorization.is_some_and(|given| constant_time_eq(given.as_bytes(), expected.as_bytes())) {
+        return None;
+    }
+
+    Some(ApiResponse {
+        status: 401,
+        body: json!({ "error": "missing or invalid bearer token" }),
+    })
+}
+
+fn route(db: &mut Database, request: &ApiRequest) -> Result<ApiResponse> {
+    let (path, query) = request.url.split_once('?').unwrap_or((request.url, ""));
+    let query = parse_query(query);
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(percent_decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    match (request.method, segments.as_slice()) {
+        ("GET", ["openapi.json"]) => ApiResponse::ok(openapi()),
+
+        ("GET", ["account-types"]) => ApiResponse::ok(db.list_account_types()?),
+
+        ("GET", ["accounts"]) => ApiResponse::ok(db.list_accounts()?),
+        ("POST", ["accounts"]) => {
+            let account: NewAccount = body(request)?;
+            ApiResponse::created(db.open_account(&account)?)
+        }
+        ("POST", ["accounts", number, "close"]) => {
+            let close: CloseAccount = body(request)?;
+            db.close_account(number, close.closing_date)?;
+            ApiResponse::no_content()
+        }
+        ("GET", ["accounts", number, "register"]) => ApiResponse::ok(db.register(number)?),
+
+        ("GET", ["assets"]) => ApiResponse::ok(db.list_assets()?),
+        ("GET", ["assets", code]) => ApiResponse::ok(db.get_asset(code)?.ok_or(Error::NotFound)?),
+        ("POST", ["assets"]) => {
+            let asset: NewAsset = body(request)?;
+            let id = db.create_asset(
+                asset.code.as_str(),
+                asset.name.as_str(),
+                asset.asset_type,
+                asset.decimals,
+                asset.description.as_deref(),
+            )?;
+            ApiResponse::created(id)
+        }
+
+        ("GET", ["rates"]) => {
+            let date = match query.get("date") {
+                Some(_) => param::<NaiveDate>(&query, "date")?,
+                None => Utc::now().date_naive(),
+            };
+            let (from, to) = (
+                param::<String>(&query, "from")?,
+                param::<String>(&query, "to")?,
+            );
+            let rate = db
+                .exchange_rate_at(&from, &to, end_of_day(date))?
+                .ok_or(Error::NotFound)?;
+            ApiResponse::ok(
+                json!({ "from": from, "to": to, "date": date, "rate": Money::new(rate) }),
+            )
+        }
+        ("POST", ["rates"]) => {
+            let rate: NewExchangeRate = body(request)?;
+            let id = db.create_exchange_rate(
+                rate.from.as_str(),
+                rate.to.as_str(),
+                rate.rate.amount(),
+                rate.date,
+            )?;
+            ApiResponse::created(id)
+        }
+
+        ("GET", ["entries"]) => {
+            let status = match query.get("status") {
+                Some(status) => status.to_uppercase().parse().map_err(Error::InvalidData)?,
+                None => EntryStatus::Posted,
+            };
+            let entries: Vec<EntryWithId> = db
+                .journal_entries(status)?
+                .into_iter()
+                .map(|(id, entry)| EntryWithId { id, entry })
+                .collect();
+            ApiResponse::ok(entries)
+        }
+        ("POST", ["entries"]) => {
+            let entry: NewJournalEntry = body(request)?;
+            ApiResponse::created(db.create_journal_entry(&entry)?)
+        }
+        ("POST", ["entries", id, "post"]) => {
+            db.post_journal_entry(entry_id(id)?)?;
+            ApiResponse::no_content()
+        }
+        ("POST", ["entries", id, "void"]) => {
+            db.void_journal_entry(entry_id(id)?)?;
+            ApiResponse::no_content()
+        }
+
+        ("GET", ["reports", "balance"]) => ApiResponse::ok(db.get_general_balance()?),
+        ("GET", ["reports", "valuation"]) => ApiResponse::ok(db.get_valuation_report(
+            param::<NaiveDate>(&query, "date")?,
+            param::<String>(&query, "asset")?,
+        )?),
+        ("GET", ["reports", "performance"]) => ApiResponse::ok(db.portfolio_performance(
+            param::<String>(&query, "account")?,
+            param(&query, "from")?,
+            param(&query, "to")?,
+            param::<String>(&query, "asset")?,
+        )?),
+
+        _ => Ok(ApiResponse {
+            status: 404,
+            body: json!({ "error": format!("no route for {} {path}", request.method) }),
+        }),
+    }
+}
+
+fn body<T: DeserializeOwned>(request: &ApiRequest) -> Result<T> {
+    Ok(serde_json::from_slice(request.body)?)
+}
+
+fn entry_id(text: &str) -> Result<i64> {
+    text.parse()
+        .map_err(|_| Error::InvalidData(format!("invalid journal entry id {text:?}")))
+}
+
+/// Reads a required query parameter
+fn param<T: std::str::FromStr>(query: &HashMap<String, String>, name: &str) -> Result<T> {
+    let value = query
+        .get(name)
+        .ok_or_else(|| Error::InvalidData(format!("missing query parameter {name:?}")))?;
+    value
+        .parse()
+        .map_err(|_| Error::InvalidData(format!("invalid query parameter {name}={value:?}")))
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (
+                percent_decode(&name.replace('+', " ")),
+                percent_decode(&value.replace('+', " ")),
+            )
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Compares tokens without returning early on the first mismatch
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// OpenAPI 3.0 description of the API
+pub fn openapi() -> Value {
+    let money = json!({ "type": "string", "pattern": "^-?[0-9]+(\\.[0-9]+)?$" });
+    let error = json!({ "description": "Error", "content": { "application/json": {
+        "schema": { "$ref": "#/components/schemas/Error" } } } });
+    let created = json!({ "description": "Created", "content": { "application/json": {
+        "schema": { "$ref": "#/components/schemas/Created" } } } });
+    let no_content = json!({ "description": "Done" });
+    let list = |schema: &str| {
+        json!({ "description": "OK", "content": { "application/json": {
+            "schema": { "type": "array", "items": { "$ref": format!("#/components/schemas/{schema}") } } } } })
+    };
+    let single = |schema: &str| {
+        json!({ "description": "OK", "content": { "application/json": {
+            "schema": { "$ref": format!("#/components/schemas/{schema}") } } } })
+    };
+    let request = |schema: &str| {
+        json!({ "required": true, "content": { "application/json": {
+            "schema": { "$ref": format!("#/components/schemas/{schema}") } } } })
+    };
+    let path_param = |name: &str, schema: Value| json!({ "name": name, "in": "path", "required": true, "schema": schema });
+    let query_param = |name: &str, required: bool, schema: Value| json!({ "name": name, "in": "query", "required": required, "schema": schema });
+    let date = json!({ "type": "string", "format": "date" });
+    let string = json!({ "type": "string" });
+    let id = json!({ "type": "integer", "format": "int64" });
+
+    let paths = json!({
+        "/openapi.json": { "get": {
+            "summary": "This document",
+            "responses": { "200": { "description": "OK" } } } },
+        "/account-types": { "get": {
+            "summary": "List account types",
+            "responses": { "200": list("AccountType"), "default": error } } },
+        "/accounts": {
+            "get": { "summary": "List accounts", "responses": { "200": list("Account"), "default": error } },
+            "post": { "summary": "Add an account", "requestBody": request("NewAccount"),
+                "responses": { "201": created, "default": error } } },
+        "/accounts/{number}/close": { "post": {
+            "summary": "Close an account without a balance",
+            "parameters": [path_param("number", string.clone())],
+            "requestBody": request("CloseAccount"),
+            "responses": { "204": no_content, "default": error } } },
+        "/accounts/{number}/register": { "get": {
+            "summary": "Draft and posted lines of an account with running balances",
+            "parameters": [path_param("number", string.clone())],
+            "responses": { "200": list("RegisterLine"), "default": error } } },
+        "/assets": {
+            "get": { "summary": "List assets", "responses": { "200": list("Asset"), "default": error } },
+            "post": { "summary": "Add an asset", "requestBody": request("NewAsset"),
+                "responses": { "201": created, "default": error } } },
+        "/assets/{code}": { "get": {
+            "summary": "Get an asset",
+            "parameters": [path_param("code", string.clone())],
+            "responses": { "200": single("Asset"), "default": error } } },
+        "/rates": {
+            "get": { "summary": "Most recent exchange rate by the end of a date",
+                "parameters": [
+                    query_param("from", true, string.clone()),
+                    query_param("to", true, string.clone()),
+                    query_param("date", false, date.clone()),
+                ],
+                "responses": { "200": single("Rate"), "default": error } },
+            "post": { "summary": "Record an exchange rate", "requestBody": request("NewExchangeRate"),
+                "responses": { "201": created, "default": error } } },
+        "/entries": {
+            "get": { "summary": "List journal entries by status",
+                "parameters": [query_param("status", false,
+                    json!({ "type": "string", "enum": ["DRAFT", "POSTED", "VOID"], "default": "POSTED" }))],
+                "responses": { "200": list("JournalEntry"), "default": error } },
+            "post": { "summary": "Record a balanced journal entry", "requestBody": request("NewJournalEntry"),
+                "responses": { "201": created, "default": error } } },
+        "/entries/{id}/post": { "post": {
+            "summary": "Post a draft entry",
+            "parameters": [path_param("id", id.clone())],
+            "responses": { "204": no_content, "default": error } } },
+        "/entries/{id}/void": { "post": {
+            "summary": "Void an entry",
+            "parameters": [path_param("id", id.clone())],
+            "responses": { "204": no_content, "default": error } } },
+        "/reports/balance": { "get": {
+            "summary": "Posted balance of every account",
+            "responses": { "200": list("BalanceRow"), "default": error } } },
+        "/reports/valuation": { "get": {
+            "summary": "Holdings valued in a reporting asset",
+            "parameters": [query_param("date", true, date.clone()), query_param("asset", true, string.clone())],
+            "responses": { "200": list("ValuationRow"), "default": error } } },
+        "/reports/performance": { "get": {
+            "summary": "Time- and money-weighted returns of an account and its children",
+            "parameters": [
+                query_param("account", true, string.clone()),
+                query_param("from", true, date.clone()),
+                query_param("to", true, date.clone()),
+                query_param("asset", true, string.clone()),
+            ],
+            "responses": { "200": single("PortfolioPerformance"), "default": error } } },
+    });
+    let schemas = json!({
+        "Money": money,
+        "Error": { "type": "object", "required": ["error"],
+            "properties": { "error": string } },
+        "Created": { "type": "object", "required": ["id"], "properties": { "id": id } },
+        "AccountType": { "type": "object", "properties": {
+            "id": id, "name": string,
+            "normal_balance": { "type": "string", "enum": ["DEBIT", "CREDIT"] },
+            "description": { "type": "string", "nullable": true } } },
+        "Account": { "type": "object", "properties": {
+            "id": id, "account_number": string, "name": string,
+            "account_type_id": id, "parent_account_id": { "type": "integer", "nullable": true },
+            "is_active": { "type": "boolean" }, "opening_date": date,
+            "closing_date": { "type": "string", "format": "date", "nullable": true },
+            "description": { "type": "string", "nullable": true } } },
+        "NewAccount": { "type": "object",
+            "required": ["account_number", "name", "account_type", "opening_date"],
+            "properties": {
+                "account_number": string, "name": string,
+                "account_type": { "type": "string", "description": "Account type name" },
+                "parent_account_number": { "type": "string", "nullable": true },
+                "opening_date": date,
+                "description": { "type": "string", "nullable": true } } },
+        "CloseAccount": { "type": "object", "required": ["closing_date"],
+            "properties": { "closing_date": date } },
+        "Asset": { "type": "object", "properties": {
+            "id": id, "code": string, "name": string,
+            "asset_type": { "$ref": "#/components/schemas/AssetType" },
+            "decimals": { "type": "integer" },
+            "description": { "type": "string", "nullable": true } } },
+        "AssetType": { "type": "string",
+            "enum": ["FIAT", "STOCK", "BOND", "ETF", "ETC", "ETN", "CRYPTO", "COMMODITY"] },
+        "NewAsset": { "type": "object", "required": ["code", "name", "asset_type", "decimals"],
+            "properties": {
+                "code": string, "name": string,
+                "asset_type": { "$ref": "#/components/schemas/AssetType" },
+                "decimals": { "type": "integer" },
+                "description": { "type": "string", "nullable": true } } },
+        "Rate": { "type": "object", "properties": {
+            "from": string, "to": string, "date": date,
+            "rate": { "$ref": "#/components/schemas/Money" } } },
+        "NewExchangeRate": { "type": "object", "required": ["from", "to", "rate", "date"],
+            "properties": {
+                "from": string, "to": string,
+                "rate": { "$ref": "#/components/schemas/Money" },
+                "date": { "type": "string", "format": "date-time" } } },
+        "NewJournalEntryLine": { "type": "object",
+            "required": ["account_number", "asset_code", "entry_type", "amount"],
+            "properties": {
+                "account_number": string, "asset_code": string,
+                "entry_type": { "type": "string", "enum": ["DEBIT", "CREDIT"] },
+                "amount": { "$ref": "#/components/schemas/Money" },
+                "reference_amount": { "allOf": [{ "$ref": "#/components/schemas/Money" }],
+                    "nullable": true,
+                    "description": "Value in the reference asset, for lines in another asset" },
+                "description": { "type": "string", "nullable": true } } },
+        "NewJournalEntry": { "type": "object",
+            "required": ["date", "description", "reference_asset_code", "status", "lines"],
+            "properties": {
+                "date": { "type": "string", "format": "date-time" },
+                "description": string,
+                "reference_number": { "type": "string", "nullable": true },
+                "reference_asset_code": string,
+                "status": { "type": "string", "enum": ["DRAFT", "POSTED"] },
+                "lines": { "type": "array", "minItems": 2,
+                    "items": { "$ref": "#/components/schemas/NewJournalEntryLine" } } } },
+        "JournalEntry": { "allOf": [
+            { "$ref": "#/components/schemas/NewJournalEntry" },
+            { "type": "object", "required": ["id"], "properties": { "id": id } },
+        ] },
+        "RegisterLine": { "type": "object", "properties": {
+            "journal_entry_id": id,
+            "date": { "type": "string", "format": "date-time" },
+            "description": string,
+            "reference_number": { "type": "string", "nullable": true },
+            "status": { "type": "string", "enum": ["DRAFT", "POSTED"] },
+            "asset": string,
+            "amount": { "$ref": "#/components/schemas/Money" },
+            "balance": { "$ref": "#/components/schemas/Money" } } },
+        "BalanceRow": { "type": "object", "properties": {
+            "account_number": string, "account_name": string, "asset": string,
+            "balance": { "$ref": "#/components/schemas/Money" } } },
+        "ValuationRow": { "type": "object", "properties": {
+            "account_number": string, "account_name": string, "asset": string,
+            "balance": { "$ref": "#/components/schemas/Money" },
+            "value": { "allOf": [{ "$ref": "#/components/schemas/Money" }], "nullable": true } } },
+        "PerformanceSummary": { "type": "object", "properties": {
+            "start_value": { "$ref": "#/components/schemas/Money" },
+            "end_value": { "$ref": "#/components/schemas/Money" },
+            "net_contributions": { "$ref": "#/components/schemas/Money" },
+            "absolute_gain": { "$ref": "#/components/schemas/Money" },
+            "time_weighted_return": { "type": "string", "nullable": true },
+            "money_weighted_return": { "type": "string", "nullable": true } } },
+        "PortfolioPerformance": { "type": "object", "properties": {
+            "account_number": string, "reporting_asset": string, "from": date, "to": date,
+            "total": { "$ref": "#/components/schemas/PerformanceSummary" },
+            "by_asset": { "type": "array", "items": { "type": "object", "properties": {
+                "asset": string,
+                "quantity": { "$ref": "#/components/schemas/Money" },
+                "cost_basis": { "allOf": [{ "$ref": "#/components/schemas/Money" }], "nullable": true },
+                "summary": { "$ref": "#/components/schemas/PerformanceSummary" } } } },
+            "by_month": { "type": "array", "items": { "type": "object", "properties": {
+                "month": date,
+                "summary": { "$ref": "#/components/schemas/PerformanceSummary" } } } } } },
+    });
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "mm-schema",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Double-entry book served from a local SQLite database. \
+                Money amounts are decimal strings.",
+        },
+        "servers": [{ "url": "http://127.0.0.1:8080" }],
+        "security": [{ "bearer": [] }],
+        "paths": paths,
+        "components": {
+            "securitySchemes": { "bearer": { "type": "http", "scheme": "bearer" } },
+            "schemas": schemas,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeding::init_sample_data;
+
+    fn request(db: &mut Database, method: &str, url: &str, body: Value) -> ApiResponse {
+        let body = match body {
+            Value::Null => Vec::new(),
+            body => body.to_string().into_bytes(),
+        };
+        handle(
+            db,
+            "secret",
+            &ApiRequest {
+                method,
+                url,
+                authorization: Some("Bearer secret"),
+                body: &body,
+            },
+        )
+    }
+
+    fn sample_book() -> Result<Database> {
+        let mut db = Database::new_in_memory()?;
+        db.init_schema()?;
+        init_sample_data(&mut db)?;
+        Ok(db)
+    }
+
+    #[test]
+    fn test_bearer_token() -> Result<()> {
+        let mut db = sample_book()?;
+        for authorization in [None, Some("Bearer wrong"), Some("secret")] {
+            let response = handle(
+                &mut db,
+                "secret",
+                &ApiRequest {
+                    method: "GET",
+                    url: "/accounts",
+                    authorization,
+                    body: &[],
+                },
+            );
+            assert_eq!(response.status, 401);
+        }
+        assert_eq!(
+            request(&mut db, "GET", "/accounts", Value::Null).status,
+            200
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entries_and_status_transitions() -> Result<()> {
+        let mut db = sample_book()?;
+
+        let entry = json!({
+            "date": "2025-03-03T00:00:00Z",
+            "description": "Groceries",
+            "reference_number": null,
+            "reference_asset_code": "USD",
+            "status": "DRAFT",
+            "lines": [
+                { "account_number": "5301", "asset_code": "USD", "entry_type": "DEBIT",
+                  "amount": "45.20", "reference_amount": null, "description": null },
+                { "account_number": "1101", "asset_code": "USD", "entry_type": "CREDIT",
+                  "amount": "45.20", "reference_amount": null, "description": null },
+            ],
+        });
+        let created = request(&mut db, "POST", "/entries", entry.clone());
+        assert_eq!(created.status, 201);
+        assert_eq!(created.body, json!({ "id": 1 }));
+
+        let drafts = request(&mut db, "GET", "/entries?status=draft", Value::Null);
+        assert_eq!(drafts.body[0]["id"], 1);
+        assert_eq!(drafts.body[0]["lines"][1]["account_number"], "1101");
+
+        let mut unbalanced = entry;
+        unbalanced["lines"][0]["amount"] = json!("45");
+        assert_eq!(request(&mut db, "POST", "/entries", unbalanced).status, 400);
+
+        assert_eq!(
+            request(&mut db, "POST", "/entries/1/post", Value::Null).status,
+            204
+        );
+        assert_eq!(
+            request(&mut db, "POST", "/entries/1/post", Value::Null).status,
+            400
+        );
+        assert_eq!(
+            request(&mut db, "POST", "/entries/9/void", Value::Null).status,
+            404
+        );
+
+        let balance = request(&mut db, "GET", "/reports/balance", Value::Null);
+        let groceries = balance
+            .body
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|row| row["account_number"] == "5301");
+        assert_eq!(groceries.unwrap()["balance"], "45.2");
+
+        let register = request(&mut db, "GET", "/accounts/1101/register", Value::Null);
+        assert_eq!(register.body[0]["balance"], "-45.2");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_accounts_assets_and_rates() -> Result<()> {
+        let mut db = sample_book()?;
+
+        let account = json!({
+            "account_number": "5600",
+            "name": "Pets",
+            "account_type": "expense",
+            "parent_account_number": "5000",
+            "opening_date": "2025-01-01",
+            "description": null,
+        });
+        assert_eq!(
+            request(&mut db, "POST", "/accounts", account.clone()).status,
+            201
+        );
+        assert_eq!(request(&mut db, "POST", "/accounts", account).status, 409);
+        let close = json!({ "closing_date": "2025-12-31" });
+        assert_eq!(
+            request(&mut db, "POST", "/accounts/5600/close", close).status,
+            204
+        );
+
+        let asset = json!({ "code": "CHF", "name": "Swiss Franc", "asset_type": "FIAT",
+                            "decimals": 2, "description": null });
+        assert_eq!(request(&mut db, "POST", "/assets", asset).status, 201);
+        assert_eq!(
+            request(&mut db, "GET", "/assets/CHF", Value::Null).body["name"],
+            "Swiss Franc"
+        );
+
+        let rate =
+            json!({ "from": "CHF", "to": "USD", "rate": "1.1", "date": "2025-02-01T00:00:00Z" });
+        assert_eq!(request(&mut db, "POST", "/rates", rate).status, 201);
+        let rate = request(
+            &mut db,
+            "GET",
+            "/rates?from=CHF&to=USD&date=2025-03-01",
+            Value::Null,
+        );
+        assert_eq!(rate.body["rate"], "1.1");
+        // Rates recorded during the day apply to it
+        let rate =
+            json!({ "from": "CHF", "to": "USD", "rate": "1.2", "date": "2025-03-01T15:00:00Z" });
+        assert_eq!(request(&mut db, "POST", "/rates", rate).status, 201);
+        let rate = request(
+            &mut db,
+            "GET",
+            "/rates?from=CHF&to=USD&date=2025-03-01",
+            Value::Null,
+        );
+        assert_eq!(rate.body["rate"], "1.2");
+        let missing = request(&mut db, "GET", "/rates?from=CHF", Value::Null);
+        assert_eq!(missing.status, 400);
+
+        assert_eq!(request(&mut db, "GET", "/nothing", Value::Null).status, 404);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_openapi_covers_routes() {
+        let document = openapi();
+        let paths = document["paths"].as_object().unwrap();
+        for path in [
+            "/accounts",
+            "/entries/{id}/post",
+            "/reports/performance",
+            "/rates",
+        ] {
+            assert!(paths.contains_key(path), "{path}");
+        }
+        // Every schema reference resolves
+        let text = document.to_string();
+        for reference in text.split("#/components/schemas/").skip(1) {
+            let name = reference.split('"').next().unwrap();
+            assert!(
+                document["components"]["schemas"].get(name).is_some(),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_serve_rejects_remote_addresses() {
+        let mut db = Database::new_in_memory().unwrap();
+        let addr = "0.0.0.0:8080".parse().unwrap();
+        assert!(matches!(
+            serve(&mut db, addr, "secret"),
+            Err(Error::InvalidData(_))
+        ));
+
+        let addr = "127.0.0.1:0".parse().unwrap();
+        assert!(matches!(
+            serve(&mut db, addr, ""),
+            Err(Error::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_body_is_capped() {
+        let body = vec![b' '; MAX_BODY_BYTES + 1];
+        assert_eq!(
+            read_body(&body[..MAX_BODY_BYTES], None).unwrap().len(),
+            MAX_BODY_BYTES
+        );
+        assert_eq!(read_body(&body[..], None).unwrap_err().status, 413);
+        assert_eq!(
+            read_body(&[][..], Some(MAX_BODY_BYTES + 1))
+                .unwrap_err()
+                .status,
+            413
+        );
+    }
+}
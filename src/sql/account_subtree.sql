@@ -0,0 +1,9 @@
+WITH RECURSIVE subtree(id) AS (
+    SELECT id FROM accounts WHERE account_number = :account_number
+    UNION ALL
+    SELECT a.id FROM accounts a JOIN subtree s ON a.parent_account_id = s.id
+)
+SELECT a.account_number
+FROM accounts a
+JOIN subtree s ON s.id = a.id
+ORDER BY a.account_number;
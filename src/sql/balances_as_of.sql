@@ -0,0 +1,32 @@
+WITH balances AS (
+    SELECT
+        a.account_number,
+        a.name AS account_name,
+        jel.asset_id,
+        date(je.date) AS day,
+        at.normal_balance,
+        dec_sum(
+            CASE
+                WHEN jel.entry_type = 'DEBIT' THEN jel.amount
+                ELSE dec_neg(jel.amount)
+            END
+        ) AS balance
+    FROM accounts a
+    JOIN account_types at ON at.id = a.account_type_id
+    JOIN journal_entry_lines jel ON jel.account_id = a.id
+    JOIN journal_entries je ON je.id = jel.journal_entry_id
+    WHERE je.status = 'POSTED'
+      AND date(je.date) <= :as_of
+    GROUP BY a.id, jel.asset_id, date(je.date)
+)
+SELECT
+    account_number,
+    account_name,
+    asset_id,
+    day,
+    CASE
+        WHEN normal_balance = 'DEBIT' THEN balance
+        ELSE dec_neg(balance)
+    END AS balance
+FROM balances
+ORDER BY account_number, day;
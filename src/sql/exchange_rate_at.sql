@@ -0,0 +1,18 @@
+SELECT rate, inverted
+FROM (
+    SELECT er.rate, false AS inverted, er.date
+    FROM exchange_rates er
+    WHERE er.from_asset_id = (SELECT id FROM assets WHERE code = :from_asset_code)
+      AND er.to_asset_id = (SELECT id FROM assets WHERE code = :to_asset_code)
+      AND er.date <= :date
+
+    UNION ALL
+
+    SELECT er.rate, true AS inverted, er.date
+    FROM exchange_rates er
+    WHERE er.from_asset_id = (SELECT id FROM assets WHERE code = :to_asset_code)
+      AND er.to_asset_id = (SELECT id FROM assets WHERE code = :from_asset_code)
+      AND er.date <= :date
+)
+ORDER BY date DESC, inverted ASC
+LIMIT 1;
@@ -4,10 +4,10 @@ WITH balances AS (
         a.name AS account_name,
         ast.code AS asset,
         at.normal_balance,
-        SUM(
+        dec_sum(
             CASE
                 WHEN jel.entry_type = 'DEBIT' THEN jel.amount
-                ELSE -jel.amount
+                ELSE dec_neg(jel.amount)
             END
         ) AS balance
     FROM accounts a
@@ -24,7 +24,7 @@ SELECT
     asset,
     CASE
         WHEN normal_balance = 'DEBIT' THEN balance
-        ELSE -balance
+        ELSE dec_neg(balance)
     END AS balance
 FROM balances
 ORDER BY account_number;
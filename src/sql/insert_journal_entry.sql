@@ -2,10 +2,12 @@ INSERT INTO journal_entries (
     date,
     description,
     reference_number,
+    reference_asset_id,
     status
 ) VALUES (
     :date,
     :description,
     :reference_number,
+    (SELECT id FROM assets WHERE code = :reference_asset_code),
     :status
-);
+) RETURNING id;
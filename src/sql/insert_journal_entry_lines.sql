@@ -6,14 +6,14 @@ INSERT INTO journal_entry_lines (
     amount,
     description
 ) VALUES
-    (last_insert_rowid(),
+    (:journal_entry_id,
      (SELECT id FROM accounts WHERE account_number = :debit_account_number),
      (SELECT id FROM assets WHERE code = :debit_asset_code),
      'DEBIT',
      :amount,
      :description),
 
-    (last_insert_rowid(),
+    (:journal_entry_id,
      (SELECT id FROM accounts WHERE account_number = :credit_account_number),
      (SELECT id FROM assets WHERE code = :credit_asset_code),
      'CREDIT',
@@ -0,0 +1,33 @@
+-- Import Profiles (saved column mappings for bank CSV exports)
+CREATE TABLE import_profiles (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    delimiter TEXT NOT NULL DEFAULT ',',
+    has_headers BOOLEAN NOT NULL DEFAULT true,
+    skip_lines INTEGER NOT NULL DEFAULT 0, -- preamble lines before the header
+    date_column TEXT NOT NULL,
+    date_format TEXT NOT NULL,
+    amount_column TEXT, -- signed amount, positive for money in ...
+    debit_column TEXT, -- ... or money out ...
+    credit_column TEXT, -- ... and money in, in separate columns
+    description_column TEXT NOT NULL,
+    reference_column TEXT,
+    decimal_separator TEXT NOT NULL DEFAULT '.',
+    thousands_separator TEXT,
+    CHECK(amount_column IS NOT NULL OR (debit_column IS NOT NULL AND credit_column IS NOT NULL))
+);
+
+-- Imported Transactions (one row per statement line, to skip it when re-imported)
+CREATE TABLE imported_transactions (
+    id INTEGER PRIMARY KEY,
+    account_id INTEGER NOT NULL,
+    external_id TEXT NOT NULL, -- bank id such as the OFX FITID, or a fingerprint of the line
+    journal_entry_id INTEGER NOT NULL,
+    imported_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id),
+    FOREIGN KEY (journal_entry_id) REFERENCES journal_entries(id),
+    UNIQUE(account_id, external_id)
+);
+CREATE INDEX idx_imported_transactions_entry ON imported_transactions(journal_entry_id);
+
+PRAGMA user_version = 2;
@@ -0,0 +1,9 @@
+-- External Accounts (bank or broker account ids mapped to ledger accounts for imports)
+CREATE TABLE external_accounts (
+    id INTEGER PRIMARY KEY,
+    external_id TEXT NOT NULL UNIQUE, -- e.g. the OFX ACCTID or an IBAN
+    account_id INTEGER NOT NULL,
+    FOREIGN KEY (account_id) REFERENCES accounts(id)
+);
+
+PRAGMA user_version = 3;
@@ -0,0 +1,324 @@
+-- Audit Log (append-only record of every change to accounts, assets, rates
+-- and journal entries, written by the triggers below)
+--
+-- `before` and `after` hold the row as JSON in the shape of its model's serde
+-- representation, NULL for the side that does not exist. Dates are copied as
+-- stored and written as serde writes them by Database::history. The triggers
+-- are plain SQL, so changes made with other tools such as the sqlite3 shell
+-- are recorded too.
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY,
+    timestamp DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now')),
+    actor TEXT NOT NULL,
+    operation TEXT CHECK(operation IN ('INSERT', 'UPDATE', 'STATUS_CHANGE', 'DELETE')) NOT NULL,
+    table_name TEXT NOT NULL,
+    record_id INTEGER NOT NULL,
+    -- Entry of the line changed, for the history of an entry to take in its lines
+    journal_entry_id INTEGER,
+    before TEXT,
+    after TEXT
+);
+CREATE INDEX idx_audit_log_record ON audit_log(table_name, record_id);
+CREATE INDEX idx_audit_log_journal_entry ON audit_log(journal_entry_id);
+
+-- Who changes made from now on are recorded as made by, in a single row set
+-- by Database::set_actor. It is kept in the book, so other connections and
+-- tools share it until it is set again.
+CREATE TABLE audit_context (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    actor TEXT NOT NULL
+);
+INSERT INTO audit_context (id, actor) VALUES (1, 'unknown');
+
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'the audit log is append-only');
+END;
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'the audit log is append-only');
+END;
+
+CREATE TRIGGER audit_accounts_insert AFTER INSERT ON accounts
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'INSERT', 'accounts', NEW.id, NULL, json_object(
+        'id', NEW.id,
+        'account_number', NEW.account_number,
+        'name', NEW.name,
+        'account_type_id', NEW.account_type_id,
+        'parent_account_id', NEW.parent_account_id,
+        'is_active', json(CASE WHEN NEW.is_active THEN 'true' ELSE 'false' END),
+        'opening_date', NEW.opening_date,
+        'closing_date', NEW.closing_date,
+        'description', NEW.description
+    ));
+END;
+CREATE TRIGGER audit_accounts_update AFTER UPDATE ON accounts
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES (
+        (SELECT actor FROM audit_context),
+        'UPDATE',
+        'accounts',
+        NEW.id,
+        json_object(
+            'id', OLD.id,
+            'account_number', OLD.account_number,
+            'name', OLD.name,
+            'account_type_id', OLD.account_type_id,
+            'parent_account_id', OLD.parent_account_id,
+            'is_active', json(CASE WHEN OLD.is_active THEN 'true' ELSE 'false' END),
+            'opening_date', OLD.opening_date,
+            'closing_date', OLD.closing_date,
+            'description', OLD.description
+        ),
+        json_object(
+            'id', NEW.id,
+            'account_number', NEW.account_number,
+            'name', NEW.name,
+            'account_type_id', NEW.account_type_id,
+            'parent_account_id', NEW.parent_account_id,
+            'is_active', json(CASE WHEN NEW.is_active THEN 'true' ELSE 'false' END),
+            'opening_date', NEW.opening_date,
+            'closing_date', NEW.closing_date,
+            'description', NEW.description
+        )
+    );
+END;
+CREATE TRIGGER audit_accounts_delete AFTER DELETE ON accounts
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'DELETE', 'accounts', OLD.id, json_object(
+        'id', OLD.id,
+        'account_number', OLD.account_number,
+        'name', OLD.name,
+        'account_type_id', OLD.account_type_id,
+        'parent_account_id', OLD.parent_account_id,
+        'is_active', json(CASE WHEN OLD.is_active THEN 'true' ELSE 'false' END),
+        'opening_date', OLD.opening_date,
+        'closing_date', OLD.closing_date,
+        'description', OLD.description
+    ), NULL);
+END;
+
+CREATE TRIGGER audit_assets_insert AFTER INSERT ON assets
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'INSERT', 'assets', NEW.id, NULL, json_object(
+        'id', NEW.id,
+        'code', NEW.code,
+        'name', NEW.name,
+        'asset_type', NEW.type,
+        'decimals', NEW.decimals,
+        'description', NEW.description
+    ));
+END;
+CREATE TRIGGER audit_assets_update AFTER UPDATE ON assets
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES (
+        (SELECT actor FROM audit_context),
+        'UPDATE',
+        'assets',
+        NEW.id,
+        json_object(
+            'id', OLD.id,
+            'code', OLD.code,
+            'name', OLD.name,
+            'asset_type', OLD.type,
+            'decimals', OLD.decimals,
+            'description', OLD.description
+        ),
+        json_object(
+            'id', NEW.id,
+            'code', NEW.code,
+            'name', NEW.name,
+            'asset_type', NEW.type,
+            'decimals', NEW.decimals,
+            'description', NEW.description
+        )
+    );
+END;
+CREATE TRIGGER audit_assets_delete AFTER DELETE ON assets
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'DELETE', 'assets', OLD.id, json_object(
+        'id', OLD.id,
+        'code', OLD.code,
+        'name', OLD.name,
+        'asset_type', OLD.type,
+        'decimals', OLD.decimals,
+        'description', OLD.description
+    ), NULL);
+END;
+
+CREATE TRIGGER audit_exchange_rates_insert AFTER INSERT ON exchange_rates
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'INSERT', 'exchange_rates', NEW.id, NULL, json_object(
+        'id', NEW.id,
+        'from_asset_id', NEW.from_asset_id,
+        'to_asset_id', NEW.to_asset_id,
+        'rate', NEW.rate,
+        'date', NEW.date
+    ));
+END;
+CREATE TRIGGER audit_exchange_rates_update AFTER UPDATE ON exchange_rates
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES (
+        (SELECT actor FROM audit_context),
+        'UPDATE',
+        'exchange_rates',
+        NEW.id,
+        json_object(
+            'id', OLD.id,
+            'from_asset_id', OLD.from_asset_id,
+            'to_asset_id', OLD.to_asset_id,
+            'rate', OLD.rate,
+            'date', OLD.date
+        ),
+        json_object(
+            'id', NEW.id,
+            'from_asset_id', NEW.from_asset_id,
+            'to_asset_id', NEW.to_asset_id,
+            'rate', NEW.rate,
+            'date', NEW.date
+        )
+    );
+END;
+CREATE TRIGGER audit_exchange_rates_delete AFTER DELETE ON exchange_rates
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'DELETE', 'exchange_rates', OLD.id, json_object(
+        'id', OLD.id,
+        'from_asset_id', OLD.from_asset_id,
+        'to_asset_id', OLD.to_asset_id,
+        'rate', OLD.rate,
+        'date', OLD.date
+    ), NULL);
+END;
+
+CREATE TRIGGER audit_journal_entries_insert AFTER INSERT ON journal_entries
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'INSERT', 'journal_entries', NEW.id, NULL, json_object(
+        'id', NEW.id,
+        'date', NEW.date,
+        'description', NEW.description,
+        'reference_number', NEW.reference_number,
+        'reference_asset_id', NEW.reference_asset_id,
+        'status', NEW.status,
+        'created_at', NEW.created_at
+    ));
+END;
+CREATE TRIGGER audit_journal_entries_update AFTER UPDATE ON journal_entries
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES (
+        (SELECT actor FROM audit_context),
+        CASE WHEN OLD.status IS NOT NEW.status THEN 'STATUS_CHANGE' ELSE 'UPDATE' END,
+        'journal_entries',
+        NEW.id,
+        json_object(
+            'id', OLD.id,
+            'date', OLD.date,
+            'description', OLD.description,
+            'reference_number', OLD.reference_number,
+            'reference_asset_id', OLD.reference_asset_id,
+            'status', OLD.status,
+            'created_at', OLD.created_at
+        ),
+        json_object(
+            'id', NEW.id,
+            'date', NEW.date,
+            'description', NEW.description,
+            'reference_number', NEW.reference_number,
+            'reference_asset_id', NEW.reference_asset_id,
+            'status', NEW.status,
+            'created_at', NEW.created_at
+        )
+    );
+END;
+CREATE TRIGGER audit_journal_entries_delete AFTER DELETE ON journal_entries
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'DELETE', 'journal_entries', OLD.id, json_object(
+        'id', OLD.id,
+        'date', OLD.date,
+        'description', OLD.description,
+        'reference_number', OLD.reference_number,
+        'reference_asset_id', OLD.reference_asset_id,
+        'status', OLD.status,
+        'created_at', OLD.created_at
+    ), NULL);
+END;
+
+CREATE TRIGGER audit_journal_entry_lines_insert AFTER INSERT ON journal_entry_lines
+BEGIN
+    INSERT INTO audit_log (
+        actor, operation, table_name, record_id, journal_entry_id, before, after
+    )
+    VALUES ((SELECT actor FROM audit_context), 'INSERT', 'journal_entry_lines', NEW.id, NEW.journal_entry_id, NULL, json_object(
+        'id', NEW.id,
+        'journal_entry_id', NEW.journal_entry_id,
+        'account_id', NEW.account_id,
+        'asset_id', NEW.asset_id,
+        'entry_type', NEW.entry_type,
+        'amount', NEW.amount,
+        'reference_amount', NEW.reference_amount,
+        'description', NEW.description
+    ));
+END;
+CREATE TRIGGER audit_journal_entry_lines_update AFTER UPDATE ON journal_entry_lines
+BEGIN
+    INSERT INTO audit_log (
+        actor, operation, table_name, record_id, journal_entry_id, before, after
+    )
+    VALUES (
+        (SELECT actor FROM audit_context),
+        'UPDATE',
+        'journal_entry_lines',
+        NEW.id,
+        NEW.journal_entry_id,
+        json_object(
+            'id', OLD.id,
+            'journal_entry_id', OLD.journal_entry_id,
+            'account_id', OLD.account_id,
+            'asset_id', OLD.asset_id,
+            'entry_type', OLD.entry_type,
+            'amount', OLD.amount,
+            'reference_amount', OLD.reference_amount,
+            'description', OLD.description
+        ),
+        json_object(
+            'id', NEW.id,
+            'journal_entry_id', NEW.journal_entry_id,
+            'account_id', NEW.account_id,
+            'asset_id', NEW.asset_id,
+            'entry_type', NEW.entry_type,
+            'amount', NEW.amount,
+            'reference_amount', NEW.reference_amount,
+            'description', NEW.description
+        )
+    );
+END;
+CREATE TRIGGER audit_journal_entry_lines_delete AFTER DELETE ON journal_entry_lines
+BEGIN
+    INSERT INTO audit_log (
+        actor, operation, table_name, record_id, journal_entry_id, before, after
+    )
+    VALUES ((SELECT actor FROM audit_context), 'DELETE', 'journal_entry_lines', OLD.id, OLD.journal_entry_id, json_object(
+        'id', OLD.id,
+        'journal_entry_id', OLD.journal_entry_id,
+        'account_id', OLD.account_id,
+        'asset_id', OLD.asset_id,
+        'entry_type', OLD.entry_type,
+        'amount', OLD.amount,
+        'reference_amount', OLD.reference_amount,
+        'description', OLD.description
+    ), NULL);
+END;
+
+PRAGMA user_version = 4;
@@ -0,0 +1,23 @@
+WITH RECURSIVE subtree(id) AS (
+    SELECT id FROM accounts WHERE account_number = :account_number
+    UNION ALL
+    SELECT a.id FROM accounts a JOIN subtree s ON a.parent_account_id = s.id
+)
+SELECT
+    date(je.date) AS day,
+    jel.asset_id,
+    dec_sum(
+        CASE
+            WHEN jel.entry_type = at.normal_balance THEN jel.amount
+            ELSE dec_neg(jel.amount)
+        END
+    ) AS quantity
+FROM journal_entry_lines jel
+JOIN journal_entries je ON je.id = jel.journal_entry_id
+JOIN accounts a ON a.id = jel.account_id
+JOIN account_types at ON at.id = a.account_type_id
+WHERE je.status = 'POSTED'
+  AND date(je.date) <= :to
+  AND jel.account_id IN (SELECT id FROM subtree)
+GROUP BY day, jel.asset_id
+ORDER BY day, jel.asset_id;
@@ -0,0 +1,13 @@
+WITH RECURSIVE subtree(id) AS (
+    SELECT id FROM accounts WHERE account_number = :account_number
+    UNION ALL
+    SELECT a.id FROM accounts a JOIN subtree s ON a.parent_account_id = s.id
+)
+SELECT ast.code, cost.code, dec_sum(l.cost_basis)
+FROM lots l
+JOIN assets ast ON ast.id = l.asset_id
+JOIN assets cost ON cost.id = l.cost_asset_id
+WHERE l.account_id IN (SELECT id FROM subtree)
+  AND dec_sign(l.quantity) != 0
+  AND l.acquired_date <= :as_of
+GROUP BY ast.code, cost.code;
@@ -0,0 +1,26 @@
+WITH RECURSIVE subtree(id) AS (
+    SELECT id FROM accounts WHERE account_number = :account_number
+    UNION ALL
+    SELECT a.id FROM accounts a JOIN subtree s ON a.parent_account_id = s.id
+)
+SELECT
+    je.id,
+    date(je.date) AS day,
+    jel.account_id IN (SELECT id FROM subtree) AS in_portfolio,
+    at.name AS account_type,
+    ast.code AS asset,
+    jel.entry_type,
+    jel.amount
+FROM journal_entries je
+JOIN journal_entry_lines jel ON jel.journal_entry_id = je.id
+JOIN accounts a ON a.id = jel.account_id
+JOIN account_types at ON at.id = a.account_type_id
+JOIN assets ast ON ast.id = jel.asset_id
+WHERE je.status = 'POSTED'
+  AND date(je.date) BETWEEN :from AND :to
+  AND je.id IN (
+      SELECT journal_entry_id
+      FROM journal_entry_lines
+      WHERE account_id IN (SELECT id FROM subtree)
+  )
+ORDER BY je.date, je.id, jel.id;
@@ -1,3 +1,6 @@
+-- Amounts, rates and quantities are stored as TEXT decimals (see money.rs)
+-- and aggregated with the dec_* SQL functions.
+
 -- Account Types
 CREATE TABLE account_types (
     id INTEGER PRIMARY KEY,
@@ -13,6 +16,7 @@ CREATE TABLE assets (
     code TEXT NOT NULL UNIQUE,
     name TEXT NOT NULL,
     type TEXT NOT NULL CHECK(type IN ('FIAT', 'STOCK', 'BOND', 'ETF', 'ETC', 'ETN', 'CRYPTO', 'COMMODITY')),
+    decimals INTEGER NOT NULL DEFAULT 2 CHECK(decimals BETWEEN 0 AND 28),
     description TEXT
 );
 CREATE INDEX idx_assets_code ON assets(code); -- for asset code lookup
@@ -24,7 +28,7 @@ CREATE TABLE exchange_rates (
     id INTEGER PRIMARY KEY,
     from_asset_id INTEGER NOT NULL,
     to_asset_id INTEGER NOT NULL,
-    rate DECIMAL(19,8) NOT NULL,
+    rate TEXT NOT NULL,
     date DATETIME NOT NULL,
     FOREIGN KEY (from_asset_id) REFERENCES assets(id),
     FOREIGN KEY (to_asset_id) REFERENCES assets(id),
@@ -60,8 +64,10 @@ CREATE TABLE journal_entries (
     date DATETIME NOT NULL,
     description TEXT NOT NULL,
     reference_number TEXT,
+    reference_asset_id INTEGER,
     status TEXT CHECK(status IN ('DRAFT', 'POSTED', 'VOID')) DEFAULT 'DRAFT',
-    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (reference_asset_id) REFERENCES assets(id)
 );
 CREATE INDEX idx_journal_entries_date ON journal_entries(date);
 CREATE INDEX idx_journal_entries_status ON journal_entries(status);
@@ -76,7 +82,8 @@ CREATE TABLE journal_entry_lines (
     account_id INTEGER NOT NULL,
     asset_id INTEGER NOT NULL,
     entry_type TEXT CHECK(entry_type IN ('DEBIT', 'CREDIT')) NOT NULL,
-    amount INTEGER NOT NULL,
+    amount TEXT NOT NULL,
+    reference_amount TEXT, -- value in the entry's reference asset, if the line's asset differs
     description TEXT,
     FOREIGN KEY (journal_entry_id) REFERENCES journal_entries(id),
     FOREIGN KEY (account_id) REFERENCES accounts(id),
@@ -86,3 +93,454 @@ CREATE INDEX idx_journal_entry_lines_entry ON journal_entry_lines(journal_entry_
 CREATE INDEX idx_journal_entry_lines_account ON journal_entry_lines(account_id);
 CREATE INDEX idx_journal_entry_lines_asset ON journal_entry_lines(asset_id);
 CREATE INDEX idx_journal_entry_lines_account_asset ON journal_entry_lines(account_id, asset_id); -- For balance queries
+
+-- Asset Prices (market quotes for non-fiat assets, kept apart from FX rates)
+CREATE TABLE asset_prices (
+    id INTEGER PRIMARY KEY,
+    asset_id INTEGER NOT NULL,
+    quote_asset_id INTEGER NOT NULL,
+    date DATE NOT NULL,
+    open TEXT,
+    high TEXT,
+    low TEXT,
+    close TEXT NOT NULL,
+    FOREIGN KEY (asset_id) REFERENCES assets(id),
+    FOREIGN KEY (quote_asset_id) REFERENCES assets(id),
+    UNIQUE(asset_id, quote_asset_id, date)
+);
+CREATE INDEX idx_asset_prices_lookup ON asset_prices(asset_id, quote_asset_id, date); -- For price_at lookups
+
+-- Lots (open positions and their acquisition cost, for lot-aware holdings)
+CREATE TABLE lots (
+    id INTEGER PRIMARY KEY,
+    account_id INTEGER NOT NULL,
+    asset_id INTEGER NOT NULL,
+    journal_entry_id INTEGER,
+    acquired_date DATE NOT NULL,
+    quantity TEXT NOT NULL, -- remaining open quantity
+    cost_basis TEXT NOT NULL, -- cost of the remaining quantity
+    cost_asset_id INTEGER NOT NULL,
+    FOREIGN KEY (account_id) REFERENCES accounts(id),
+    FOREIGN KEY (asset_id) REFERENCES assets(id),
+    FOREIGN KEY (journal_entry_id) REFERENCES journal_entries(id),
+    FOREIGN KEY (cost_asset_id) REFERENCES assets(id)
+);
+CREATE INDEX idx_lots_account_asset ON lots(account_id, asset_id);
+
+-- Corporate Actions (splits, ticker renames and mergers)
+CREATE TABLE corporate_actions (
+    id INTEGER PRIMARY KEY,
+    asset_id INTEGER NOT NULL,
+    type TEXT NOT NULL CHECK(type IN ('SPLIT', 'RENAME', 'MERGER')),
+    date DATE NOT NULL,
+    ratio_new INTEGER NOT NULL DEFAULT 1, -- units received ...
+    ratio_old INTEGER NOT NULL DEFAULT 1, -- ... for every this many units held
+    old_code TEXT,
+    new_code TEXT,
+    target_asset_id INTEGER,
+    description TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (asset_id) REFERENCES assets(id),
+    FOREIGN KEY (target_asset_id) REFERENCES assets(id),
+    CHECK(ratio_new > 0 AND ratio_old > 0)
+);
+CREATE INDEX idx_corporate_actions_asset_date ON corporate_actions(asset_id, date);
+
+-- Lot Adjustments (audit trail of lots rewritten by corporate actions)
+CREATE TABLE lot_adjustments (
+    id INTEGER PRIMARY KEY,
+    lot_id INTEGER NOT NULL,
+    corporate_action_id INTEGER NOT NULL,
+    asset_id_before INTEGER NOT NULL,
+    asset_id_after INTEGER NOT NULL,
+    quantity_before TEXT NOT NULL,
+    quantity_after TEXT NOT NULL,
+    FOREIGN KEY (lot_id) REFERENCES lots(id),
+    FOREIGN KEY (corporate_action_id) REFERENCES corporate_actions(id),
+    FOREIGN KEY (asset_id_before) REFERENCES assets(id),
+    FOREIGN KEY (asset_id_after) REFERENCES assets(id)
+);
+CREATE INDEX idx_lot_adjustments_lot ON lot_adjustments(lot_id);
+
+-- Dividends (investment income linked to the paying asset, for yield reports)
+CREATE TABLE dividends (
+    id INTEGER PRIMARY KEY,
+    journal_entry_id INTEGER NOT NULL,
+    asset_id INTEGER NOT NULL,
+    account_id INTEGER NOT NULL,
+    currency_asset_id INTEGER NOT NULL,
+    kind TEXT NOT NULL CHECK(kind IN ('DIVIDEND', 'INTEREST')),
+    date DATETIME NOT NULL,
+    gross TEXT NOT NULL,
+    withholding TEXT NOT NULL,
+    FOREIGN KEY (journal_entry_id) REFERENCES journal_entries(id),
+    FOREIGN KEY (asset_id) REFERENCES assets(id),
+    FOREIGN KEY (account_id) REFERENCES accounts(id),
+    FOREIGN KEY (currency_asset_id) REFERENCES assets(id)
+);
+CREATE INDEX idx_dividends_asset_date ON dividends(asset_id, date);
+
+-- Import Profiles (saved column mappings for bank CSV exports)
+CREATE TABLE import_profiles (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    delimiter TEXT NOT NULL DEFAULT ',',
+    has_headers BOOLEAN NOT NULL DEFAULT true,
+    skip_lines INTEGER NOT NULL DEFAULT 0, -- preamble lines before the header
+    date_column TEXT NOT NULL,
+    date_format TEXT NOT NULL,
+    amount_column TEXT, -- signed amount, positive for money in ...
+    debit_column TEXT, -- ... or money out ...
+    credit_column TEXT, -- ... and money in, in separate columns
+    description_column TEXT NOT NULL,
+    reference_column TEXT,
+    decimal_separator TEXT NOT NULL DEFAULT '.',
+    thousands_separator TEXT,
+    CHECK(amount_column IS NOT NULL OR (debit_column IS NOT NULL AND credit_column IS NOT NULL))
+);
+
+-- Imported Transactions (one row per statement line, to skip it when re-imported)
+CREATE TABLE imported_transactions (
+    id INTEGER PRIMARY KEY,
+    account_id INTEGER NOT NULL,
+    external_id TEXT NOT NULL, -- bank id such as the OFX FITID, or a fingerprint of the line
+    journal_entry_id INTEGER NOT NULL,
+    imported_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id),
+    FOREIGN KEY (journal_entry_id) REFERENCES journal_entries(id),
+    UNIQUE(account_id, external_id)
+);
+CREATE INDEX idx_imported_transactions_entry ON imported_transactions(journal_entry_id);
+
+-- External Accounts (bank or broker account ids mapped to ledger accounts for imports)
+CREATE TABLE external_accounts (
+    id INTEGER PRIMARY KEY,
+    external_id TEXT NOT NULL UNIQUE, -- e.g. the OFX ACCTID or an IBAN
+    account_id INTEGER NOT NULL,
+    FOREIGN KEY (account_id) REFERENCES accounts(id)
+);
+
+-- Audit Log (append-only record of every change to accounts, assets, rates
+-- and journal entries, written by the triggers below)
+--
+-- `before` and `after` hold the row as JSON in the shape of its model's serde
+-- representation, NULL for the side that does not exist. Dates are copied as
+-- stored and written as serde writes them by Database::history. The triggers
+-- are plain SQL, so changes made with other tools such as the sqlite3 shell
+-- are recorded too.
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY,
+    timestamp DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now')),
+    actor TEXT NOT NULL,
+    operation TEXT CHECK(operation IN ('INSERT', 'UPDATE', 'STATUS_CHANGE', 'DELETE')) NOT NULL,
+    table_name TEXT NOT NULL,
+    record_id INTEGER NOT NULL,
+    -- Entry of the line changed, for the history of an entry to take in its lines
+    journal_entry_id INTEGER,
+    before TEXT,
+    after TEXT
+);
+CREATE INDEX idx_audit_log_record ON audit_log(table_name, record_id);
+CREATE INDEX idx_audit_log_journal_entry ON audit_log(journal_entry_id);
+
+-- Who changes made from now on are recorded as made by, in a single row set
+-- by Database::set_actor. It is kept in the book, so other connections and
+-- tools share it until it is set again.
+CREATE TABLE audit_context (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    actor TEXT NOT NULL
+);
+INSERT INTO audit_context (id, actor) VALUES (1, 'unknown');
+
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'the audit log is append-only');
+END;
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'the audit log is append-only');
+END;
+
+CREATE TRIGGER audit_accounts_insert AFTER INSERT ON accounts
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'INSERT', 'accounts', NEW.id, NULL, json_object(
+        'id', NEW.id,
+        'account_number', NEW.account_number,
+        'name', NEW.name,
+        'account_type_id', NEW.account_type_id,
+        'parent_account_id', NEW.parent_account_id,
+        'is_active', json(CASE WHEN NEW.is_active THEN 'true' ELSE 'false' END),
+        'opening_date', NEW.opening_date,
+        'closing_date', NEW.closing_date,
+        'description', NEW.description
+    ));
+END;
+CREATE TRIGGER audit_accounts_update AFTER UPDATE ON accounts
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES (
+        (SELECT actor FROM audit_context),
+        'UPDATE',
+        'accounts',
+        NEW.id,
+        json_object(
+            'id', OLD.id,
+            'account_number', OLD.account_number,
+            'name', OLD.name,
+            'account_type_id', OLD.account_type_id,
+            'parent_account_id', OLD.parent_account_id,
+            'is_active', json(CASE WHEN OLD.is_active THEN 'true' ELSE 'false' END),
+            'opening_date', OLD.opening_date,
+            'closing_date', OLD.closing_date,
+            'description', OLD.description
+        ),
+        json_object(
+            'id', NEW.id,
+            'account_number', NEW.account_number,
+            'name', NEW.name,
+            'account_type_id', NEW.account_type_id,
+            'parent_account_id', NEW.parent_account_id,
+            'is_active', json(CASE WHEN NEW.is_active THEN 'true' ELSE 'false' END),
+            'opening_date', NEW.opening_date,
+            'closing_date', NEW.closing_date,
+            'description', NEW.description
+        )
+    );
+END;
+CREATE TRIGGER audit_accounts_delete AFTER DELETE ON accounts
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'DELETE', 'accounts', OLD.id, json_object(
+        'id', OLD.id,
+        'account_number', OLD.account_number,
+        'name', OLD.name,
+        'account_type_id', OLD.account_type_id,
+        'parent_account_id', OLD.parent_account_id,
+        'is_active', json(CASE WHEN OLD.is_active THEN 'true' ELSE 'false' END),
+        'opening_date', OLD.opening_date,
+        'closing_date', OLD.closing_date,
+        'description', OLD.description
+    ), NULL);
+END;
+
+CREATE TRIGGER audit_assets_insert AFTER INSERT ON assets
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'INSERT', 'assets', NEW.id, NULL, json_object(
+        'id', NEW.id,
+        'code', NEW.code,
+        'name', NEW.name,
+        'asset_type', NEW.type,
+        'decimals', NEW.decimals,
+        'description', NEW.description
+    ));
+END;
+CREATE TRIGGER audit_assets_update AFTER UPDATE ON assets
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES (
+        (SELECT actor FROM audit_context),
+        'UPDATE',
+        'assets',
+        NEW.id,
+        json_object(
+            'id', OLD.id,
+            'code', OLD.code,
+            'name', OLD.name,
+            'asset_type', OLD.type,
+            'decimals', OLD.decimals,
+            'description', OLD.description
+        ),
+        json_object(
+            'id', NEW.id,
+            'code', NEW.code,
+            'name', NEW.name,
+            'asset_type', NEW.type,
+            'decimals', NEW.decimals,
+            'description', NEW.description
+        )
+    );
+END;
+CREATE TRIGGER audit_assets_delete AFTER DELETE ON assets
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'DELETE', 'assets', OLD.id, json_object(
+        'id', OLD.id,
+        'code', OLD.code,
+        'name', OLD.name,
+        'asset_type', OLD.type,
+        'decimals', OLD.decimals,
+        'description', OLD.description
+    ), NULL);
+END;
+
+CREATE TRIGGER audit_exchange_rates_insert AFTER INSERT ON exchange_rates
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'INSERT', 'exchange_rates', NEW.id, NULL, json_object(
+        'id', NEW.id,
+        'from_asset_id', NEW.from_asset_id,
+        'to_asset_id', NEW.to_asset_id,
+        'rate', NEW.rate,
+        'date', NEW.date
+    ));
+END;
+CREATE TRIGGER audit_exchange_rates_update AFTER UPDATE ON exchange_rates
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES (
+        (SELECT actor FROM audit_context),
+        'UPDATE',
+        'exchange_rates',
+        NEW.id,
+        json_object(
+            'id', OLD.id,
+            'from_asset_id', OLD.from_asset_id,
+            'to_asset_id', OLD.to_asset_id,
+            'rate', OLD.rate,
+            'date', OLD.date
+        ),
+        json_object(
+            'id', NEW.id,
+            'from_asset_id', NEW.from_asset_id,
+            'to_asset_id', NEW.to_asset_id,
+            'rate', NEW.rate,
+            'date', NEW.date
+        )
+    );
+END;
+CREATE TRIGGER audit_exchange_rates_delete AFTER DELETE ON exchange_rates
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'DELETE', 'exchange_rates', OLD.id, json_object(
+        'id', OLD.id,
+        'from_asset_id', OLD.from_asset_id,
+        'to_asset_id', OLD.to_asset_id,
+        'rate', OLD.rate,
+        'date', OLD.date
+    ), NULL);
+END;
+
+CREATE TRIGGER audit_journal_entries_insert AFTER INSERT ON journal_entries
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'INSERT', 'journal_entries', NEW.id, NULL, json_object(
+        'id', NEW.id,
+        'date', NEW.date,
+        'description', NEW.description,
+        'reference_number', NEW.reference_number,
+        'reference_asset_id', NEW.reference_asset_id,
+        'status', NEW.status,
+        'created_at', NEW.created_at
+    ));
+END;
+CREATE TRIGGER audit_journal_entries_update AFTER UPDATE ON journal_entries
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES (
+        (SELECT actor FROM audit_context),
+        CASE WHEN OLD.status IS NOT NEW.status THEN 'STATUS_CHANGE' ELSE 'UPDATE' END,
+        'journal_entries',
+        NEW.id,
+        json_object(
+            'id', OLD.id,
+            'date', OLD.date,
+            'description', OLD.description,
+            'reference_number', OLD.reference_number,
+            'reference_asset_id', OLD.reference_asset_id,
+            'status', OLD.status,
+            'created_at', OLD.created_at
+        ),
+        json_object(
+            'id', NEW.id,
+            'date', NEW.date,
+            'description', NEW.description,
+            'reference_number', NEW.reference_number,
+            'reference_asset_id', NEW.reference_asset_id,
+            'status', NEW.status,
+            'created_at', NEW.created_at
+        )
+    );
+END;
+CREATE TRIGGER audit_journal_entries_delete AFTER DELETE ON journal_entries
+BEGIN
+    INSERT INTO audit_log (actor, operation, table_name, record_id, before, after)
+    VALUES ((SELECT actor FROM audit_context), 'DELETE', 'journal_entries', OLD.id, json_object(
+        'id', OLD.id,
+        'date', OLD.date,
+        'description', OLD.description,
+        'reference_number', OLD.reference_number,
+        'reference_asset_id', OLD.reference_asset_id,
+        'status', OLD.status,
+        'created_at', OLD.created_at
+    ), NULL);
+END;
+
+CREATE TRIGGER audit_journal_entry_lines_insert AFTER INSERT ON journal_entry_lines
+BEGIN
+    INSERT INTO audit_log (
+        actor, operation, table_name, record_id, journal_entry_id, before, after
+    )
+    VALUES ((SELECT actor FROM audit_context), 'INSERT', 'journal_entry_lines', NEW.id, NEW.journal_entry_id, NULL, json_object(
+        'id', NEW.id,
+        'journal_entry_id', NEW.journal_entry_id,
+        'account_id', NEW.account_id,
+        'asset_id', NEW.asset_id,
+        'entry_type', NEW.entry_type,
+        'amount', NEW.amount,
+        'reference_amount', NEW.reference_amount,
+        'description', NEW.description
+    ));
+END;
+CREATE TRIGGER audit_journal_entry_lines_update AFTER UPDATE ON journal_entry_lines
+BEGIN
+    INSERT INTO audit_log (
+        actor, operation, table_name, record_id, journal_entry_id, before, after
+    )
+    VALUES (
+        (SELECT actor FROM audit_context),
+        'UPDATE',
+        'journal_entry_lines',
+        NEW.id,
+        NEW.journal_entry_id,
+        json_object(
+            'id', OLD.id,
+            'journal_entry_id', OLD.journal_entry_id,
+            'account_id', OLD.account_id,
+            'asset_id', OLD.asset_id,
+            'entry_type', OLD.entry_type,
+            'amount', OLD.amount,
+            'reference_amount', OLD.reference_amount,
+            'description', OLD.description
+        ),
+        json_object(
+            'id', NEW.id,
+            'journal_entry_id', NEW.journal_entry_id,
+            'account_id', NEW.account_id,
+            'asset_id', NEW.asset_id,
+            'entry_type', NEW.entry_type,
+            'amount', NEW.amount,
+            'reference_amount', NEW.reference_amount,
+            'description', NEW.description
+        )
+    );
+END;
+CREATE TRIGGER audit_journal_entry_lines_delete AFTER DELETE ON journal_entry_lines
+BEGIN
+    INSERT INTO audit_log (
+        actor, operation, table_name, record_id, journal_entry_id, before, after
+    )
+    VALUES ((SELECT actor FROM audit_context), 'DELETE', 'journal_entry_lines', OLD.id, OLD.journal_entry_id, json_object(
+        'id', OLD.id,
+        'journal_entry_id', OLD.journal_entry_id,
+        'account_id', OLD.account_id,
+        'asset_id', OLD.asset_id,
+        'entry_type', OLD.entry_type,
+        'amount', OLD.amount,
+        'reference_amount', OLD.reference_amount,
+        'description', OLD.description
+    ), NULL);
+END;
+
+PRAGMA user_version = 4;
@@ -0,0 +1,23 @@
+INSERT INTO asset_prices (
+    asset_id,
+    quote_asset_id,
+    date,
+    open,
+    high,
+    low,
+    close
+) VALUES (
+    (SELECT id FROM assets WHERE code = :asset_code),
+    (SELECT id FROM assets WHERE code = :quote_asset_code),
+    :date,
+    :open,
+    :high,
+    :low,
+    :close
+)
+ON CONFLICT (asset_id, quote_asset_id, date) DO UPDATE SET
+    open = excluded.open,
+    high = excluded.high,
+    low = excluded.low,
+    close = excluded.close
+RETURNING id;
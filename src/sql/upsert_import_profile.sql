@@ -0,0 +1,43 @@
+INSERT INTO import_profiles (
+    name,
+    delimiter,
+    has_headers,
+    skip_lines,
+    date_column,
+    date_format,
+    amount_column,
+    debit_column,
+    credit_column,
+    description_column,
+    reference_column,
+    decimal_separator,
+    thousands_separator
+) VALUES (
+    :name,
+    :delimiter,
+    :has_headers,
+    :skip_lines,
+    :date_column,
+    :date_format,
+    :amount_column,
+    :debit_column,
+    :credit_column,
+    :description_column,
+    :reference_column,
+    :decimal_separator,
+    :thousands_separator
+)
+ON CONFLICT (name) DO UPDATE SET
+    delimiter = excluded.delimiter,
+    has_headers = excluded.has_headers,
+    skip_lines = excluded.skip_lines,
+    date_column = excluded.date_column,
+    date_format = excluded.date_format,
+    amount_column = excluded.amount_column,
+    debit_column = excluded.debit_column,
+    credit_column = excluded.credit_column,
+    description_column = excluded.description_column,
+    reference_column = excluded.reference_column,
+    decimal_separator = excluded.decimal_separator,
+    thousands_separator = excluded.thousands_separator
+RETURNING id;
@@ -0,0 +1,562 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+use crate::{
+    error::Result,
+    interface::{Database, GeneralBalanceReport},
+    journal::RegisterLine,
+    models::{Account, AccountType, Asset, AssetType, EntryStatus, NewJournalEntry, NormalBalance},
+};
+
+/// The core bookkeeping operations, independent of where the book is kept.
+///
+/// [`Database`] implements it on SQLite and [`MemoryStore`](crate::MemoryStore)
+/// on plain maps. Both report the same errors for the same mistakes: a missing
+/// account or entry is [`Error::NotFound`](crate::Error::NotFound), a rejected
+/// entry is [`Error::InvalidData`](crate::Error::InvalidData).
+pub trait LedgerStore {
+    fn create_account_type(
+        &mut self,
+        name: &str,
+        normal_balance: NormalBalance,
+        description: Option<&str>,
+    ) -> Result<i64>;
+
+    /// Every account type, in id order
+    fn list_account_types(&self) -> Result<Vec<AccountType>>;
+
+    fn create_asset(
+        &mut self,
+        code: &str,
+        name: &str,
+        asset_type: AssetType,
+        decimals: i64,
+        description: Option<&str>,
+    ) -> Result<i64>;
+
+    fn get_asset(&self, code: &str) -> Result<Option<Asset>>;
+
+    /// Every asset, in id order
+    fn list_assets(&self) -> Result<Vec<Asset>>;
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_account(
+        &mut self,
+        account_number: &str,
+        name: &str,
+        account_type_id: i64,
+        parent_account_id: Option<i64>,
+        is_active: bool,
+        opening_date: NaiveDate,
+        closing_date: Option<NaiveDate>,
+        description: Option<&str>,
+    ) -> Result<i64>;
+
+    /// Every account, in id order
+    fn list_accounts(&self) -> Result<Vec<Account>>;
+
+    /// Closes an account without a balance on `closing_date`
+    fn close_account(&mut self, account_number: &str, closing_date: NaiveDate) -> Result<()>;
+
+    fn create_exchange_rate(
+        &mut self,
+        from_asset_code: &str,
+        to_asset_code: &str,
+        rate: Decimal,
+        date: DateTime<Utc>,
+    ) -> Result<i64>;
+
+    /// The most recent rate at or before `date`, inverting the opposite pair
+    /// if that is all there is
+    fn exchange_rate_at(
+        &self,
+        from_asset_code: &str,
+        to_asset_code: &str,
+        date: DateTime<Utc>,
+    ) -> Result<Option<Decimal>>;
+
+    fn create_journal_entry(&mut self, entry: &NewJournalEntry) -> Result<i64>;
+
+    fn post_journal_entry(&mut self, journal_entry_id: i64) -> Result<()>;
+
+    fn void_journal_entry(&mut self, journal_entry_id: i64) -> Result<()>;
+
+    /// The journal entries with `status` in date order, each with its id
+    fn journal_entries(&self, status: EntryStatus) -> Result<Vec<(i64, NewJournalEntry)>>;
+
+    /// The draft and posted lines of an account with a running balance per asset
+    fn register(&self, account_number: &str) -> Result<Vec<RegisterLine>>;
+
+    /// Posted balances per account and asset, in each account's natural sign
+    fn get_general_balance(&self) -> Result<Vec<GeneralBalanceReport>>;
+}
+
+impl LedgerStore for Database {
+    fn create_account_type(
+        &mut self,
+        name: &str,
+        normal_balance: NormalBalance,
+        description: Option<&str>,
+    ) -> Result<i64> {
+        Ok(Database::create_account_type(
+            self,
+            name,
+            normal_balance,
+            description,
+        )?)
+    }
+
+    fn list_account_types(&self) -> Result<Vec<AccountType>> {
+        Ok(Database::list_account_types(self)?)
+    }
+
+    fn create_asset(
+        &mut self,
+        code: &str,
+        name: &str,
+        asset_type: AssetType,
+        decimals: i64,
+        description: Option<&str>,
+    ) -> Result<i64> {
+        Ok(Database::create_asset(
+            self,
+            code,
+            name,
+            asset_type,
+            decimals,
+            description,
+        )?)
+    }
+
+    fn get_asset(&self, code: &str) -> Result<Option<Asset>> {
+        Ok(Database::get_asset(self, code)?)
+    }
+
+    fn list_assets(&self) -> Result<Vec<Asset>> {
+        Ok(Database::list_assets(self)?)
+    }
+
+    fn create_account(
+        &mut self,
+        account_number: &str,
+        name: &str,
+        account_type_id: i64,
+        parent_account_id: Option<i64>,
+        is_active: bool,
+        opening_date: NaiveDate,
+        closing_date: Option<NaiveDate>,
+        description: Option<&str>,
+    ) -> Result<i64> {
+        Ok(Database::create_account(
+            self,
+            account_number,
+            name,
+            account_type_id,
+            parent_account_id,
+            is_active,
+            opening_date,
+            closing_date,
+            description,
+        )?)
+    }
+
+    fn list_accounts(&self) -> Result<Vec<Account>> {
+        Ok(Database::list_accounts(self)?)
+    }
+
+    fn close_account(&mut self, account_number: &str, closing_date: NaiveDate) -> Result<()> {
+        Database::close_account(self, account_number, closing_date)
+    }
+
+    fn create_exchange_rate(
+        &mut self,
+        from_asset_code: &str,
+        to_asset_code: &str,
+        rate: Decimal,
+        date: DateTime<Utc>,
+    ) -> Result<i64> {
+        Ok(Database::create_exchange_rate(
+            self,
+            from_asset_code,
+            to_asset_code,
+            rate,
+            date,
+        )?)
+    }
+
+    fn exchange_rate_at(
+        &self,
+        from_asset_code: &str,
+        to_asset_code: &str,
+        date: DateTime<Utc>,
+    ) -> Result<Option<Decimal>> {
+        Ok(Database::exchange_rate_at(
+            self,
+            from_asset_code,
+            to_asset_code,
+            date,
+        )?)
+    }
+
+    fn create_journal_entry(&mut self, entry: &NewJournalEntry) -> Result<i64> {
+        Database::create_journal_entry(self, entry)
+    }
+
+    fn post_journal_entry(&mut self, journal_entry_id: i64) -> Result<()> {
+        Database::post_journal_entry(self, journal_entry_id)
+    }
+
+    fn void_journal_entry(&mut self, journal_entry_id: i64) -> Result<()> {
+        Database::void_journal_entry(self, journal_entry_id)
+    }
+
+    fn journal_entries(&self, status: EntryStatus) -> Result<Vec<(i64, NewJournalEntry)>> {
+        Database::journal_entries(self, status)
+    }
+
+    fn register(&self, account_number: &str) -> Result<Vec<RegisterLine>> {
+        Database::register(self, account_number)
+    }
+
+    fn get_general_balance(&self) -> Result<Vec<GeneralBalanceReport>> {
+        Ok(Database::get_general_balance(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        error::Error, models::NewJournalEntryLine, money::Money, seeding::init_sample_data,
+    };
+
+    /// Runs each conformance check against both backends
+    macro_rules! conformance {
+        ($($check:ident),* $(,)?) => {
+            mod sqlite {
+                $(
+                    #[test]
+                    fn $check() -> crate::error::Result<()> {
+                        let mut db = crate::interface::Database::new_in_memory()?;
+                        db.init_schema()?;
+                        super::$check(&mut db)
+                    }
+                )*
+            }
+
+            mod memory {
+                $(
+                    #[test]
+                    fn $check() -> crate::error::Result<()> {
+                        super::$check(&mut crate::memory::MemoryStore::new())
+                    }
+                )*
+            }
+        };
+    }
+
+    conformance!(
+        catalog,
+        exchange_rates,
+        entry_lifecycle,
+        register_and_balance,
+        close_account,
+    );
+
+    fn date(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, day, 12, 0, 0).unwrap()
+    }
+
+    fn entry(day: u32, status: EntryStatus, lines: &[(&str, &str, Decimal)]) -> NewJournalEntry {
+        NewJournalEntry {
+            date: date(day),
+            description: format!("Entry on day {day}"),
+            reference_number: None,
+            reference_asset_code: "USD".to_string(),
+            status,
+            lines: lines
+                .iter()
+                .map(|&(account, asset, amount)| NewJournalEntryLine {
+                    account_number: account.to_string(),
+                    asset_code: asset.to_string(),
+                    entry_type: if amount.is_sign_negative() {
+                        NormalBalance::Credit
+                    } else {
+                        NormalBalance::Debit
+                    },
+                    amount: Money::new(amount.abs()),
+                    reference_amount: None,
+                    description: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn balances(store: &dyn LedgerStore) -> Result<Vec<(String, String, Money)>> {
+        Ok(store
+            .get_general_balance()?
+            .into_iter()
+            .map(|row| (row.account_number, row.asset, row.balance))
+            .collect())
+    }
+
+    fn catalog(store: &mut dyn LedgerStore) -> Result<()> {
+        init_sample_data(store)?;
+
+        let account_types = store.list_account_types()?;
+        assert_eq!(account_types.len(), 5);
+        assert_eq!(account_types[3].name, "Income");
+        assert_eq!(account_types[3].normal_balance, NormalBalance::Credit);
+
+        let codes: Vec<String> = store.list_assets()?.into_iter().map(|a| a.code).collect();
+        assert_eq!(codes, ["USD", "EUR", "AAPL", "VWCE", "ETH", "BTC"]);
+        let eth = store.get_asset("ETH")?.unwrap();
+        assert_eq!((eth.id, eth.decimals), (5, 18));
+        assert!(store.get_asset("XAU")?.is_none());
+
+        let accounts = store.list_accounts()?;
+        let checking = accounts
+            .iter()
+            .find(|a| a.account_number == "1101")
+            .unwrap();
+        let cash = accounts
+            .iter()
+            .find(|a| a.account_number == "1100")
+            .unwrap();
+        assert_eq!(checking.parent_account_id, Some(cash.id));
+        assert!(checking.is_active);
+        assert!(accounts.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+        // Names and codes are unique, decimals bounded
+        assert!(store
+            .create_account_type("Asset", NormalBalance::Debit, None)
+            .is_err());
+        assert!(store
+            .create_asset("USD", "Dollar", AssetType::Fiat, 2, None)
+            .is_err());
+        assert!(store
+            .create_asset("XAU", "Gold", AssetType::Commodity, 29, None)
+            .is_err());
+        let opened = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        assert!(store
+            .create_account("1101", "Again", 1, None, true, opened, None, None)
+            .is_err());
+
+        let id = store.create_asset("XAU", "Gold", AssetType::Commodity, 4, Some("Troy ounce"))?;
+        let gold = store.get_asset("XAU")?.unwrap();
+        assert_eq!(gold.id, id);
+        assert_eq!(gold.description.as_deref(), Some("Troy ounce"));
+
+        Ok(())
+    }
+
+    fn exchange_rates(store: &mut dyn LedgerStore) -> Result<()> {
+        init_sample_data(store)?;
+
+        store.create_exchange_rate("USD", "EUR", dec!(0.90), date(2))?;
+        store.create_exchange_rate("EUR", "USD", dec!(1.25), date(4))?;
+        assert!(store
+            .create_exchange_rate("USD", "EUR", dec!(0.95), date(2))
+            .is_err());
+        assert!(store
+            .create_exchange_rate("USD", "XAU", dec!(0.0003), date(2))
+            .is_err());
+
+        assert_eq!(store.exchange_rate_at("USD", "EUR", date(1))?, None);
+        assert_eq!(
+            store.exchange_rate_at("USD", "EUR", date(3))?,
+            Some(dec!(0.9))
+        );
+        assert_eq!(
+            store.exchange_rate_at("EUR", "USD", date(3))?,
+            Some(Decimal::ONE / dec!(0.9))
+        );
+        assert_eq!(
+            store.exchange_rate_at("USD", "EUR", date(4))?,
+            Some(dec!(0.8))
+        );
+
+        // On the same date the direct pair wins
+        store.create_exchange_rate("USD", "EUR", dec!(0.5), date(4))?;
+        assert_eq!(
+            store.exchange_rate_at("USD", "EUR", date(5))?,
+            Some(dec!(0.5))
+        );
+        assert_eq!(
+            store.exchange_rate_at("EUR", "USD", date(5))?,
+            Some(dec!(1.25))
+        );
+
+        // A zero rate has no inverse
+        store.create_exchange_rate("BTC", "ETH", Decimal::ZERO, date(1))?;
+        assert_eq!(store.exchange_rate_at("ETH", "BTC", date(5))?, None);
+        assert_eq!(store.exchange_rate_at("USD", "XAU", date(5))?, None);
+
+        Ok(())
+    }
+
+    fn entry_lifecycle(store: &mut dyn LedgerStore) -> Result<()> {
+        init_sample_data(store)?;
+
+        let unbalanced = entry(
+            3,
+            EntryStatus::Posted,
+            &[("5301", "USD", dec!(45)), ("1101", "USD", dec!(-40))],
+        );
+        assert!(matches!(
+            store.create_journal_entry(&unbalanced),
+            Err(Error::InvalidData(_))
+        ));
+        let unknown = entry(
+            3,
+            EntryStatus::Posted,
+            &[("5301", "USD", dec!(45)), ("9999", "USD", dec!(-45))],
+        );
+        assert!(matches!(
+            store.create_journal_entry(&unknown),
+            Err(Error::InvalidData(_))
+        ));
+        let mut unknown_asset = entry(
+            3,
+            EntryStatus::Posted,
+            &[("5301", "USD", dec!(45)), ("1101", "USD", dec!(-45))],
+        );
+        unknown_asset.reference_asset_code = "XAU".to_string();
+        assert!(matches!(
+            store.create_journal_entry(&unknown_asset),
+            Err(Error::InvalidData(_))
+        ));
+
+        let later = store.create_journal_entry(&entry(
+            9,
+            EntryStatus::Draft,
+            &[
+                ("1101", "USD", dec!(3000.00)),
+                ("4100", "USD", dec!(-3000.00)),
+            ],
+        ))?;
+        let earlier = store.create_journal_entry(&entry(
+            5,
+            EntryStatus::Draft,
+            &[("5301", "USD", dec!(45.20)), ("1101", "USD", dec!(-45.20))],
+        ))?;
+
+        let drafts = store.journal_entries(EntryStatus::Draft)?;
+        let ids: Vec<i64> = drafts.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, [earlier, later]);
+        assert_eq!(drafts[1].1.lines[0].amount, Money::new(dec!(3000)));
+        assert_eq!(drafts[1].1.lines[1].entry_type, NormalBalance::Credit);
+        assert!(store.get_general_balance()?.is_empty());
+
+        store.post_journal_entry(later)?;
+        assert!(matches!(
+            store.post_journal_entry(later),
+            Err(Error::InvalidData(_))
+        ));
+        assert_eq!(store.journal_entries(EntryStatus::Posted)?.len(), 1);
+
+        store.void_journal_entry(earlier)?;
+        assert!(matches!(
+            store.void_journal_entry(earlier),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(matches!(
+            store.post_journal_entry(earlier),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(matches!(store.void_journal_entry(99), Err(Error::NotFound)));
+        assert!(matches!(store.post_journal_entry(99), Err(Error::NotFound)));
+        assert!(store.journal_entries(EntryStatus::Draft)?.is_empty());
+        assert_eq!(store.journal_entries(EntryStatus::Void)?.len(), 1);
+
+        Ok(())
+    }
+
+    fn register_and_balance(store: &mut dyn LedgerStore) -> Result<()> {
+        init_sample_data(store)?;
+
+        store.create_journal_entry(&entry(
+            1,
+            EntryStatus::Posted,
+            &[("1101", "USD", dec!(3000)), ("4100", "USD", dec!(-3000))],
+        ))?;
+        store.create_journal_entry(&entry(
+            3,
+            EntryStatus::Draft,
+            &[("5301", "USD", dec!(45.20)), ("1101", "USD", dec!(-45.20))],
+        ))?;
+        let mut eth = entry(
+            2,
+            EntryStatus::Posted,
+            &[("1202", "ETH", dec!(0.5)), ("1101", "USD", dec!(-1500))],
+        );
+        eth.lines[0].reference_amount = Some(Money::new(dec!(1500)));
+        store.create_journal_entry(&eth)?;
+
+        let register = store.register("1101")?;
+        let running: Vec<(i64, Money)> = register
+            .iter()
+            .map(|line| (line.journal_entry_id, line.balance))
+            .collect();
+        assert_eq!(
+            running,
+            [
+                (1, Money::new(dec!(3000))),
+                (3, Money::new(dec!(1500))),
+                (2, Money::new(dec!(1454.8))),
+            ]
+        );
+        assert_eq!(register[2].amount, Money::new(dec!(-45.2)));
+        assert_eq!(register[2].status, EntryStatus::Draft);
+        assert_eq!(register[1].description, "Entry on day 2");
+        assert!(matches!(store.register("9999"), Err(Error::NotFound)));
+
+        let usd = |amount| ("USD".to_string(), Money::new(amount));
+        let expected: Vec<(String, String, Money)> = [
+            ("1101", usd(dec!(1500))),
+            ("1202", ("ETH".to_string(), Money::new(dec!(0.5)))),
+            ("4100", usd(dec!(3000))),
+        ]
+        .into_iter()
+        .map(|(account, (asset, balance))| (account.to_string(), asset, balance))
+        .collect();
+        assert_eq!(balances(store)?, expected);
+
+        Ok(())
+    }
+
+    fn close_account(store: &mut dyn LedgerStore) -> Result<()> {
+        init_sample_data(store)?;
+        let closed = NaiveDate::from_ymd_opt(2025, 6, 30).unwrap();
+
+        let id = store.create_journal_entry(&entry(
+            3,
+            EntryStatus::Draft,
+            &[("5301", "USD", dec!(45.20)), ("1103", "USD", dec!(-45.20))],
+        ))?;
+        assert!(matches!(
+            store.close_account("1103", closed),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(matches!(
+            store.close_account("9999", closed),
+            Err(Error::NotFound)
+        ));
+
+        store.void_journal_entry(id)?;
+        store.close_account("1103", closed)?;
+
+        let accounts = store.list_accounts()?;
+        let wallet = accounts
+            .iter()
+            .find(|a| a.account_number == "1103")
+            .unwrap();
+        assert!(!wallet.is_active);
+        assert_eq!(wallet.closing_date, Some(closed));
+
+        Ok(())
+    }
+}
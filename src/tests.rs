@@ -1,11 +1,23 @@
 use crate::{
-    error::Result,
-    models::{AssetType, NormalBalance},
+    error::{Error, Result},
+    interface::Database,
+    models::{
+        AssetType, EntryStatus, IncomeKind, NewJournalEntry, NewJournalEntryLine, NormalBalance,
+    },
+    money::Money,
+    prices::DEFAULT_PRICE_LOOKBACK_DAYS,
     seeding::init_sample_data,
 };
+#[cfg(feature = "importers")]
+use crate::{
+    models::{AmountColumns, CsvImportProfile},
+    plain_text::PlainTextFormat,
+    qif::QifOptions,
+};
 
 use super::*;
-use chrono::NaiveDate;
+use chrono::{NaiveDate, TimeZone, Utc};
+use rust_decimal_macros::dec;
 
 #[test]
 fn test_create_account_type() -> Result<()> {
@@ -24,11 +36,17 @@ fn test_create_asset() -> Result<()> {
     db.init_schema()?;
 
     let asset_type_id = db.create_account_type("Assets", NormalBalance::Debit, None)?;
-    let asset_id = db.create_asset("USD", "US Dollar", AssetType::Fiat, None)?;
+    let asset_id = db.create_asset("USD", "US Dollar", AssetType::Fiat, 2, None)?;
 
     assert!(asset_type_id > 0);
     assert!(asset_id > 0);
 
+    let asset = db.get_asset("USD")?.unwrap();
+    assert_eq!(asset.id, asset_id);
+    assert_eq!(asset.asset_type, AssetType::Fiat);
+    assert_eq!(asset.decimals, 2);
+    assert!(db.get_asset("XXX")?.is_none());
+
     Ok(())
 }
 
@@ -89,6 +107,1778 @@ fn test_init_sample_data() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_price_at_lookback() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db)?;
+
+    // Friday close, nothing over the weekend
+    let friday = NaiveDate::from_ymd_opt(2025, 3, 7).unwrap();
+    db.upsert_asset_price(
+        "AAPL",
+        "USD",
+        friday,
+        None,
+        None,
+        None,
+        Money::new(dec!(239.07)),
+    )?;
+
+    let sunday = NaiveDate::from_ymd_opt(2025, 3, 9).unwrap();
+    let price = db.price_at("AAPL", sunday, "USD", DEFAULT_PRICE_LOOKBACK_DAYS)?;
+    assert_eq!(
+        price.map(|p| (p.date, p.close)),
+        Some((friday, Money::new(dec!(239.07))))
+    );
+
+    // Outside of the lookback window there is no price
+    assert!(db.price_at("AAPL", sunday, "USD", 1)?.is_none());
+    // Nor before the first quote
+    assert!(db
+        .price_at("AAPL", friday.pred_opt().unwrap(), "USD", 7)?
+        .is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_import_asset_prices_csv() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db)?;
+
+    let csv = "Date,Open,High,Low,Close\n\
+               2025-01-02,100.0,101.5,99.0,101.0\n\
+               2025-01-03,,,,102.25\n";
+    assert_eq!(
+        db.import_asset_prices_csv("VWCE", "EUR", csv.as_bytes())?,
+        2
+    );
+
+    let jan_2 = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+    let price = db.price_at("VWCE", jan_2, "EUR", 0)?.unwrap();
+    assert_eq!(price.high, Some(Money::new(dec!(101.5))));
+    assert_eq!(price.close, Money::new(dec!(101.0)));
+
+    let jan_3 = NaiveDate::from_ymd_opt(2025, 1, 3).unwrap();
+    let price = db.price_at("VWCE", jan_3, "EUR", 0)?.unwrap();
+    assert_eq!(price.open, None);
+    assert_eq!(price.close, Money::new(dec!(102.25)));
+
+    // Re-importing replaces the existing quotes instead of failing
+    let csv = "date,close\n2025-01-03,103\n";
+    assert_eq!(
+        db.import_asset_prices_csv("VWCE", "EUR", csv.as_bytes())?,
+        1
+    );
+    let price = db.price_at("VWCE", jan_3, "EUR", 0)?.unwrap();
+    assert_eq!(price.close, Money::new(dec!(103)));
+
+    let csv = "date,close\n2025-01-06,oops\n";
+    assert!(db
+        .import_asset_prices_csv("VWCE", "EUR", csv.as_bytes())
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_valuation_report() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db)?;
+
+    let date = Utc.with_ymd_and_hms(2025, 2, 3, 10, 0, 0).unwrap();
+    db.insert_transaction(
+        date,
+        "Opening balance",
+        "OB-1",
+        EntryStatus::Posted,
+        "1101",
+        "3100",
+        "USD",
+        "USD",
+        Money::new(dec!(1000)),
+    )?;
+    db.insert_transaction(
+        date,
+        "Buy AAPL",
+        "INV-1",
+        EntryStatus::Posted,
+        "1201",
+        "3100",
+        "AAPL",
+        "AAPL",
+        Money::new(dec!(10)),
+    )?;
+
+    db.create_exchange_rate("EUR", "USD", dec!(1.25), date)?;
+    db.upsert_asset_price(
+        "AAPL",
+        "USD",
+        date.date_naive(),
+        None,
+        None,
+        None,
+        Money::new(dec!(200)),
+    )?;
+
+    let as_of = NaiveDate::from_ymd_opt(2025, 2, 4).unwrap();
+    let report = db.get_valuation_report(as_of, "EUR")?;
+
+    let value = |account: &str| {
+        report
+            .iter()
+            .find(|row| row.account_number == account)
+            .and_then(|row| row.value)
+    };
+
+    // USD is converted with the inverse of the EUR -> USD rate
+    assert_eq!(value("1101"), Some(Money::new(dec!(800))));
+    // AAPL is priced in USD and then converted to EUR
+    assert_eq!(value("1201"), Some(Money::new(dec!(1600))));
+
+    // Nothing was held yet the day before
+    let report = db.get_valuation_report(as_of - chrono::Days::new(2), "EUR")?;
+    assert!(report.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_stock_split_restates_lots_and_valuation() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db)?;
+
+    let bought = Utc.with_ymd_and_hms(2025, 3, 3, 15, 0, 0).unwrap();
+    let entry_id = db.insert_transaction(
+        bought,
+        "Buy AAPL",
+        "INV-1",
+        EntryStatus::Posted,
+        "1201",
+        "3100",
+        "AAPL",
+        "AAPL",
+        Money::new(dec!(10)),
+    )?;
+    db.open_lot(
+        "1201",
+        "AAPL",
+        bought.date_naive(),
+        Money::new(dec!(10)),
+        Money::new(dec!(1750)),
+        "USD",
+        Some(entry_id),
+    )?;
+
+    let before_split = NaiveDate::from_ymd_opt(2025, 3, 5).unwrap();
+    let split_date = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+    db.upsert_asset_price(
+        "AAPL",
+        "USD",
+        before_split,
+        None,
+        None,
+        None,
+        Money::new(dec!(180)),
+    )?;
+    db.upsert_asset_price(
+        "AAPL",
+        "USD",
+        split_date,
+        None,
+        None,
+        None,
+        Money::new(dec!(45)),
+    )?;
+
+    db.record_split("AAPL", split_date, 4, 1, Some("4:1 split"))?;
+
+    // The lot now holds four times the shares for the same total cost
+    let lots = db.open_lots("1201", "AAPL")?;
+    assert_eq!(lots.len(), 1);
+    assert_eq!(lots[0].quantity, Money::new(dec!(40)));
+    assert_eq!(lots[0].cost_basis, Money::new(dec!(1750)));
+
+    // The journal is untouched
+    let balance = db.get_general_balance()?;
+    let aapl = balance.iter().find(|row| row.asset == "AAPL").unwrap();
+    assert_eq!(aapl.balance, Money::new(dec!(10)));
+
+    // Valuations before the split use the old share count, after it the new one
+    let value = |as_of| -> Result<_> {
+        Ok(db
+            .get_valuation_report(as_of, "USD")?
+            .into_iter()
+            .find(|row| row.account_number == "1201")
+            .map(|row| (row.balance, row.value)))
+    };
+    assert_eq!(
+        value(before_split)?,
+        Some((Money::new(dec!(10)), Some(Money::new(dec!(1800)))))
+    );
+    assert_eq!(
+        value(split_date)?,
+        Some((Money::new(dec!(40)), Some(Money::new(dec!(1800)))))
+    );
+
+    let adjustments: i64 =
+        db.conn()
+            .query_row("SELECT COUNT(*) FROM lot_adjustments", [], |row| row.get(0))?;
+    assert_eq!(adjustments, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_split_keeps_the_precision_of_the_asset() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db)?;
+
+    // ETH is seeded with 18 decimals
+    let bought = NaiveDate::from_ymd_opt(2025, 3, 3).unwrap();
+    db.open_lot(
+        "1201",
+        "ETH",
+        bought,
+        Money::new(dec!(0.123456789012345678)),
+        Money::new(dec!(300)),
+        "USD",
+        None,
+    )?;
+
+    let split_date = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+    db.upsert_asset_price(
+        "ETH",
+        "USD",
+        split_date,
+        None,
+        None,
+        None,
+        Money::new(dec!(1000)),
+    )?;
+    db.record_split("ETH", split_date, 3, 1, None)?;
+
+    let lots = db.open_lots("1201", "ETH")?;
+    assert_eq!(lots[0].quantity, Money::new(dec!(0.370370367037037034)));
+
+    db.insert_transaction(
+        Utc.with_ymd_and_hms(2025, 3, 3, 15, 0, 0).unwrap(),
+        "Buy ETH",
+        "ETH-1",
+        EntryStatus::Posted,
+        "1201",
+        "3100",
+        "ETH",
+        "ETH",
+        Money::new(dec!(0.123456789012345678)),
+    )?;
+
+    // The balance keeps all 18 decimals, the value is rounded to cents
+    let row = db
+        .get_valuation_report(split_date, "USD")?
+        .into_iter()
+        .find(|row| row.account_number == "1201" && row.asset == "ETH")
+        .unwrap();
+    assert_eq!(row.balance, Money::new(dec!(0.370370367037037034)));
+    assert_eq!(row.value, Some(Money::new(dec!(370.37))));
+
+    Ok(())
+}
+
+#[test]
+fn test_rename_and_merger() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db)?;
+
+    let bought = NaiveDate::from_ymd_opt(2025, 1, 10).unwrap();
+    db.open_lot(
+        "1201",
+        "VWCE",
+        bought,
+        Money::new(dec!(3)),
+        Money::new(dec!(300)),
+        "EUR",
+        None,
+    )?;
+
+    let renamed = NaiveDate::from_ymd_opt(2025, 2, 1).unwrap();
+    db.record_rename("VWCE", "VWRA", renamed, None)?;
+    assert_eq!(db.open_lots("1201", "VWRA")?.len(), 1);
+
+    let merged = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+    db.record_merger("VWRA", "AAPL", merged, 1, 2, None)?;
+
+    assert!(db.open_lots("1201", "VWRA")?.is_empty());
+    let lots = db.open_lots("1201", "AAPL")?;
+    assert_eq!(lots[0].quantity, Money::new(dec!(1.5)));
+    assert_eq!(lots[0].cost_basis, Money::new(dec!(300)));
+
+    let actions = db.list_corporate_actions()?;
+    assert_eq!(actions.len(), 2);
+    assert_eq!(actions[0].old_code.as_deref(), Some("VWCE"));
+
+    assert!(db.record_split("AAPL", merged, 0, 1, None).is_err());
+    assert!(db
+        .record_merger("AAPL", "AAPL", merged, 1, 1, None)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_create_journal_entry_validation() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db)?;
+
+    let line = |account: &str, asset: &str, entry_type, amount, reference_amount: Option<_>| {
+        NewJournalEntryLine {
+            account_number: account.to_string(),
+            asset_code: asset.to_string(),
+            entry_type,
+            amount: Money::new(amount),
+            reference_amount: reference_amount.map(Money::new),
+            description: None,
+        }
+    };
+    let entry = |lines| NewJournalEntry {
+        date: Utc.with_ymd_and_hms(2025, 4, 1, 9, 0, 0).unwrap(),
+        description: "Buy AAPL".to_string(),
+        reference_number: None,
+        reference_asset_code: "USD".to_string(),
+        status: EntryStatus::Posted,
+        lines,
+    };
+
+    // Lines in different assets balance through their reference amounts
+    let unbalanced = db.create_journal_entry(&entry(vec![
+        line(
+            "1201",
+            "AAPL",
+            NormalBalance::Debit,
+            dec!(10),
+            Some(dec!(1750)),
+        ),
+        line("1101", "USD", NormalBalance::Credit, dec!(1740), None),
+    ]));
+    assert!(unbalanced.is_err());
+
+    db.create_journal_entry(&entry(vec![
+        line(
+            "1201",
+            "AAPL",
+            NormalBalance::Debit,
+            dec!(10),
+            Some(dec!(1750)),
+        ),
+        line("5503", "USD", NormalBalance::Debit, dec!(10), None),
+        line("1101", "USD", NormalBalance::Credit, dec!(1760), None),
+    ]))?;
+
+    // A foreign asset line without a reference amount cannot be balanced
+    assert!(db
+        .create_journal_entry(&entry(vec![
+            line("1201", "AAPL", NormalBalance::Debit, dec!(10), None),
+            line("1101", "USD", NormalBalance::Credit, dec!(1750), None),
+        ]))
+        .is_err());
+
+    // Unknown accounts are rejected and nothing is written
+    assert!(db
+        .create_journal_entry(&entry(vec![
+            line("9999", "USD", NormalBalance::Debit, dec!(5), None),
+            line("1101", "USD", NormalBalance::Credit, dec!(5), None),
+        ]))
+        .is_err());
+
+    let count: i64 = db
+        .conn()
+        .query_row("SELECT COUNT(*) FROM journal_entries", [], |row| row.get(0))?;
+    assert_eq!(count, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_record_dividend_with_withholding() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db)?;
+
+    let paid = Utc.with_ymd_and_hms(2025, 5, 15, 12, 0, 0).unwrap();
+    db.create_journal_entry(&NewJournalEntry {
+        date: paid,
+        description: "Buy AAPL".to_string(),
+        reference_number: None,
+        reference_asset_code: "USD".to_string(),
+        status: EntryStatus::Posted,
+        lines: vec![
+            NewJournalEntryLine {
+                account_number: "1201".to_string(),
+                asset_code: "AAPL".to_string(),
+                entry_type: NormalBalance::Debit,
+                amount: Money::new(dec!(100)),
+                reference_amount: Some(Money::new(dec!(20000))),
+                description: None,
+            },
+            NewJournalEntryLine {
+                account_number: "1101".to_string(),
+                asset_code: "USD".to_string(),
+                entry_type: NormalBalance::Credit,
+                amount: Money::new(dec!(20000)),
+                reference_amount: None,
+                description: None,
+            },
+        ],
+    })?;
+    db.upsert_asset_price(
+        "AAPL",
+        "USD",
+        paid.date_naive(),
+        None,
+        None,
+        None,
+        Money::new(dec!(200)),
+    )?;
+
+    db.record_dividend(
+        "AAPL",
+        "1201",
+        "USD",
+        Money::new(dec!(25)),
+        Money::new(dec!(3.75)),
+        paid,
+    )?;
+    db.record_interest(
+        "USD",
+        "1102",
+        "USD",
+        Money::new(dec!(4)),
+        Money::new(dec!(0)),
+        paid,
+    )?;
+
+    let balance = db.get_general_balance()?;
+    let balance_of = |account: &str| {
+        balance
+            .iter()
+            .find(|row| row.account_number == account && row.asset == "USD")
+            .map(|row| row.balance)
+    };
+    assert_eq!(balance_of("1201"), Some(Money::new(dec!(21.25))));
+    assert_eq!(balance_of("5504"), Some(Money::new(dec!(3.75))));
+    assert_eq!(balance_of("4201"), Some(Money::new(dec!(25))));
+    assert_eq!(balance_of("4203"), Some(Money::new(dec!(4))));
+
+    let dividends = db.list_dividends("AAPL")?;
+    assert_eq!(dividends.len(), 1);
+    assert_eq!(dividends[0].kind, IncomeKind::Dividend);
+    assert_eq!(dividends[0].withholding, Money::new(dec!(3.75)));
+
+    let day = paid.date_naive();
+    let report = db.get_income_yield_report(day, day, "USD")?;
+    let aapl = report.iter().find(|row| row.asset == "AAPL").unwrap();
+    assert_eq!(aapl.net, Money::new(dec!(21.25)));
+    assert_eq!(aapl.market_value, Some(Money::new(dec!(20000))));
+    assert_eq!(aapl.yield_rate, Some(dec!(0.00125)));
+
+    assert!(db
+        .record_dividend(
+            "AAPL",
+            "1201",
+            "USD",
+            Money::new(dec!(1)),
+            Money::new(dec!(2)),
+            paid
+        )
+        .is_err());
+
+    // All of it may be withheld, in which case nothing reaches the account
+    db.record_dividend(
+        "AAPL",
+        "1201",
+        "USD",
+        Money::new(dec!(2)),
+        Money::new(dec!(2)),
+        paid,
+    )?;
+    let balance = db.get_general_balance()?;
+    let balance_of = |account: &str| {
+        balance
+            .iter()
+            .find(|row| row.account_number == account && row.asset == "USD")
+            .map(|row| row.balance)
+    };
+    assert_eq!(balance_of("1201"), Some(Money::new(dec!(21.25))));
+    assert_eq!(balance_of("5504"), Some(Money::new(dec!(5.75))));
+    assert_eq!(balance_of("4201"), Some(Money::new(dec!(27))));
+
+    Ok(())
+}
+
+#[test]
+fn test_portfolio_performance() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db)?;
+
+    let at = |month, day| Utc.with_ymd_and_hms(2025, month, day, 12, 0, 0).unwrap();
+    let line = |account: &str, asset: &str, entry_type, amount, reference_amount: Option<_>| {
+        NewJournalEntryLine {
+            account_number: account.to_string(),
+            asset_code: asset.to_string(),
+            entry_type,
+            amount: Money::new(amount),
+            reference_amount: reference_amount.map(Money::new),
+            description: None,
+        }
+    };
+    let entry = |date, lines| NewJournalEntry {
+        date,
+        description: "Brokerage".to_string(),
+        reference_number: None,
+        reference_asset_code: "USD".to_string(),
+        status: EntryStatus::Posted,
+        lines,
+    };
+
+    // Contribution from the checking account, then a purchase inside the portfolio
+    db.create_journal_entry(&entry(
+        at(1, 1),
+        vec![
+            line("1201", "USD", NormalBalance::Debit, dec!(1000), None),
+            line("1101", "USD", NormalBalance::Credit, dec!(1000), None),
+        ],
+    ))?;
+    db.create_journal_entry(&entry(
+        at(1, 2),
+        vec![
+            line(
+                "1201",
+                "AAPL",
+                NormalBalance::Debit,
+                dec!(10),
+                Some(dec!(1000)),
+            ),
+            line("1201", "USD", NormalBalance::Credit, dec!(1000), None),
+        ],
+    ))?;
+    db.open_lot(
+        "1201",
+        "AAPL",
+        at(1, 2).date_naive(),
+        Money::new(dec!(10)),
+        Money::new(dec!(1000)),
+        "USD",
+        None,
+    )?;
+    // A dividend is a return, not a contribution
+    db.record_dividend(
+        "AAPL",
+        "1201",
+        "USD",
+        Money::new(dec!(10)),
+        Money::new(dec!(0)),
+        at(2, 15),
+    )?;
+
+    for (month, day, price) in [(1, 2, dec!(100)), (1, 31, dec!(110)), (2, 28, dec!(120))] {
+        let date = at(month, day).date_naive();
+        db.upsert_asset_price("AAPL", "USD", date, None, None, None, Money::new(price))?;
+    }
+
+    let from = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+    let to = NaiveDate::from_ymd_opt(2025, 2, 28).unwrap();
+    let performance = db.portfolio_performance("1200", from, to, "USD")?;
+
+    let total = &performance.total;
+    assert_eq!(total.start_value, Money::new(dec!(0)));
+    assert_eq!(total.end_value, Money::new(dec!(1210)));
+    assert_eq!(total.net_contributions, Money::new(dec!(1000)));
+    assert_eq!(total.absolute_gain, Money::new(dec!(210)));
+    assert_eq!(total.time_weighted_return, Some(dec!(0.21)));
+    assert!(total.money_weighted_return.unwrap() > dec!(0.21));
+
+    let aapl = performance
+        .by_asset
+        .iter()
+        .find(|asset| asset.asset == "AAPL")
+        .unwrap();
+    assert_eq!(aapl.quantity, Money::new(dec!(10)));
+    assert_eq!(aapl.cost_basis, Some(Money::new(dec!(1000))));
+    assert_eq!(aapl.summary.time_weighted_return, Some(dec!(0.2)));
+
+    let months = &performance.by_month;
+    assert_eq!(months.len(), 2);
+    assert_eq!(months[0].summary.time_weighted_return, Some(dec!(0.1)));
+    assert_eq!(months[1].summary.time_weighted_return, Some(dec!(0.1)));
+    assert_eq!(months[1].summary.net_contributions, Money::new(dec!(0)));
+
+    // Holdings are restated for the splits that took effect by each date
+    let split = NaiveDate::from_ymd_opt(2025, 2, 15).unwrap();
+    db.record_split("AAPL", split, 2, 1, None)?;
+    db.upsert_asset_price("AAPL", "USD", to, None, None, None, Money::new(dec!(60)))?;
+    let performance = db.portfolio_performance("1200", from, to, "USD")?;
+    assert_eq!(performance.total.end_value, Money::new(dec!(1210)));
+    assert_eq!(performance.by_asset[0].quantity, Money::new(dec!(20)));
+    assert_eq!(
+        performance.by_month[1].summary.time_weighted_return,
+        Some(dec!(0.1))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_migrate_legacy_integer_amounts() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.conn().execute_batch(include_str!("sql/schema_v0.sql"))?;
+    db.conn().execute_batch(
+        "INSERT INTO account_types (id, name, normal_balance) VALUES
+            (1, 'Assets', 'DEBIT'), (2, 'Equity', 'CREDIT');
+         INSERT INTO assets (id, code, name, type) VALUES
+            (1, 'EUR', 'Euro', 'FIAT'), (2, 'BTC', 'Bitcoin', 'CRYPTO');
+         INSERT INTO accounts (id, account_number, name, account_type_id, opening_date) VALUES
+            (1, '1101', 'Bank', 1, '2024-01-01'), (2, '3100', 'Capital', 2, '2024-01-01');
+         INSERT INTO exchange_rates (from_asset_id, to_asset_id, rate, date) VALUES
+            (2, 1, 4000000000000, '2024-01-01T00:00:00Z');
+         INSERT INTO journal_entries (id, date, description, status) VALUES
+            (1, '2024-01-02T00:00:00Z', 'Opening', 'POSTED');
+         INSERT INTO journal_entry_lines (journal_entry_id, account_id, asset_id, entry_type, amount) VALUES
+            (1, 1, 1, 'DEBIT', 9223372036854775807),
+            (1, 2, 1, 'CREDIT', 9223372036854775807);",
+    )?;
+    assert_eq!(db.schema_version()?, 0);
+
+    db.migrate()?;
+    assert_eq!(db.schema_version()?, migrations::SCHEMA_VERSION);
+    let audited = |db: &Database| -> Result<i64> {
+        Ok(db
+            .conn()
+            .query_row("SELECT count(*) FROM audit_log", [], |row| row.get(0))?)
+    };
+    // Refilling the tables is not a change to the book
+    assert_eq!(audited(&db)?, 0);
+
+    let balance = db.get_general_balance()?;
+    assert_eq!(balance[0].balance.amount(), dec!(92233720368.54775807));
+    assert_eq!(
+        db.exchange_rate_at(
+            "BTC",
+            "EUR",
+            Utc.with_ymd_and_hms(2024, 1, 3, 0, 0, 0).unwrap()
+        )?,
+        Some(dec!(40000))
+    );
+    assert_eq!(db.get_asset("BTC")?.unwrap().decimals, 8);
+
+    // Past the old i64 limit, and down to the wei
+    db.create_asset("ETH", "Ether", AssetType::Crypto, 18, None)?;
+    db.insert_transaction(
+        Utc.with_ymd_and_hms(2024, 1, 3, 0, 0, 0).unwrap(),
+        "Top up",
+        "TOPUP-01",
+        EntryStatus::Posted,
+        "1101",
+        "3100",
+        "EUR",
+        "EUR",
+        Money::new(dec!(0.00000000000000001)),
+    )?;
+    db.insert_transaction(
+        Utc.with_ymd_and_hms(2024, 1, 4, 0, 0, 0).unwrap(),
+        "Gas refund",
+        "ETH-01",
+        EntryStatus::Posted,
+        "1101",
+        "3100",
+        "ETH",
+        "ETH",
+        Money::new(dec!(1.000000000000000001)),
+    )?;
+
+    let balance = db.get_general_balance()?;
+    let bank = |asset: &str| {
+        balance
+            .iter()
+            .find(|row| row.account_number == "1101" && row.asset == asset)
+            .map(|row| row.balance.amount())
+    };
+    assert_eq!(bank("EUR"), Some(dec!(92233720368.54775807000000001)));
+    assert_eq!(bank("ETH"), Some(dec!(1.000000000000000001)));
+    assert!(audited(&db)? > 0);
+
+    // Already up to date
+    db.migrate()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_migrate_empty_database() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.migrate()?;
+
+    assert_eq!(db.schema_version()?, migrations::SCHEMA_VERSION);
+    init_sample_data(&mut db).unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn test_migrate_from_version_1() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    migrations::drop_audit_log(db.conn())?;
+    db.conn().execute_batch(
+        "DROP TABLE import_profiles;
+         DROP TABLE imported_transactions;
+         DROP TABLE external_accounts;
+         PRAGMA user_version = 1;",
+    )?;
+
+    db.migrate()?;
+
+    assert_eq!(db.schema_version()?, migrations::SCHEMA_VERSION);
+    // The tables added since version 1 exist, and are empty
+    let profiles: i64 = db
+        .conn()
+        .query_row("SELECT count(*) FROM import_profiles", [], |row| row.get(0))?;
+    assert_eq!(profiles, 0);
+
+    Ok(())
+}
+
+#[cfg(feature = "importers")]
+#[test]
+fn test_import_csv_with_profile() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db).unwrap();
+
+    db.save_import_profile(&CsvImportProfile {
+        name: "Sparkasse".into(),
+        delimiter: ';',
+        has_headers: true,
+        skip_lines: 2,
+        date_column: "Buchungstag".into(),
+        date_format: "%d.%m.%Y".into(),
+        amount_columns: AmountColumns::DebitCredit {
+            debit: "Soll".into(),
+            credit: "Haben".into(),
+        },
+        description_column: "Verwendungszweck".into(),
+        reference_column: None,
+        decimal_separator: ',',
+        thousands_separator: Some('.'),
+    })?;
+    assert_eq!(db.list_import_profiles()?.len(), 1);
+
+    let january = "Kontoauszug Girokonto\n\
+        Zeitraum: 01.01.2025 - 31.01.2025\n\
+        Buchungstag;Verwendungszweck;Soll;Haben\n\
+        02.01.2025;Supermarkt;45,20;\n\
+        02.01.2025;Supermarkt;45,20;\n\
+        15.01.2025;Gehalt Januar;;2.500,00\n";
+    let summary = db.import_csv("Sparkasse", "1101", "EUR", january.as_bytes())?;
+    assert_eq!(summary.entry_ids.len(), 3);
+    assert_eq!(summary.duplicates, 0);
+
+    // Overlaps January, including only one of the two identical purchases
+    let overlapping = "Kontoauszug Girokonto\n\
+        Zeitraum: 02.01.2025 - 03.02.2025\n\
+        Buchungstag;Verwendungszweck;Soll;Haben\n\
+        02.01.2025;Supermarkt;45,20;\n\
+        15.01.2025;Gehalt  Januar;;2.500,00\n\
+        03.02.2025;Miete;900,00;\n";
+    let summary = db.import_csv("Sparkasse", "1101", "EUR", overlapping.as_bytes())?;
+    assert_eq!(summary.entry_ids.len(), 1);
+    assert_eq!(summary.duplicates, 2);
+
+    let rent = db.conn().query_row(
+        "SELECT je.status, jel.entry_type, jel.amount
+             FROM journal_entries je
+             JOIN journal_entry_lines jel ON jel.journal_entry_id = je.id
+             JOIN accounts a ON a.id = jel.account_id
+             WHERE je.id = ?1 AND a.account_number = '1101'",
+        [summary.entry_ids[0]],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Money>(2)?,
+            ))
+        },
+    )?;
+    assert_eq!(
+        rent,
+        ("DRAFT".into(), "CREDIT".into(), Money::new(dec!(900)))
+    );
+
+    // Drafts stay out of the balance until they are reviewed and posted
+    assert!(db
+        .get_general_balance()?
+        .iter()
+        .all(|row| row.account_number != "1101"));
+
+    Ok(())
+}
+
+#[cfg(feature = "importers")]
+#[test]
+fn test_parse_csv_statement_without_headers() -> Result<()> {
+    let profile = CsvImportProfile {
+        name: "Card".into(),
+        delimiter: ',',
+        has_headers: false,
+        skip_lines: 0,
+        date_column: "0".into(),
+        date_format: "%m/%d/%Y".into(),
+        amount_columns: AmountColumns::Signed("2".into()),
+        description_column: "1".into(),
+        reference_column: Some("3".into()),
+        decimal_separator: '.',
+        thousands_separator: Some(','),
+    };
+
+    let lines = csv_import::parse_csv_statement(
+        &profile,
+        "01/31/2025,\"Coffee, large\",-4.50,A1\n02/01/2025,Refund,\"1,200.00\",\n".as_bytes(),
+    )?;
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].date, NaiveDate::from_ymd_opt(2025, 1, 31).unwrap());
+    assert_eq!(lines[0].amount, Money::new(dec!(-4.5)));
+    assert_eq!(lines[0].reference.as_deref(), Some("A1"));
+    assert_eq!(lines[1].amount, Money::new(dec!(1200)));
+    assert_eq!(lines[1].reference, None);
+
+    let invalid =
+        csv_import::parse_csv_statement(&profile, "31/01/2025,Coffee,-4.50,\n".as_bytes());
+    assert!(matches!(invalid, Err(Error::InvalidData(_))));
+
+    Ok(())
+}
+
+#[cfg(feature = "importers")]
+#[test]
+fn test_import_ofx_bank_statement() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db).unwrap();
+
+    let statement = "OFXHEADER:100\nDATA:OFXSGML\nVERSION:102\n\n\
+        <OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><CURDEF>EUR\
+        <BANKACCTFROM><BANKID>12345<ACCTID>DE0001<ACCTTYPE>CHECKING</BANKACCTFROM>\
+        <BANKTRANLIST>\
+        <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20250102<TRNAMT>-45.20<FITID>A1<NAME>Supermarket</STMTTRN>\
+        <STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20250115<TRNAMT>2500.00<FITID>A2<NAME>Salary</STMTTRN>\
+        </BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>";
+
+    // The bank's account id has to be mapped to one of ours first
+    assert!(matches!(
+        db.import_ofx(statement.as_bytes()),
+        Err(Error::InvalidData(_))
+    ));
+    assert!(matches!(
+        db.map_external_account("DE0001", "9999"),
+        Err(Error::NotFound)
+    ));
+    db.map_external_account("DE0001", "1101")?;
+    assert_eq!(db.mapped_account("DE0001")?.as_deref(), Some("1101"));
+
+    let summary = db.import_ofx(statement.as_bytes())?;
+    assert_eq!(summary.entry_ids.len(), 2);
+
+    // FITIDs are unique per account, so importing the file again is a no-op
+    let summary = db.import_ofx(statement.as_bytes())?;
+    assert!(summary.entry_ids.is_empty());
+    assert_eq!(summary.duplicates, 2);
+
+    let drafts: i64 = db.conn().query_row(
+        "SELECT COUNT(*) FROM journal_entries WHERE status = 'DRAFT'",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!(drafts, 2);
+
+    Ok(())
+}
+
+#[cfg(feature = "importers")]
+#[test]
+fn test_import_ofx_investment_statement() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db).unwrap();
+
+    let transactions = |transactions: &str| {
+        format!(
+            "<?xml version=\"1.0\"?><?OFX OFXHEADER=\"200\" VERSION=\"220\"?>\
+             <OFX><INVSTMTMSGSRSV1><INVSTMTTRNRS><INVSTMTRS><CURDEF>USD</CURDEF>\
+             <INVACCTFROM><BROKERID>broker.example</BROKERID><ACCTID>U123</ACCTID></INVACCTFROM>\
+             <INVTRANLIST>{transactions}</INVTRANLIST></INVSTMTRS></INVSTMTTRNRS></INVSTMTMSGSRSV1>\
+             <SECLISTMSGSRSV1><SECLIST><MFINFO><SECINFO>\
+             <SECID><UNIQUEID>US9229087690</UNIQUEID><UNIQUEIDTYPE>ISIN</UNIQUEIDTYPE></SECID>\
+             <SECNAME>Vanguard Total Stock Market ETF</SECNAME><TICKER>VTI</TICKER>\
+             </SECINFO></MFINFO></SECLIST></SECLISTMSGSRSV1></OFX>"
+        )
+    };
+    let buy = |fitid: &str, date: &str, units: &str, total: &str| {
+        format!(
+            "<BUYMF><INVBUY><INVTRAN><FITID>{fitid}</FITID><DTTRADE>{date}</DTTRADE></INVTRAN>\
+             <SECID><UNIQUEID>US9229087690</UNIQUEID><UNIQUEIDTYPE>ISIN</UNIQUEIDTYPE></SECID>\
+             <UNITS>{units}</UNITS><UNITPRICE>0</UNITPRICE><TOTAL>-{total}</TOTAL>\
+             </INVBUY><BUYTYPE>BUY</BUYTYPE></BUYMF>"
+        )
+    };
+
+    let first = transactions(&format!(
+        "{}{}",
+        buy("B1", "20250110", "10", "2000.00"),
+        buy("B2", "20250210", "10", "2400.00"),
+    ));
+    let summary = db.import_ofx(first.as_bytes())?;
+    assert_eq!(summary.entry_ids.len(), 2);
+
+    let vti = db.get_asset("VTI")?.unwrap();
+    assert_eq!(vti.asset_type, AssetType::Etf);
+    assert_eq!(vti.decimals, 8);
+
+    // The second statement repeats B2, sells 15 units and pays a dividend
+    let second = transactions(&format!(
+        "{}\
+         <SELLMF><INVSELL><INVTRAN><FITID>S1</FITID><DTTRADE>20250310</DTTRADE></INVTRAN>\
+         <SECID><UNIQUEID>US9229087690</UNIQUEID><UNIQUEIDTYPE>ISIN</UNIQUEIDTYPE></SECID>\
+         <UNITS>-15</UNITS><UNITPRICE>250</UNITPRICE><COMMISSION>5</COMMISSION>\
+         </INVSELL><SELLTYPE>SELL</SELLTYPE></SELLMF>\
+         <INCOME><INVTRAN><FITID>D1</FITID><DTTRADE>20250320</DTTRADE></INVTRAN>\
+         <SECID><UNIQUEID>US9229087690</UNIQUEID><UNIQUEIDTYPE>ISIN</UNIQUEIDTYPE></SECID>\
+         <INCOMETYPE>DIV</INCOMETYPE><TOTAL>12.50</TOTAL><WITHHOLDING>1.88</WITHHOLDING></INCOME>",
+        buy("B2", "20250210", "10", "2400.00"),
+    ));
+    let summary = db.import_ofx(second.as_bytes())?;
+    assert_eq!(summary.entry_ids.len(), 2);
+    assert_eq!(summary.duplicates, 1);
+
+    // FIFO: all of the first lot (2000) and half of the second (1200)
+    let lots = db.open_lots("1201", "VTI")?;
+    assert_eq!(lots.len(), 1);
+    assert_eq!(lots[0].quantity, Money::new(dec!(5)));
+    assert_eq!(lots[0].cost_basis, Money::new(dec!(1200)));
+
+    // Proceeds of 3745 against a cost of 3200
+    let gain: Money = db.conn().query_row(
+        "SELECT jel.amount
+         FROM journal_entry_lines jel
+         JOIN accounts a ON a.id = jel.account_id
+         WHERE jel.journal_entry_id = ?1 AND a.account_number = '4202'
+           AND jel.entry_type = 'CREDIT'",
+        [summary.entry_ids[0]],
+        |row| row.get(0),
+    )?;
+    assert_eq!(gain, Money::new(dec!(545)));
+
+    let dividends = db.list_dividends("VTI")?;
+    assert_eq!(dividends.len(), 1);
+    assert_eq!(dividends[0].gross, Money::new(dec!(12.50)));
+    assert_eq!(dividends[0].withholding, Money::new(dec!(1.88)));
+
+    Ok(())
+}
+
+#[cfg(feature = "importers")]
+#[test]
+fn test_import_and_export_qif() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db).unwrap();
+
+    let qif = "!Type:Cat\n\
+        NPets:Vet\n\
+        E\n\
+        ^\n\
+        !Type:Bank\n\
+        D01/02/2025\n\
+        T-45.20\n\
+        PSupermarket\n\
+        LGroceries\n\
+        ^\n\
+        D01/15/2025\n\
+        T2,750.00\n\
+        PACME Corp\n\
+        SSalary\n\
+        $2,500.00\n\
+        SBonus\n\
+        EYear end\n\
+        $250.00\n\
+        ^\n\
+        D01/20/2025\n\
+        T-500.00\n\
+        N1001\n\
+        PTo savings\n\
+        L[Savings Account]\n\
+        ^\n\
+        D01/25/2025\n\
+        T-80.00\n\
+        PVet clinic\n\
+        LPets:Vet/Rex\n\
+        ^\n\
+        D01/28/2025\n\
+        T-12.00\n\
+        PUnknown\n\
+        ^\n";
+
+    let summary = db.import_qif("1101", "EUR", qif.as_bytes(), &QifOptions::default())?;
+    assert_eq!(summary.entry_ids.len(), 5);
+
+    let summary = db.import_qif("1101", "EUR", qif.as_bytes(), &QifOptions::default())?;
+    assert!(summary.entry_ids.is_empty());
+    assert_eq!(summary.duplicates, 5);
+
+    // Known categories map onto the seeded accounts, new ones are created
+    // below the Income and Expense roots following the numbering
+    let account = |name: &str| -> Result<(String, String)> {
+        Ok(db.conn().query_row(
+            "SELECT a.account_number, p.account_number
+             FROM accounts a JOIN accounts p ON p.id = a.parent_account_id
+             WHERE a.name = ?1",
+            [name],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?)
+    };
+    assert_eq!(account("Pets")?, ("5600".into(), "5000".into()));
+    assert_eq!(account("Vet")?, ("5601".into(), "5600".into()));
+    assert_eq!(account("Bonus")?, ("4400".into(), "4000".into()));
+
+    let balances: Vec<(String, Money)> = db
+        .get_general_balance()?
+        .into_iter()
+        .map(|row| (row.account_number, row.balance))
+        .collect();
+    for (account_number, balance) in [
+        ("1101", dec!(2124.80)),
+        ("1102", dec!(500)),
+        ("4100", dec!(2500)),
+        ("4400", dec!(250)),
+        ("5301", dec!(45.20)),
+        ("5601", dec!(80)),
+    ] {
+        assert!(
+            balances.contains(&(account_number.to_string(), Money::new(balance))),
+            "{account_number}: {balances:?}"
+        );
+    }
+
+    // The uncategorized transaction waits for review
+    let status: String = db.conn().query_row(
+        "SELECT status FROM journal_entries WHERE description = 'Unknown'",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!(status, "DRAFT");
+
+    let mut exported = Vec::new();
+    db.export_qif("1101", "EUR", &mut exported, &QifOptions::default())?;
+    let exported = String::from_utf8(exported).unwrap();
+    assert!(exported.starts_with("!Account\nNMain Checking Account\nTBank\n^\n!Type:Bank\n"));
+    assert!(exported.contains("D01/02/2025\nT-45.20\nPSupermarket\nLLiving:Groceries\n^\n"));
+    assert!(exported.contains("T2750.00\nPACME Corp\nSSalary\n$2500.00\n"));
+    assert!(exported.contains("SBonus\nEYear end\n$250.00\n"));
+    assert!(exported.contains("N1001\nPTo savings\nL[Savings Account]\n"));
+
+    // Importing the export into a fresh ledger gives the same balances
+    let mut copy = Database::new_in_memory()?;
+    copy.init_schema()?;
+    init_sample_data(&mut copy).unwrap();
+    copy.import_qif("1101", "EUR", exported.as_bytes(), &QifOptions::default())?;
+    let copied: Vec<(String, Money)> = copy
+        .get_general_balance()?
+        .into_iter()
+        .map(|row| (row.account_number, row.balance))
+        .collect();
+    assert_eq!(copied, balances);
+
+    Ok(())
+}
+
+#[cfg(feature = "importers")]
+#[test]
+fn test_import_qif_investments() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db).unwrap();
+
+    let qif = "!Type:Security\n\
+        NVanguard Total Stock Market\n\
+        SVTI\n\
+        TMutual Fund\n\
+        ^\n\
+        !Type:Invst\n\
+        D1/10'25\n\
+        NBuy\n\
+        YApple Inc.\n\
+        I150\n\
+        Q10\n\
+        O1\n\
+        ^\n\
+        D2/10'25\n\
+        NBuyX\n\
+        YVanguard Total Stock Market\n\
+        Q4\n\
+        T1000\n\
+        L[Main Checking Account]\n\
+        ^\n\
+        D3/10'25\n\
+        NSell\n\
+        YApple Inc.\n\
+        Q4\n\
+        T700\n\
+        ^\n\
+        D3/20'25\n\
+        NDiv\n\
+        YApple Inc.\n\
+        T2.40\n\
+        ^\n\
+        D3/21'25\n\
+        NStkSplit\n\
+        YApple Inc.\n\
+        Q20\n\
+        ^\n";
+
+    let summary = db.import_qif("1201", "USD", qif.as_bytes(), &QifOptions::default())?;
+    assert_eq!(summary.entry_ids.len(), 4);
+    assert_eq!(summary.skipped, 1);
+
+    // 1501 for 10 units, 4 of which were sold for 700
+    let lots = db.open_lots("1201", "AAPL")?;
+    assert_eq!(lots[0].quantity, Money::new(dec!(6)));
+    assert_eq!(lots[0].cost_basis, Money::new(dec!(900.6)));
+    assert_eq!(
+        db.get_asset("VTI")?.map(|asset| asset.asset_type),
+        Some(AssetType::Etf)
+    );
+    assert_eq!(db.list_dividends("AAPL")?.len(), 1);
+
+    let balances = db.get_general_balance()?;
+    let balance = |account_number: &str, asset: &str| {
+        balances
+            .iter()
+            .find(|row| row.account_number == account_number && row.asset == asset)
+            .map(|row| row.balance)
+    };
+    assert_eq!(balance("1101", "USD"), Some(Money::new(dec!(-1000))));
+    assert_eq!(balance("4202", "USD"), Some(Money::new(dec!(99.6))));
+
+    Ok(())
+}
+
+#[cfg(feature = "importers")]
+#[test]
+fn test_import_bank_statements_checks_balances() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db).unwrap();
+
+    let camt = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">
+          <BkToCstmrStmt><Stmt>
+            <Id>2025-01</Id>
+            <Acct><Id><IBAN>DE89370400440532013000</IBAN></Id><Ccy>EUR</Ccy></Acct>
+            <Bal>
+              <Tp><CdOrPrtry><Cd>OPBD</Cd></CdOrPrtry></Tp>
+              <Amt Ccy="EUR">0</Amt><CdtDbtInd>CRDT</CdtDbtInd><Dt><Dt>2025-01-01</Dt></Dt>
+            </Bal>
+            <Bal>
+              <Tp><CdOrPrtry><Cd>CLBD</Cd></CdOrPrtry></Tp>
+              <Amt Ccy="EUR">2454.80</Amt><CdtDbtInd>CRDT</CdtDbtInd><Dt><Dt>2025-01-31</Dt></Dt>
+            </Bal>
+            <Ntry>
+              <Amt Ccy="EUR">45.20</Amt><CdtDbtInd>DBIT</CdtDbtInd><Sts>BOOK</Sts>
+              <BookgDt><Dt>2025-01-02</Dt></BookgDt><ValDt><Dt>2025-01-02</Dt></ValDt>
+              <AcctSvcrRef>A1</AcctSvcrRef>
+              <NtryDtls><TxDtls><RltdPties><Cdtr><Nm>Supermarket</Nm></Cdtr></RltdPties></TxDtls></NtryDtls>
+            </Ntry>
+            <Ntry>
+              <Amt Ccy="EUR">2500.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><Sts>BOOK</Sts>
+              <BookgDt><Dt>2025-01-15</Dt></BookgDt><ValDt><Dt>2025-01-15</Dt></ValDt>
+              <AcctSvcrRef>A2</AcctSvcrRef>
+              <NtryDtls><TxDtls>
+                <RltdPties><Dbtr><Nm>ACME Corp</Nm></Dbtr></RltdPties>
+                <RmtInf><Ustrd>Salary January</Ustrd></RmtInf>
+              </TxDtls></NtryDtls>
+            </Ntry>
+          </Stmt></BkToCstmrStmt>
+        </Document>"#;
+
+    assert!(matches!(
+        db.import_camt053(camt.as_bytes()),
+        Err(Error::InvalidData(_))
+    ));
+    db.map_external_account("DE89370400440532013000", "1101")?;
+
+    let import = db.import_camt053(camt.as_bytes())?;
+    assert_eq!(import.summary.entry_ids.len(), 2);
+    assert!(import.is_balanced(), "{:?}", import.balance_checks);
+
+    let description: String = db.conn().query_row(
+        "SELECT description FROM journal_entries WHERE id = ?1",
+        [import.summary.entry_ids[1]],
+        |row| row.get(0),
+    )?;
+    assert_eq!(description, "ACME Corp - Salary January");
+
+    // Re-importing changes nothing and still agrees with the ledger
+    let import = db.import_camt053(camt.as_bytes())?;
+    assert_eq!(import.summary.duplicates, 2);
+    assert!(import.is_balanced());
+
+    // The February MT940 statement misses the rent paid on February 1st,
+    // so its closing balance is 900 below the ledger
+    db.create_journal_entry(&NewJournalEntry {
+        date: Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap(),
+        description: "Rent".to_string(),
+        reference_number: None,
+        reference_asset_code: "EUR".to_string(),
+        status: EntryStatus::Posted,
+        lines: vec![
+            NewJournalEntryLine {
+                account_number: "5101".to_string(),
+                asset_code: "EUR".to_string(),
+                entry_type: NormalBalance::Debit,
+                amount: Money::new(dec!(900)),
+                reference_amount: None,
+                description: None,
+            },
+            NewJournalEntryLine {
+                account_number: "1101".to_string(),
+                asset_code: "EUR".to_string(),
+                entry_type: NormalBalance::Credit,
+                amount: Money::new(dec!(900)),
+                reference_amount: None,
+                description: None,
+            },
+        ],
+    })?;
+    db.map_external_account("37040044/0532013000", "1101")?;
+    let mt940 = ":20:2025-02\n\
+        :25:37040044/0532013000\n\
+        :28C:2/1\n\
+        :60F:C250131EUR2454,80\n\
+        :61:2502100210D60,NDDTNONREF//B2\n\
+        :86:Electricity February\n\
+        :62F:C250228EUR2394,80\n\
+        -\n";
+
+    let import = db.import_mt940(mt940.as_bytes())?;
+    assert_eq!(import.summary.entry_ids.len(), 1);
+    assert!(!import.is_balanced());
+    let check = &import.balance_checks[0];
+    assert_eq!(check.account_number, "1101");
+    assert_eq!(check.opening_balance, check.ledger_opening_balance);
+    assert_eq!(check.closing_balance, Money::new(dec!(2394.8)));
+    assert_eq!(check.ledger_closing_balance, Money::new(dec!(1494.8)));
+
+    Ok(())
+}
+
+#[test]
+fn test_export_plain_text() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db).unwrap();
+
+    let line = |account_number: &str,
+                asset_code: &str,
+                entry_type,
+                amount,
+                reference_amount: Option<Money>| NewJournalEntryLine {
+        account_number: account_number.to_string(),
+        asset_code: asset_code.to_string(),
+        entry_type,
+        amount: Money::new(amount),
+        reference_amount,
+        description: None,
+    };
+    let mut entry = |day, description: &str, status, lines| {
+        db.create_journal_entry(&NewJournalEntry {
+            date: Utc.with_ymd_and_hms(2025, 3, day, 12, 0, 0).unwrap(),
+            description: description.to_string(),
+            reference_number: None,
+            reference_asset_code: "USD".to_string(),
+            status,
+            lines,
+        })
+    };
+
+    entry(
+        2,
+        "Buy \"AAPL\"",
+        EntryStatus::Posted,
+        vec![
+            line(
+                "1201",
+                "AAPL",
+                NormalBalance::Debit,
+                dec!(10),
+                Some(Money::new(dec!(1501))),
+            ),
+            line("1101", "USD", NormalBalance::Credit, dec!(1501), None),
+        ],
+    )?;
+    entry(
+        3,
+        "Groceries",
+        EntryStatus::Draft,
+        vec![
+            line("5301", "USD", NormalBalance::Debit, dec!(45.2), None),
+            line("1101", "USD", NormalBalance::Credit, dec!(45.2), None),
+        ],
+    )?;
+    let void = entry(
+        4,
+        "Mistake",
+        EntryStatus::Posted,
+        vec![
+            line("5302", "USD", NormalBalance::Debit, dec!(10), None),
+            line("1101", "USD", NormalBalance::Credit, dec!(10), None),
+        ],
+    )?;
+    db.conn().execute(
+        "UPDATE journal_entries SET status = 'VOID' WHERE id = ?1",
+        [void],
+    )?;
+    db.conn().execute(
+        "UPDATE accounts SET closing_date = '2025-06-30' WHERE account_number = '2202'",
+        [],
+    )?;
+    db.create_exchange_rate(
+        "EUR",
+        "USD",
+        dec!(1.08),
+        Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap(),
+    )?;
+
+    let mut beancount = Vec::new();
+    db.export_beancount(&mut beancount)?;
+    let beancount = String::from_utf8(beancount).unwrap();
+
+    assert!(beancount.contains("2025-01-01 commodity AAPL\n  name: \"Apple Inc.\"\n"));
+    assert!(beancount
+        .contains("2025-01-01 open Expenses:Housing:Rent-Mortgage-Payment\n  number: \"5101\"\n"));
+    assert!(beancount.contains("2025-06-30 close Liabilities:Loans:Car-Loan\n"));
+    assert!(beancount.contains("2025-03-01 price EUR 1.08 USD\n"));
+    assert!(beancount.contains(
+        "2025-03-02 * \"Buy \\\"AAPL\\\"\"\n\
+         \x20 Assets:Investment-Accounts:Stock-Brokerage-Account  10.00000000 AAPL @@ 1501.00 USD\n\
+         \x20 Assets:Cash-and-Bank:Main-Checking-Account  -1501.00 USD\n"
+    ));
+    assert!(beancount.contains("2025-03-03 ! \"Groceries\"\n"));
+    assert!(beancount.contains("; 2025-03-04 * \"Mistake\"\n;   status: \"void\"\n"));
+
+    let mut ledger = Vec::new();
+    db.export_ledger(&mut ledger)?;
+    let ledger = String::from_utf8(ledger).unwrap();
+
+    assert!(ledger.contains("commodity USD\n    ; name: US Dollar\n    format 0.00 USD\n"));
+    assert!(ledger.contains(
+        "account Liabilities:Loans:Car Loan\n    ; number: 2202\n    \
+         ; opened: 2025-01-01\n    ; closed: 2025-06-30\n"
+    ));
+    assert!(ledger.contains("P 2025-03-01 EUR 1.08 USD\n"));
+    assert!(ledger.contains(
+        "2025-03-03 ! Groceries\n\
+         \x20   Expenses:Living:Groceries  45.20 USD\n\
+         \x20   Assets:Cash and Bank:Main Checking Account  -45.20 USD\n"
+    ));
+    assert!(ledger.contains("; 2025-03-04 * Mistake\n"));
+
+    // Exports of the same book are identical
+    let mut again = Vec::new();
+    db.export_ledger(&mut again)?;
+    assert_eq!(String::from_utf8(again).unwrap(), ledger);
+
+    Ok(())
+}
+
+#[cfg(feature = "importers")]
+#[test]
+fn test_import_plain_text_round_trip() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db).unwrap();
+
+    db.create_journal_entry(&NewJournalEntry {
+        date: Utc.with_ymd_and_hms(2025, 3, 2, 0, 0, 0).unwrap(),
+        description: "Buy AAPL".to_string(),
+        reference_number: Some("T-1".to_string()),
+        reference_asset_code: "USD".to_string(),
+        status: EntryStatus::Posted,
+        lines: vec![
+            NewJournalEntryLine {
+                account_number: "1201".to_string(),
+                asset_code: "AAPL".to_string(),
+                entry_type: NormalBalance::Debit,
+                amount: Money::new(dec!(10)),
+                reference_amount: Some(Money::new(dec!(1501))),
+                description: None,
+            },
+            NewJournalEntryLine {
+                account_number: "1101".to_string(),
+                asset_code: "USD".to_string(),
+                entry_type: NormalBalance::Credit,
+                amount: Money::new(dec!(1501)),
+                reference_amount: None,
+                description: Some("settlement".to_string()),
+            },
+        ],
+    })?;
+    db.create_exchange_rate(
+        "EUR",
+        "USD",
+        dec!(1.08),
+        Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap(),
+    )?;
+
+    let balances = |db: &Database| -> Result<Vec<(String, String, Money)>> {
+        Ok(db
+            .get_general_balance()?
+            .into_iter()
+            .map(|row| (row.account_number, row.asset, row.balance))
+            .collect())
+    };
+
+    for format in [PlainTextFormat::Beancount, PlainTextFormat::Ledger] {
+        let mut journal = Vec::new();
+        db.export_plain_text(format, &mut journal)?;
+
+        // Into the same chart of accounts: matched by number, nothing created
+        let mut seeded = Database::new_in_memory()?;
+        seeded.init_schema()?;
+        init_sample_data(&mut seeded).unwrap();
+        let import = seeded.import_plain_text(format, journal.as_slice())?;
+        assert!(import.accounts.is_empty());
+        assert!(import.assets.is_empty());
+        assert_eq!(import.prices, 1);
+        assert_eq!(import.entry_ids.len(), 1);
+        assert_eq!(import.warnings, vec![]);
+        assert_eq!(balances(&seeded)?, balances(&db)?);
+
+        // Into an empty book: the chart of accounts is rebuilt
+        let mut empty = Database::new_in_memory()?;
+        empty.init_schema()?;
+        let import = empty.import_plain_text(format, journal.as_slice())?;
+        assert!(import.accounts.contains(&"5301".to_string()));
+        assert!(import.assets.contains(&"AAPL".to_string()));
+        assert_eq!(balances(&empty)?, balances(&db)?);
+
+        let (name, decimals): (String, i64) = empty.conn().query_row(
+            "SELECT name, decimals FROM assets WHERE code = 'AAPL'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!(name, "Apple Inc.");
+        assert_eq!(decimals, 8);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "importers")]
+#[test]
+fn test_import_beancount_lots_and_warnings() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db).unwrap();
+
+    let journal = r#"
+option "operating_currency" "USD"
+2025-01-01 open Expenses:Pets
+2025-03-01 * "Buy"
+  Assets:Investment-Accounts:Stock-Brokerage-Account   10 AAPL {150 USD}
+  Assets:Cash-and-Bank:Main-Checking-Account
+2025-03-02 * "Buy"
+  Assets:Investment-Accounts:Stock-Brokerage-Account   10 AAPL {160 USD}
+  Assets:Cash-and-Bank:Main-Checking-Account          -1600 USD
+2025-04-01 ! "Sell"
+  Assets:Investment-Accounts:Stock-Brokerage-Account  -15 AAPL {} @ 170 USD
+  Assets:Cash-and-Bank:Main-Checking-Account           2550 USD
+  Income:Investment-Income:Capital-Gains
+2025-04-02 * "Vet"
+  Expenses:Pets:Vet    80 USD
+  Assets:Cash-and-Bank:Main-Checking-Account
+2025-04-03 balance Assets:Cash-and-Bank:Main-Checking-Account  0 USD
+"#;
+    let import = db.import_beancount(journal.as_bytes())?;
+
+    assert_eq!(import.accounts, ["5600", "5601"]);
+    assert_eq!(import.entry_ids.len(), 4);
+    let warnings: Vec<_> = import
+        .warnings
+        .iter()
+        .map(|warning| (warning.line, warning.directive.as_str()))
+        .collect();
+    assert_eq!(warnings, [(2, "option"), (17, "balance"), (15, "open")]);
+
+    // FIFO: all of the first lot and half of the second
+    let lots = db.open_lots("1201", "AAPL")?;
+    assert_eq!(lots.len(), 1);
+    assert_eq!(lots[0].quantity, Money::new(dec!(5)));
+    assert_eq!(lots[0].cost_basis, Money::new(dec!(800)));
+
+    let gain: (String, Money) = db.conn().query_row(
+        "SELECT je.status, jel.amount FROM journal_entry_lines jel
+         JOIN journal_entries je ON je.id = jel.journal_entry_id
+         WHERE jel.account_id = (SELECT id FROM accounts WHERE account_number = '4202')",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    assert_eq!(gain, ("DRAFT".to_string(), Money::new(dec!(250))));
+
+    // Unbalanced transactions abort the import
+    let unbalanced = "2025-05-01 * \"Oops\"\n  Expenses:Pets  1 USD\n  Assets:Cash-and-Bank:Main-Checking-Account  -2 USD\n";
+    assert!(matches!(
+        db.import_beancount(unbalanced.as_bytes()),
+        Err(Error::InvalidData(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_export_and_import_json() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db).unwrap();
+
+    let entry = db.create_journal_entry(&NewJournalEntry {
+        date: Utc.with_ymd_and_hms(2025, 3, 2, 0, 0, 0).unwrap(),
+        description: "Buy AAPL".to_string(),
+        reference_number: Some("T-1".to_string()),
+        reference_asset_code: "USD".to_string(),
+        status: EntryStatus::Posted,
+        lines: vec![
+            NewJournalEntryLine {
+                account_number: "1201".to_string(),
+                asset_code: "AAPL".to_string(),
+                entry_type: NormalBalance::Debit,
+                amount: Money::new(dec!(10)),
+                reference_amount: Some(Money::new(dec!(1501))),
+                description: None,
+            },
+            NewJournalEntryLine {
+                account_number: "1101".to_string(),
+                asset_code: "USD".to_string(),
+                entry_type: NormalBalance::Credit,
+                amount: Money::new(dec!(1501)),
+                reference_amount: None,
+                description: Some("settlement".to_string()),
+            },
+        ],
+    })?;
+    db.conn().execute(
+        "UPDATE journal_entries SET status = 'VOID' WHERE id = ?1",
+        [entry],
+    )?;
+    db.create_exchange_rate(
+        "EUR",
+        "USD",
+        dec!(1.08),
+        Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap(),
+    )?;
+
+    let mut json = Vec::new();
+    db.export_json(&mut json)?;
+
+    let mut copy = Database::new_in_memory()?;
+    copy.init_schema()?;
+    let import = copy.import_json(json.as_slice())?;
+    assert_eq!(import.accounts, db.book_document()?.accounts.len());
+    assert_eq!(import.exchange_rates, 1);
+
+    let mut copied = Vec::new();
+    copy.export_json(&mut copied)?;
+    assert_eq!(
+        String::from_utf8(copied).unwrap(),
+        String::from_utf8(json.clone()).unwrap()
+    );
+
+    // Into a book that already has the chart of accounts: only entries are added
+    let mut seeded = Database::new_in_memory()?;
+    seeded.init_schema()?;
+    init_sample_data(&mut seeded).unwrap();
+    let import = seeded.import_json(json.as_slice())?;
+    assert_eq!(
+        (import.account_types, import.assets, import.accounts),
+        (0, 0, 0)
+    );
+    assert_eq!(import.journal_entry_ids.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_import_json_validates_before_writing() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db).unwrap();
+    db.insert_transaction(
+        Utc.with_ymd_and_hms(2025, 3, 3, 0, 0, 0).unwrap(),
+        "Groceries",
+        "R-1",
+        EntryStatus::Posted,
+        "5301",
+        "1101",
+        "USD",
+        "USD",
+        Money::new(dec!(45.2)),
+    )?;
+    let document = db.book_document()?;
+
+    let mut empty = Database::new_in_memory()?;
+    empty.init_schema()?;
+    let accounts = |db: &Database| -> Result<i64> {
+        Ok(db
+            .conn()
+            .query_row("SELECT COUNT(*) FROM accounts", [], |row| row.get(0))?)
+    };
+
+    let mut unbalanced = document.clone();
+    unbalanced.journal_entries[0].lines[0].amount = Money::new(dec!(45));
+    assert!(matches!(
+        empty.import_book_document(&unbalanced),
+        Err(Error::InvalidData(_))
+    ));
+
+    let mut dangling = document.clone();
+    dangling.journal_entries[0].lines[0].account_id = 999;
+    assert!(matches!(
+        empty.import_book_document(&dangling),
+        Err(Error::InvalidData(_))
+    ));
+
+    let mut looping = document.clone();
+    let root = looping.accounts[0].id;
+    looping.accounts[0].parent_account_id = Some(looping.accounts[1].id);
+    assert_eq!(looping.accounts[1].parent_account_id, Some(root));
+    assert!(matches!(
+        empty.import_book_document(&looping),
+        Err(Error::InvalidData(_))
+    ));
+
+    let mut newer = document.clone();
+    newer.version += 1;
+    assert!(matches!(
+        empty.import_book_document(&newer),
+        Err(Error::InvalidData(_))
+    ));
+
+    assert_eq!(accounts(&empty)?, 0);
+    empty.import_book_document(&document)?;
+    assert_eq!(accounts(&empty)?, accounts(&db)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_import_json_rejects_rows_that_disagree_with_the_book() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db).unwrap();
+    let document = db.book_document()?;
+
+    let mut flipped = document.clone();
+    flipped.account_types[0].normal_balance = match flipped.account_types[0].normal_balance {
+        NormalBalance::Debit => NormalBalance::Credit,
+        NormalBalance::Credit => NormalBalance::Debit,
+    };
+    let mut rescaled = document.clone();
+    rescaled.assets[0].decimals += 1;
+    let mut retyped = document.clone();
+    retyped.assets[0].asset_type = match retyped.assets[0].asset_type {
+        AssetType::Fiat => AssetType::Crypto,
+        _ => AssetType::Fiat,
+    };
+    let mut moved = document.clone();
+    let other_type = moved
+        .account_types
+        .iter()
+        .map(|t| t.id)
+        .find(|id| *id != moved.accounts[0].account_type_id)
+        .unwrap();
+    moved.accounts[0].account_type_id = other_type;
+
+    for document in [flipped, rescaled, retyped, moved] {
+        assert!(matches!(
+            db.import_book_document(&document),
+            Err(Error::InvalidData(_))
+        ));
+    }
+    let unchanged = db.book_document()?;
+    assert_eq!(
+        serde_json::to_value(&unchanged)?,
+        serde_json::to_value(&document)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_review_draft_journal_entries() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.init_schema()?;
+    init_sample_data(&mut db)?;
+
+    let entry = |description: &str, status| NewJournalEntry {
+        date: Utc.with_ymd_and_hms(2025, 5, 2, 0, 0, 0).unwrap(),
+        description: description.to_string(),
+        reference_number: Some("R-1".to_string()),
+        reference_asset_code: "USD".to_string(),
+        status,
+        lines: [
+            ("5301", NormalBalance::Debit),
+            ("1101", NormalBalance::Credit),
+        ]
+        .map(|(account, entry_type)| NewJournalEntryLine {
+            account_number: account.to_string(),
+            asset_code: "USD".to_string(),
+            entry_type,
+            amount: Money::new(dec!(12.5)),
+            reference_amount: None,
+            description: None,
+        })
+        .to_vec(),
+    };
+    let posted = db.create_journal_entry(&entry("Posted", EntryStatus::Posted))?;
+    let draft = db.create_journal_entry(&entry("Draft", EntryStatus::Draft))?;
+
+    let drafts = db.draft_journal_entries()?;
+    assert_eq!(drafts.len(), 1);
+    assert_eq!(drafts[0].0, draft);
+    assert_eq!(drafts[0].1.reference_number.as_deref(), Some("R-1"));
+    assert_eq!(drafts[0].1.lines[1].account_number, "1101");
+    assert_eq!(drafts[0].1.lines[1].entry_type, NormalBalance::Credit);
+
+    assert!(matches!(
+        db.post_journal_entry(posted),
+        Err(Error::InvalidData(_))
+    ));
+    assert!(matches!(db.post_journal_entry(99), Err(Error::NotFound)));
+
+    db.post_journal_entry(draft)?;
+    assert!(db.draft_journal_entries()?.is_empty());
+    let balance = db.get_general_balance()?;
+    let groceries = balance.iter().find(|row| row.account_number == "5301");
+    assert_eq!(groceries.unwrap().balance, Money::new(dec!(25)));
+
+    Ok(())
+}
+
 // #[test]
 // fn test_get_general_balance() -> Result<()> {
 //     let db = Database::new_in_memory()?;
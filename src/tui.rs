@@ -0,0 +1,1154 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{NaiveDate, NaiveTime, Utc};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, Paragraph, Row, Table, TableState, Tabs},
+    DefaultTerminal, Frame,
+};
+
+use mm_schema::{
+    Account, Asset, AssetType, Database, EntryStatus, Error, Money, NewJournalEntry,
+    NewJournalEntryLine, NormalBalance, RegisterLine, Result,
+};
+
+use crate::cli::journal_entry_line;
+
+/// Runs the terminal UI until the user quits, restoring the terminal after
+pub fn run(db: &mut Database) -> Result<()> {
+    let mut app = App::new(db)?;
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Screen {
+    Accounts,
+    Entry,
+    Drafts,
+}
+
+const SCREENS: [(Screen, &str); 3] = [
+    (Screen::Accounts, "1 Accounts"),
+    (Screen::Entry, "2 New entry"),
+    (Screen::Drafts, "3 Drafts"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Message {
+    Info(String),
+    Error(String),
+}
+
+/// A visible row of the account tree
+#[derive(Debug, Clone)]
+struct TreeRow {
+    account_id: i64,
+    account_number: String,
+    name: String,
+    account_type_id: i64,
+    depth: usize,
+    has_children: bool,
+    collapsed: bool,
+    /// Posted balance of the account and its descendants, in the natural
+    /// sign of the account as the general balance reports it
+    balances: BTreeMap<String, Money>,
+}
+
+/// Builds the visible rows of the account tree from the `parent_account_id`
+/// hierarchy, rolling balances up into parent accounts. Accounts whose parent
+/// is not in `accounts` are shown as roots.
+fn account_tree(
+    accounts: &[Account],
+    balances: &HashMap<String, BTreeMap<String, Money>>,
+    collapsed: &HashSet<i64>,
+) -> Vec<TreeRow> {
+    let ids: HashSet<i64> = accounts.iter().map(|a| a.id).collect();
+    let mut children: HashMap<Option<i64>, Vec<&Account>> = HashMap::new();
+    for account in accounts {
+        let parent = account.parent_account_id.filter(|id| ids.contains(id));
+        children.entry(parent).or_default().push(account);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|a, b| a.account_number.cmp(&b.account_number));
+    }
+
+    fn push_rows(
+        account: &Account,
+        depth: usize,
+        visible: bool,
+        children: &HashMap<Option<i64>, Vec<&Account>>,
+        balances: &HashMap<String, BTreeMap<String, Money>>,
+        collapsed: &HashSet<i64>,
+        rows: &mut Vec<TreeRow>,
+    ) -> BTreeMap<String, Money> {
+        let mut total = balances
+            .get(&account.account_number)
+            .cloned()
+            .unwrap_or_default();
+        let own = children.get(&Some(account.id));
+        let is_collapsed = collapsed.contains(&account.id);
+
+        let index = rows.len();
+        if visible {
+            rows.push(TreeRow {
+                account_id: account.id,
+                account_number: account.account_number.clone(),
+                name: account.name.clone(),
+                account_type_id: account.account_type_id,
+                depth,
+                has_children: own.is_some(),
+                collapsed: is_collapsed,
+                balances: BTreeMap::new(),
+            });
+        }
+
+        for child in own.into_iter().flatten() {
+            let subtotal = push_rows(
+                child,
+                depth + 1,
+                visible && !is_collapsed,
+                children,
+                balances,
+                collapsed,
+                rows,
+            );
+            for (asset, balance) in subtotal {
+                *total.entry(asset).or_default() += balance;
+            }
+        }
+        total.retain(|_, balance| !balance.is_zero());
+
+        if visible {
+            rows[index].balances = total.clone();
+        }
+        total
+    }
+
+    let mut rows = Vec::new();
+    for root in children.get(&None).into_iter().flatten() {
+        push_rows(root, 0, true, &children, balances, collapsed, &mut rows);
+    }
+    rows
+}
+
+/// State of the new entry form. Every field is kept as typed and only parsed
+/// when the entry is saved.
+#[derive(Debug)]
+struct EntryForm {
+    date: String,
+    description: String,
+    asset: String,
+    reference: String,
+    lines: Vec<FormLine>,
+    focus: usize,
+    /// Highlighted account suggestion
+    suggestion: usize,
+}
+
+#[derive(Debug, Default)]
+struct FormLine {
+    account: String,
+    /// Positive for debits, optionally followed by `@@ REFERENCE_AMOUNT`
+    amount: String,
+    /// Reference asset if left empty
+    asset: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Date,
+    Description,
+    Asset,
+    Reference,
+    Account(usize),
+    Amount(usize),
+    LineAsset(usize),
+}
+
+impl EntryForm {
+    fn new(date: NaiveDate, asset: &str) -> Self {
+        EntryForm {
+            date: date.to_string(),
+            description: String::new(),
+            asset: asset.to_string(),
+            reference: String::new(),
+            lines: vec![FormLine::default(), FormLine::default()],
+            focus: 0,
+            suggestion: 0,
+        }
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields = vec![
+            Field::Date,
+            Field::Description,
+            Field::Asset,
+            Field::Reference,
+        ];
+        for i in 0..self.lines.len() {
+            fields.extend([Field::Account(i), Field::Amount(i), Field::LineAsset(i)]);
+        }
+        fields
+    }
+
+    fn focused(&self) -> Field {
+        self.fields()[self.focus]
+    }
+
+    fn value(&self, field: Field) -> &str {
+        match field {
+            Field::Date => &self.date,
+            Field::Description => &self.description,
+            Field::Asset => &self.asset,
+            Field::Reference => &self.reference,
+            Field::Account(i) => &self.lines[i].account,
+            Field::Amount(i) => &self.lines[i].amount,
+            Field::LineAsset(i) => &self.lines[i].asset,
+        }
+    }
+
+    fn value_mut(&mut self, field: Field) -> &mut String {
+        match field {
+            Field::Date => &mut self.date,
+            Field::Description => &mut self.description,
+            Field::Asset => &mut self.asset,
+            Field::Reference => &mut self.reference,
+            Field::Account(i) => &mut self.lines[i].account,
+            Field::Amount(i) => &mut self.lines[i].amount,
+            Field::LineAsset(i) => &mut self.lines[i].asset,
+        }
+    }
+
+    /// Moves to the next field, adding a line after the last one
+    fn next_field(&mut self) {
+        if self.focus + 1 == self.fields().len() {
+            self.lines.push(FormLine::default());
+        }
+        self.focus += 1;
+        self.suggestion = 0;
+    }
+
+    fn previous_field(&mut self) {
+        self.focus = self.focus.saturating_sub(1);
+        self.suggestion = 0;
+    }
+
+    fn remove_line(&mut self) {
+        let line = match self.focused() {
+            Field::Account(i) | Field::Amount(i) | Field::LineAsset(i) => i,
+            _ => return,
+        };
+        if self.lines.len() > 2 {
+            self.lines.remove(line);
+            self.focus = self.focus.min(self.fields().len() - 1);
+        } else {
+            self.lines[line] = FormLine::default();
+        }
+    }
+
+    /// Parses the lines that are not blank, using the same syntax as `tx add`
+    fn parsed_lines(&self) -> Result<Vec<NewJournalEntryLine>> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| !(line.account.trim().is_empty() && line.amount.trim().is_empty()))
+            .map(|(i, line)| {
+                if line.account.trim().is_empty() {
+                    return Err(Error::InvalidData(format!("line {} has no account", i + 1)));
+                }
+                let (amount, reference_amount) = match line.amount.split_once("@@") {
+                    Some((amount, reference)) => (amount, format!("@@ {}", reference.trim())),
+                    None => (line.amount.as_str(), String::new()),
+                };
+                let text = format!(
+                    "{} {} {} {}",
+                    line.account.trim(),
+                    amount.trim(),
+                    line.asset.trim(),
+                    reference_amount
+                );
+                journal_entry_line(&text, self.asset.trim())
+            })
+            .collect()
+    }
+
+    fn entry(&self, status: EntryStatus) -> Result<NewJournalEntry> {
+        let date = NaiveDate::parse_from_str(self.date.trim(), "%Y-%m-%d").map_err(|_| {
+            Error::InvalidData(format!("invalid date {:?}, expected YYYY-MM-DD", self.date))
+        })?;
+        let reference = self.reference.trim();
+
+        Ok(NewJournalEntry {
+            date: date.and_time(NaiveTime::MIN).and_utc(),
+            description: self.description.trim().to_string(),
+            reference_number: (!reference.is_empty()).then(|| reference.to_string()),
+            reference_asset_code: self.asset.trim().to_string(),
+            status,
+            lines: self.parsed_lines()?,
+        })
+    }
+
+    /// Debit and credit totals in the reference asset, or `None` while a line
+    /// cannot be parsed or lacks its value in the reference asset
+    fn totals(&self) -> Option<(Money, Money)> {
+        let mut debits = Money::default();
+        let mut credits = Money::default();
+        for line in self.parsed_lines().ok()? {
+            let value = match line.asset_code == self.asset.trim() {
+                true => line.amount,
+                false => line.reference_amount?,
+            };
+            match line.entry_type {
+                NormalBalance::Debit => debits += value,
+                NormalBalance::Credit => credits += value,
+            }
+        }
+        Some((debits, credits))
+    }
+}
+
+struct App<'a> {
+    db: &'a mut Database,
+    screen: Screen,
+    assets: HashMap<String, Asset>,
+    normal_balances: HashMap<i64, NormalBalance>,
+    /// Open accounts
+    accounts: Vec<Account>,
+    collapsed: HashSet<i64>,
+    tree: Vec<TreeRow>,
+    tree_state: TableState,
+    register: Vec<RegisterLine>,
+    register_state: TableState,
+    form: EntryForm,
+    drafts: Vec<(i64, NewJournalEntry)>,
+    draft_state: TableState,
+    message: Option<Message>,
+    quit: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(db: &'a mut Database) -> Result<Self> {
+        let assets: HashMap<String, Asset> = db
+            .list_assets()?
+            .into_iter()
+            .map(|asset| (asset.code.clone(), asset))
+            .collect();
+        let normal_balances = db
+            .list_account_types()?
+            .into_iter()
+            .map(|t| (t.id, t.normal_balance))
+            .collect();
+
+        // Entries default to the first currency in the book
+        let mut currencies: Vec<&Asset> = assets
+            .values()
+            .filter(|a| a.asset_type == AssetType::Fiat)
+            .collect();
+        currencies.sort_by_key(|a| a.id);
+        let asset = currencies
+            .first()
+            .map(|a| a.code.clone())
+            .unwrap_or_default();
+
+        let mut app = App {
+            db,
+            screen: Screen::Accounts,
+            assets,
+            normal_balances,
+            accounts: Vec::new(),
+            collapsed: HashSet::new(),
+            tree: Vec::new(),
+            tree_state: TableState::default().with_selected(0),
+            register: Vec::new(),
+            register_state: TableState::default(),
+            form: EntryForm::new(Utc::now().date_naive(), &asset),
+            drafts: Vec::new(),
+            draft_state: TableState::default().with_selected(0),
+            message: None,
+            quit: false,
+        };
+        app.refresh()?;
+
+        Ok(app)
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.handle_key(key);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reloads accounts, balances and drafts after the book changed
+    fn refresh(&mut self) -> Result<()> {
+        self.accounts = self
+            .db
+            .list_accounts()?
+            .into_iter()
+            .filter(|a| a.is_active)
+            .collect();
+        self.drafts = self.db.draft_journal_entries()?;
+        if self.drafts.is_empty() {
+            self.draft_state.select(None);
+        } else {
+            let selected = self.draft_state.selected().unwrap_or(0);
+            self.draft_state
+                .select(Some(selected.min(self.drafts.len() - 1)));
+        }
+        self.rebuild_tree()
+    }
+
+    fn rebuild_tree(&mut self) -> Result<()> {
+        let selected = self.selected_account().map(|row| row.account_id);
+
+        let mut balances: HashMap<String, BTreeMap<String, Money>> = HashMap::new();
+        for row in self.db.get_general_balance()? {
+            balances
+                .entry(row.account_number)
+                .or_default()
+                .insert(row.asset, row.balance);
+        }
+        self.tree = account_tree(&self.accounts, &balances, &self.collapsed);
+
+        let index = selected
+            .and_then(|id| self.tree.iter().position(|row| row.account_id == id))
+            .unwrap_or(0);
+        self.tree_state
+            .select((!self.tree.is_empty()).then_some(index));
+        self.load_register()
+    }
+
+    fn selected_account(&self) -> Option<&TreeRow> {
+        self.tree_state.selected().and_then(|i| self.tree.get(i))
+    }
+
+    fn load_register(&mut self) -> Result<()> {
+        self.register = match self.selected_account() {
+            Some(row) => self.db.register(&row.account_number)?,
+            None => Vec::new(),
+        };
+        // Start at the most recent line
+        self.register_state
+            .select(self.register.len().checked_sub(1));
+
+        Ok(())
+    }
+
+    /// Flips the sign of balances of credit-normal accounts, so that income,
+    /// liabilities and equity read as positive amounts
+    fn natural_sign(&self, account_type_id: i64, money: Money) -> Money {
+        match self.normal_balances.get(&account_type_id) {
+            Some(NormalBalance::Credit) => -money,
+            _ => money,
+        }
+    }
+
+    fn format_money(&self, money: Money, asset: &str) -> String {
+        let mut amount = money.amount();
+        match self.assets.get(asset) {
+            Some(a) if a.asset_type == AssetType::Fiat => amount.rescale(a.decimals as u32),
+            _ => amount = amount.normalize(),
+        }
+        amount.to_string()
+    }
+
+    /// Open accounts matching what was typed in the focused account field,
+    /// by number prefix or by name
+    fn suggestions(&self) -> Vec<&Account> {
+        let Field::Account(i) = self.form.focused() else {
+            return Vec::new();
+        };
+        let query = self.form.lines[i].account.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches: Vec<&Account> = self
+            .accounts
+            .iter()
+            .filter(|a| {
+                a.account_number.starts_with(&query) || a.name.to_lowercase().contains(&query)
+            })
+            .collect();
+        matches.sort_by(|a, b| a.account_number.cmp(&b.account_number));
+        if matches.len() == 1 && matches[0].account_number == query {
+            return Vec::new();
+        }
+        matches.truncate(8);
+        matches
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if let Err(e) = self.try_handle_key(key) {
+            self.message = Some(Message::Error(e.to_string()));
+        }
+    }
+
+    fn try_handle_key(&mut self, key: KeyEvent) -> Result<()> {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        if control && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return Ok(());
+        }
+        if self.screen == Screen::Entry {
+            return self.handle_form_key(key.code, control);
+        }
+
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Char('1') => self.screen = Screen::Accounts,
+            KeyCode::Char('2') | KeyCode::Char('n') => self.screen = Screen::Entry,
+            KeyCode::Char('3') | KeyCode::Char('d') => self.screen = Screen::Drafts,
+            KeyCode::Tab => {
+                self.screen = match self.screen {
+                    Screen::Accounts => Screen::Entry,
+                    _ => Screen::Accounts,
+                }
+            }
+            code => match self.screen {
+                Screen::Accounts => self.handle_accounts_key(code)?,
+                Screen::Drafts => self.handle_drafts_key(code)?,
+                Screen::Entry => {}
+            },
+        }
+
+        Ok(())
+    }
+
+    fn handle_accounts_key(&mut self, code: KeyCode) -> Result<()> {
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.tree_state.select_previous();
+                self.load_register()?;
+            }
+            KeyCode::Down | KeyCode::Char('j')
+                if self.tree_state.selected() < Some(self.tree.len().saturating_sub(1)) =>
+            {
+                self.tree_state.select_next();
+                self.load_register()?;
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                if let Some(row) = self.selected_account().filter(|row| row.has_children) {
+                    let id = row.account_id;
+                    if !self.collapsed.remove(&id) {
+                        self.collapsed.insert(id);
+                    }
+                    self.rebuild_tree()?;
+                }
+            }
+            KeyCode::PageUp => self.register_state.scroll_up_by(10),
+            KeyCode::PageDown => self.register_state.scroll_down_by(10),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn handle_drafts_key(&mut self, code: KeyCode) -> Result<()> {
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => self.draft_state.select_previous(),
+            KeyCode::Down | KeyCode::Char('j')
+                if self.draft_state.selected() < Some(self.drafts.len().saturating_sub(1)) =>
+            {
+                self.draft_state.select_next()
+            }
+            KeyCode::Char('p') | KeyCode::Enter | KeyCode::Char('v') => {
+                let Some(&(id, _)) = self.draft_state.selected().and_then(|i| self.drafts.get(i))
+                else {
+                    return Ok(());
+                };
+                let message = match code {
+                    KeyCode::Char('v') => {
+                        self.db.void_journal_entry(id)?;
+                        format!("Voided entry {id}")
+                    }
+                    _ => {
+                        self.db.post_journal_entry(id)?;
+                        format!("Posted entry {id}")
+                    }
+                };
+                self.message = Some(Message::Info(message));
+                self.refresh()?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn handle_form_key(&mut self, code: KeyCode, control: bool) -> Result<()> {
+        match code {
+            KeyCode::Esc => self.screen = Screen::Accounts,
+            KeyCode::Char('s') if control => self.save(EntryStatus::Posted)?,
+            KeyCode::Char('d') if control => self.save(EntryStatus::Draft)?,
+            KeyCode::Char('x') if control => self.form.remove_line(),
+            KeyCode::Tab | KeyCode::Enter => {
+                let completion = self
+                    .suggestions()
+                    .get(self.form.suggestion)
+                    .map(|a| a.account_number.clone());
+                match completion {
+                    Some(number) => {
+                        let field = self.form.focused();
+                        *self.form.value_mut(field) = number;
+                    }
+                    None => self.form.next_field(),
+                }
+            }
+            KeyCode::BackTab => self.form.previous_field(),
+            KeyCode::Up => match self.suggestions().is_empty() {
+                true => self.form.previous_field(),
+                false => self.form.suggestion = self.form.suggestion.saturating_sub(1),
+            },
+            KeyCode::Down => {
+                let suggestions = self.suggestions().len();
+                match suggestions {
+                    0 => self.form.next_field(),
+                    _ => self.form.suggestion = (self.form.suggestion + 1).min(suggestions - 1),
+                }
+            }
+            KeyCode::Backspace => {
+                let field = self.form.focused();
+                self.form.value_mut(field).pop();
+                self.form.suggestion = 0;
+            }
+            KeyCode::Char(c) if !control => {
+                let field = self.form.focused();
+                self.form.value_mut(field).push(c);
+                self.form.suggestion = 0;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn save(&mut self, status: EntryStatus) -> Result<()> {
+        let entry = self.form.entry(status)?;
+        let id = self.db.create_journal_entry(&entry)?;
+
+        self.message = Some(Message::Info(match status {
+            EntryStatus::Draft => format!("Saved entry {id} as a draft"),
+            _ => format!("Recorded entry {id}"),
+        }));
+        // Keep the date and asset for the next entry of a batch
+        let date = entry.date.date_naive();
+        self.form = EntryForm::new(date, &entry.reference_asset_code);
+        self.refresh()
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [tabs, body, status, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let selected = SCREENS.iter().position(|(s, _)| *s == self.screen);
+        frame.render_widget(
+            Tabs::new(SCREENS.iter().map(|(_, title)| *title))
+                .select(selected)
+                .highlight_style(Style::new().reversed()),
+            tabs,
+        );
+
+        match self.screen {
+            Screen::Accounts => self.draw_accounts(frame, body),
+            Screen::Entry => self.draw_form(frame, body),
+            Screen::Drafts => self.draw_drafts(frame, body),
+        }
+
+        let message = match &self.message {
+            Some(Message::Info(text)) => Line::from(text.as_str()).green(),
+            Some(Message::Error(text)) => Line::from(text.as_str()).red(),
+            None => Line::default(),
+        };
+        frame.render_widget(Paragraph::new(message), status);
+        frame.render_widget(
+            Paragraph::new(match self.screen {
+                Screen::Accounts => {
+                    "↑↓ select  Enter fold  PgUp/PgDn scroll register  n new entry  d drafts  q quit"
+                }
+                Screen::Entry => {
+                    "Tab next/complete  ⇧Tab back  ^S post  ^D save draft  ^X clear line  Esc back"
+                }
+                Screen::Drafts => "↑↓ select  p post  v void  1 accounts  q quit",
+            })
+            .dim(),
+            help,
+        );
+    }
+
+    fn draw_accounts(&mut self, frame: &mut Frame, area: Rect) {
+        let [tree, register] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)])
+                .areas(area);
+
+        let rows: Vec<Row> = self
+            .tree
+            .iter()
+            .map(|row| {
+                let marker = match (row.has_children, row.collapsed) {
+                    (false, _) => " ",
+                    (true, false) => "▾",
+                    (true, true) => "▸",
+                };
+                let balances = row
+                    .balances
+                    .iter()
+                    .map(|(asset, balance)| {
+                        format!("{} {asset}", self.format_money(*balance, asset))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let name = format!(
+                    "{}{marker} {} {}",
+                    "  ".repeat(row.depth),
+                    row.account_number,
+                    row.name
+                );
+                let style = match row.depth {
+                    0 => Style::new().bold(),
+                    _ => Style::new(),
+                };
+                Row::new([name, balances]).style(style)
+            })
+            .collect();
+        frame.render_stateful_widget(
+            Table::new(
+                rows,
+                [Constraint::Percentage(60), Constraint::Percentage(40)],
+            )
+            .header(Row::new(["Account", "Balance"]).underlined())
+            .block(Block::bordered().title("Accounts"))
+            .row_highlight_style(Style::new().reversed()),
+            tree,
+            &mut self.tree_state,
+        );
+
+        let title = match self.selected_account() {
+            Some(row) => format!("Register {} {}", row.account_number, row.name),
+            None => "Register".to_string(),
+        };
+        let account_type_id = self.selected_account().map(|row| row.account_type_id);
+        let rows: Vec<Row> = self
+            .register
+            .iter()
+            .map(|line| {
+                let sign = |money| match account_type_id {
+                    Some(id) => self.natural_sign(id, money),
+                    None => money,
+                };
+                let row = Row::new([
+                    line.date.date_naive().to_string(),
+                    line.description.clone(),
+                    self.format_money(sign(line.amount), &line.asset),
+                    format!(
+                        "{} {}",
+                        self.format_money(sign(line.balance), &line.asset),
+                        line.asset
+                    ),
+                ]);
+                match line.status {
+                    EntryStatus::Draft => row.italic().dim(),
+                    _ => row,
+                }
+            })
+            .collect();
+        frame.render_stateful_widget(
+            Table::new(
+                rows,
+                [
+                    Constraint::Length(10),
+                    Constraint::Min(10),
+                    Constraint::Length(14),
+                    Constraint::Length(20),
+                ],
+            )
+            .header(Row::new(["Date", "Description", "Amount", "Balance"]).underlined())
+            .block(Block::bordered().title(title))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED)),
+            register,
+            &mut self.register_state,
+        );
+    }
+
+    fn draw_form(&mut self, frame: &mut Frame, area: Rect) {
+        let [form, suggestions] =
+            Layout::horizontal([Constraint::Min(0), Constraint::Length(40)]).areas(area);
+        let [header, lines, totals] = Layout::vertical([
+            Constraint::Length(6),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(form);
+
+        let focused = self.form.focused();
+        let value = |field: Field| {
+            let text = self.form.value(field).to_string();
+            match field == focused {
+                true => Line::from(format!("{text}_")).reversed(),
+                false => Line::from(text),
+            }
+        };
+
+        let header_rows = [
+            ("Date", Field::Date),
+            ("Description", Field::Description),
+            ("Asset", Field::Asset),
+            ("Reference", Field::Reference),
+        ]
+        .map(|(label, field)| Row::new([Line::from(label), value(field)]));
+        frame.render_widget(
+            Table::new(header_rows, [Constraint::Length(12), Constraint::Min(0)])
+                .block(Block::bordered().title("New entry")),
+            header,
+        );
+
+        let line_rows: Vec<Row> = self
+            .form
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let name = self
+                    .accounts
+                    .iter()
+                    .find(|a| a.account_number == line.account.trim())
+                    .map(|a| a.name.clone())
+                    .unwrap_or_default();
+                Row::new([
+                    Line::from((i + 1).to_string()),
+                    value(Field::Account(i)),
+                    Line::from(name).dim(),
+                    value(Field::Amount(i)),
+                    value(Field::LineAsset(i)),
+                ])
+            })
+            .collect();
+        frame.render_widget(
+            Table::new(
+                line_rows,
+                [
+                    Constraint::Length(3),
+                    Constraint::Length(10),
+                    Constraint::Min(10),
+                    Constraint::Length(24),
+                    Constraint::Length(8),
+                ],
+            )
+            .header(Row::new(["#", "Account", "", "Amount (+ debit)", "Asset"]).underlined())
+            .block(Block::bordered().title("Lines")),
+            lines,
+        );
+
+        let asset = self.form.asset.trim();
+        let status = match self.form.totals() {
+            Some((debits, credits)) if debits == credits => Line::from(format!(
+                "Debits {} = credits {} {asset}, balanced",
+                self.format_money(debits, asset),
+                self.format_money(credits, asset),
+            ))
+            .green(),
+            Some((debits, credits)) => Line::from(format!(
+                "Debits {} ≠ credits {} {asset}, off by {}",
+                self.format_money(debits, asset),
+                self.format_money(credits, asset),
+                self.format_money((debits - credits).abs(), asset),
+            ))
+            .yellow(),
+            None => Line::from(format!(
+                "Lines in another asset need their value in {asset} after @@"
+            ))
+            .yellow(),
+        };
+        frame.render_widget(Paragraph::new(status), totals);
+
+        let rows: Vec<Row> = self
+            .suggestions()
+            .iter()
+            .enumerate()
+            .map(|(i, account)| {
+                let row = Row::new([account.account_number.clone(), account.name.clone()]);
+                match i == self.form.suggestion {
+                    true => row.reversed(),
+                    false => row,
+                }
+            })
+            .collect();
+        frame.render_widget(
+            Table::new(rows, [Constraint::Length(6), Constraint::Min(0)])
+                .block(Block::bordered().title("Accounts")),
+            suggestions,
+        );
+    }
+
+    fn draw_drafts(&mut self, frame: &mut Frame, area: Rect) {
+        let [queue, detail] =
+            Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(area);
+
+        let rows: Vec<Row> = self
+            .drafts
+            .iter()
+            .map(|(id, entry)| {
+                let total = entry
+                    .lines
+                    .iter()
+                    .filter(|line| line.entry_type == NormalBalance::Debit)
+                    .map(|line| match line.asset_code == entry.reference_asset_code {
+                        true => line.amount,
+                        false => line.reference_amount.unwrap_or_default(),
+                    })
+                    .fold(Money::default(), |total, amount| total + amount);
+                Row::new([
+                    id.to_string(),
+                    entry.date.date_naive().to_string(),
+                    entry.description.clone(),
+                    format!(
+                        "{} {}",
+                        self.format_money(total, &entry.reference_asset_code),
+                        entry.reference_asset_code
+                    ),
+                ])
+            })
+            .collect();
+        let title = format!("Drafts awaiting review ({})", self.drafts.len());
+        frame.render_stateful_widget(
+            Table::new(
+                rows,
+                [
+                    Constraint::Length(6),
+                    Constraint::Length(10),
+                    Constraint::Min(10),
+                    Constraint::Length(20),
+                ],
+            )
+            .header(Row::new(["Id", "Date", "Description", "Amount"]).underlined())
+            .block(Block::bordered().title(title))
+            .row_highlight_style(Style::new().reversed()),
+            queue,
+            &mut self.draft_state,
+        );
+
+        let lines: Vec<Row> = self
+            .draft_state
+            .selected()
+            .and_then(|i| self.drafts.get(i))
+            .map(|(_, entry)| {
+                entry
+                    .lines
+                    .iter()
+                    .map(|line| {
+                        let name = self
+                            .accounts
+                            .iter()
+                            .find(|a| a.account_number == line.account_number)
+                            .map(|a| a.name.clone())
+                            .unwrap_or_default();
+                        let amount = match line.entry_type {
+                            NormalBalance::Debit => line.amount,
+                            NormalBalance::Credit => -line.amount,
+                        };
+                        Row::new([
+                            line.account_number.clone(),
+                            name,
+                            format!(
+                                "{} {}",
+                                self.format_money(amount, &line.asset_code),
+                                line.asset_code
+                            ),
+                        ])
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        frame.render_widget(
+            Table::new(
+                lines,
+                [
+                    Constraint::Length(8),
+                    Constraint::Min(10),
+                    Constraint::Length(24),
+                ],
+            )
+            .header(Row::new(["Account", "", "Amount (+ debit)"]).underlined())
+            .block(Block::bordered().title("Lines")),
+            detail,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mm_schema::init_sample_data;
+    use ratatui::{backend::TestBackend, Terminal};
+    use rust_decimal_macros::dec;
+
+    fn sample_book() -> Result<Database> {
+        let mut db = Database::new_in_memory()?;
+        db.init_schema()?;
+        init_sample_data(&mut db)?;
+        Ok(db)
+    }
+
+    fn press(app: &mut App, keys: &str) {
+        for c in keys.chars() {
+            app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+    }
+
+    fn key(app: &mut App, code: KeyCode, modifiers: KeyModifiers) {
+        app.handle_key(KeyEvent::new(code, modifiers));
+    }
+
+    fn screen(app: &mut App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(140, 80)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(buffer.area.width as usize)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Fills in the form for a groceries purchase paid from checking
+    fn enter_groceries(app: &mut App) {
+        press(app, "2");
+        for _ in 0..10 {
+            key(app, KeyCode::Backspace, KeyModifiers::NONE);
+        }
+        press(app, "2025-03-03");
+        key(app, KeyCode::Tab, KeyModifiers::NONE);
+        press(app, "Weekly shopping");
+        // Keep the reference asset and skip the reference number
+        key(app, KeyCode::Tab, KeyModifiers::NONE);
+        key(app, KeyCode::Tab, KeyModifiers::NONE);
+        key(app, KeyCode::Tab, KeyModifiers::NONE);
+
+        press(app, "groc");
+        assert_eq!(app.suggestions()[0].account_number, "5301");
+        key(app, KeyCode::Tab, KeyModifiers::NONE);
+        assert_eq!(app.form.lines[0].account, "5301");
+        key(app, KeyCode::Tab, KeyModifiers::NONE);
+        press(app, "45.20");
+        key(app, KeyCode::Tab, KeyModifiers::NONE);
+        key(app, KeyCode::Tab, KeyModifiers::NONE);
+        press(app, "1101");
+        key(app, KeyCode::Tab, KeyModifiers::NONE);
+        press(app, "-45.2");
+    }
+
+    #[test]
+    fn test_account_tree_rolls_up_balances() -> Result<()> {
+        let mut db = sample_book()?;
+        let mut app = App::new(&mut db)?;
+        enter_groceries(&mut app);
+        key(&mut app, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(
+            app.message,
+            Some(Message::Info("Recorded entry 1".to_string()))
+        );
+
+        let expenses = app.tree.iter().find(|row| row.account_number == "5000");
+        assert_eq!(
+            expenses.unwrap().balances.get("USD"),
+            Some(&Money::new(dec!(45.20)))
+        );
+
+        // Credit-normal accounts read positive after rolling up
+        app.form.description = "Salary".to_string();
+        app.form.lines = [("1101", "3000"), ("4100", "-3000")]
+            .map(|(account, amount)| FormLine {
+                account: account.to_string(),
+                amount: amount.to_string(),
+                asset: String::new(),
+            })
+            .into();
+        key(&mut app, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        let income = app.tree.iter().find(|row| row.account_number == "4000");
+        assert_eq!(
+            income.unwrap().balances.get("USD"),
+            Some(&Money::new(dec!(3000)))
+        );
+
+        key(&mut app, KeyCode::Esc, KeyModifiers::NONE);
+        let text = screen(&mut app);
+        assert!(text.contains("Groceries"));
+        assert!(text.contains("45.20 USD"));
+        assert!(text.contains("3000.00 USD") && !text.contains("-3000.00 USD"));
+
+        // Folding the root hides its children
+        let rows = app.tree.len();
+        key(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(app.tree.len() < rows);
+        assert!(app.tree[0].collapsed);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_form_rejects_unbalanced_entries() -> Result<()> {
+        let mut db = sample_book()?;
+        let mut app = App::new(&mut db)?;
+        enter_groceries(&mut app);
+        key(&mut app, KeyCode::Backspace, KeyModifiers::NONE);
+
+        assert_eq!(
+            app.form.totals(),
+            Some((Money::new(dec!(45.20)), Money::new(dec!(45))))
+        );
+        assert!(screen(&mut app).contains("off by 0.20"));
+        key(&mut app, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert!(matches!(app.message, Some(Message::Error(_))));
+        assert!(app.db.get_general_balance()?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_review_drafts() -> Result<()> {
+        let mut db = sample_book()?;
+        let mut app = App::new(&mut db)?;
+        enter_groceries(&mut app);
+        key(&mut app, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        key(&mut app, KeyCode::Esc, KeyModifiers::NONE);
+        enter_groceries(&mut app);
+        key(&mut app, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        key(&mut app, KeyCode::Esc, KeyModifiers::NONE);
+        assert!(app.db.get_general_balance()?.is_empty());
+
+        press(&mut app, "3");
+        assert!(screen(&mut app).contains("Drafts awaiting review (2)"));
+        press(&mut app, "p");
+        assert_eq!(
+            app.message,
+            Some(Message::Info("Posted entry 1".to_string()))
+        );
+        press(&mut app, "v");
+        assert!(app.drafts.is_empty());
+
+        let balance = app.db.get_general_balance()?;
+        let groceries = balance.iter().find(|row| row.account_number == "5301");
+        assert_eq!(groceries.unwrap().balance, Money::new(dec!(45.2)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use rusqlite::named_params;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{
+    corporate_actions::adjust_position,
+    error::{Error, Result},
+    interface::Database,
+    models::AssetType,
+    money::{Money, Rounding},
+    prices::DEFAULT_PRICE_LOOKBACK_DAYS,
+};
+
+/// Quantities keyed by account number and asset code, with the account name
+pub(crate) type Positions = BTreeMap<(String, String), (String, Decimal)>;
+
+/// Represents a row in the valuation report
+#[derive(Debug, Serialize)]
+pub struct ValuationReport {
+    pub account_number: String,
+    pub account_name: String,
+    pub asset: String,
+    pub balance: Money,
+    /// Balance converted into the reporting asset, `None` if no price or rate was found
+    pub value: Option<Money>,
+}
+
+/// The last instant of `date`, so that rates recorded during the day apply to it
+pub(crate) fn end_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN).and_utc() + chrono::Duration::days(1)
+        - chrono::Duration::nanoseconds(1)
+}
+
+impl Database {
+    /// Returns the value of one unit of `asset_code` expressed in `reporting_asset_code`
+    /// on `as_of`.
+    ///
+    /// Fiat currencies are converted through the exchange rates table. Every other
+    /// asset type (stocks, ETFs, crypto, ...) is valued from its price history: a
+    /// price quoted directly in the reporting asset is preferred, otherwise a price
+    /// in any other quote asset is converted with the matching exchange rate.
+    pub fn unit_value_at<S: AsRef<str>>(
+        &self,
+        asset_code: S,
+        as_of: NaiveDate,
+        reporting_asset_code: S,
+    ) -> Result<Option<Decimal>> {
+        let (asset_code, reporting_asset_code) =
+            (asset_code.as_ref(), reporting_asset_code.as_ref());
+
+        if asset_code == reporting_asset_code {
+            return Ok(Some(Decimal::ONE));
+        }
+
+        let end_of_day = end_of_day(as_of);
+
+        let asset = self.get_asset(asset_code)?.ok_or(Error::NotFound)?;
+        if asset.asset_type == AssetType::Fiat {
+            return Ok(self.exchange_rate_at(asset_code, reporting_asset_code, end_of_day)?);
+        }
+
+        if let Some(price) = self.price_at(
+            asset_code,
+            as_of,
+            reporting_asset_code,
+            DEFAULT_PRICE_LOOKBACK_DAYS,
+        )? {
+            return Ok(Some(price.close.amount()));
+        }
+
+        for quote_asset_code in self.price_quote_assets(asset_code)? {
+            let Some(price) = self.price_at(
+                asset_code,
+                as_of,
+                &quote_asset_code,
+                DEFAULT_PRICE_LOOKBACK_DAYS,
+            )?
+            else {
+                continue;
+            };
+
+            if let Some(rate) =
+                self.exchange_rate_at(quote_asset_code.as_str(), reporting_asset_code, end_of_day)?
+            {
+                return Ok(Some(price.close.amount() * rate));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Balances of every account as of `as_of`, valued in `reporting_asset_code`
+    ///
+    /// Positions are restated for the splits and mergers that took effect after
+    /// they were booked, so holdings match the current share count and asset even
+    /// though the underlying journal lines are left as they were recorded.
+    pub fn get_valuation_report<S: AsRef<str>>(
+        &self,
+        as_of: NaiveDate,
+        reporting_asset_code: S,
+    ) -> Result<Vec<ValuationReport>> {
+        let positions = self.positions_as_of(as_of)?;
+        let decimals = self.asset_decimals()?;
+        let reporting_decimals = *decimals
+            .get(reporting_asset_code.as_ref())
+            .ok_or(Error::NotFound)?;
+
+        let mut results = Vec::with_capacity(positions.len());
+        for ((account_number, asset), (account_name, quantity)) in positions {
+            let asset_decimals = *decimals.get(&asset).ok_or(Error::NotFound)?;
+            let balance = Money::new(quantity).round(asset_decimals, Rounding::Bankers);
+            let value = self
+                .unit_value_at(asset.as_str(), as_of, reporting_asset_code.as_ref())?
+                .map(|unit_value| {
+                    Money::new(balance.amount() * unit_value)
+                        .round(reporting_decimals, Rounding::Bankers)
+                });
+
+            results.push(ValuationReport {
+                account_number,
+                account_name,
+                asset,
+                balance,
+                value,
+            });
+        }
+
+        Ok(results)
+    }
+
+    /// Quantity held per account number and asset code as of `as_of`, restated
+    /// for corporate actions, together with the account name.
+    pub(crate) fn positions_as_of(&self, as_of: NaiveDate) -> Result<Positions> {
+        let actions = self.list_corporate_actions()?;
+        let asset_codes = self.asset_codes()?;
+
+        let mut stmt = self
+            .conn()
+            .prepare(include_str!("sql/balances_as_of.sql"))?;
+
+        let rows = stmt.query_map(named_params! { ":as_of": as_of }, |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, NaiveDate>(3)?,
+                row.get::<_, Money>(4)?,
+            ))
+        })?;
+
+        let mut positions = Positions::new();
+        for row in rows {
+            let (account_number, account_name, asset_id, day, balance) = row?;
+            let (asset_id, quantity) =
+                adjust_position(&actions, asset_id, balance.amount(), day, as_of);
+            let asset = asset_codes.get(&asset_id).cloned().ok_or(Error::NotFound)?;
+
+            positions
+                .entry((account_number, asset))
+                .or_insert((account_name, Decimal::ZERO))
+                .1 += quantity;
+        }
+
+        Ok(positions)
+    }
+
+    pub(crate) fn asset_codes(&self) -> Result<HashMap<i64, String>> {
+        let mut stmt = self.conn().prepare("SELECT id, code FROM assets")?;
+        let codes = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(codes)
+    }
+
+    /// Decimal places of every asset keyed by code, clamped like
+    /// [`Denomination`](crate::money::Denomination)
+    pub(crate) fn asset_decimals(&self) -> Result<HashMap<String, u32>> {
+        let mut stmt = self.conn().prepare("SELECT code, decimals FROM assets")?;
+        let decimals = stmt
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get::<_, i64>(1)?.clamp(0, 28) as u32))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(decimals)
+    }
+}
@@ -0,0 +1,88 @@
+//! Pins the public surface of the library: removing or renaming an export
+//! breaks this file before it reaches a release. Run `cargo semver-checks`
+//! for the signatures themselves.
+
+#[allow(unused_imports)]
+use mm_schema::{
+    init_sample_data, Account, AccountType, Amount, AmountColumns, Asset, AssetPerformance,
+    AssetPrice, AssetType, AuditOperation, AuditRecord, AuditTable, BookDocument,
+    BookDocumentEntry, CorporateAction, CorporateActionType, CsvImportProfile, Database,
+    DatabaseOptions, Denomination, Dividend, EntryStatus, Error, ExchangeRate,
+    GeneralBalanceReport, IncomeKind, IncomeYieldReport, JournalEntry, JournalEntryLine,
+    JsonImport, LedgerStore, Lot, MemoryStore, Money, MoneyError, MoneyFormat, MonthlyPerformance,
+    NegativeStyle, NewAccount, NewJournalEntry, NewJournalEntryLine, NormalBalance,
+    PerformanceSummary, PlainTextFormat, PortfolioPerformance, RegisterLine, Result, Rounding,
+    SnapshotPolicy, SymbolPosition, Synchronous, ValuationReport, DEFAULT_PRICE_LOOKBACK_DAYS,
+    DEFAULT_SNAPSHOT_RETENTION, DIVIDENDS_ACCOUNT, INTEREST_INCOME_ACCOUNT, JSON_FORMAT,
+    JSON_FORMAT_VERSION, SCHEMA_VERSION, WITHHOLDING_TAX_ACCOUNT,
+};
+
+#[cfg(feature = "importers")]
+#[allow(unused_imports)]
+use mm_schema::{
+    BalanceCheck, ImportSummary, ImportWarning, InvestmentKind, PlainTextImport, QifOptions,
+    StatementImport, StatementLine, BROKERAGE_ACCOUNT, CAPITAL_GAINS_ACCOUNT, SUSPENSE_ACCOUNT,
+};
+
+#[cfg(feature = "server")]
+#[allow(unused_imports)]
+use mm_schema::{openapi, serve};
+
+#[cfg(feature = "async")]
+#[allow(unused_imports)]
+use mm_schema::{AsyncDatabase, DEFAULT_QUEUE_CAPACITY};
+
+use chrono::{TimeZone, Utc};
+use rust_decimal_macros::dec;
+
+#[test]
+fn test_book_through_public_api() -> Result<()> {
+    let mut db = Database::new_in_memory()?;
+    db.migrate()?;
+    assert_eq!(db.schema_version()?, SCHEMA_VERSION);
+    init_sample_data(&mut db)?;
+
+    let line = |account: &str, entry_type| NewJournalEntryLine {
+        account_number: account.to_string(),
+        asset_code: "USD".to_string(),
+        entry_type,
+        amount: Money::new(dec!(45.20)),
+        reference_amount: None,
+        description: None,
+    };
+    let id = db.create_journal_entry(&NewJournalEntry {
+        date: Utc.with_ymd_and_hms(2025, 3, 3, 0, 0, 0).unwrap(),
+        description: "Groceries".to_string(),
+        reference_number: None,
+        reference_asset_code: "USD".to_string(),
+        status: EntryStatus::Posted,
+        lines: vec![
+            line("5301", NormalBalance::Debit),
+            line("1101", NormalBalance::Credit),
+        ],
+    })?;
+
+    let register: Vec<RegisterLine> = db.register("1101")?;
+    assert_eq!(register[0].journal_entry_id, id);
+    assert_eq!(register[0].balance, Money::new(dec!(-45.2)));
+
+    let document: BookDocument = db.book_document()?;
+    assert_eq!(document.format, JSON_FORMAT);
+    assert_eq!(document.journal_entries.len(), 1);
+
+    assert!(matches!(db.void_journal_entry(99), Err(Error::NotFound)));
+
+    Ok(())
+}
+
+#[test]
+fn test_memory_store_through_public_api() -> Result<()> {
+    let mut store = MemoryStore::new();
+    init_sample_data(&mut store)?;
+
+    let store: &dyn LedgerStore = &store;
+    assert_eq!(store.list_account_types()?.len(), 5);
+    assert!(store.get_general_balance()?.is_empty());
+
+    Ok(())
+}